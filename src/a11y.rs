@@ -0,0 +1,75 @@
+use wasm_bindgen::JsCast;
+use web_sys::{Document, Element, HtmlElement};
+use crate::automaton::DynAutomaton;
+use crate::error::AppError;
+use crate::render::{cell_bounds, CELL_SIZE_PX};
+
+/// Hidden from the screen but not from screen readers
+const VISUALLY_HIDDEN_STYLE: &str = "position: absolute; width: 1px; height: 1px; overflow: hidden; clip: rect(0 0 0 0); white-space: nowrap;";
+
+/// Element that screen readers read out when its text changes
+#[derive(Debug)]
+pub struct LiveRegion {
+    element: Element,
+}
+impl LiveRegion {
+    pub fn new(document: &Document) -> Result<Self, AppError> {
+        let element = document.create_element("div")?;
+        element.set_attribute("role", "status")?;
+        element.set_attribute("aria-live", "polite")?;
+        element.set_attribute("style", VISUALLY_HIDDEN_STYLE)?;
+        Ok(Self { element })
+    }
+    pub fn element(&self) -> &Element {
+        &self.element
+    }
+    pub fn announce(&self, text: &str) {
+        self.element.set_text_content(Some(text));
+    }
+}
+
+/// Cell chosen with the arrow keys, outlined over the field while the field has the keyboard focus
+#[derive(Debug)]
+pub struct CellCursor {
+    outline: HtmlElement,
+    row: usize,
+    col: usize,
+}
+impl CellCursor {
+    /// The outline has to be put over the canvas of the cells, in a positioned container
+    pub fn new(document: &Document) -> Result<Self, AppError> {
+        let outline = document.create_element("div")?.dyn_into::<HtmlElement>()?;
+        outline.set_attribute("aria-hidden", "true")?;
+        outline.set_attribute("style", "position: absolute; pointer-events: none; outline: 2px solid #1E90FF; display: none;")?;
+        Ok(Self { outline, row: 0, col: 0 })
+    }
+    pub fn element(&self) -> &HtmlElement {
+        &self.outline
+    }
+    /// The cell, clamped to the field, which can be smaller than when the cell was chosen
+    pub fn cell(&self, field: &dyn DynAutomaton) -> (usize, usize) {
+        (self.row.min(field.get_height() - 1), self.col.min(field.get_width() - 1))
+    }
+    /// Moves the cursor by the offsets, it stops at the edges of the field
+    pub fn move_by(&mut self, field: &dyn DynAutomaton, rows: isize, cols: isize, cell_size_px: usize) {
+        let (row, col) = self.cell(field);
+        self.row = row.saturating_add_signed(rows).min(field.get_height() - 1);
+        self.col = col.saturating_add_signed(cols).min(field.get_width() - 1);
+        self.update(field, cell_size_px);
+    }
+    pub fn set_visible(&self, is_visible: bool) {
+        // setting styles can't fail for these properties
+        let _ = self.outline.style().set_property("display", if is_visible { "block" } else { "none" });
+    }
+    /// Puts the outline over the cell, for the current size of cells on the screen
+    pub fn update(&self, field: &dyn DynAutomaton, cell_size_px: usize) {
+        let (row, col) = self.cell(field);
+        let scale = (cell_size_px + 1) as f64 / (CELL_SIZE_PX + 1) as f64;
+        let (x, y, width, height) = cell_bounds(field, row, col);
+        let style = self.outline.style();
+        let _ = style.set_property("left", &format!("{}px", x * scale));
+        let _ = style.set_property("top", &format!("{}px", y * scale));
+        let _ = style.set_property("width", &format!("{}px", width * scale));
+        let _ = style.set_property("height", &format!("{}px", height * scale));
+    }
+}
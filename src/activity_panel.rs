@@ -0,0 +1,127 @@
+use web_sys::{Document, Element};
+use crate::automaton::Activity;
+use crate::error::AppError;
+use crate::ring_buffer::{RingBuffer, Sum};
+
+/// Generations in the short rolling average
+const SHORT_WINDOW: usize = 10;
+/// Generations in the long rolling average, and in the history
+const LONG_WINDOW: usize = 100;
+
+/// Births and deaths of the recent generations
+#[derive(Debug)]
+pub struct ActivityHistory {
+    births: RingBuffer<u32, Sum>,
+    deaths: RingBuffer<u32, Sum>,
+}
+impl ActivityHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self { births: RingBuffer::new(capacity), deaths: RingBuffer::new(capacity) }
+    }
+    pub fn push(&mut self, activity: Activity) {
+        // counts over u32::MAX would need a field with billions of cells
+        self.births.push(u32::try_from(activity.births).unwrap_or(u32::MAX));
+        self.deaths.push(u32::try_from(activity.deaths).unwrap_or(u32::MAX));
+    }
+    pub fn clear(&mut self) {
+        self.births.truncate();
+        self.deaths.truncate();
+    }
+    pub fn last(&self) -> Option<Activity> {
+        Some(Activity { births: *self.births.last()? as usize, deaths: *self.deaths.last()? as usize })
+    }
+    /// Mean births and deaths of the last `window` generations, None if there are none
+    pub fn mean(&self, window: usize) -> Option<(f64, f64)> {
+        if window >= self.births.len() {
+            // the whole history, the buffers keep its sum
+            return Some((self.births.mean()?, self.deaths.mean()?));
+        }
+        let mean = |buffer: &RingBuffer<u32, Sum>| buffer.iter().skip(buffer.len() - window).map(|&count| count as f64).sum::<f64>() / window as f64;
+        (window > 0).then(|| (mean(&self.births), mean(&self.deaths)))
+    }
+}
+
+/// Births, deaths and activity of the last generation, with rolling averages.
+/// The activity shows whether a soup is still interesting, it drops when the soup settles into still lifes and oscillators.
+/// The text is updated at most once per interval, like the fps meter. Under it is the status of the field, like the period it has settled into
+#[derive(Debug)]
+pub struct ActivityPanel {
+    container: Element,
+    text: Element,
+    status: Element,
+    history: ActivityHistory,
+    update_interval_ms: f64,
+    last_update_ts_ms: Option<f64>,
+}
+impl ActivityPanel {
+    pub fn new(document: &Document, update_interval_ms: f64) -> Result<Self, AppError> {
+        let container = document.create_element("div")?;
+        let text = document.create_element("div")?;
+        text.set_attribute("title", "Activity is the number of births and deaths in a generation")?;
+        container.append_child(&text)?;
+        let status = document.create_element("div")?;
+        container.append_child(&status)?;
+        Ok(Self { container, text, status, history: ActivityHistory::new(LONG_WINDOW), update_interval_ms, last_update_ts_ms: None })
+    }
+    pub fn element(&self) -> &Element {
+        &self.container
+    }
+    pub fn show_stabilized(&self, generation: u64, period: usize) {
+        self.status.set_text_content(Some(&format!("stabilized at generation {generation} with period {period}")));
+    }
+    pub fn clear_status(&self) {
+        self.status.set_text_content(None);
+    }
+    /// Records the births and deaths of a generation, and shows them if the update interval has passed
+    pub fn push(&mut self, timestamp_ms: f64, activity: Activity) {
+        self.history.push(activity);
+        if self.last_update_ts_ms.is_some_and(|last_ts_ms| (timestamp_ms - last_ts_ms) < self.update_interval_ms) {
+            return;
+        }
+        self.last_update_ts_ms = Some(timestamp_ms);
+        self.text.set_text_content(Some(&self.text()));
+    }
+    /// Forgets the generations of the previous field
+    pub fn clear(&mut self) {
+        self.history.clear();
+        self.last_update_ts_ms = None;
+        self.text.set_text_content(None);
+        self.clear_status();
+    }
+    fn text(&self) -> String {
+        let Some(last) = self.history.last() else {
+            return String::new();
+        };
+        let mut text = format!("births: {}, deaths: {}, activity: {}", last.births, last.deaths, last.total());
+        for window in [SHORT_WINDOW, LONG_WINDOW] {
+            if let Some((births, deaths)) = self.history.mean(window) {
+                text += &format!(" | avg {window}: births {births:.1}, deaths {deaths:.1}, activity {:.1}", births + deaths);
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_activity_history() {
+        let mut history = ActivityHistory::new(4);
+        assert_eq!(None, history.last());
+        assert_eq!(None, history.mean(2));
+
+        for (births, deaths) in [(1, 9), (2, 0), (3, 4), (4, 2), (5, 6)] {
+            history.push(Activity { births, deaths });
+        }
+        assert_eq!(Some(Activity { births: 5, deaths: 6 }), history.last());
+        assert_eq!(Some((4.5, 4.0)), history.mean(2));
+        // the first generation doesn't fit anymore
+        assert_eq!(Some((3.5, 3.0)), history.mean(4));
+        assert_eq!(Some((3.5, 3.0)), history.mean(10));
+        assert_eq!(None, history.mean(0));
+
+        history.clear();
+        assert_eq!(None, history.last());
+    }
+}
@@ -0,0 +1,142 @@
+/// Time of a generation that the app tries to stay within, in milliseconds
+pub const DEFAULT_BUDGET_MS: f64 = 20.0;
+/// Consecutive frames over the budget or with headroom before the quality is changed
+const DEFAULT_FRAMES: u32 = 10;
+/// Frames that take less than this part of the budget have headroom for a better quality.
+/// The gap to the budget keeps the quality from flipping back and forth
+const HEADROOM_RATIO: f64 = 0.6;
+
+/// When to lower and restore the quality. The frame times are the time spent updating and drawing a generation,
+/// not the time between the frames, which also includes the waiting of a limited frame rate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    /// Frames slower than this are over the budget
+    pub budget_ms: f64,
+    /// Frames faster than this have headroom
+    pub headroom_ms: f64,
+    /// Number of consecutive frames over the budget or with headroom that change the quality
+    pub frames: u32,
+}
+impl Thresholds {
+    pub fn with_budget(budget_ms: f64) -> Self {
+        Self {
+            budget_ms,
+            headroom_ms: budget_ms * HEADROOM_RATIO,
+            frames: DEFAULT_FRAMES,
+        }
+    }
+}
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self::with_budget(DEFAULT_BUDGET_MS)
+    }
+}
+
+/// Steps of the quality, from the best one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Quality {
+    Full,
+    /// The field is drawn as an image instead of cell by cell
+    CoarseRenderer,
+    /// The coarse renderer and fewer generations per second, to keep the page responsive
+    ReducedRate,
+}
+impl Quality {
+    fn lower(self) -> Option<Self> {
+        match self {
+            Self::Full => Some(Self::CoarseRenderer),
+            Self::CoarseRenderer => Some(Self::ReducedRate),
+            Self::ReducedRate => None,
+        }
+    }
+    fn higher(self) -> Option<Self> {
+        match self {
+            Self::Full => None,
+            Self::CoarseRenderer => Some(Self::Full),
+            Self::ReducedRate => Some(Self::CoarseRenderer),
+        }
+    }
+}
+
+/// Lowers the quality one step when the frames are over the budget for a while, and restores it when there is headroom again
+#[derive(Debug)]
+pub struct AdaptiveQuality {
+    pub thresholds: Thresholds,
+    quality: Quality,
+    slow_frames: u32,
+    fast_frames: u32,
+}
+impl AdaptiveQuality {
+    pub fn new(thresholds: Thresholds) -> Self {
+        Self { thresholds, quality: Quality::Full, slow_frames: 0, fast_frames: 0 }
+    }
+    #[cfg(test)]
+    pub fn quality(&self) -> Quality {
+        self.quality
+    }
+    /// Records the time of a frame, returns the new quality if it has changed
+    pub fn record(&mut self, frame_ms: f64) -> Option<Quality> {
+        if frame_ms > self.thresholds.budget_ms {
+            self.slow_frames += 1;
+            self.fast_frames = 0;
+        } else if frame_ms < self.thresholds.headroom_ms {
+            self.fast_frames += 1;
+            self.slow_frames = 0;
+        } else {
+            self.reset();
+        }
+        let next = if self.slow_frames >= self.thresholds.frames {
+            self.quality.lower()
+        } else if self.fast_frames >= self.thresholds.frames {
+            self.quality.higher()
+        } else {
+            return None;
+        };
+        // the next change needs a new run of frames
+        self.reset();
+        self.quality = next?;
+        next
+    }
+    /// Forgets the recent frames, e.g. after a pause
+    pub fn reset(&mut self) {
+        self.slow_frames = 0;
+        self.fast_frames = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_adaptive_quality() {
+        let mut adaptive = AdaptiveQuality::new(Thresholds { budget_ms: 20.0, headroom_ms: 10.0, frames: 3 });
+        assert_eq!(None, adaptive.record(30.0));
+        assert_eq!(None, adaptive.record(30.0));
+        // a frame within the budget breaks the run
+        assert_eq!(None, adaptive.record(15.0));
+        assert_eq!(None, adaptive.record(30.0));
+        assert_eq!(None, adaptive.record(30.0));
+        assert_eq!(Some(Quality::CoarseRenderer), adaptive.record(30.0));
+        for _ in 0..2 {
+            assert_eq!(None, adaptive.record(30.0));
+        }
+        assert_eq!(Some(Quality::ReducedRate), adaptive.record(30.0));
+        for _ in 0..10 {
+            assert_eq!(None, adaptive.record(30.0));
+        }
+        assert_eq!(Quality::ReducedRate, adaptive.quality());
+
+        for _ in 0..2 {
+            assert_eq!(None, adaptive.record(5.0));
+        }
+        assert_eq!(Some(Quality::CoarseRenderer), adaptive.record(5.0));
+        for _ in 0..2 {
+            assert_eq!(None, adaptive.record(5.0));
+        }
+        assert_eq!(Some(Quality::Full), adaptive.record(5.0));
+        for _ in 0..10 {
+            assert_eq!(None, adaptive.record(5.0));
+        }
+        assert_eq!(Quality::Full, adaptive.quality());
+    }
+}
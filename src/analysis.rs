@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+use crate::automaton::DynAutomaton;
+
+/// Number of different 2x2 blocks of dead and live cells
+pub const BLOCK_PATTERNS: usize = 16;
+/// Generations kept by `Analysis`, the oldest ones are dropped on longer runs
+pub const MAX_GENERATIONS: usize = 100_000;
+
+/// Measures of a single generation. Cells in the state with palette index 0 are dead, all other states are alive
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerationAnalysis {
+    pub generation: u64,
+    /// Share of live cells, from 0 to 1
+    pub density: f64,
+    /// Shannon entropy of the 2x2 block patterns in bits, from 0 for a uniform field to 4 for random noise
+    pub entropy: f64,
+    /// Number of 2x2 blocks of each pattern, the field is split into blocks without overlaps.
+    /// The bits of the index are the cells: 1 top left, 2 top right, 4 bottom left, 8 bottom right
+    pub blocks: [usize; BLOCK_PATTERNS],
+}
+impl GenerationAnalysis {
+    pub fn new(generation: u64, field: &dyn DynAutomaton) -> Self {
+        let (width, height) = (field.get_width(), field.get_height());
+        let is_alive = |row: usize, col: usize| field.state_at((row * width) + col) != 0;
+        let live_cells = (0..(width * height)).filter(|&index| field.state_at(index) != 0).count();
+        let mut blocks = [0; BLOCK_PATTERNS];
+        // the last row and column are not in any block on fields with odd sizes
+        for row in (0..(height / 2)).map(|block_row| block_row * 2) {
+            for col in (0..(width / 2)).map(|block_col| block_col * 2) {
+                let pattern = is_alive(row, col) as usize
+                    | ((is_alive(row, col + 1) as usize) << 1)
+                    | ((is_alive(row + 1, col) as usize) << 2)
+                    | ((is_alive(row + 1, col + 1) as usize) << 3);
+                blocks[pattern] += 1;
+            }
+        }
+        Self {
+            generation,
+            density: live_cells as f64 / (width * height) as f64,
+            entropy: entropy(&blocks),
+            blocks,
+        }
+    }
+}
+
+/// Shannon entropy of the frequencies in bits, 0 if there are none
+fn entropy(counts: &[usize]) -> f64 {
+    let total = counts.iter().sum::<usize>() as f64;
+    counts.iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let probability = count as f64 / total;
+            -probability * probability.log2()
+        })
+        .sum::<f64>()
+        // a single pattern gives -0.0
+        .max(0.0)
+}
+
+/// Measures of the generations of a run, for exporting them as CSV
+#[derive(Debug, Default)]
+pub struct Analysis {
+    generations: VecDeque<GenerationAnalysis>,
+}
+impl Analysis {
+    pub fn push(&mut self, generation: u64, field: &dyn DynAutomaton) {
+        if self.generations.len() >= MAX_GENERATIONS {
+            self.generations.pop_front();
+        }
+        self.generations.push_back(GenerationAnalysis::new(generation, field));
+    }
+    pub fn clear(&mut self) {
+        self.generations.clear();
+    }
+    /// Forgets the generations after the given one, e.g. when the run continues from an earlier generation
+    pub fn truncate_after(&mut self, generation: u64) {
+        let len = self.generations.partition_point(|analysis| analysis.generation <= generation);
+        self.generations.truncate(len);
+    }
+    pub fn generations(&self) -> impl Iterator<Item = &GenerationAnalysis> {
+        self.generations.iter()
+    }
+    /// One line per generation, with a header
+    pub fn to_csv(&self) -> String {
+        let mut res = String::from("generation,density,entropy");
+        for pattern in 0..BLOCK_PATTERNS {
+            // writing into a String never fails
+            let _ = write!(res, ",block_{pattern:04b}");
+        }
+        res.push('\n');
+        for analysis in &self.generations {
+            let _ = write!(res, "{},{:.6},{:.6}", analysis.generation, analysis.density, analysis.entropy);
+            for count in analysis.blocks {
+                let _ = write!(res, ",{count}");
+            }
+            res.push('\n');
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+    use crate::game_of_life::Field;
+    #[test]
+    fn test_generation_analysis() {
+        let field = Field::from_str("____\n____\n____").unwrap();
+        let analysis = GenerationAnalysis::new(3, &field);
+        assert_eq!(3, analysis.generation);
+        assert_eq!(0.0, analysis.density);
+        assert_eq!(0.0, analysis.entropy);
+        assert_eq!(2, analysis.blocks[0]);
+
+        // the last row is not in any block
+        let field = Field::from_str("#_##\n__##\n####").unwrap();
+        let analysis = GenerationAnalysis::new(0, &field);
+        assert_eq!(0.75, analysis.density);
+        assert_eq!(1, analysis.blocks[0b0001]);
+        assert_eq!(1, analysis.blocks[0b1111]);
+        assert_eq!(2, analysis.blocks.iter().sum::<usize>());
+        assert_eq!(1.0, analysis.entropy);
+    }
+    #[test]
+    fn test_to_csv() {
+        let mut analysis = Analysis::default();
+        let mut field = Field::from_str("_#__\n_#__\n_#__\n____").unwrap();
+        analysis.push(0, &field);
+        field.update();
+        analysis.push(1, &field);
+        assert_eq!(2, analysis.generations().count());
+        let csv = analysis.to_csv();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(3, lines.len());
+        assert!(lines[0].starts_with("generation,density,entropy,block_0000,block_0001,"));
+        assert!(lines[0].ends_with(",block_1111"));
+        assert_eq!("0,0.187500,1.500000,2,0,1,0,0,0,0,0,0,0,1,0,0,0,0,0", lines[1]);
+        assert_eq!(19, lines[2].split(',').count());
+
+        analysis.truncate_after(0);
+        assert_eq!(1, analysis.generations().count());
+
+        analysis.clear();
+        assert_eq!(1, analysis.to_csv().lines().count());
+    }
+}
@@ -0,0 +1,1807 @@
+use std::cell::Cell;
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::rc::{Rc, Weak};
+use std::str::FromStr;
+use wasm_bindgen::convert::FromWasmAbi;
+use wasm_bindgen::prelude::*;
+use web_sys::{CanvasRenderingContext2d, Document, HtmlCanvasElement, HtmlInputElement, HtmlSelectElement, Window};
+use crate::a11y::{CellCursor, LiveRegion};
+use crate::activity_panel::ActivityPanel;
+use crate::adaptive::{AdaptiveQuality, Quality, Thresholds};
+use crate::analysis::Analysis;
+use crate::automaton::{CellState, DynAutomaton, Layout};
+use crate::board_library::{self, BoardCommand, BoardLibrary};
+use crate::brush::{self, BrushTool};
+use crate::dispatcher::Dispatcher;
+use crate::edit_flash::EditFlash;
+use crate::embed::{self, EmbedHost};
+use crate::colored_life::{ColorCell, ColoredLife};
+use crate::compare::{self, CompareCommand, ComparePanel};
+use crate::context_menu::{self, ContextMenu, MenuCommand};
+use crate::control_panel::ControlPanel;
+use crate::diagnostics::{self, DiagnosticsCommand};
+use crate::{console_debug, console_error, console_log, console_warn};
+#[cfg(feature = "webgl")]
+use crate::torus_view::{self, TorusView};
+#[cfg(feature = "webgl")]
+use crate::voxel_view::{Orbit, VoxelView};
+#[cfg(feature = "audio")]
+use crate::audio::{self, lane_notes, Playhead, SoundCommand, Sonifier, Tone};
+use crate::log;
+use crate::metrics::{GenerationMetrics, MetricsRecorder};
+#[cfg(feature = "network")]
+use crate::peer::{self, PeerCommand, PeerSession};
+use crate::events;
+use crate::export::{self, download_blob, ExportCommand};
+use crate::field_input::{self, FieldInput, LineDraft};
+use crate::frame_stats::{FpsMeter, FrameHistory};
+#[cfg(feature = "webgl")]
+use crate::life_3d::Life3d;
+use crate::error::{AppError, ErrorBanner};
+use crate::game_of_life::{Field, Rule};
+use crate::history::{StateHistory, DEFAULT_HISTORY_SIZE};
+use crate::listeners::Listeners;
+use crate::magnifier::Magnifier;
+use crate::minimap::Minimap;
+use crate::options::Options;
+use crate::period::PeriodDetector;
+use crate::pinch::Pinch;
+use crate::panic;
+use crate::pattern_file::{self, FileCommand};
+use crate::presets::{self, make_pattern_field};
+use crate::profile;
+use crate::recorder::{self, save_recording, GifRecorder, RecordCommand, VideoControls};
+use crate::replay::{Change, Initial, Replay};
+use crate::render::{
+    cell_size_device_px, context_2d, draw_cells, draw_ghost, draw_initial_state, draw_neighbour_counts, fit_cell_size, ImageRenderer, TrailRenderer, CELL_SIZE_PX,
+};
+use crate::rule_editor::{self, create_noise_input, create_topology_select, RuleCommand};
+use crate::scrubber::Scrubber;
+use crate::share::{self, ShareCommand};
+use crate::stamp_preview::StampPreview;
+use crate::stats_graph::StatsGraph;
+use crate::status_bar::{Mode, Status, StatusBar};
+use crate::theme::Theme;
+use crate::view_settings::{self, ViewCommand};
+#[cfg(feature = "network")]
+use crate::websocket_sync::{self, SyncCommand, WebSocketSync};
+
+/// The grid is hidden when cells are smaller than this on the screen, e.g. when the page is zoomed out
+const MIN_GRID_CELL_SIZE_PX: f64 = 4.0;
+/// Speed of the animation when the speed is limited and there is no configured target fps
+const REDUCED_FPS: f64 = 30.0;
+/// Speed of the animation for users who prefer reduced motion, when there is no configured target fps
+const REDUCED_MOTION_FPS: f64 = 5.0;
+/// Neighbour counts are only shown on fields up to this size, on bigger ones they are too slow and unreadable
+const MAX_COUNTS_FIELD_SIZE: usize = 100;
+/// Pattern of the stamp brush and of the Stamp action of the context menu, a glider
+const STAMP_PATTERN: &str = "_#_\n__#\n###";
+/// Every generation that is a multiple of this is announced to screen reader users while running
+const ANNOUNCED_GENERATIONS: u64 = 1000;
+
+#[derive(Debug)]
+pub struct AnimationState {
+    pub next_frame: Option<i32>,
+    pub next_timeout: Option<i32>,
+    /// Speed limit of the animation, a generation is drawn every animation frame if not set
+    pub target_fps: Option<f64>,
+    /// Timestamp of the previous animation frame, not set before the first frame after a resume
+    pub last_frame_ts_ms: Option<f64>,
+    pub time_history_ms: FrameHistory,
+    pub fps_meter: FpsMeter,
+    pub stats_graph: StatsGraph,
+    /// Births and deaths of the recent generations
+    pub activity_panel: ActivityPanel,
+    /// Density and entropy of the generations since the field was created, for the CSV export
+    pub analysis: Analysis,
+    /// Population, births, deaths and frame times of the generations since the field was created, for the export
+    pub metrics: MetricsRecorder,
+    /// Copies of the field at the recent generations, for scrubbing through them while paused
+    pub history: StateHistory,
+    /// Cells painted around the cursor when dragging, or a line from where the drag has started
+    pub brush: BrushTool,
+    /// Rule from the rule editor, applied to every new field that supports it
+    pub life_rule: Rule,
+    /// Draws the field as an image instead of cell by cell, if set
+    pub image_renderer: Option<ImageRenderer>,
+    /// Lowers the quality when the generations take too long, if enabled
+    pub adaptive: Option<AdaptiveQuality>,
+    /// The image renderer was enabled by the adaptive quality, so it can also disable it
+    pub adaptive_renderer: bool,
+    /// The speed limit was set by the adaptive quality, so it can also remove it
+    pub adaptive_fps: bool,
+    /// Background canvas with the grid lines
+    pub grid_context: CanvasRenderingContext2d,
+    /// Grid visibility chosen by the user, the grid is also hidden when the cells are too small
+    pub show_grid: bool,
+    pub minimap: Minimap,
+    /// Enlarged view of the cells around the cursor
+    pub magnifier: Magnifier,
+    /// Draws fading trails behind moving cells, if set
+    pub trail_renderer: Option<TrailRenderer>,
+    /// While paused, show the changes of the next generation over the field
+    pub show_ghost: bool,
+    /// Write the number of live neighbours into the cells of small fields
+    pub show_counts: bool,
+    /// Captures every generation while recording
+    pub recorder: Option<GifRecorder>,
+    /// Initial state and edits of the current field, only recorded for life fields
+    pub replay: Option<Replay>,
+    /// Cells and their new states edited while running, applied at the start of the next generation
+    pub pending_edits: Vec<(usize, usize, u8)>,
+    /// The user has edited the field since it was loaded or the replay was saved, replacing it asks for a confirmation
+    pub is_edited: bool,
+    /// The field is played back from the replay, the changes of the replay are applied when the field reaches their generations
+    pub is_playback: bool,
+    /// Size of cells on the screen in css pixels
+    pub cell_size_px: usize,
+    /// Cell size from the options, fitting the field into the container only makes the cells smaller
+    pub max_cell_size_px: usize,
+    /// Width of the container in css pixels that the new fields are fitted into, not set if the field isn't fitted
+    pub fit_width: Option<f64>,
+    /// Colors of exported images
+    pub theme: Theme,
+    /// Element of the app that receives the lifecycle events
+    pub event_target: web_sys::Element,
+    /// Shows the errors of the event handlers
+    pub banner: ErrorBanner,
+    /// Number of generations since the field was created, shared with the panic overlay
+    pub generation: Rc<Cell<u64>>,
+    /// The field has died out or stopped changing, the event is only dispatched when this changes
+    pub is_stable: bool,
+    /// Pause when the field becomes a still life or starts repeating with a short period
+    pub auto_pause: bool,
+    /// The animation pauses when the field reaches this generation, set by the Run button
+    pub stop_at: Option<u64>,
+    /// Recent generations of the field, only recorded with `auto_pause`
+    pub periods: PeriodDetector,
+    /// The field has been found repeating, it is only reported once until the field is changed by the user
+    pub is_periodic: bool,
+    /// Called with the generation number and the population after every generation
+    pub on_tick: Option<js_sys::Function>,
+    /// Called with the generation number and the population when the animation stops
+    pub on_pause: Option<js_sys::Function>,
+    /// Called with the generation number and the population when the animation starts
+    pub on_resume: Option<js_sys::Function>,
+    /// Page that embeds the app in an iframe and scripts it, it gets the events as messages
+    pub embed_host: Option<EmbedHost>,
+}
+impl AnimationState {
+    fn new(
+        stats_graph: StatsGraph,
+        fps_meter: FpsMeter,
+        activity_panel: ActivityPanel,
+        grid_context: CanvasRenderingContext2d,
+        document: &Document,
+        event_target: web_sys::Element,
+        banner: ErrorBanner,
+    ) -> Result<Self, AppError> {
+        Ok(Self {
+            next_frame: None,
+            next_timeout: None,
+            target_fps: None,
+            last_frame_ts_ms: None,
+            time_history_ms: FrameHistory::new(),
+            fps_meter,
+            stats_graph,
+            activity_panel,
+            analysis: Analysis::default(),
+            metrics: MetricsRecorder::default(),
+            history: StateHistory::new(DEFAULT_HISTORY_SIZE),
+            brush: brush::PRESETS[0].1,
+            life_rule: Rule::default(),
+            image_renderer: None,
+            adaptive: None,
+            adaptive_renderer: false,
+            adaptive_fps: false,
+            grid_context,
+            show_grid: true,
+            minimap: Minimap::new(document)?,
+            magnifier: Magnifier::new(document)?,
+            trail_renderer: None,
+            show_ghost: false,
+            show_counts: false,
+            recorder: None,
+            replay: None,
+            pending_edits: Vec::new(),
+            is_edited: false,
+            is_playback: false,
+            cell_size_px: CELL_SIZE_PX,
+            max_cell_size_px: CELL_SIZE_PX,
+            fit_width: None,
+            theme: Theme::default(),
+            event_target,
+            banner,
+            generation: Rc::new(Cell::new(0)),
+            is_stable: false,
+            auto_pause: false,
+            stop_at: None,
+            periods: PeriodDetector::default(),
+            is_periodic: false,
+            on_tick: None,
+            on_pause: None,
+            on_resume: None,
+            embed_host: None,
+        })
+    }
+    pub fn is_running(&self) -> bool {
+        self.next_frame.is_some() || self.next_timeout.is_some()
+    }
+    /// Starts looking for repeats again, after the field has been replaced or changed by the user
+    pub fn reset_periods(&mut self) {
+        self.periods.clear();
+        self.is_periodic = false;
+        self.activity_panel.clear_status();
+    }
+    /// Dispatches the event and calls the matching callback
+    pub fn dispatch_event(&self, name: &str, field: &dyn DynAutomaton) {
+        events::dispatch(&self.event_target, name, self.generation.get(), field);
+        let callback = match name {
+            events::GENERATION => &self.on_tick,
+            events::PAUSED => &self.on_pause,
+            events::RESUMED => &self.on_resume,
+            _ => &None,
+        };
+        if let Some(callback) = callback {
+            let generation = JsValue::from_f64(self.generation.get() as f64);
+            let population = JsValue::from_f64(events::population(field) as f64);
+            if let Err(err) = callback.call2(&JsValue::NULL, &generation, &population) {
+                console_error!("Callback for {name} failed: {err:?}");
+            }
+        }
+        if let Some(host) = &self.embed_host {
+            host.post(embed::STATE, &[
+                ("event", name.into()),
+                ("generation", JsValue::from_f64(self.generation.get() as f64)),
+                ("population", JsValue::from_f64(events::population(field) as f64)),
+                ("running", self.is_running().into()),
+            ]);
+        }
+    }
+}
+
+
+/// The whole application: its elements, event listeners and the animation loop.
+/// Instances don't share any state, so several of them can run on one page
+#[wasm_bindgen]
+pub struct GameOfLifeApp {
+    dispatcher: Rc<AppDispatcher>,
+    listeners: Listeners,
+    /// Fits the field into the container when it is resized, if enabled by the options
+    resize_watcher: Option<ResizeWatcher>,
+}
+
+#[wasm_bindgen]
+impl GameOfLifeApp {
+    /// Creates the app at the end of the container, or of the page body if there is no container.
+    /// `options` is an object with the properties of `Options` in camelCase, or `undefined` for the defaults
+    #[wasm_bindgen(constructor)]
+    pub fn new(container: Option<web_sys::Element>, options: JsValue) -> Result<GameOfLifeApp, JsValue> {
+        let mut options = Options::from_js(options)?;
+        let window = web_sys::window().ok_or(AppError::Missing("window"))?;
+        let document = window.document().ok_or(AppError::Missing("document"))?;
+        // the page url can override the options, so that demo configurations can be linked directly
+        let mut query_options = options.clone();
+        match window.location().search().map_err(|err| format!("{err:?}")).and_then(|query| query_options.apply_query(&query)) {
+            Ok(()) => options = query_options,
+            Err(err) => console_warn!("Ignoring the options in the url: {err}"),
+        }
+        let container = match container {
+            Some(container) => container,
+            None => document.body().ok_or(AppError::Missing("document body"))?.into(),
+        };
+        Ok(Self::create(&container, options)?)
+    }
+    /// Registers `callback(generation, population)` to be called after every generation, replacing the previous one.
+    /// Callbacks are called during the update, the methods of the app that they call take effect after it
+    #[wasm_bindgen(js_name = onTick)]
+    pub fn on_tick(&self, callback: Option<js_sys::Function>) {
+        self.dispatcher.send(Message::SetCallback(events::GENERATION, callback));
+    }
+    /// Registers `callback(generation, population)` to be called when the animation stops
+    #[wasm_bindgen(js_name = onPause)]
+    pub fn on_pause(&self, callback: Option<js_sys::Function>) {
+        self.dispatcher.send(Message::SetCallback(events::PAUSED, callback));
+    }
+    /// Registers `callback(generation, population)` to be called when the animation starts
+    #[wasm_bindgen(js_name = onResume)]
+    pub fn on_resume(&self, callback: Option<js_sys::Function>) {
+        self.dispatcher.send(Message::SetCallback(events::RESUMED, callback));
+    }
+    /// Replay of the current field as JSON, undefined if the field is not a life field or the app is busy
+    #[wasm_bindgen(js_name = replayJson)]
+    pub fn replay_json(&self) -> Option<String> {
+        self.dispatcher.try_with(|app| app.state.replay.as_ref().and_then(|replay| replay_to_json(replay).ok())).flatten()
+    }
+    /// Plays back a replay from `replayJson`, the playback starts with the animation
+    #[wasm_bindgen(js_name = playReplay)]
+    pub fn play_replay(&self, json: String) {
+        self.dispatcher.send(Message::LoadReplay(json));
+    }
+    /// Starts the animation and pauses it after exactly this number of generations
+    #[wasm_bindgen(js_name = runFor)]
+    pub fn run_for(&self, generations: u64) {
+        self.dispatcher.send(Message::RunFor(generations));
+    }
+    /// Restores the field as it was loaded or generated, and starts again from generation 0
+    pub fn reset(&self) {
+        self.dispatcher.send(Message::Reset);
+    }
+    /// Stops the animation, removes the event listeners and the elements of the app and frees its memory
+    pub fn destroy(self) {
+        self.dispatcher.send(Message::Destroy);
+        // the animation closures only hold weak references, so the app is freed with the last listener
+        drop(self.listeners);
+        drop(self.resize_watcher);
+    }
+}
+
+impl GameOfLifeApp {
+    /// Creates the app at the end of the container and draws the initial field.
+    /// If that fails, the app only shows the error
+    pub(crate) fn create(container: &web_sys::Element, options: Options) -> Result<GameOfLifeApp, AppError> {
+        panic::install_hook();
+
+        let window = web_sys::window().ok_or(AppError::Missing("window"))?;
+        let document = window.document().ok_or(AppError::Missing("document"))?;
+        let root = document.create_element("div")?;
+        container.append_child(&root)?;
+        let banner = ErrorBanner::new(&document)?;
+        root.append_child(banner.element())?;
+        Self::build(window, document, root.clone(), banner.clone(), options).inspect_err(|err| {
+            // the elements created so far are removed, so that only the error is left on the page
+            root.set_text_content(None);
+            let _ = root.append_child(banner.element());
+            banner.show(err);
+        })
+    }
+
+    fn build(window: Window, document: Document, root: web_sys::Element, banner: ErrorBanner, options: Options) -> Result<GameOfLifeApp, AppError> {
+        if let Some(level) = options.log_level {
+            log::set_level(level);
+        }
+        if options.log_panel {
+            log::show_panel(&document)?;
+        }
+        if options.profile {
+            profile::set_enabled(true);
+        }
+        console_debug!("Creating the app with {options:?}");
+        let mut listeners = Listeners::default();
+        let hide_closure = {
+            let banner = banner.clone();
+            Closure::<dyn Fn()>::new(move || banner.hide())
+        };
+        listeners.add(banner.element(), &["click"], hide_closure)?;
+
+        let canvas = document.create_element("canvas")?;
+        let canvas = canvas.dyn_into::<web_sys::HtmlCanvasElement>()?;
+        let context = context_2d(&canvas)?;
+
+        // the grid is drawn once on a separate canvas under the transparent cell canvas
+        let grid_canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+        grid_canvas.set_attribute("style", "position: absolute; left: 0; top: 0;")?;
+        let grid_context = context_2d(&grid_canvas)?;
+        canvas.set_attribute("style", "position: relative;")?;
+        // focusable, and the keys go to the app instead of the screen reader
+        canvas.set_attribute("tabindex", "0")?;
+        canvas.set_attribute("role", "application")?;
+        canvas.set_attribute("aria-label", "Field. Arrow keys move between the cells, Enter or Space toggles the cell")?;
+
+        let stats_canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+        let stats_context = context_2d(&stats_canvas)?;
+        let size = options.field_size;
+        let density = options.density / 100.0;
+        let fps_meter = FpsMeter::new(&document, &root, options.fps_update_interval)?;
+        let activity_panel = ActivityPanel::new(&document, options.fps_update_interval)?;
+        let mut state = AnimationState::new(StatsGraph::new(stats_context), fps_meter, activity_panel, grid_context, &document, root.clone(), banner)?;
+        // the speed can still be changed with the fps toggle
+        let reduced_motion = options.reduced_motion.unwrap_or_else(|| prefers_reduced_motion(&window));
+        let reduced_fps = options.target_fps.unwrap_or(if reduced_motion { REDUCED_MOTION_FPS } else { REDUCED_FPS });
+        state.target_fps = if reduced_motion { Some(reduced_fps) } else { options.target_fps };
+        state.time_history_ms.set_capacity(options.fps_window);
+        state.auto_pause = options.auto_pause;
+        state.history.set_capacity(options.history_size);
+        if options.adaptive {
+            state.adaptive = Some(AdaptiveQuality::new(Thresholds::with_budget(options.frame_budget)));
+        }
+        state.cell_size_px = options.cell_size;
+        state.max_cell_size_px = options.cell_size;
+        state.theme = options.theme.theme();
+        if let Some(rule) = &options.rule {
+            state.life_rule = rule.parse().map_err(|_| AppError::InvalidOptions(format!("invalid rule {rule}")))?;
+        }
+
+        let (mut field, initial) = match &options.pattern {
+            Some(pattern) => (make_pattern_field(pattern, size, density)?, None),
+            None => {
+                let initial = random_soup(size, density);
+                let mut field = Field::new(NonZeroUsize::MIN, NonZeroUsize::MIN);
+                // soups are always valid
+                let _ = initial.fill(&mut field);
+                (Box::new(field) as Box<dyn DynAutomaton>, Some(initial))
+            }
+        };
+        // automata without a configurable rule keep their own
+        let _ = field.set_rule(&state.life_rule.to_string());
+        state.replay = start_replay(field.as_mut(), initial);
+        draw_initial_state(field.as_ref(), &canvas, &context, &state.grid_context, state.cell_size_px)?;
+        update_grid_visibility(&state)?;
+        state.analysis.push(0, field.as_ref());
+        state.history.push(0, field.as_ref());
+        let scrubber = Scrubber::new(&document)?;
+        scrubber.update(&state.history, 0, true);
+        let scrubber_input = scrubber.input().clone();
+        let scrubber_element = scrubber.element().clone();
+
+        let generation = Rc::clone(&state.generation);
+        let initial_rule = state.life_rule;
+        let stats_graph_canvas = state.stats_graph.canvas();
+        let minimap_canvas = state.minimap.canvas();
+        let magnifier_canvas = state.magnifier.canvas();
+        let activity_element = state.activity_panel.element().clone();
+        let video = VideoControls::new(&document)?;
+        let benchmark_report = document.create_element("pre")?;
+        #[cfg(feature = "network")]
+        let peer_signal = document.create_element("textarea")?.dyn_into::<web_sys::HtmlTextAreaElement>()?;
+        let live_region = LiveRegion::new(&document)?;
+        let live_region_element = live_region.element().clone();
+        let cursor = CellCursor::new(&document)?;
+        let cursor_element = cursor.element().clone();
+        #[cfg(feature = "audio")]
+        let playhead = Playhead::new(&document)?;
+        #[cfg(feature = "audio")]
+        let playhead_element = playhead.element().clone();
+        let status_bar = StatusBar::new(&document)?;
+        let status_bar_element = status_bar.element().clone();
+        let context_menu = ContextMenu::new(&document)?;
+        let context_menu_element = context_menu.element().clone();
+        let edit_flash = EditFlash::new(&document)?;
+        let edit_flash_canvas = edit_flash.canvas().clone();
+        let stamp = Field::from_str(STAMP_PATTERN).map_err(|err| AppError::InvalidOptions(format!("invalid stamp: {err:?}")))?;
+        let stamp_preview = StampPreview::new(&document)?;
+        let stamp_preview_canvas = stamp_preview.canvas().clone();
+        let compare = ComparePanel::new(&document)?;
+        let compare_element = compare.element().clone();
+        let board_library = BoardLibrary::new(&document)?;
+        let topology_select = create_topology_select(&document)?;
+        let noise_input = create_noise_input(&document)?;
+        let dispatcher = Rc::new_cyclic(|dispatcher: &Weak<AppDispatcher>| {
+            let frame_closure = {
+                let dispatcher = dispatcher.clone();
+                Closure::<dyn Fn(f64)>::new(move |timestamp_ms: f64| {
+                    if let Some(dispatcher) = dispatcher.upgrade() {
+                        dispatcher.send(Message::Frame(timestamp_ms));
+                    }
+                })
+            };
+            let flash_closure = {
+                let dispatcher = dispatcher.clone();
+                Closure::<dyn Fn(f64)>::new(move |timestamp_ms: f64| {
+                    if let Some(dispatcher) = dispatcher.upgrade() {
+                        dispatcher.send(Message::FlashFrame(timestamp_ms));
+                    }
+                })
+            };
+            let timeout_closure = {
+                let dispatcher = dispatcher.clone();
+                Closure::<dyn Fn()>::new(move || {
+                    if let Some(dispatcher) = dispatcher.upgrade() {
+                        dispatcher.send(Message::RequestFrame);
+                    }
+                })
+            };
+            let mut app = App {
+                dispatcher: dispatcher.clone(),
+                seed: Seed { field: field.clone_box(), replay: None },
+                pixel_ratio: window.device_pixel_ratio(),
+                window: window.clone(),
+                document: document.clone(),
+                root: root.clone(),
+                canvas: canvas.clone(),
+                context,
+                field,
+                state,
+                frame_closure,
+                timeout_closure,
+                flash_closure,
+                board_closure: board_library::loaded_closure(dispatcher),
+                paint_state: None,
+                line_draft: None,
+                pinch: None,
+                reduced_fps,
+                video,
+                scrubber,
+                live_region,
+                cursor,
+                status_bar,
+                topology_select: topology_select.clone(),
+                noise_input: noise_input.clone(),
+                context_menu,
+                edit_flash,
+                stamp,
+                stamp_preview,
+                compare,
+                board_library,
+                benchmark_report: benchmark_report.clone(),
+                #[cfg(feature = "network")]
+                sync: None,
+                #[cfg(feature = "network")]
+                peer: None,
+                #[cfg(feature = "network")]
+                peer_signal: peer_signal.clone(),
+                #[cfg(feature = "audio")]
+                sonifier: None,
+                #[cfg(feature = "audio")]
+                playhead,
+                #[cfg(feature = "webgl")]
+                torus: None,
+                #[cfg(feature = "webgl")]
+                voxels: None,
+            };
+            app.update_status();
+            Dispatcher::new(app, App::handle)
+        });
+        panic::register(&root, Rc::downgrade(&dispatcher) as Weak<dyn panic::FieldSource>, generation);
+
+        let panel = ControlPanel::new(&document)?;
+        let playback = panel.group("Playback", true)?;
+        let board = panel.group("Board", true)?;
+        let rule = panel.group("Rule", false)?;
+        let rendering = panel.group("Rendering", false)?;
+        let export = panel.group("Import/Export", false)?;
+        let saved_boards = panel.group("Saved Boards", false)?;
+        #[cfg(feature = "network")]
+        let live_sync = panel.group("Live Sync", false)?;
+
+        let play_button = create_button("Play/Pause", || Message::TogglePlay, &document, &mut listeners, &dispatcher)?;
+        // the slash is read out otherwise, the state is announced in the live region
+        play_button.set_attribute("aria-label", "Play or pause")?;
+        playback.add(&play_button)?;
+
+        let reset_button = create_button("Reset", || Message::Reset, &document, &mut listeners, &dispatcher)?;
+        playback.add(&reset_button)?;
+
+        let run_button = create_run_button(&document, &mut listeners, &dispatcher)?;
+        playback.add(&run_button)?;
+
+        let fps_button = create_button("Toggle FPS", || Message::ToggleFps, &document, &mut listeners, &dispatcher)?;
+        playback.add(&fps_button)?;
+
+        #[cfg(feature = "audio")]
+        audio::add_sound_controls(&playback, &document, &mut listeners, &dispatcher)?;
+
+        let auto_pause_button = create_button("Toggle Auto Pause", || Message::ToggleAutoPause, &document, &mut listeners, &dispatcher)?;
+        playback.add(&auto_pause_button)?;
+
+        view_settings::add_view_controls(&rendering, &document, &mut listeners, &dispatcher)?;
+        #[cfg(feature = "webgl")]
+        torus_view::add_torus_button(&rendering, &document, &mut listeners, &dispatcher)?;
+        diagnostics::add_diagnostics_controls(&rendering, &document, &mut listeners, &dispatcher)?;
+
+        export::add_export_controls(&export, &document, &mut listeners, &dispatcher)?;
+        share::add_share_button(&export, &document, &mut listeners, &dispatcher)?;
+        pattern_file::add_file_controls(&export, &canvas, &document, &mut listeners, &dispatcher)?;
+
+        let replay_button = create_button("Save Replay", || Message::SaveReplay, &document, &mut listeners, &dispatcher)?;
+        export.add(&replay_button)?;
+
+        let replay_input = create_replay_input(&document, &mut listeners, &dispatcher)?;
+        export.add(&replay_input)?;
+
+        recorder::add_record_controls(&export, &document, &mut listeners, &dispatcher)?;
+
+        #[cfg(feature = "network")]
+        {
+            let sync_controls = websocket_sync::create_sync_controls(&document, &mut listeners, &dispatcher)?;
+            live_sync.add(&sync_controls)?;
+            let peer_controls = peer::create_peer_controls(&peer_signal, &document, &mut listeners, &dispatcher)?;
+            live_sync.add(&peer_controls)?;
+        }
+
+        board_library::add_board_controls(&saved_boards, &document, &mut listeners, &dispatcher)?;
+
+        field_input::add_input_listeners(&canvas, &minimap_canvas, &window, &mut listeners, &dispatcher)?;
+        context_menu::add_menu_controls(&canvas, &document, &mut listeners, &dispatcher)?;
+        if embed::parent(&window).is_some() {
+            add_event_listener(&mut listeners, &window, &["message"], &dispatcher, Message::Embedded)?;
+        }
+        add_message_listener(&mut listeners, &window, &["scroll", "resize"], &dispatcher, || Message::ViewportChanged)?;
+
+        let brush_select = field_input::create_brush_select(&document, &mut listeners, &dispatcher)?;
+        board.add(&brush_select)?;
+        presets::add_preset_controls(&board, size, density, &document, &mut listeners, &dispatcher)?;
+
+        rule_editor::add_rule_controls(&rule, initial_rule, &topology_select, &noise_input, &document, &mut listeners, &dispatcher)?;
+        compare::add_compare_controls(&rule, &document, &mut listeners, &dispatcher)?;
+        root.append_child(panel.element())?;
+        root.append_child(&benchmark_report)?;
+
+        let br = document.create_element("br")?;
+        root.append_child(&br)?;
+
+        let layers = document.create_element("div")?;
+        layers.set_attribute("style", "position: relative; display: inline-block;")?;
+        layers.append_child(&grid_canvas)?;
+        layers.append_child(&canvas)?;
+        layers.append_child(&stamp_preview_canvas)?;
+        layers.append_child(&edit_flash_canvas)?;
+        layers.append_child(&cursor_element)?;
+        #[cfg(feature = "audio")]
+        layers.append_child(&playhead_element)?;
+        // the status bar and the scrubber are under the field, next to the other panels
+        let field_column = document.create_element("div")?;
+        field_column.set_attribute("style", "display: inline-block; vertical-align: top;")?;
+        field_column.append_child(&layers)?;
+        field_column.append_child(&status_bar_element)?;
+        field_column.append_child(&scrubber_element)?;
+        field_column.append_child(&compare_element)?;
+        add_message_listener(&mut listeners, &scrubber_input, &["input"], &dispatcher, || Message::Scrub)?;
+        root.append_child(&field_column)?;
+        root.append_child(&stats_graph_canvas)?;
+        root.append_child(&minimap_canvas)?;
+        root.append_child(&magnifier_canvas)?;
+        root.append_child(&activity_element)?;
+        root.append_child(&live_region_element)?;
+        root.append_child(&context_menu_element)?;
+
+        if options.autoplay {
+            dispatcher.send(Message::Play);
+        }
+
+        // the observer also reports the initial size
+        let resize_watcher = if options.fit { Some(ResizeWatcher::new(&root, &dispatcher)?) } else { None };
+
+        Ok(GameOfLifeApp { dispatcher, listeners, resize_watcher })
+    }
+}
+
+/// Wasm memory, so that JS can create views over the buffers returned by `Field::cells_ptr`
+#[wasm_bindgen]
+pub fn wasm_memory() -> JsValue {
+    wasm_bindgen::memory()
+}
+
+/// Requests to the app from the event listeners, the animation loop and the JS API.
+/// The listeners only send them, the app handles them one at a time in `App::handle`.
+/// The requests of a feature are grouped in its own enum, and handled in its module
+pub enum Message {
+    /// Starts the animation, if it isn't running
+    Play,
+    /// Stops the animation, if it is running
+    Pause,
+    TogglePlay,
+    /// Starts the animation and pauses it after this number of generations
+    RunFor(u64),
+    /// Animation frame, with the time when the frame started
+    Frame(f64),
+    /// The wait of a limited frame rate has passed
+    RequestFrame,
+    /// The cells have been changed other than by a new generation, by an edit or a new field. The views that are drawn
+    /// after the message, the 3D ones and the frames of the broadcast, are only updated after this one and after frames
+    FieldChanged,
+    /// Message posted to the window, commands of the page that embeds the app are handled
+    Embedded(web_sys::MessageEvent),
+    /// Animation frame of the outlines of the edited cells
+    FlashFrame(f64),
+    /// Mouse, touch and keyboard input on the field
+    Input(FieldInput),
+    Menu(MenuCommand),
+    /// Replaces the field with a new one from the factory
+    LoadField(Rc<dyn Fn() -> Box<dyn DynAutomaton>>),
+    /// Refills the field in place if it is a life field, otherwise replaces it with a new life field
+    FillField(Rc<dyn Fn(&mut Field)>),
+    /// Restores the field as it was loaded or generated, and starts again from generation 0
+    Reset,
+    /// Fills the field with a square random soup of this size and share of live cells, from a new seed
+    LoadSoup(NonZeroUsize, f64),
+    /// Replaces the field with an imported life field
+    ShowField(Field),
+    /// Replaces the field with a life field with the text written in cells
+    LoadText(String),
+    /// Shows the generation chosen with the scrubber, while paused
+    Scrub,
+    /// Downloads the replay of the current field as JSON
+    SaveReplay,
+    /// Starts the playback of a replay in JSON
+    LoadReplay(String),
+    File(FileCommand),
+    #[cfg(feature = "network")]
+    Sync(SyncCommand),
+    #[cfg(feature = "network")]
+    Peer(PeerCommand),
+    Board(BoardCommand),
+    Rule(RuleCommand),
+    View(ViewCommand),
+    ToggleFps,
+    /// Toggles pausing when the field becomes a still life or starts repeating
+    ToggleAutoPause,
+    #[cfg(feature = "audio")]
+    Sound(SoundCommand),
+    /// Shows or hides the field on a 3D torus
+    #[cfg(feature = "webgl")]
+    ToggleTorus,
+    /// Turns or zooms the camera of the 3D field
+    #[cfg(feature = "webgl")]
+    Orbit(Orbit),
+    Compare(CompareCommand),
+    Export(ExportCommand),
+    Share(ShareCommand),
+    Record(RecordCommand),
+    Diagnostics(DiagnosticsCommand),
+    /// The window has been scrolled or resized
+    ViewportChanged,
+    /// The content of the app element has this width in css pixels now
+    ContainerResized(f64),
+    /// Replaces the callback of the event with this name
+    SetCallback(&'static str, Option<js_sys::Function>),
+    /// Stops the animation and removes the elements of the app
+    Destroy,
+}
+
+pub type AppDispatcher = Dispatcher<App, Message>;
+
+/// State of an app together with its elements, owned by its dispatcher
+pub struct App {
+    /// Sends the results of background tasks, see `spawn`
+    pub dispatcher: Weak<AppDispatcher>,
+    pub window: Window,
+    pub document: Document,
+    /// Element that contains everything the app has added to the page
+    pub root: web_sys::Element,
+    pub canvas: HtmlCanvasElement,
+    pub context: CanvasRenderingContext2d,
+    pub field: Box<dyn DynAutomaton>,
+    /// The field as it was loaded, for the Reset button
+    pub seed: Seed,
+    pub state: AnimationState,
+    /// Sends `Message::Frame`. The animation closures only hold weak references to the dispatcher, so that they don't keep each other alive
+    pub frame_closure: Closure<dyn Fn(f64)>,
+    /// Sends `Message::RequestFrame` after the wait of a limited frame rate
+    pub timeout_closure: Closure<dyn Fn()>,
+    /// Sends `Message::FlashFrame`, the outlines of the edits are animated also while paused
+    pub flash_closure: Closure<dyn Fn(f64)>,
+    /// Sends `BoardCommand::Loaded` when a saved board has been read
+    pub board_closure: Closure<dyn Fn(web_sys::Event)>,
+    /// State of the cell that was clicked, it is painted on all the cells the mouse is dragged over
+    pub paint_state: Option<u8>,
+    /// Line that follows the cursor while dragging with the line brush
+    pub line_draft: Option<LineDraft>,
+    /// Two finger gesture that zooms and pans, while both fingers are on the field
+    pub pinch: Option<Pinch>,
+    /// The field is redrawn when `devicePixelRatio` changes, e.g. on browser zoom or when moving to another screen
+    pub pixel_ratio: f64,
+    /// Speed limit of the Toggle FPS button
+    pub reduced_fps: f64,
+    pub video: VideoControls,
+    pub scrubber: Scrubber,
+    /// Reads out the changes of the state to screen reader users
+    pub live_region: LiveRegion,
+    /// Cell chosen with the keyboard
+    pub cursor: CellCursor,
+    pub status_bar: StatusBar,
+    /// Shows the topology of the current Life field, disabled for other fields
+    pub topology_select: HtmlSelectElement,
+    /// Shows the noise of the current Life field, disabled for other fields
+    pub noise_input: HtmlInputElement,
+    pub context_menu: ContextMenu,
+    /// Outlines the cells that the user has just edited
+    pub edit_flash: EditFlash,
+    /// Pattern of the stamp, as rotated and flipped by the user
+    pub stamp: Field,
+    /// Shows where the stamp would be pasted
+    pub stamp_preview: StampPreview,
+    /// Copies of the field with different rules, advancing with the animation
+    pub compare: ComparePanel,
+    /// Boards saved in IndexedDB, with their gallery
+    pub board_library: BoardLibrary,
+    /// Shows the report of the last benchmark or engine verification
+    pub benchmark_report: web_sys::Element,
+    /// Connection that mirrors the field to or from other viewers
+    #[cfg(feature = "network")]
+    pub sync: Option<WebSocketSync>,
+    /// Shared editing with another browser
+    #[cfg(feature = "network")]
+    pub peer: Option<PeerSession>,
+    /// The offer or answer of this browser, and the one pasted from the other browser
+    #[cfg(feature = "network")]
+    pub peer_signal: web_sys::HtmlTextAreaElement,
+    /// Created by the first sound toggle, browsers don't allow audio before a user gesture
+    #[cfg(feature = "audio")]
+    pub sonifier: Option<Sonifier>,
+    /// Row whose live cells are played as notes instead of the tone of the population
+    #[cfg(feature = "audio")]
+    pub playhead: Playhead,
+    /// Created when it is first shown
+    #[cfg(feature = "webgl")]
+    pub torus: Option<TorusView>,
+    /// Created when the first 3D field is loaded, shown while the field is 3D
+    #[cfg(feature = "webgl")]
+    pub voxels: Option<VoxelView>,
+}
+impl App {
+    /// Handles the message, the errors are shown by `handle`. The requests of the features are routed to their modules
+    pub fn update(&mut self, message: Message) -> Result<(), AppError> {
+        match message {
+            Message::Play if !self.state.is_running() => self.play(),
+            Message::Play => Ok(()),
+            Message::Pause => {
+                if self.state.is_running() {
+                    self.pause();
+                }
+                Ok(())
+            }
+            Message::TogglePlay if self.state.is_running() => {
+                self.pause();
+                Ok(())
+            }
+            Message::TogglePlay => self.play(),
+            Message::RunFor(0) => Ok(()),
+            Message::RunFor(generations) => {
+                let stop_at = stop_generation(self.state.generation.get(), generations);
+                let result = if self.state.is_running() { Ok(()) } else { self.play() };
+                // playing clears the stop of the previous run
+                self.state.stop_at = Some(stop_at);
+                result
+            }
+            Message::Embedded(event) => self.handle_host_message(&event),
+            Message::Frame(timestamp_ms) => {
+                let result = self.draw_frame(timestamp_ms);
+                if result.is_err() {
+                    // the animation can't continue without drawing the frames
+                    pause(&self.window, &mut self.state);
+                }
+                result
+            }
+            Message::RequestFrame => self.request_frame(),
+            // handled by `handle`, after the edit has been drawn
+            Message::FieldChanged => Ok(()),
+            Message::FlashFrame(timestamp_ms) => self.flash_frame(timestamp_ms),
+            Message::Input(input) => self.update_input(input),
+            Message::Menu(command) => self.update_menu(command),
+            Message::LoadField(_) | Message::Reset | Message::FillField(_) | Message::LoadSoup(..) | Message::LoadReplay(_) if !self.confirm_discard() => Ok(()),
+            Message::LoadField(factory) => {
+                self.field = factory();
+                self.seed = Seed { field: self.field.clone_box(), replay: None };
+                let result = show_new_field(&mut self.state, &self.context, self.field.as_mut(), None);
+                self.update_scrubber();
+                self.queue_field_changed();
+                #[cfg(feature = "webgl")]
+                let result = result.and(self.create_voxels());
+                result
+            }
+            Message::Reset => self.reset(),
+            Message::FillField(fill) => self.fill_field(fill.as_ref(), None),
+            Message::LoadSoup(size, density) => {
+                let initial = random_soup(size, density);
+                // soups are always valid
+                self.fill_field(&|field| { let _ = initial.fill(field); }, Some(initial.clone()))
+            }
+            Message::ShowField(field) => self.show_life_field(field),
+            Message::LoadText(text) if text.trim().is_empty() => Err(AppError::InvalidInput("enter the text to write".to_string())),
+            Message::LoadText(_) if !self.confirm_discard() => Ok(()),
+            Message::LoadText(text) => self.show_life_field(Field::from_text(&text).with_rule(self.state.life_rule)),
+            Message::Scrub => self.scrub(),
+            Message::SaveReplay => self.save_replay(),
+            Message::LoadReplay(json) => self.load_replay(&json),
+            Message::File(command) => self.update_file(command),
+            #[cfg(feature = "network")]
+            Message::Sync(command) => self.update_sync(command),
+            #[cfg(feature = "network")]
+            Message::Peer(command) => self.update_peer(command),
+            Message::Board(command) => self.update_board(command),
+            Message::Rule(command) => self.update_rule(command),
+            Message::View(command) => self.update_view(command),
+            Message::ToggleFps => {
+                self.toggle_fps();
+                Ok(())
+            }
+            Message::ToggleAutoPause => {
+                self.state.auto_pause = !self.state.auto_pause;
+                self.state.reset_periods();
+                Ok(())
+            }
+            #[cfg(feature = "audio")]
+            Message::Sound(command) => self.update_sound(command),
+            #[cfg(feature = "webgl")]
+            Message::ToggleTorus => self.toggle_torus(),
+            #[cfg(feature = "webgl")]
+            Message::Orbit(orbit) => {
+                self.orbit(orbit);
+                Ok(())
+            }
+            Message::Compare(command) => self.update_compare(command),
+            Message::Export(command) => self.update_export(command),
+            Message::Share(command) => self.update_share(command),
+            Message::Record(command) => self.update_recording(command),
+            Message::Diagnostics(command) => self.update_diagnostics(command),
+            Message::ViewportChanged => {
+                // the menu is fixed to the window, it would stay there while the field moves
+                self.context_menu.hide();
+                self.viewport_changed()
+            }
+            Message::ContainerResized(width) => {
+                self.state.fit_width = Some(width);
+                self.set_cell_size(fit_cell_size(self.field.as_ref(), width, self.state.max_cell_size_px))
+            }
+            Message::SetCallback(name, callback) => {
+                match name {
+                    events::GENERATION => self.state.on_tick = callback,
+                    events::PAUSED => self.state.on_pause = callback,
+                    events::RESUMED => self.state.on_resume = callback,
+                    _ => console_warn!("No callback for the event {name}"),
+                }
+                Ok(())
+            }
+            Message::Destroy => {
+                pause(&self.window, &mut self.state);
+                panic::unregister(&self.root);
+                self.root.remove();
+                Ok(())
+            }
+        }
+    }
+
+    /// Handles the message and shows its error, then updates what follows every change
+    fn handle(&mut self, message: Message) {
+        #[cfg(feature = "webgl")]
+        let is_frame = matches!(message, Message::Frame(_));
+        #[cfg(any(feature = "webgl", feature = "network"))]
+        let is_edit = matches!(message, Message::FieldChanged);
+        #[cfg(feature = "network")]
+        let (generation, was_running) = (self.state.generation.get(), self.state.is_running());
+        let result = self.update(message);
+        self.state.banner.report(result);
+        #[cfg(feature = "audio")]
+        {
+            if let Some(sonifier) = self.sonifier.as_ref().filter(|_| !self.state.is_running()) {
+                sonifier.silence();
+            }
+            // the field can have been resized or replaced
+            self.playhead.update(self.field.as_ref(), self.state.cell_size_px);
+        }
+        // the torus only turns while running, and it is drawn again after edits while paused
+        #[cfg(feature = "webgl")]
+        if is_frame || is_edit {
+            if let Some(torus) = &mut self.torus {
+                torus.draw(self.field.as_ref(), now_ms(&self.window));
+            }
+            if let Some(voxels) = &self.voxels {
+                let field = self.field.as_any_mut().downcast_ref::<Life3d>();
+                voxels.set_visible(field.is_some());
+                if let Some(field) = field {
+                    voxels.draw(field);
+                }
+            }
+        }
+        // edits while paused are mirrored too, not only the generations
+        #[cfg(feature = "network")]
+        {
+            if is_edit || (self.state.generation.get() != generation) {
+                let result = self.broadcast();
+                self.state.banner.report(result);
+            }
+            self.share_running(was_running);
+        }
+        self.update_status();
+    }
+
+    /// Shows the state after every message, the status bar skips the writes when nothing has changed
+    fn update_status(&mut self) {
+        let mode = match (self.state.is_running(), self.state.is_playback) {
+            (true, true) => Mode::Playback,
+            (true, false) => Mode::Run,
+            (false, _) => Mode::Edit,
+        };
+        self.status_bar.render(Status {
+            name: self.field.meta().and_then(|meta| meta.name.clone()),
+            generation: self.state.generation.get(),
+            population: events::population(self.field.as_ref()),
+            teams: self.field.as_any_mut().downcast_ref::<ColoredLife>().map_or_else(Vec::new, |field| {
+                ColorCell::NAMES[1..].iter().copied().zip(field.team_populations()).collect()
+            }),
+            rule: self.field.rule(),
+            width: self.field.get_width(),
+            height: self.field.get_height(),
+            zoom_percent: (self.state.cell_size_px + 1) * 100 / (CELL_SIZE_PX + 1),
+            mode,
+        });
+        self.update_rule_controls();
+    }
+
+    pub fn play(&mut self) -> Result<(), AppError> {
+        self.state.stop_at = None;
+        if self.state.show_ghost {
+            // only the changed cells are drawn while running, so the ghost has to be removed first
+            draw_field(&self.context, self.field.as_ref(), &self.state, true);
+        }
+        self.drop_later_generations();
+        self.live_region.announce("Playing");
+        self.state.dispatch_event(events::RESUMED, self.field.as_ref());
+        let result = self.start_animation();
+        self.update_scrubber();
+        result
+    }
+
+    pub fn pause(&mut self) {
+        pause(&self.window, &mut self.state);
+        // the edits are not left for a generation that won't come
+        self.apply_pending_edits();
+        draw_ghost_if_paused(&self.context, self.field.as_mut(), &self.state);
+        self.state.dispatch_event(events::PAUSED, self.field.as_ref());
+        self.update_scrubber();
+        self.announce_pause("Paused");
+    }
+
+    /// Announces why the animation has stopped and at which generation
+    fn announce_pause(&self, reason: &str) {
+        self.live_region.announce(&format!("{reason} at generation {}", self.state.generation.get()));
+    }
+
+    /// Forgets the generations after the current one, the run continues from the generation the user has gone back to.
+    /// The playback of a replay keeps its changes, they are applied again when the field reaches their generations
+    fn drop_later_generations(&mut self) {
+        let generation = self.state.generation.get();
+        self.state.analysis.truncate_after(generation);
+        self.state.metrics.truncate_after(generation);
+        if let (Some(replay), false) = (&mut self.state.replay, self.state.is_playback) {
+            replay.truncate_after(generation);
+        }
+    }
+
+    /// Shows the generation chosen with the scrubber
+    fn scrub(&mut self) -> Result<(), AppError> {
+        if self.state.is_running() {
+            return Ok(());
+        }
+        let Some(snapshot) = self.scrubber.position().and_then(|position| self.state.history.get(position)) else {
+            return Ok(());
+        };
+        let old_size = (self.field.get_width(), self.field.get_height());
+        self.field = snapshot.field.clone_box();
+        self.state.generation.set(snapshot.generation);
+        self.state.reset_periods();
+        if self.state.trail_renderer.is_some() {
+            self.state.trail_renderer = Some(TrailRenderer::default());
+        }
+        if old_size == (self.field.get_width(), self.field.get_height()) {
+            draw_field(&self.context, self.field.as_ref(), &self.state, true);
+        } else {
+            draw_initial_state(self.field.as_ref(), &self.canvas, &self.context, &self.state.grid_context, self.state.cell_size_px)?;
+            if self.state.image_renderer.is_some() {
+                draw_field(&self.context, self.field.as_ref(), &self.state, true);
+            }
+        }
+        update_minimap(&self.context, self.field.as_ref(), &self.state);
+        update_magnifier(&self.context, self.field.as_ref(), &self.state);
+        draw_ghost_if_paused(&self.context, self.field.as_mut(), &self.state);
+        self.state.is_stable = false;
+        self.state.dispatch_event(events::FIELD_CHANGED, self.field.as_ref());
+        self.update_scrubber();
+        self.queue_field_changed();
+        Ok(())
+    }
+
+    pub fn update_scrubber(&self) {
+        self.scrubber.update(&self.state.history, self.state.generation.get(), !self.state.is_running());
+    }
+
+    /// Starts the animation from the next animation frame
+    fn start_animation(&mut self) -> Result<(), AppError> {
+        // the time while paused is not a frame time
+        self.state.last_frame_ts_ms = None;
+        if let Some(adaptive) = &mut self.state.adaptive {
+            adaptive.reset();
+        }
+        self.request_frame()
+    }
+
+    /// Schedules the next animation frame
+    fn request_frame(&mut self) -> Result<(), AppError> {
+        self.state.next_frame = Some(self.window.request_animation_frame(self.frame_closure.as_ref().unchecked_ref())?);
+        Ok(())
+    }
+
+    /// Calculates and draws the next generation, and schedules the next frame.
+    /// Animation frame callbacks get the time when the frame started, it is the same for all callbacks of the frame
+    fn draw_frame(&mut self, timestamp_ms: f64) -> Result<(), AppError> {
+        let _frame_span = profile::span(profile::FRAME);
+        let work_start_ms = now_ms(&self.window);
+        // drawn before the step, so the changes of the step are drawn over them
+        self.apply_pending_edits();
+        let state = &mut self.state;
+        let field = &mut self.field;
+        let context = &self.context;
+
+        let frame_ms = record_frame_time(state, timestamp_ms);
+        state.fps_meter.update(timestamp_ms, &state.time_history_ms);
+
+        let old_size = (field.get_width(), field.get_height());
+        let has_alive = {
+            let _span = profile::span(profile::UPDATE);
+            field.step()
+        };
+        state.generation.set(state.generation.get() + 1);
+        // the compared copies advance in lockstep with the field
+        self.compare.step();
+        let mut is_replay_changed = false;
+        if let (Some(replay), Some(life_field), true) = (&state.replay, field.as_any_mut().downcast_mut::<Field>(), state.is_playback) {
+            is_replay_changed = replay.apply(state.generation.get(), life_field).map_err(|err| AppError::InvalidReplay(format!("{err:?}")))? > 0;
+        }
+        let draw_span = profile::span(profile::DRAW);
+        if old_size == (field.get_width(), field.get_height()) {
+            // the changes of the replay are not among the changed cells of the step
+            draw_field(context, field.as_ref(), state, is_replay_changed);
+        } else {
+            // the field has grown, so the canvas has to be resized
+            draw_initial_state(field.as_ref(), &self.canvas, context, &state.grid_context, state.cell_size_px)?;
+            if state.image_renderer.is_some() {
+                draw_field(context, field.as_ref(), state, true);
+            }
+            update_minimap(context, field.as_ref(), state);
+            update_magnifier(context, field.as_ref(), state);
+        }
+        drop(draw_span);
+        let stats_span = profile::span(profile::STATS);
+        if let Some(mut recorder) = state.recorder.take() {
+            if recorder.capture(field.as_ref()) {
+                state.recorder = Some(recorder);
+            } else {
+                save_recording(recorder)?;
+            }
+        }
+        let counts = field.count_by_state();
+        let population = counts.iter().skip(1).sum();
+        state.stats_graph.push(counts);
+        state.stats_graph.draw(field.palette());
+        let activity = field.activity();
+        if let Some(activity) = activity {
+            state.activity_panel.push(timestamp_ms, activity);
+        }
+        state.metrics.push(GenerationMetrics { generation: state.generation.get(), population, activity, frame_ms });
+        #[cfg(feature = "audio")]
+        if let Some(sonifier) = &self.sonifier {
+            match self.playhead.lane(field.as_ref()) {
+                Some(lane) => {
+                    // the notes last until the next generation, so the tempo follows the speed
+                    let generation_s = state.target_fps.map_or_else(|| frame_ms.unwrap_or(0.0) / 1000.0, |fps| 1.0 / fps);
+                    sonifier.silence();
+                    sonifier.play_notes(&lane_notes(&lane, self.playhead.scale), generation_s)?;
+                }
+                None => sonifier.play(Tone::for_generation(population, activity, field.get_width() * field.get_height())),
+            }
+        }
+        state.analysis.push(state.generation.get(), field.as_ref());
+        state.history.push(state.generation.get(), field.as_ref());
+        state.dispatch_event(events::GENERATION, field.as_ref());
+        let is_stable = !has_alive || field.changed_cells().is_some_and(|cells| cells.is_empty());
+        if is_stable && !state.is_stable {
+            state.dispatch_event(events::STABILIZED, field.as_ref());
+        }
+        state.is_stable = is_stable;
+        let period = if state.auto_pause && !state.is_periodic { state.periods.push(field.as_ref()) } else { None };
+        drop(stats_span);
+        adapt_quality(state, field.as_ref(), context, now_ms(&self.window) - work_start_ms)?;
+        if !has_alive {
+            pause(&self.window, state);
+            state.dispatch_event(events::PAUSED, field.as_ref());
+            self.update_scrubber();
+            self.announce_pause("Died out");
+            return Ok(());
+        }
+        if state.stop_at.is_some_and(|stop_at| state.generation.get() >= stop_at) {
+            state.stop_at = None;
+            pause(&self.window, state);
+            state.dispatch_event(events::PAUSED, field.as_ref());
+            self.update_scrubber();
+            self.announce_pause("Paused");
+            return Ok(());
+        }
+        if let Some(period) = period {
+            state.is_periodic = true;
+            // the first generation of the cycle
+            state.activity_panel.show_stabilized(state.generation.get().saturating_sub(period as u64), period);
+            pause(&self.window, state);
+            state.dispatch_event(events::PAUSED, field.as_ref());
+            self.update_scrubber();
+            self.announce_pause(&format!("Stabilized with period {period}, paused"));
+            return Ok(());
+        }
+        if state.generation.get().is_multiple_of(ANNOUNCED_GENERATIONS) {
+            self.live_region.announce(&format!("Generation {}", state.generation.get()));
+        }
+        if let Some(target_fps) = state.target_fps {
+            let timeout_id = self.window.set_timeout_with_callback_and_timeout_and_arguments_0(
+                self.timeout_closure.as_ref().unchecked_ref(),
+                (1000.0 / target_fps) as i32
+            )?;
+            state.next_timeout.replace(timeout_id);
+            Ok(())
+        } else {
+            // target default fps
+            self.request_frame()
+        }
+    }
+
+    /// Sets the cells to the state and records them in the replay. While running, the edits are queued until the next generation,
+    /// so that they don't get between the step and the drawing of its changes
+    pub fn edit(&mut self, cells: &[(usize, usize)], new_state: u8) {
+        self.flash(cells);
+        if self.state.is_running() {
+            self.state.pending_edits.extend(cells.iter().map(|&(row, col)| (row, col, new_state)));
+            return;
+        }
+        for &(row, col) in cells {
+            if self.field.set_state(row, col, new_state).is_some() {
+                self.record_change(Change::SetCell { row, col, state: new_state });
+            }
+        }
+        self.show_edit();
+    }
+
+    /// Outlines the edited cells for a moment, also the queued edits so that the clicks while running are visible right away
+    pub fn flash(&mut self, cells: &[(usize, usize)]) {
+        let result = self.edit_flash.add(cells, now_ms(&self.window), self.field.as_ref(), self.state.cell_size_px).and_then(|is_started| {
+            if is_started {
+                self.window.request_animation_frame(self.flash_closure.as_ref().unchecked_ref())?;
+            }
+            Ok(())
+        });
+        // the edit itself doesn't depend on the outlines
+        if let Err(err) = result {
+            console_warn!("Failed to outline the edited cells: {err}");
+        }
+    }
+
+    fn flash_frame(&mut self, timestamp_ms: f64) -> Result<(), AppError> {
+        if self.edit_flash.draw(self.field.as_ref(), timestamp_ms) {
+            self.window.request_animation_frame(self.flash_closure.as_ref().unchecked_ref())?;
+        }
+        Ok(())
+    }
+
+    /// Applies the edits made while running, before the step of the next generation or when pausing
+    fn apply_pending_edits(&mut self) {
+        if self.state.pending_edits.is_empty() {
+            return;
+        }
+        for (row, col, state) in std::mem::take(&mut self.state.pending_edits) {
+            if self.field.set_state(row, col, state).is_some() {
+                self.record_change(Change::SetCell { row, col, state });
+            }
+        }
+        self.show_edit();
+    }
+
+    /// Changes the cell size and scrolls the page so that the point of the field that was at `from` is at `to`, in client coordinates
+    pub fn zoom(&mut self, cell_size_px: usize, (from_x, from_y): (f64, f64), (to_x, to_y): (f64, f64)) -> Result<(), AppError> {
+        let rect = self.canvas.get_bounding_client_rect();
+        let fraction_x = (from_x - rect.left()) / rect.width();
+        let fraction_y = (from_y - rect.top()) / rect.height();
+        self.set_cell_size(cell_size_px)?;
+        // the browser keeps the scroll position within the page
+        let rect = self.canvas.get_bounding_client_rect();
+        self.window.scroll_by_with_x_and_y(
+            rect.left() + (fraction_x * rect.width()) - to_x,
+            rect.top() + (fraction_y * rect.height()) - to_y,
+        );
+        Ok(())
+    }
+
+    /// Resizes the canvases for the new size of cells on the screen and draws the field again
+    fn set_cell_size(&mut self, cell_size_px: usize) -> Result<(), AppError> {
+        if cell_size_px == self.state.cell_size_px {
+            return Ok(());
+        }
+        self.state.cell_size_px = cell_size_px;
+        self.cursor.update(self.field.as_ref(), cell_size_px);
+        // it is resized when it is shown again
+        self.stamp_preview.hide();
+        draw_initial_state(self.field.as_ref(), &self.canvas, &self.context, &self.state.grid_context, cell_size_px)?;
+        draw_field(&self.context, self.field.as_ref(), &self.state, true);
+        draw_ghost_if_paused(&self.context, self.field.as_mut(), &self.state);
+        update_grid_visibility(&self.state)
+    }
+
+    /// Records a change of the user in the replay. Changes during the playback end it,
+    /// the changes of the replay after the current generation are dropped, also after going back with the scrubber
+    pub fn record_change(&mut self, change: Change) {
+        #[cfg(feature = "network")]
+        self.share_change(&change);
+        self.record_in_replay(change);
+    }
+
+    /// Records the change without sharing it, e.g. for the changes that come from the peer
+    pub fn record_in_replay(&mut self, change: Change) {
+        let generation = self.state.generation.get();
+        let Some(replay) = &mut self.state.replay else {
+            return;
+        };
+        self.state.is_playback = false;
+        replay.truncate_after(generation);
+        replay.record(generation, change);
+    }
+
+    /// Draws the field after it was edited by the user
+    pub fn show_edit(&mut self) {
+        self.state.is_edited = true;
+        self.drop_later_generations();
+        self.state.reset_periods();
+        // replaces the snapshot from before the edit and the later ones
+        self.state.history.push(self.state.generation.get(), self.field.as_ref());
+        self.update_scrubber();
+        draw_field(&self.context, self.field.as_ref(), &self.state, true);
+        draw_ghost_if_paused(&self.context, self.field.as_mut(), &self.state);
+        self.state.is_stable = false;
+        self.state.dispatch_event(events::FIELD_CHANGED, self.field.as_ref());
+        self.queue_field_changed();
+    }
+
+    /// The 3D views and the viewers of the broadcast are updated after the current message, see `Message::FieldChanged`
+    pub fn queue_field_changed(&self) {
+        if let Some(dispatcher) = self.dispatcher.upgrade() {
+            dispatcher.send(Message::FieldChanged);
+        }
+    }
+
+    /// Refills the field, the replay starts from `initial` if given, otherwise from the new cells
+    fn fill_field(&mut self, fill: &dyn Fn(&mut Field), initial: Option<Initial>) -> Result<(), AppError> {
+        match self.field.as_any_mut().downcast_mut::<Field>() {
+            Some(life_field) => fill(life_field),
+            None => {
+                let mut life_field = Field::new(NonZeroUsize::MIN, NonZeroUsize::MIN);
+                fill(&mut life_field);
+                self.field = Box::new(life_field);
+            }
+        }
+        self.seed = Seed { field: self.field.clone_box(), replay: None };
+        let result = show_new_field(&mut self.state, &self.context, self.field.as_mut(), initial);
+        self.update_scrubber();
+        self.queue_field_changed();
+        result
+    }
+
+    /// Restores the field as it was loaded or generated. Random fields get the same cells again,
+    /// and the replay of a soup keeps its seed
+    fn reset(&mut self) -> Result<(), AppError> {
+        if let Some(replay) = &self.seed.replay {
+            return self.play_back(replay.clone());
+        }
+        let initial = self.state.replay.as_ref().map(|replay| replay.initial.clone());
+        self.field = self.seed.field.clone_box();
+        let result = show_new_field(&mut self.state, &self.context, self.field.as_mut(), initial);
+        self.update_scrubber();
+        self.queue_field_changed();
+        result
+    }
+
+    fn save_replay(&mut self) -> Result<(), AppError> {
+        let Some(replay) = &self.state.replay else {
+            console_warn!("Replays are only recorded for life fields");
+            return Ok(());
+        };
+        let json = replay_to_json(replay)?;
+        let blob = web_sys::Blob::new_with_str_sequence(&js_sys::Array::of1(&json.into()))?;
+        download_blob(&self.document, &blob, "replay.json")?;
+        // the edits can be loaded again from the replay
+        self.state.is_edited = false;
+        Ok(())
+    }
+
+    /// Asks the user whether the edits of the field can be discarded, true if there are none
+    pub fn confirm_discard(&self) -> bool {
+        // the field is replaced if the dialog can't be shown, like before the confirmation
+        !self.state.is_edited || self.window.confirm_with_message("Discard your edits of the field?").unwrap_or(true)
+    }
+
+    fn load_replay(&mut self, json: &str) -> Result<(), AppError> {
+        let value = js_sys::JSON::parse(json)?;
+        let replay = serde_wasm_bindgen::from_value::<Replay>(value).map_err(|err| AppError::InvalidReplay(err.to_string()))?;
+        self.play_back(replay.clone())?;
+        self.seed = Seed { field: self.field.clone_box(), replay: Some(replay) };
+        Ok(())
+    }
+
+    /// Runs the task in the background. Its message is handled when it completes, its error is shown in the banner
+    pub fn spawn(&self, task: impl Future<Output = Result<Option<Message>, AppError>> + 'static) {
+        let dispatcher = self.dispatcher.clone();
+        let banner = self.state.banner.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            match task.await {
+                Ok(message) => {
+                    if let (Some(message), Some(dispatcher)) = (message, dispatcher.upgrade()) {
+                        dispatcher.send(message);
+                    }
+                }
+                Err(err) => banner.show(&err),
+            }
+        });
+    }
+
+    /// Replaces the field with the loaded one, which starts from generation 0 with its own rule
+    pub fn show_life_field(&mut self, mut field: Field) -> Result<(), AppError> {
+        self.state.life_rule = field.get_rule();
+        show_new_field(&mut self.state, &self.context, &mut field, None)?;
+        self.queue_field_changed();
+        self.field = Box::new(field);
+        self.seed = Seed { field: self.field.clone_box(), replay: None };
+        self.update_scrubber();
+        Ok(())
+    }
+
+    /// Shows the initial field of the replay, the playback starts with the animation
+    fn play_back(&mut self, replay: Replay) -> Result<(), AppError> {
+        let invalid = |err| AppError::InvalidReplay(format!("{err:?}"));
+        let mut field = replay.initial_field().map_err(invalid)?;
+        self.state.life_rule = field.get_rule();
+        show_new_field(&mut self.state, &self.context, &mut field, None)?;
+        self.queue_field_changed();
+        if replay.apply(0, &mut field).map_err(invalid)? > 0 {
+            draw_field(&self.context, &field, &self.state, true);
+            self.state.history.push(0, &field);
+        }
+        self.field = Box::new(field);
+        self.state.replay = Some(replay);
+        self.state.is_playback = true;
+        self.update_scrubber();
+        Ok(())
+    }
+
+    /// Toggles between the full speed and `reduced_fps`
+    fn toggle_fps(&mut self) {
+        self.state.target_fps = match self.state.target_fps {
+            Some(_) => None,
+            None => Some(self.reduced_fps),
+        };
+        self.state.adaptive_fps = false;
+        self.state.time_history_ms.truncate();
+    }
+
+    fn viewport_changed(&mut self) -> Result<(), AppError> {
+        let ratio = self.window.device_pixel_ratio();
+        if ratio != self.pixel_ratio {
+            self.pixel_ratio = ratio;
+            draw_initial_state(self.field.as_ref(), &self.canvas, &self.context, &self.state.grid_context, self.state.cell_size_px)?;
+            if self.state.image_renderer.is_some() {
+                draw_field(&self.context, self.field.as_ref(), &self.state, true);
+            }
+            update_grid_visibility(&self.state)?;
+        }
+        update_minimap(&self.context, self.field.as_ref(), &self.state);
+        update_magnifier(&self.context, self.field.as_ref(), &self.state);
+        Ok(())
+    }
+}
+
+/// The panic overlay reads the field through the dispatcher, it is not available if the panic happened while handling a message
+impl panic::FieldSource for AppDispatcher {
+    fn with_field(&self, read: &mut dyn FnMut(&dyn DynAutomaton)) -> bool {
+        self.try_with(|app| read(app.field.as_ref())).is_some()
+    }
+}
+
+/// Observes the size of the app element, it is disconnected when dropped
+struct ResizeWatcher {
+    observer: web_sys::ResizeObserver,
+    _closure: Closure<dyn Fn(js_sys::Array)>,
+}
+impl ResizeWatcher {
+    fn new(target: &web_sys::Element, dispatcher: &Rc<AppDispatcher>) -> Result<Self, AppError> {
+        let dispatcher = Rc::clone(dispatcher);
+        let closure = Closure::<dyn Fn(js_sys::Array)>::new(move |entries: js_sys::Array| {
+            // only the app element is observed, the last entry is its newest size
+            if let Ok(entry) = entries.at(-1).dyn_into::<web_sys::ResizeObserverEntry>() {
+                dispatcher.send(Message::ContainerResized(entry.content_rect().width()));
+            }
+        });
+        let observer = web_sys::ResizeObserver::new(closure.as_ref().unchecked_ref())?;
+        observer.observe(target);
+        Ok(Self { observer, _closure: closure })
+    }
+}
+impl Drop for ResizeWatcher {
+    fn drop(&mut self) {
+        self.observer.disconnect();
+    }
+}
+
+/// Field as it was loaded or generated, so that the run can be repeated
+pub struct Seed {
+    pub field: Box<dyn DynAutomaton>,
+    /// Replay that has been loaded, it is played back again from the start
+    pub replay: Option<Replay>,
+}
+
+/// Sends the message on each of the events of the target
+pub fn add_message_listener(
+    listeners: &mut Listeners,
+    target: &web_sys::EventTarget,
+    event_types: &[&'static str],
+    dispatcher: &Rc<AppDispatcher>,
+    message: impl Fn() -> Message + 'static,
+) -> Result<(), AppError> {
+    let dispatcher = Rc::clone(dispatcher);
+    listeners.add(target, event_types, Closure::<dyn Fn()>::new(move || dispatcher.send(message())))?;
+    Ok(())
+}
+
+/// Sends the message with the event, like a mouse or a touch event, on each of the events of the target
+pub fn add_event_listener<E: FromWasmAbi + 'static>(
+    listeners: &mut Listeners,
+    target: &web_sys::EventTarget,
+    event_types: &[&'static str],
+    dispatcher: &Rc<AppDispatcher>,
+    message: impl Fn(E) -> Message + 'static,
+) -> Result<(), AppError> {
+    let dispatcher = Rc::clone(dispatcher);
+    let closure = Closure::<dyn Fn(_)>::new(move |event: E| dispatcher.send(message(event)));
+    listeners.add(target, event_types, closure)?;
+    Ok(())
+}
+
+pub fn create_button(
+    name: &'static str,
+    message: impl Fn() -> Message + 'static,
+    document: &Document,
+    listeners: &mut Listeners,
+    dispatcher: &Rc<AppDispatcher>,
+) -> Result<web_sys::Element, AppError> {
+    let button = document.create_element("button")?;
+    // not a submit button when the app is inside a form
+    button.set_attribute("type", "button")?;
+    button.set_text_content(Some(name));
+    add_message_listener(listeners, &button, &["click"], dispatcher, message)?;
+    Ok(button)
+}
+
+/// File input that plays back the chosen replay
+fn create_replay_input(document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<web_sys::Element, AppError> {
+    let label = document.create_element("label")?;
+    label.set_text_content(Some("Load Replay "));
+    let input = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    input.set_type("file");
+    input.set_accept(".json,application/json");
+    label.append_child(&input)?;
+    let closure = {
+        let input = input.clone();
+        let dispatcher = Rc::downgrade(dispatcher);
+        Closure::<dyn Fn()>::new(move || {
+            let Some(file) = input.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            // the same file can be chosen again
+            input.set_value("");
+            let Ok(reader) = web_sys::FileReader::new() else {
+                return;
+            };
+            let on_load = {
+                let reader = reader.clone();
+                let dispatcher = dispatcher.clone();
+                Closure::once_into_js(move || {
+                    let json = reader.result().ok().and_then(|result| result.as_string());
+                    if let (Some(json), Some(dispatcher)) = (json, dispatcher.upgrade()) {
+                        dispatcher.send(Message::LoadReplay(json));
+                    }
+                })
+            };
+            reader.set_onload(Some(on_load.unchecked_ref()));
+            if let Err(err) = reader.read_as_text(&file) {
+                console_error!("Failed to read the replay: {err:?}");
+            }
+        })
+    };
+    listeners.add(&input, &["change"], closure)?;
+    Ok(label)
+}
+
+fn create_run_button(document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<web_sys::Element, AppError> {
+    let container = document.create_element("span")?;
+    let generations_input = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    generations_input.set_type("number");
+    generations_input.set_min("1");
+    generations_input.set_value("100");
+    generations_input.set_title("Generations to run before pausing");
+    container.append_child(&generations_input)?;
+
+    let message = move || {
+        let generations = generations_input.value_as_number();
+        Message::RunFor(if generations.is_nan() { 100 } else { generations.max(1.0) as u64 })
+    };
+    let button = create_button("Run", message, document, listeners, dispatcher)?;
+    container.append_child(&button)?;
+    Ok(container)
+}
+
+/// The user has asked the system to minimize animations
+fn prefers_reduced_motion(window: &Window) -> bool {
+    matches!(window.match_media("(prefers-reduced-motion: reduce)"), Ok(Some(query)) if query.matches())
+}
+
+/// Current time for measuring the work of a frame, 0 if the performance API is not available
+pub fn now_ms(window: &Window) -> f64 {
+    window.performance().map_or(0.0, |performance| performance.now())
+}
+
+/// Records the time of the frame for the adaptive quality, and applies the quality when it changes.
+/// Only the settings changed by the adaptive quality are restored, the choices of the user are kept
+fn adapt_quality(state: &mut AnimationState, field: &dyn DynAutomaton, context: &CanvasRenderingContext2d, frame_ms: f64) -> Result<(), AppError> {
+    let Some(quality) = state.adaptive.as_mut().and_then(|adaptive| adaptive.record(frame_ms)) else {
+        return Ok(());
+    };
+    console_log!("A generation took {frame_ms:.1} ms, changing the quality to {quality:?}");
+    let is_coarse = quality >= Quality::CoarseRenderer;
+    if is_coarse && state.image_renderer.is_none() {
+        let document = field_canvas(context)?.owner_document().ok_or(AppError::Missing("document"))?;
+        state.image_renderer = Some(ImageRenderer::new(&document)?);
+        state.adaptive_renderer = true;
+    } else if !is_coarse && state.adaptive_renderer {
+        state.adaptive_renderer = false;
+        if state.image_renderer.take().is_some() {
+            // bring the grid back
+            draw_initial_state(field, &field_canvas(context)?, context, &state.grid_context, state.cell_size_px)?;
+        }
+    }
+    let is_reduced = quality == Quality::ReducedRate;
+    if is_reduced && state.target_fps.is_none() {
+        state.target_fps = Some(REDUCED_FPS);
+        state.adaptive_fps = true;
+    } else if !is_reduced && state.adaptive_fps {
+        state.adaptive_fps = false;
+        state.target_fps = None;
+    }
+    state.time_history_ms.truncate();
+    Ok(())
+}
+
+/// Canvas of the cells, the context is always created from a canvas element
+fn field_canvas(context: &CanvasRenderingContext2d) -> Result<HtmlCanvasElement, AppError> {
+    context.canvas().ok_or(AppError::Missing("field canvas"))
+}
+
+pub fn pause(window: &web_sys::Window, state: &mut AnimationState) {
+    if let Some(frame_id) = state.next_frame.take() {
+        // cancelling can only fail for invalid ids
+        let _ = window.cancel_animation_frame(frame_id);
+    }
+    if let Some(timeout_id) = state.next_timeout.take() {
+        window.clear_timeout_with_handle(timeout_id);
+    }
+}
+
+/// Draws the changes of the next generation translucently, if enabled and the animation is paused
+pub fn draw_ghost_if_paused(context: &CanvasRenderingContext2d, field: &mut dyn DynAutomaton, state: &AnimationState) {
+    if !state.show_ghost || state.is_running() {
+        return;
+    }
+    if let Some(next) = field.preview_next() {
+        draw_ghost(context, field, &next);
+    }
+}
+
+/// Resets the state of the previous field and draws the new one. The replay of life fields starts from `initial` if given,
+/// otherwise from the cells of the field
+pub fn show_new_field(state: &mut AnimationState, context: &CanvasRenderingContext2d, field: &mut dyn DynAutomaton, initial: Option<Initial>) -> Result<(), AppError> {
+    state.time_history_ms.truncate();
+    state.stats_graph.clear();
+    state.activity_panel.clear();
+    state.reset_periods();
+    state.stop_at = None;
+    state.pending_edits.clear();
+    state.is_edited = false;
+    if state.trail_renderer.is_some() {
+        state.trail_renderer = Some(TrailRenderer::default());
+    }
+    // automata without a configurable rule keep their own
+    let _ = field.set_rule(&state.life_rule.to_string());
+    state.replay = start_replay(field, initial);
+    if let Some(width) = state.fit_width {
+        state.cell_size_px = fit_cell_size(field, width, state.max_cell_size_px);
+        update_grid_visibility(state)?;
+    }
+    state.is_playback = false;
+    draw_initial_state(field, &field_canvas(context)?, context, &state.grid_context, state.cell_size_px)?;
+    if state.image_renderer.is_some() {
+        draw_field(context, field, state, true);
+    }
+    update_minimap(context, field, state);
+    update_magnifier(context, field, state);
+    state.generation.set(0);
+    state.analysis.clear();
+    state.metrics.clear();
+    state.history.clear();
+    state.analysis.push(0, field);
+    state.history.push(0, field);
+    state.is_stable = false;
+    state.dispatch_event(events::FIELD_CHANGED, field);
+    Ok(())
+}
+
+/// Soup from a new seed, so that it can be replayed
+pub fn random_soup(size: NonZeroUsize, density: f64) -> Initial {
+    let seed = (js_sys::Math::random() * u32::MAX as f64) as u64;
+    Initial::Soup { width: size.get(), height: size.get(), density, seed }
+}
+
+/// Starts recording the replay of a life field, other automata don't have replays
+fn start_replay(field: &mut dyn DynAutomaton, initial: Option<Initial>) -> Option<Replay> {
+    let field = field.as_any_mut().downcast_mut::<Field>()?;
+    Some(Replay::from_field(initial.unwrap_or_else(|| Initial::cells(field)), field))
+}
+
+fn replay_to_json(replay: &Replay) -> Result<String, AppError> {
+    let value = serde_wasm_bindgen::to_value(replay).map_err(|err| AppError::InvalidReplay(err.to_string()))?;
+    js_sys::JSON::stringify(&value)?.as_string().ok_or(AppError::Missing("replay json"))
+}
+
+/// Shows the grid if the user wants it and the cells are big enough on the screen for the grid to be useful
+pub fn update_grid_visibility(state: &AnimationState) -> Result<(), AppError> {
+    let is_visible = state.show_grid && (cell_size_device_px(state.cell_size_px) >= MIN_GRID_CELL_SIZE_PX);
+    let grid_canvas = state.grid_context.canvas().ok_or(AppError::Missing("grid canvas"))?;
+    // setting styles can't fail for this property
+    let _ = grid_canvas.style().set_property("visibility", if is_visible { "visible" } else { "hidden" });
+    Ok(())
+}
+
+/// Draws the cells with the image renderer if it is enabled and supports the layout of the field
+pub fn draw_field(context: &CanvasRenderingContext2d, field: &dyn DynAutomaton, state: &AnimationState, force: bool) {
+    let counts = if state.show_counts && (field.get_width() <= MAX_COUNTS_FIELD_SIZE) && (field.get_height() <= MAX_COUNTS_FIELD_SIZE) {
+        field.neighbour_counts()
+    } else {
+        None
+    };
+    match &state.image_renderer {
+        Some(renderer) if field.layout() == Layout::Square => renderer.draw(context, field),
+        _ => {
+            // counts of unchanged cells can change too, so all cells have to be redrawn
+            draw_cells(context, field, force || counts.is_some());
+            if let Some(trail_renderer) = &state.trail_renderer {
+                trail_renderer.draw(context, field, !force);
+            }
+        }
+    }
+    if let Some(counts) = counts {
+        draw_neighbour_counts(context, field, &counts);
+    }
+    update_minimap(context, field, state);
+    update_magnifier(context, field, state);
+}
+
+/// Redraws the magnifier from the grid, if it's shown, and the cells
+pub fn update_magnifier(context: &CanvasRenderingContext2d, field: &dyn DynAutomaton, state: &AnimationState) {
+    let layers: Vec<_> = [state.grid_context.canvas().filter(|_| state.show_grid), context.canvas()].into_iter().flatten().collect();
+    state.magnifier.update(field, &layers);
+}
+
+pub fn update_minimap(context: &CanvasRenderingContext2d, field: &dyn DynAutomaton, state: &AnimationState) {
+    if let Some(window) = web_sys::window() {
+        if let Some(canvas) = context.canvas() {
+            state.minimap.update(&window, field, &canvas);
+        }
+    }
+}
+
+/// Generation at which a run of this number of generations pauses. The host and the Run input can ask for any number,
+/// runs past the last generation don't pause
+fn stop_generation(generation: u64, generations: u64) -> u64 {
+    generation.saturating_add(generations)
+}
+
+/// Adds the time since the previous frame to the history, and returns it.
+/// The timestamp is reset on resume, so the time while paused is never counted
+fn record_frame_time(state: &mut AnimationState, timestamp_ms: f64) -> Option<f64> {
+    let frame_ms = timestamp_ms - state.last_frame_ts_ms.replace(timestamp_ms)?;
+    state.time_history_ms.push(frame_ms);
+    Some(frame_ms)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_stop_generation() {
+        assert_eq!(15, stop_generation(5, 10));
+        assert_eq!(u64::MAX, stop_generation(5, u64::MAX));
+        assert_eq!(u64::MAX, stop_generation(u64::MAX, 1));
+    }
+}
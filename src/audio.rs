@@ -0,0 +1,329 @@
+use std::rc::Rc;
+use wasm_bindgen::JsCast;
+use web_sys::{AudioContext, Document, GainNode, HtmlElement, HtmlSelectElement, OscillatorNode, OscillatorType};
+use crate::app::{add_message_listener, create_button, App, AppDispatcher, Message};
+use crate::automaton::{Activity, DynAutomaton};
+use crate::control_panel::Group;
+use crate::error::AppError;
+use crate::listeners::Listeners;
+use crate::render::{cell_bounds, CELL_SIZE_PX};
+
+/// Pitch of an empty field and of a full one, three octaves apart
+const MIN_FREQUENCY_HZ: f64 = 110.0;
+const MAX_FREQUENCY_HZ: f64 = 880.0;
+/// Volume when every live cell is born or dies in the step, kept low as the tone doesn't stop while running
+const MAX_GAIN: f64 = 0.2;
+/// Time constant of the changes of pitch and volume in seconds, so that the generations glide into each other without clicks
+const SMOOTHING_S: f64 = 0.03;
+/// Lowest note of the playhead lane, C3
+const LANE_BASE_HZ: f64 = 130.81;
+/// The columns of the lane are spread over this many octaves of the scale
+const LANE_OCTAVES: usize = 3;
+/// Most notes played at once, a busy lane would be noise otherwise
+const MAX_VOICES: usize = 8;
+/// Volume of each note of the lane
+const NOTE_GAIN: f64 = 0.1;
+/// Shortest and longest notes, a note lasts a generation otherwise
+const MIN_NOTE_S: f64 = 0.05;
+const MAX_NOTE_S: f64 = 1.0;
+/// Time of the volume to rise at the start of a note, so that it doesn't click
+const NOTE_ATTACK_S: f64 = 0.005;
+
+/// Pitch and volume of a generation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    pub frequency_hz: f64,
+    pub gain: f64,
+}
+impl Tone {
+    /// The pitch rises with the share of live cells, the volume with the births and deaths relative to the population,
+    /// so still lifes are silent and busy soups are loud. Steps that resized the field have no activity and are silent
+    pub fn for_generation(population: usize, activity: Option<Activity>, cells: usize) -> Self {
+        let density = population as f64 / cells.max(1) as f64;
+        // soups rarely get above a third of the cells alive, the square root spreads the usual densities over more of the range
+        let frequency_hz = MIN_FREQUENCY_HZ * (MAX_FREQUENCY_HZ / MIN_FREQUENCY_HZ).powf(density.sqrt().min(1.0));
+        let changes = activity.map_or(0, |activity| activity.total());
+        let gain = MAX_GAIN * (changes as f64 / population.max(1) as f64).min(1.0);
+        Self { frequency_hz, gain }
+    }
+}
+
+/// Scales of the notes of the playhead lane
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scale {
+    Major,
+    Minor,
+    Pentatonic,
+    Chromatic,
+}
+impl Scale {
+    /// Semitones of the notes of an octave from its first note
+    fn semitones(self) -> &'static [u8] {
+        match self {
+            Self::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Self::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            Self::Pentatonic => &[0, 2, 4, 7, 9],
+            Self::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+        }
+    }
+    /// Frequency of the note of the scale, counted from the base of the lane
+    fn frequency_hz(self, note: usize) -> f64 {
+        let semitones = self.semitones();
+        let semitone = (12 * (note / semitones.len())) + semitones[note % semitones.len()] as usize;
+        LANE_BASE_HZ * 2.0_f64.powf(semitone as f64 / 12.0)
+    }
+}
+
+/// Names of the scales for the select of the ui
+pub const SCALES: [(&str, Scale); 4] = [
+    ("Pentatonic", Scale::Pentatonic),
+    ("Major", Scale::Major),
+    ("Minor", Scale::Minor),
+    ("Chromatic", Scale::Chromatic),
+];
+
+/// Frequencies of the live cells of the lane, the columns from left to right go up the scale.
+/// Columns that share a note play it once, and only the lowest `MAX_VOICES` notes are played
+pub fn lane_notes(lane: &[bool], scale: Scale) -> Vec<f64> {
+    let notes = LANE_OCTAVES * scale.semitones().len();
+    let mut played: Vec<usize> = lane.iter()
+        .enumerate()
+        .filter(|(_, &is_alive)| is_alive)
+        .map(|(col, _)| col * notes / lane.len())
+        .collect();
+    played.dedup();
+    played.truncate(MAX_VOICES);
+    played.into_iter().map(|note| scale.frequency_hz(note)).collect()
+}
+
+/// Plays the generations of the field as a continuous tone, or as the notes of the playhead lane, through Web Audio.
+/// Browsers only start audio after a click or a key press, so it is created by the sound toggle or by marking the lane
+pub struct Sonifier {
+    context: AudioContext,
+    oscillator: OscillatorNode,
+    gain: GainNode,
+    is_muted: bool,
+}
+impl Sonifier {
+    pub fn new() -> Result<Self, AppError> {
+        let context = AudioContext::new()?;
+        let oscillator = context.create_oscillator()?;
+        oscillator.set_type(OscillatorType::Triangle);
+        let gain = context.create_gain()?;
+        gain.gain().set_value(0.0);
+        oscillator.connect_with_audio_node(&gain)?;
+        gain.connect_with_audio_node(&context.destination())?;
+        oscillator.start()?;
+        Ok(Self { context, oscillator, gain, is_muted: false })
+    }
+    pub fn is_muted(&self) -> bool {
+        self.is_muted
+    }
+    pub fn set_muted(&mut self, is_muted: bool) {
+        self.is_muted = is_muted;
+        if is_muted {
+            self.silence();
+        } else {
+            // the context is suspended if it was created without a user gesture, or by the browser when the tab was hidden
+            let _ = self.context.resume();
+        }
+    }
+    /// Glides to the tone, unless muted
+    pub fn play(&self, tone: Tone) {
+        if self.is_muted {
+            return;
+        }
+        let time = self.context.current_time();
+        let _ = self.oscillator.frequency().set_target_at_time(tone.frequency_hz as f32, time, SMOOTHING_S);
+        let _ = self.gain.gain().set_target_at_time(tone.gain as f32, time, SMOOTHING_S);
+    }
+    /// Plays the notes once for the duration, e.g. the time of a generation. Nothing is played while muted
+    pub fn play_notes(&self, frequencies: &[f64], duration_s: f64) -> Result<(), AppError> {
+        if self.is_muted {
+            return Ok(());
+        }
+        let start = self.context.current_time();
+        let end = start + duration_s.clamp(MIN_NOTE_S, MAX_NOTE_S);
+        for &frequency_hz in frequencies {
+            // the nodes are freed by the browser once the note has stopped
+            let oscillator = self.context.create_oscillator()?;
+            oscillator.set_type(OscillatorType::Sine);
+            oscillator.frequency().set_value(frequency_hz as f32);
+            let envelope = self.context.create_gain()?;
+            let gain = envelope.gain();
+            gain.set_value_at_time(0.0, start)?;
+            gain.linear_ramp_to_value_at_time(NOTE_GAIN as f32, start + NOTE_ATTACK_S)?;
+            gain.exponential_ramp_to_value_at_time(0.001, end)?;
+            oscillator.connect_with_audio_node(&envelope)?;
+            envelope.connect_with_audio_node(&self.context.destination())?;
+            oscillator.start_with_when(start)?;
+            oscillator.stop_with_when(end)?;
+        }
+        Ok(())
+    }
+    /// Fades out, e.g. when the animation is paused
+    pub fn silence(&self) {
+        let _ = self.gain.gain().set_target_at_time(0.0, self.context.current_time(), SMOOTHING_S);
+    }
+}
+impl Drop for Sonifier {
+    fn drop(&mut self) {
+        let _ = self.context.close();
+    }
+}
+
+/// Row of the field whose live cells are played as notes every generation, highlighted over the field
+#[derive(Debug)]
+pub struct Playhead {
+    lane: HtmlElement,
+    row: Option<usize>,
+    pub scale: Scale,
+}
+impl Playhead {
+    /// The highlight has to be put over the canvas of the cells, in a positioned container
+    pub fn new(document: &Document) -> Result<Self, AppError> {
+        let lane = document.create_element("div")?.dyn_into::<HtmlElement>()?;
+        lane.set_attribute("aria-hidden", "true")?;
+        lane.set_attribute("style", "position: absolute; pointer-events: none; background-color: rgba(255, 165, 0, 0.25); outline: 1px solid #FFA500; display: none;")?;
+        Ok(Self { lane, row: None, scale: SCALES[0].1 })
+    }
+    pub fn element(&self) -> &HtmlElement {
+        &self.lane
+    }
+    /// The row of the lane, None if there is no lane or the field has become smaller than it
+    pub fn row(&self, field: &dyn DynAutomaton) -> Option<usize> {
+        self.row.filter(|&row| row < field.get_height())
+    }
+    /// Marks the row as the lane, or removes the lane if it is on the row already
+    pub fn toggle(&mut self, row: usize, field: &dyn DynAutomaton, cell_size_px: usize) {
+        self.row = if self.row == Some(row) { None } else { Some(row) };
+        self.update(field, cell_size_px);
+    }
+    /// Whether the cells of the lane are alive, from left to right
+    pub fn lane(&self, field: &dyn DynAutomaton) -> Option<Vec<bool>> {
+        let row = self.row(field)?;
+        Some((0..field.get_width()).map(|col| field.state_by_coords(row, col).is_some_and(|state| state != 0)).collect())
+    }
+    /// Puts the highlight over the row, for the current size of cells on the screen
+    pub fn update(&self, field: &dyn DynAutomaton, cell_size_px: usize) {
+        let style = self.lane.style();
+        // setting styles can't fail for these properties
+        let Some(row) = self.row(field) else {
+            let _ = style.set_property("display", "none");
+            return;
+        };
+        let scale = (cell_size_px + 1) as f64 / (CELL_SIZE_PX + 1) as f64;
+        let (x, y, _, height) = cell_bounds(field, row, 0);
+        let (last_x, _, last_width, _) = cell_bounds(field, row, field.get_width() - 1);
+        let _ = style.set_property("left", &format!("{}px", x * scale));
+        let _ = style.set_property("top", &format!("{}px", y * scale));
+        let _ = style.set_property("width", &format!("{}px", (last_x + last_width - x) * scale));
+        let _ = style.set_property("height", &format!("{}px", height * scale));
+        let _ = style.set_property("display", "block");
+    }
+}
+
+/// Requests of the sound controls
+pub enum SoundCommand {
+    /// Turns the sonification on, or mutes and unmutes it
+    Toggle,
+    /// Scale of the notes of the playhead lane
+    SetScale(Scale),
+}
+
+impl App {
+    pub fn update_sound(&mut self, command: SoundCommand) -> Result<(), AppError> {
+        match command {
+            SoundCommand::Toggle => self.toggle_sound(),
+            SoundCommand::SetScale(scale) => {
+                self.playhead.scale = scale;
+                Ok(())
+            }
+        }
+    }
+
+    fn toggle_sound(&mut self) -> Result<(), AppError> {
+        let is_muted = match &mut self.sonifier {
+            Some(sonifier) => {
+                sonifier.set_muted(!sonifier.is_muted());
+                sonifier.is_muted()
+            }
+            None => {
+                self.sonifier = Some(Sonifier::new()?);
+                false
+            }
+        };
+        self.live_region.announce(if is_muted { "Sound off" } else { "Sound on" });
+        Ok(())
+    }
+}
+
+/// Adds the sound toggle and the scale of the playhead lane to the group
+pub fn add_sound_controls(group: &Group, document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<(), AppError> {
+    let sound_button = create_button("Toggle Sound", || Message::Sound(SoundCommand::Toggle), document, listeners, dispatcher)?;
+    sound_button.set_attribute("title", "Play the population as the pitch and the births and deaths as the volume")?;
+    group.add(&sound_button)?;
+    let scale_select = create_scale_select(document, listeners, dispatcher)?;
+    group.add(&scale_select)
+}
+
+fn create_scale_select(document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<HtmlSelectElement, AppError> {
+    let select = document.create_element("select")?.dyn_into::<HtmlSelectElement>()?;
+    select.set_title("Scale of the notes of the playhead lane, set from the menu of a row");
+    for (name, _) in SCALES {
+        let option = document.create_element("option")?;
+        option.set_text_content(Some(name));
+        select.append_child(&option)?;
+    }
+    let message = {
+        let select = select.clone();
+        move || {
+            let index = usize::try_from(select.selected_index()).unwrap_or(0);
+            Message::Sound(SoundCommand::SetScale(SCALES.get(index).unwrap_or(&SCALES[0]).1))
+        }
+    };
+    add_message_listener(listeners, &select, &["change"], dispatcher, message)?;
+    Ok(select)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_tone() {
+        let empty = Tone::for_generation(0, Some(Activity { births: 0, deaths: 0 }), 100);
+        assert_eq!(Tone { frequency_hz: MIN_FREQUENCY_HZ, gain: 0.0 }, empty);
+        let full = Tone::for_generation(100, Some(Activity { births: 0, deaths: 100 }), 100);
+        assert_eq!(Tone { frequency_hz: MAX_FREQUENCY_HZ, gain: MAX_GAIN }, full);
+
+        // a quarter alive is halfway up the range
+        let soup = Tone::for_generation(25, Some(Activity { births: 5, deaths: 5 }), 100);
+        assert!((soup.frequency_hz - (MIN_FREQUENCY_HZ * MAX_FREQUENCY_HZ).sqrt()).abs() < 1e-9);
+        assert!((soup.gain - MAX_GAIN * 0.4).abs() < 1e-9);
+
+        assert_eq!(0.0, Tone::for_generation(25, Some(Activity { births: 0, deaths: 0 }), 100).gain);
+        assert_eq!(0.0, Tone::for_generation(25, None, 100).gain);
+        // a dying field is as loud as it gets
+        assert_eq!(MAX_GAIN, Tone::for_generation(0, Some(Activity { births: 0, deaths: 3 }), 100).gain);
+    }
+    #[test]
+    fn test_lane_notes() {
+        assert_eq!(LANE_BASE_HZ, Scale::Major.frequency_hz(0));
+        // an octave up
+        assert!((Scale::Pentatonic.frequency_hz(5) - 2.0 * LANE_BASE_HZ).abs() < 1e-9);
+        assert!((Scale::Minor.frequency_hz(2) - LANE_BASE_HZ * 2.0_f64.powf(3.0 / 12.0)).abs() < 1e-9);
+
+        let mut lane = vec![false; 30];
+        assert!(lane_notes(&lane, Scale::Pentatonic).is_empty());
+        lane[0] = true;
+        lane[1] = true;
+        lane[15] = true;
+        // 15 notes over 30 columns, the first two columns share a note
+        let notes = lane_notes(&lane, Scale::Pentatonic);
+        assert_eq!(vec![LANE_BASE_HZ, Scale::Pentatonic.frequency_hz(7)], notes);
+
+        let full = lane_notes(&[true; 64], Scale::Chromatic);
+        assert_eq!(MAX_VOICES, full.len());
+        assert!(full.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}
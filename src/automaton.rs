@@ -0,0 +1,299 @@
+use std::any::Any;
+use std::num::NonZeroUsize;
+use crate::game_of_life::{ParseError, PatternMeta};
+use crate::theme::Theme;
+
+/// State of a single cell of an automaton
+pub trait CellState: Copy + PartialEq + 'static {
+    /// Css colors for each state, indexed by `index()`
+    const PALETTE: &'static [&'static str];
+    /// Human readable names for each state, indexed by `index()`
+    const NAMES: &'static [&'static str];
+    fn index(self) -> u8;
+    fn from_index(index: u8) -> Option<Self>;
+    /// State that the cell switches to when it is clicked
+    fn next_in_edit_cycle(self) -> Self;
+    fn to_char(self) -> char;
+    fn from_char(char: char) -> Option<Self>;
+}
+
+/// How the cells are arranged on the screen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Layout {
+    Square,
+    /// Pointy-top hexagons, odd rows are shifted to the right by half a cell
+    Hex,
+}
+
+/// Cells that came alive and cells that died in a step.
+/// Cells in the state with palette index 0 are dead, all other states are alive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Activity {
+    pub births: usize,
+    pub deaths: usize,
+}
+impl Activity {
+    /// Births and deaths together. It drops to zero, or to the period of the oscillators, when a soup has settled
+    pub fn total(&self) -> usize {
+        self.births + self.deaths
+    }
+}
+
+/// Common interface for the cellular automata that can be shown by the UI.
+/// Cells are stored in row-major order.
+pub trait Automaton {
+    type State: CellState;
+    fn get_width(&self) -> usize;
+    fn get_height(&self) -> usize;
+    fn view(&self) -> &[Self::State];
+    /// Cells of the previous generation
+    fn view_old(&self) -> &[Self::State];
+    /// Calculates the next generation. Returns false if nothing is going to change anymore
+    fn step(&mut self) -> bool;
+    fn set(&mut self, row: usize, col: usize, value: Self::State) -> Option<()>;
+    /// Switches the cell to the next state in the edit cycle
+    fn toggle(&mut self, row: usize, col: usize) -> Option<()> {
+        if (row >= self.get_height()) || (col >= self.get_width()) {
+            return None;
+        }
+        let value = self.view()[(row * self.get_width()) + col];
+        self.set(row, col, value.next_in_edit_cycle())
+    }
+    fn parse(str: &str) -> Result<Self, ParseError> where Self: Sized;
+    fn layout(&self) -> Layout {
+        Layout::Square
+    }
+    /// Rulestring of the automaton, if it has a configurable rule
+    fn rule(&self) -> Option<String> {
+        None
+    }
+    fn set_rule(&mut self, _rule: &str) -> Result<(), ParseError> {
+        Err(ParseError::InvalidRule)
+    }
+    /// Name, author and comments of the loaded pattern, if the automaton keeps them
+    fn meta(&self) -> Option<&PatternMeta> {
+        None
+    }
+    /// Indexes of the cells changed by the last step, if the automaton keeps track of them
+    fn changed_cells(&self) -> Option<&[usize]> {
+        None
+    }
+    /// Cells of the next generation without stepping, if the automaton supports it
+    fn preview_next(&mut self) -> Option<&[Self::State]> {
+        None
+    }
+    /// Number of live neighbours of every cell, if the automaton counts neighbours
+    fn neighbour_counts(&self) -> Option<Vec<u8>> {
+        None
+    }
+    /// Vector image of the field, if the automaton supports it
+    fn to_svg(&self, _cell_size: u32, _theme: &Theme) -> Option<String> {
+        None
+    }
+    fn serialize(&self) -> String {
+        let mut res = String::with_capacity((self.get_width() + 1) * self.get_height());
+        for row in self.view().chunks(self.get_width()) {
+            res.extend(row.iter().map(|value| value.to_char()));
+            res.push('\n');
+        }
+        res
+    }
+}
+
+/// Object safe version of `Automaton`, so that the UI can switch between different automata at runtime.
+/// Cell states are represented by their palette indexes.
+pub trait DynAutomaton {
+    fn get_width(&self) -> usize;
+    fn get_height(&self) -> usize;
+    fn step(&mut self) -> bool;
+    fn toggle(&mut self, row: usize, col: usize) -> Option<()>;
+    /// Sets the cell to the state with the given palette index
+    fn set_state(&mut self, row: usize, col: usize, state: u8) -> Option<()>;
+    fn state_by_coords(&self, row: usize, col: usize) -> Option<u8>;
+    /// State that `toggle` would switch the cell to
+    fn toggled_state(&self, row: usize, col: usize) -> Option<u8>;
+    fn palette(&self) -> &'static [&'static str];
+    fn state_names(&self) -> &'static [&'static str];
+    fn state_at(&self, index: usize) -> u8;
+    fn old_state_at(&self, index: usize) -> u8;
+    fn layout(&self) -> Layout;
+    fn rule(&self) -> Option<String>;
+    fn set_rule(&mut self, rule: &str) -> Result<(), ParseError>;
+    fn meta(&self) -> Option<&PatternMeta>;
+    /// Number of cells in each state, indexed by palette index
+    fn count_by_state(&self) -> Vec<usize>;
+    fn changed_cells(&self) -> Option<&[usize]>;
+    /// Births and deaths of the last step, None if the field has been resized by it.
+    /// Only valid right after the step, e.g. the preview of the next generation reuses the old cells
+    fn activity(&self) -> Option<Activity>;
+    /// Palette indexes of the cells of the next generation without stepping, if the automaton supports it
+    fn preview_next(&mut self) -> Option<Vec<u8>>;
+    fn neighbour_counts(&self) -> Option<Vec<u8>>;
+    fn to_svg(&self, cell_size: u32, theme: &Theme) -> Option<String>;
+    fn serialize(&self) -> String;
+    /// Copy of the automaton, e.g. for going back to an earlier generation
+    fn clone_box(&self) -> Box<dyn DynAutomaton>;
+    /// For reusing the concrete automaton, e.g. refilling a life field instead of allocating a new one
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Automaton + Clone + 'static> DynAutomaton for T {
+    fn get_width(&self) -> usize {
+        Automaton::get_width(self)
+    }
+    fn get_height(&self) -> usize {
+        Automaton::get_height(self)
+    }
+    fn step(&mut self) -> bool {
+        Automaton::step(self)
+    }
+    fn toggle(&mut self, row: usize, col: usize) -> Option<()> {
+        Automaton::toggle(self, row, col)
+    }
+    fn set_state(&mut self, row: usize, col: usize, state: u8) -> Option<()> {
+        self.set(row, col, T::State::from_index(state)?)
+    }
+    fn state_by_coords(&self, row: usize, col: usize) -> Option<u8> {
+        if (row >= self.get_height()) || (col >= self.get_width()) {
+            return None;
+        }
+        Some(self.view()[(row * self.get_width()) + col].index())
+    }
+    fn toggled_state(&self, row: usize, col: usize) -> Option<u8> {
+        let state = T::State::from_index(DynAutomaton::state_by_coords(self, row, col)?)?;
+        Some(state.next_in_edit_cycle().index())
+    }
+    fn palette(&self) -> &'static [&'static str] {
+        T::State::PALETTE
+    }
+    fn state_names(&self) -> &'static [&'static str] {
+        T::State::NAMES
+    }
+    fn state_at(&self, index: usize) -> u8 {
+        self.view()[index].index()
+    }
+    fn old_state_at(&self, index: usize) -> u8 {
+        self.view_old()[index].index()
+    }
+    fn layout(&self) -> Layout {
+        Automaton::layout(self)
+    }
+    fn rule(&self) -> Option<String> {
+        Automaton::rule(self)
+    }
+    fn set_rule(&mut self, rule: &str) -> Result<(), ParseError> {
+        Automaton::set_rule(self, rule)
+    }
+    fn meta(&self) -> Option<&PatternMeta> {
+        Automaton::meta(self)
+    }
+    fn count_by_state(&self) -> Vec<usize> {
+        let mut counts = vec![0; T::State::PALETTE.len()];
+        for value in self.view() {
+            counts[value.index() as usize] += 1;
+        }
+        counts
+    }
+    fn changed_cells(&self) -> Option<&[usize]> {
+        Automaton::changed_cells(self)
+    }
+    fn activity(&self) -> Option<Activity> {
+        let (cells, old_cells) = (self.view(), self.view_old());
+        if cells.len() != old_cells.len() {
+            return None;
+        }
+        let mut activity = Activity::default();
+        let mut count = |index: usize| match (old_cells[index].index() == 0, cells[index].index() == 0) {
+            (true, false) => activity.births += 1,
+            (false, true) => activity.deaths += 1,
+            _ => {}
+        };
+        // only the changed cells have to be compared, if the automaton keeps track of them
+        match Automaton::changed_cells(self) {
+            Some(changed) => changed.iter().for_each(|&index| count(index)),
+            None => (0..cells.len()).for_each(count),
+        }
+        Some(activity)
+    }
+    fn preview_next(&mut self) -> Option<Vec<u8>> {
+        Automaton::preview_next(self).map(|cells| cells.iter().map(|value| value.index()).collect())
+    }
+    fn neighbour_counts(&self) -> Option<Vec<u8>> {
+        Automaton::neighbour_counts(self)
+    }
+    fn to_svg(&self, cell_size: u32, theme: &Theme) -> Option<String> {
+        Automaton::to_svg(self, cell_size, theme)
+    }
+    fn serialize(&self) -> String {
+        Automaton::serialize(self)
+    }
+    fn clone_box(&self) -> Box<dyn DynAutomaton> {
+        Box::new(self.clone())
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Parses a rectangular grid of cells, one line per row. Returns width, height and cells
+pub(crate) fn parse_cells<S: CellState>(str: &str) -> Result<(NonZeroUsize, NonZeroUsize, Vec<S>), ParseError> {
+    let str = str.trim();
+    if str.is_empty() {
+        return Err(ParseError::EmptyString);
+    };
+    let mut cells = Vec::new();
+    let mut width = None;
+    let mut height = 0;
+    for str_line in str.lines() {
+        let line_start = cells.len();
+        for char in str_line.trim().chars() {
+            cells.push(S::from_char(char).ok_or(ParseError::UnknownChar)?);
+        }
+        let line_width = cells.len() - line_start;
+        let expected_width = *width.get_or_insert(line_width);
+        if line_width != expected_width {
+            return Err(ParseError::WidthMismatch);
+        }
+        height += 1;
+    }
+    Ok((width.unwrap().try_into().unwrap(), height.try_into().unwrap(), cells))
+}
+
+/// Counts cells with the given value among the 8 neighbours, wrapping around the edges
+pub(crate) fn count_neighbours_with_value<S: PartialEq>(
+    cells: &[S],
+    width: usize,
+    height: usize,
+    row: usize,
+    col: usize,
+    value: S,
+) -> u8 {
+    let rows = [prev_coord_wrapped(row, height - 1), row, next_coord_wrapped(row, height - 1)];
+    let cols = [prev_coord_wrapped(col, width - 1), col, next_coord_wrapped(col, width - 1)];
+    let mut count = 0;
+    for (row_no, &check_row) in rows.iter().enumerate() {
+        for (col_no, &check_col) in cols.iter().enumerate() {
+            if (row_no == 1) && (col_no == 1) {
+                continue;
+            }
+            count += (cells[(check_row * width) + check_col] == value) as u8;
+        }
+    }
+    count
+}
+
+pub(crate) fn next_coord_wrapped(value: usize, max_value: usize) -> usize {
+    if value >= max_value {
+        0
+    } else {
+        value + 1
+    }
+}
+
+pub(crate) fn prev_coord_wrapped(value: usize, max_value: usize) -> usize {
+    if value == 0 {
+        max_value
+    } else {
+        value - 1
+    }
+}
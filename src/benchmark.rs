@@ -0,0 +1,109 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::num::NonZeroUsize;
+use crate::automaton::DynAutomaton;
+use crate::game_of_life::{Field, Rule};
+use crate::random::XorShift;
+
+/// Number of generations in the default workload
+pub const GENERATIONS: usize = 1000;
+/// Width and height of the field in cells
+pub const FIELD_SIZE: usize = 400;
+const SEED: u64 = 42;
+/// Percentage of live cells in the initial soup
+const DENSITY: f64 = 50.0;
+
+/// Times of a fixed workload, so that the engines and renderers can be compared on the same machine
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkReport {
+    pub generations: usize,
+    pub rule: Rule,
+    pub update_ms: f64,
+    pub update_render_ms: f64,
+    /// Number of live cells after the last generation, the same in both runs for a correct engine
+    pub live_cells: usize,
+}
+impl BenchmarkReport {
+    /// Time of the rendering alone, estimated as the difference of the two runs
+    pub fn render_ms(&self) -> f64 {
+        (self.update_render_ms - self.update_ms).max(0.0)
+    }
+}
+impl fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let per_generation = |total_ms: f64| total_ms / self.generations as f64;
+        writeln!(f, "{} generations of a {FIELD_SIZE}x{FIELD_SIZE} soup, rule {}", self.generations, self.rule)?;
+        writeln!(
+            f,
+            "update:          {:.1} ms ({:.3} ms/gen, {:.0} gen/s)",
+            self.update_ms,
+            per_generation(self.update_ms),
+            1000.0 / per_generation(self.update_ms),
+        )?;
+        writeln!(
+            f,
+            "update + render: {:.1} ms ({:.3} ms/gen, {:.0} gen/s)",
+            self.update_render_ms,
+            per_generation(self.update_render_ms),
+            1000.0 / per_generation(self.update_render_ms),
+        )?;
+        writeln!(f, "render:          {:.1} ms ({:.3} ms/gen)", self.render_ms(), per_generation(self.render_ms()))?;
+        write!(f, "live cells at the end: {}", self.live_cells)
+    }
+}
+
+/// The initial field of the workload, always the same for the same rule
+pub fn soup(rule: Rule) -> Field {
+    let size = NonZeroUsize::new(FIELD_SIZE).unwrap();
+    let random = RefCell::new(XorShift::new(SEED));
+    Field::generate_by_fn(size, size, |_| random.borrow_mut().next_f64() * 100.0 < DENSITY).with_rule(rule)
+}
+
+/// Runs the given number of generations once without rendering and once rendering every generation.
+/// `now` returns the current time in milliseconds, like `performance.now()`
+pub fn run(generations: usize, rule: Rule, now: impl Fn() -> f64, mut render: impl FnMut(&dyn DynAutomaton)) -> BenchmarkReport {
+    let mut field: Box<dyn DynAutomaton> = Box::new(soup(rule));
+    let start_ms = now();
+    for _ in 0..generations {
+        field.step();
+    }
+    let update_ms = now() - start_ms;
+    let live_cells = field.count_by_state()[1];
+
+    let mut field: Box<dyn DynAutomaton> = Box::new(soup(rule));
+    let start_ms = now();
+    for _ in 0..generations {
+        field.step();
+        render(field.as_ref());
+    }
+    let update_render_ms = now() - start_ms;
+    debug_assert_eq!(live_cells, field.count_by_state()[1]);
+
+    BenchmarkReport { generations, rule, update_ms, update_render_ms, live_cells }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+    #[test]
+    fn test_run() {
+        let clock = Cell::new(0.0);
+        let now = || clock.get();
+        let mut renders = 0;
+        let report = run(10, Rule::LIFE, now, |_| {
+            renders += 1;
+            clock.set(clock.get() + 0.5);
+        });
+        assert_eq!(10, renders);
+        assert_eq!(0.0, report.update_ms);
+        assert_eq!(5.0, report.update_render_ms);
+        assert_eq!(5.0, report.render_ms());
+        assert!(report.live_cells > 0);
+        assert!(report.to_string().contains("live cells at the end"));
+    }
+    #[test]
+    fn test_soup_is_reproducible() {
+        assert_eq!(soup(Rule::LIFE).count_by_state(), soup(Rule::LIFE).count_by_state());
+    }
+}
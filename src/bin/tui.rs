@@ -0,0 +1,93 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::num::NonZeroUsize;
+use std::time::Duration;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::{cursor, execute, queue, style, terminal};
+use rust_wasm::game_of_life::{CellValue, Field};
+use rust_wasm::random::XorShift;
+use rust_wasm::rle::parse_rle;
+
+/*
+Runs the game of life in a terminal, without a browser:
+cargo run --bin tui --features tui -- [pattern.rle]
+space plays and pauses, s or right arrow steps while paused, q or esc quits
+ */
+
+const DEFAULT_FIELD_SIZE: NonZeroUsize = NonZeroUsize::new(40).unwrap();
+const FRAME_DELAY: Duration = Duration::from_millis(100);
+
+fn main() -> io::Result<()> {
+    let mut field = match std::env::args().nth(1) {
+        Some(path) => {
+            let rle = std::fs::read_to_string(&path)?;
+            parse_rle(&rle).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{path}: {err:?}")))?
+        }
+        None => {
+            let random = RefCell::new(XorShift::new(std::process::id() as u64));
+            Field::generate_by_fn(DEFAULT_FIELD_SIZE, DEFAULT_FIELD_SIZE, |_| random.borrow_mut().next_below(2) == 0)
+        }
+    };
+
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+    let res = run(&mut stdout, &mut field);
+    // the terminal has to be restored even if drawing failed
+    execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    res
+}
+
+fn run(stdout: &mut impl Write, field: &mut Field) -> io::Result<()> {
+    let mut is_running = false;
+    let mut generation = 0;
+    loop {
+        draw(stdout, field, generation, is_running)?;
+        // while running, the next generation is calculated when no key is pressed for a frame
+        if is_running && !event::poll(FRAME_DELAY)? {
+            is_running = field.update();
+            generation += 1;
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char(' ') => is_running = !is_running,
+            KeyCode::Char('s') | KeyCode::Right if !is_running => {
+                field.update();
+                generation += 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Draws the part of the field that fits into the terminal, two characters per cell to keep them square
+fn draw(stdout: &mut impl Write, field: &Field, generation: u64, is_running: bool) -> io::Result<()> {
+    let (columns, rows) = terminal::size()?;
+    let visible_cols = field.get_width().min(columns as usize / 2);
+    // the last line is the status line
+    let visible_rows = field.get_height().min((rows as usize).saturating_sub(1));
+    queue!(stdout, cursor::MoveTo(0, 0), terminal::Clear(terminal::ClearType::All))?;
+    let mut line = String::with_capacity(visible_cols * 2);
+    for (index, row) in field.rows().take(visible_rows).enumerate() {
+        line.clear();
+        for &cell in &row[..visible_cols] {
+            line.push_str(if cell == CellValue::Alive { "██" } else { "  " });
+        }
+        queue!(stdout, cursor::MoveTo(0, index as u16), style::Print(&line))?;
+    }
+    let status = if is_running { "running" } else { "paused" };
+    queue!(
+        stdout,
+        cursor::MoveTo(0, visible_rows as u16),
+        style::Print(format!("generation {generation}, {status}. space: play/pause, s: step, q: quit")),
+    )?;
+    stdout.flush()
+}
@@ -0,0 +1,338 @@
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use wasm_bindgen::prelude::*;
+use web_sys::{Document, Element, Event, HtmlCanvasElement, HtmlInputElement, IdbDatabase, IdbObjectStore, IdbObjectStoreParameters, IdbRequest, IdbTransactionMode};
+use crate::app::{add_event_listener, create_button, App, AppDispatcher, Message};
+use crate::automaton::DynAutomaton;
+use crate::control_panel::Group;
+use crate::error::AppError;
+use crate::game_of_life::Field;
+use crate::listeners::Listeners;
+use crate::render::{context_2d, ImageRenderer};
+use crate::{console_error, console_warn};
+
+const DATABASE_NAME: &str = "game-of-life";
+/// Increased when the object stores change, the upgrade creates the missing ones
+const DATABASE_VERSION: u32 = 1;
+/// Saved boards by name, the values are records with the properties below
+const BOARDS_STORE: &str = "boards";
+const NAME_KEY: &str = "name";
+/// Field in the binary snapshot format, see `snapshot`
+const SNAPSHOT_KEY: &str = "snapshot";
+/// PNG data url of the small preview in the gallery
+const THUMBNAIL_KEY: &str = "thumbnail";
+const GENERATION_KEY: &str = "generation";
+const SAVED_AT_KEY: &str = "savedAt";
+/// Bigger side of the thumbnails
+const THUMBNAIL_SIZE_PX: f64 = 64.0;
+/// Attributes of the gallery buttons, clicks are handled by the app through `action`
+const ACTION_ATTRIBUTE: &str = "data-board-action";
+const NAME_ATTRIBUTE: &str = "data-board-name";
+
+/// What a click in the gallery asks for
+#[derive(Debug, Clone, PartialEq)]
+pub enum BoardAction {
+    Load(String),
+    Delete(String),
+}
+
+/// Boards saved by name in IndexedDB, which unlike localStorage has room for big fields.
+/// Lists the saved boards with their thumbnails in a gallery. The database is opened in the background,
+/// operations before that are logged and ignored
+pub struct BoardLibrary {
+    element: Element,
+    name_input: HtmlInputElement,
+    gallery: Element,
+    database: Rc<RefCell<Option<IdbDatabase>>>,
+}
+impl BoardLibrary {
+    pub fn new(document: &Document) -> Result<Self, AppError> {
+        let element = document.create_element("div")?;
+        let name_input = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+        name_input.set_placeholder("Board name");
+        name_input.set_title("Saving under an existing name replaces that board");
+        name_input.set_size(12);
+        element.append_child(&name_input)?;
+        let gallery = document.create_element("div")?;
+        gallery.set_attribute("style", "display: flex; flex-wrap: wrap; gap: 4px; margin-top: 4px;")?;
+        element.append_child(&gallery)?;
+        let library = Self { element, name_input, gallery, database: Rc::new(RefCell::new(None)) };
+        // the rest of the app works without the saved boards, e.g. in private windows of some browsers
+        if let Err(err) = library.open(document) {
+            console_warn!("The saved boards are not available: {err}");
+        }
+        Ok(library)
+    }
+    pub fn element(&self) -> &Element {
+        &self.element
+    }
+    /// The list of the boards, the app listens to clicks on it
+    pub fn gallery(&self) -> &Element {
+        &self.gallery
+    }
+    /// Adds a control, like a button, after the name input
+    pub fn add_control(&self, control: &Element) -> Result<(), AppError> {
+        self.element.insert_before(control, Some(&self.gallery))?;
+        Ok(())
+    }
+    fn open(&self, document: &Document) -> Result<(), AppError> {
+        let window = document.default_view().ok_or(AppError::Missing("window"))?;
+        let factory = window.indexed_db()?.ok_or(AppError::Missing("IndexedDB"))?;
+        let request = factory.open_with_u32(DATABASE_NAME, DATABASE_VERSION)?;
+        let on_upgrade = {
+            let request = request.clone();
+            Closure::once_into_js(move || {
+                let Some(database) = request.result().ok().and_then(|result| result.dyn_into::<IdbDatabase>().ok()) else {
+                    return;
+                };
+                if !database.object_store_names().contains(BOARDS_STORE) {
+                    let mut parameters = IdbObjectStoreParameters::new();
+                    parameters.key_path(Some(&NAME_KEY.into()));
+                    if let Err(err) = database.create_object_store_with_optional_parameters(BOARDS_STORE, &parameters) {
+                        console_error!("Failed to create the store of the boards: {err:?}");
+                    }
+                }
+            })
+        };
+        request.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+        let on_success = {
+            let request = request.clone();
+            let database = Rc::clone(&self.database);
+            let gallery = self.gallery.clone();
+            Closure::once_into_js(move || {
+                let Some(opened) = request.result().ok().and_then(|result| result.dyn_into::<IdbDatabase>().ok()) else {
+                    return;
+                };
+                refresh(&opened, &gallery);
+                *database.borrow_mut() = Some(opened);
+            })
+        };
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+        set_error_handler(&request, "open the saved boards");
+        Ok(())
+    }
+    /// Saves the life field under the name from the input, replacing the board with the same name
+    pub fn save(&self, document: &Document, field: &Field, generation: u64) -> Result<(), AppError> {
+        let name = self.name_input.value().trim().to_string();
+        if name.is_empty() {
+            return Err(AppError::InvalidInput("enter a name for the board".to_string()));
+        }
+        let Some(store) = self.store(IdbTransactionMode::Readwrite)? else {
+            return Ok(());
+        };
+        let record = js_sys::Object::new();
+        let bytes = js_sys::Uint8Array::from(field.to_bytes().as_slice());
+        js_sys::Reflect::set(&record, &NAME_KEY.into(), &name.as_str().into())?;
+        js_sys::Reflect::set(&record, &SNAPSHOT_KEY.into(), &bytes)?;
+        js_sys::Reflect::set(&record, &THUMBNAIL_KEY.into(), &thumbnail(document, field)?.into())?;
+        js_sys::Reflect::set(&record, &GENERATION_KEY.into(), &(generation as f64).into())?;
+        js_sys::Reflect::set(&record, &SAVED_AT_KEY.into(), &js_sys::Date::now().into())?;
+        let request = store.put(&record)?;
+        self.refresh_on_success(&request, "save the board");
+        Ok(())
+    }
+    pub fn delete(&self, name: &str) -> Result<(), AppError> {
+        let Some(store) = self.store(IdbTransactionMode::Readwrite)? else {
+            return Ok(());
+        };
+        let request = store.delete(&name.into())?;
+        self.refresh_on_success(&request, "delete the board");
+        Ok(())
+    }
+    /// Reads the board, `on_load` gets the success event of the request, see `loaded_field`
+    pub fn load(&self, name: &str, on_load: &Closure<dyn Fn(Event)>) -> Result<(), AppError> {
+        let Some(store) = self.store(IdbTransactionMode::Readonly)? else {
+            return Ok(());
+        };
+        let request = store.get(&name.into())?;
+        request.set_onsuccess(Some(on_load.as_ref().unchecked_ref()));
+        set_error_handler(&request, "load the board");
+        Ok(())
+    }
+    /// The board that a click on the gallery asks to load or delete, if the click is on one of its buttons
+    pub fn action(event: &Event) -> Option<BoardAction> {
+        let target = event.target()?.dyn_into::<Element>().ok()?;
+        let name = target.get_attribute(NAME_ATTRIBUTE)?;
+        match target.get_attribute(ACTION_ATTRIBUTE)?.as_str() {
+            "load" => Some(BoardAction::Load(name)),
+            "delete" => Some(BoardAction::Delete(name)),
+            _ => None,
+        }
+    }
+    fn store(&self, mode: IdbTransactionMode) -> Result<Option<IdbObjectStore>, AppError> {
+        let database = self.database.borrow();
+        let Some(database) = database.as_ref() else {
+            console_warn!("The saved boards are not available yet");
+            return Ok(None);
+        };
+        let transaction = database.transaction_with_str_and_mode(BOARDS_STORE, mode)?;
+        Ok(Some(transaction.object_store(BOARDS_STORE)?))
+    }
+    fn refresh_on_success(&self, request: &IdbRequest, action: &'static str) {
+        let on_success = {
+            let database = Rc::clone(&self.database);
+            let gallery = self.gallery.clone();
+            Closure::once_into_js(move || {
+                if let Some(database) = database.borrow().as_ref() {
+                    refresh(database, &gallery);
+                }
+            })
+        };
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+        set_error_handler(request, action);
+    }
+}
+
+/// Requests of the saved boards panel
+pub enum BoardCommand {
+    /// Saves the life field in the browser under the name from the saved boards panel
+    Save,
+    /// Loads or deletes a saved board, if a button of the gallery has been clicked
+    Click(Event),
+    /// A saved board has been read, the event is the success event of the request
+    Loaded(Event),
+}
+
+impl App {
+    pub fn update_board(&mut self, command: BoardCommand) -> Result<(), AppError> {
+        match command {
+            BoardCommand::Save => self.save_board(),
+            BoardCommand::Click(event) => self.click_board(&event),
+            BoardCommand::Loaded(event) => self.load_board(&event),
+        }
+    }
+
+    fn save_board(&mut self) -> Result<(), AppError> {
+        let Some(field) = self.field.as_any_mut().downcast_mut::<Field>() else {
+            return Err(AppError::Unsupported("only Life fields can be saved"));
+        };
+        self.board_library.save(&self.document, field, self.state.generation.get())
+    }
+
+    fn click_board(&mut self, event: &web_sys::Event) -> Result<(), AppError> {
+        match BoardLibrary::action(event) {
+            Some(BoardAction::Load(name)) if self.confirm_discard() => self.board_library.load(&name, &self.board_closure),
+            Some(BoardAction::Delete(name)) if self.window.confirm_with_message(&format!("Delete the board {name}?")).unwrap_or(false) => {
+                self.board_library.delete(&name)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn load_board(&mut self, event: &web_sys::Event) -> Result<(), AppError> {
+        self.show_life_field(loaded_field(event)?)
+    }
+}
+
+/// Adds the library to the group, with the button that saves the field and the actions of the gallery
+pub fn add_board_controls(group: &Group, document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<(), AppError> {
+    let save_button = create_button("Save Board", || Message::Board(BoardCommand::Save), document, listeners, dispatcher)?;
+    save_button.set_attribute("title", "Save the field in the browser under the name")?;
+    dispatcher.try_with(|app| {
+        app.board_library.add_control(&save_button)?;
+        add_event_listener(listeners, app.board_library.gallery(), &["click"], dispatcher, |event| Message::Board(BoardCommand::Click(event)))?;
+        group.add(app.board_library.element())
+    }).transpose()?;
+    Ok(())
+}
+
+/// Sends `BoardCommand::Loaded` when a saved board has been read
+pub fn loaded_closure(dispatcher: &Weak<AppDispatcher>) -> Closure<dyn Fn(Event)> {
+    let dispatcher = dispatcher.clone();
+    Closure::<dyn Fn(Event)>::new(move |event: Event| {
+        if let Some(dispatcher) = dispatcher.upgrade() {
+            dispatcher.send(Message::Board(BoardCommand::Loaded(event)));
+        }
+    })
+}
+
+/// Field of the board from the success event of `BoardLibrary::load`
+pub fn loaded_field(event: &Event) -> Result<Field, AppError> {
+    let request = event.target().and_then(|target| target.dyn_into::<IdbRequest>().ok()).ok_or(AppError::Missing("board request"))?;
+    let record = request.result()?;
+    if record.is_undefined() {
+        return Err(AppError::InvalidBoard("the board has been deleted".to_string()));
+    }
+    let bytes = js_sys::Reflect::get(&record, &SNAPSHOT_KEY.into())?.dyn_into::<js_sys::Uint8Array>()?;
+    Field::from_bytes(&bytes.to_vec()).map_err(|err| AppError::InvalidBoard(format!("{err:?}")))
+}
+
+/// Field scaled down to fit into the thumbnail size, as a PNG data url
+fn thumbnail(document: &Document, field: &dyn DynAutomaton) -> Result<String, AppError> {
+    let scale = THUMBNAIL_SIZE_PX / field.get_width().max(field.get_height()) as f64;
+    let width = (field.get_width() as f64 * scale).round().max(1.0);
+    let height = (field.get_height() as f64 * scale).round().max(1.0);
+    let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+    canvas.set_width(width as u32);
+    canvas.set_height(height as u32);
+    let context = context_2d(&canvas)?;
+    ImageRenderer::new(document)?.draw_scaled(&context, field, width, height);
+    Ok(canvas.to_data_url()?)
+}
+
+/// Logs the failure of the request, the database doesn't report it anywhere else
+fn set_error_handler(request: &IdbRequest, action: &'static str) {
+    let on_error = {
+        let request = request.clone();
+        Closure::once_into_js(move || console_error!("Failed to {action}: {:?}", request.error()))
+    };
+    request.set_onerror(Some(on_error.unchecked_ref()));
+}
+
+/// Reads all the boards and replaces the gallery with them
+fn refresh(database: &IdbDatabase, gallery: &Element) {
+    let request = match database.transaction_with_str(BOARDS_STORE).and_then(|transaction| transaction.object_store(BOARDS_STORE)).and_then(|store| store.get_all()) {
+        Ok(request) => request,
+        Err(err) => {
+            console_error!("Failed to list the saved boards: {err:?}");
+            return;
+        }
+    };
+    let on_success = {
+        let request = request.clone();
+        let gallery = gallery.clone();
+        Closure::once_into_js(move || {
+            let Some(records) = request.result().ok().and_then(|result| result.dyn_into::<js_sys::Array>().ok()) else {
+                return;
+            };
+            gallery.set_text_content(None);
+            for record in records.iter() {
+                if let Err(err) = add_entry(&gallery, &record) {
+                    console_error!("Failed to show a saved board: {err}");
+                }
+            }
+        })
+    };
+    request.set_onsuccess(Some(on_success.unchecked_ref()));
+    set_error_handler(&request, "list the saved boards");
+}
+
+/// Thumbnail, name and buttons of the board
+fn add_entry(gallery: &Element, record: &JsValue) -> Result<(), AppError> {
+    let document = gallery.owner_document().ok_or(AppError::Missing("document"))?;
+    let get = |key: &str| js_sys::Reflect::get(record, &key.into());
+    let name = get(NAME_KEY)?.as_string().ok_or(AppError::InvalidBoard("the board has no name".to_string()))?;
+    let entry = document.create_element("figure")?;
+    entry.set_attribute("style", "margin: 0; padding: 4px; border: 1px solid #888888; text-align: center;")?;
+    if let Some(thumbnail) = get(THUMBNAIL_KEY)?.as_string() {
+        let image = document.create_element("img")?;
+        image.set_attribute("src", &thumbnail)?;
+        image.set_attribute("alt", "")?;
+        image.set_attribute("style", "display: block; margin: auto; image-rendering: pixelated;")?;
+        entry.append_child(&image)?;
+    }
+    let caption = document.create_element("figcaption")?;
+    let generation = get(GENERATION_KEY)?.as_f64().unwrap_or(0.0);
+    caption.set_text_content(Some(&format!("{name} (gen {generation})")));
+    entry.append_child(&caption)?;
+    for (text, action) in [("Load", "load"), ("Delete", "delete")] {
+        let button = document.create_element("button")?;
+        button.set_attribute("type", "button")?;
+        button.set_text_content(Some(text));
+        button.set_attribute(ACTION_ATTRIBUTE, action)?;
+        button.set_attribute(NAME_ATTRIBUTE, &name)?;
+        entry.append_child(&button)?;
+    }
+    gallery.append_child(&entry)?;
+    Ok(())
+}
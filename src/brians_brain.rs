@@ -0,0 +1,198 @@
+use std::fmt::{Display, Formatter, Write};
+use std::mem;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+use crate::automaton::{count_neighbours_with_value, parse_cells, Automaton, CellState};
+use crate::game_of_life::ParseError;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BrainCell {
+    Off = 0,
+    On = 1,
+    Dying = 2,
+}
+impl CellState for BrainCell {
+    const PALETTE: &'static [&'static str] = &["#000000", "#FFFFFF", "#3366FF"];
+    const NAMES: &'static [&'static str] = &["Off", "On", "Dying"];
+    fn index(self) -> u8 {
+        self as u8
+    }
+    fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(BrainCell::Off),
+            1 => Some(BrainCell::On),
+            2 => Some(BrainCell::Dying),
+            _ => None,
+        }
+    }
+    fn next_in_edit_cycle(self) -> Self {
+        match self {
+            BrainCell::Off => BrainCell::On,
+            BrainCell::On => BrainCell::Dying,
+            BrainCell::Dying => BrainCell::Off,
+        }
+    }
+    fn to_char(self) -> char {
+        match self {
+            BrainCell::Off => '_',
+            BrainCell::On => '#',
+            BrainCell::Dying => '~',
+        }
+    }
+    fn from_char(char: char) -> Option<Self> {
+        match char {
+            '_' => Some(BrainCell::Off),
+            '#' => Some(BrainCell::On),
+            '~' => Some(BrainCell::Dying),
+            _ => None,
+        }
+    }
+}
+
+/// Brian's Brain: a cell turns on if it has exactly 2 neighbours that are on,
+/// cells that are on start dying on the next generation and turn off after that.
+#[derive(Clone)]
+pub struct BriansBrain {
+    width: NonZeroUsize,
+    height: NonZeroUsize,
+    cells: Vec<BrainCell>,
+    swap_cells: Vec<BrainCell>,
+}
+
+impl BriansBrain {
+    pub fn generate_by_fn(width: NonZeroUsize, height: NonZeroUsize, random_bool: impl Fn(usize) -> bool) -> Self {
+        let cell_count = width.get() * height.get();
+        let cells = (0..cell_count)
+            .map(|i| if random_bool(i) { BrainCell::On } else { BrainCell::Off })
+            .collect();
+        Self {
+            width,
+            height,
+            cells,
+            swap_cells: vec![BrainCell::Off; cell_count],
+        }
+    }
+    pub fn update(&mut self) -> bool {
+        let width = self.width.get();
+        let height = self.height.get();
+        let mut has_alive = false;
+        for (index, &value) in self.cells.iter().enumerate() {
+            let new_value = match value {
+                BrainCell::On => BrainCell::Dying,
+                BrainCell::Dying => BrainCell::Off,
+                BrainCell::Off => {
+                    let on = count_neighbours_with_value(&self.cells, width, height, index / width, index % width, BrainCell::On);
+                    if on == 2 { BrainCell::On } else { BrainCell::Off }
+                }
+            };
+            self.swap_cells[index] = new_value;
+            has_alive = has_alive || (new_value != BrainCell::Off);
+        }
+        mem::swap(&mut self.cells, &mut self.swap_cells);
+        has_alive
+    }
+}
+
+impl FromStr for BriansBrain {
+    type Err = ParseError;
+    fn from_str(str: &str) -> Result<Self, ParseError> {
+        let (width, height, cells) = parse_cells(str)?;
+        let cells_len = cells.len();
+        let res = Self {
+            width,
+            height,
+            cells,
+            swap_cells: vec![BrainCell::Off; cells_len],
+        };
+        Ok(res)
+    }
+}
+
+impl Automaton for BriansBrain {
+    type State = BrainCell;
+    fn get_width(&self) -> usize {
+        self.width.get()
+    }
+    fn get_height(&self) -> usize {
+        self.height.get()
+    }
+    fn view(&self) -> &[BrainCell] {
+        &self.cells
+    }
+    fn view_old(&self) -> &[BrainCell] {
+        &self.swap_cells
+    }
+    fn step(&mut self) -> bool {
+        self.update()
+    }
+    fn set(&mut self, row: usize, col: usize, value: BrainCell) -> Option<()> {
+        if (row >= self.height.get()) || (col >= self.width.get()) {
+            return None;
+        }
+        self.cells[(row * self.width.get()) + col] = value;
+        Some(())
+    }
+    fn parse(str: &str) -> Result<Self, ParseError> {
+        str.parse()
+    }
+}
+
+impl Display for BriansBrain {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self.width.get();
+        for row in self.cells.chunks(width) {
+            for &value in row.iter() {
+                f.write_char(value.to_char())?;
+            }
+            f.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_update() {
+        let init_state = "
+______
+__##__
+______
+______
+";
+        let mut field = BriansBrain::from_str(init_state).unwrap();
+        assert!(field.update());
+        let expected_state = "
+__##__
+__~~__
+__##__
+______
+";
+        assert_eq!(expected_state.trim_start(), field.to_string());
+        assert!(field.update());
+        let expected_state = "
+__~~__
+_#__#_
+__~~__
+_#__#_
+";
+        assert_eq!(expected_state.trim_start(), field.to_string());
+    }
+    #[test]
+    fn test_dies_out() {
+        let mut field = BriansBrain::from_str("___\n_#_\n___").unwrap();
+        assert!(field.update());
+        assert!(!field.update());
+        assert_eq!("___\n___\n___\n", field.to_string());
+    }
+    #[test]
+    fn test_toggled_state() {
+        use crate::automaton::DynAutomaton;
+        let field = BriansBrain::from_str("_#~").unwrap();
+        assert_eq!(Some(1), field.toggled_state(0, 0));
+        assert_eq!(Some(2), field.toggled_state(0, 1));
+        assert_eq!(Some(0), field.toggled_state(0, 2));
+        assert_eq!(None, field.toggled_state(1, 0));
+    }
+}
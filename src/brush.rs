@@ -0,0 +1,106 @@
+/// Cells that are painted at once when editing a field
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Brush {
+    /// Square centered on the cell, with the given odd size. Size 1 is a single cell
+    Square(usize),
+    /// Disc centered on the cell, with the given odd diameter
+    Circle(usize),
+    /// Straight line from the given row and column to the cell
+    Line(usize, usize),
+}
+impl Brush {
+    /// Cells of the brush applied at the cell, the parts outside of a field of the given size are cut off
+    pub fn cells(self, row: usize, col: usize, width: usize, height: usize) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        match self {
+            Self::Square(size) | Self::Circle(size) => {
+                let radius = (size / 2) as isize;
+                for row_offset in -radius..=radius {
+                    for col_offset in -radius..=radius {
+                        // a bit more than the exact disc, so that small circles don't look like crosses
+                        if matches!(self, Self::Circle(_)) && ((row_offset * row_offset) + (col_offset * col_offset) > (radius * radius) + radius) {
+                            continue;
+                        }
+                        cells.push((row.checked_add_signed(row_offset), col.checked_add_signed(col_offset)));
+                    }
+                }
+            }
+            Self::Line(from_row, from_col) => {
+                // Bresenham's line, every step moves to one of the 8 neighbours
+                let (mut x, mut y) = (from_col as isize, from_row as isize);
+                let (to_x, to_y) = (col as isize, row as isize);
+                let (dx, dy) = ((to_x - x).abs(), -(to_y - y).abs());
+                let (step_x, step_y) = ((to_x - x).signum(), (to_y - y).signum());
+                let mut error = dx + dy;
+                loop {
+                    cells.push((usize::try_from(y).ok(), usize::try_from(x).ok()));
+                    if (x, y) == (to_x, to_y) {
+                        break;
+                    }
+                    if 2 * error >= dy {
+                        error += dy;
+                        x += step_x;
+                    }
+                    if 2 * error <= dx {
+                        error += dx;
+                        y += step_y;
+                    }
+                }
+            }
+        }
+        cells.into_iter()
+            .filter_map(|(row, col)| Some((row?, col?)))
+            .filter(|&(row, col)| (row < height) && (col < width))
+            .collect()
+    }
+}
+
+/// Brush chosen by the user. The line goes from the cell where the drag has started, the stamp pastes a pattern on a click
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BrushTool {
+    Shape(Brush),
+    Line,
+    Stamp,
+}
+
+/// Brushes of the brush selector, the first one is the default
+pub const PRESETS: [(&str, BrushTool); 7] = [
+    ("1x1", BrushTool::Shape(Brush::Square(1))),
+    ("3x3", BrushTool::Shape(Brush::Square(3))),
+    ("5x5", BrushTool::Shape(Brush::Square(5))),
+    ("5x5 circle", BrushTool::Shape(Brush::Circle(5))),
+    ("9x9 circle", BrushTool::Shape(Brush::Circle(9))),
+    ("Line", BrushTool::Line),
+    ("Glider stamp (R rotates, F flips)", BrushTool::Stamp),
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_square() {
+        assert_eq!(vec![(2, 3)], Brush::Square(1).cells(2, 3, 5, 5));
+        assert_eq!(9, Brush::Square(3).cells(2, 2, 5, 5).len());
+        // cut off at the corner
+        assert_eq!(vec![(0, 0), (0, 1), (1, 0), (1, 1)], Brush::Square(3).cells(0, 0, 5, 5));
+        assert_eq!(vec![(3, 3), (3, 4), (4, 3), (4, 4)], Brush::Square(3).cells(4, 4, 5, 5));
+    }
+    #[test]
+    fn test_circle() {
+        let cells = Brush::Circle(5).cells(2, 2, 5, 5);
+        assert_eq!(21, cells.len());
+        assert!(!cells.contains(&(0, 0)));
+        assert!(cells.contains(&(0, 1)));
+        assert!(cells.contains(&(2, 0)));
+        assert_eq!(vec![(1, 1)], Brush::Circle(1).cells(1, 1, 5, 5));
+    }
+    #[test]
+    fn test_line() {
+        assert_eq!(vec![(0, 0), (1, 1), (2, 2)], Brush::Line(0, 0).cells(2, 2, 5, 5));
+        assert_eq!(vec![(1, 3), (1, 2), (1, 1), (1, 0)], Brush::Line(1, 3).cells(1, 0, 5, 5));
+        assert_eq!(vec![(0, 0), (1, 1), (1, 2), (1, 3)], Brush::Line(0, 0).cells(1, 3, 5, 5));
+        assert_eq!(vec![(2, 2)], Brush::Line(2, 2).cells(2, 2, 5, 5));
+        // the end outside of the field is cut off
+        assert_eq!(vec![(4, 3), (4, 4)], Brush::Line(4, 3).cells(4, 6, 5, 5));
+    }
+}
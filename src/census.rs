@@ -0,0 +1,226 @@
+use std::collections::{BTreeMap, HashSet};
+use std::num::NonZeroUsize;
+use crate::game_of_life::{CellValue, Field, Neighborhood, Rule};
+
+/// Objects are only classified if they repeat within this number of generations
+pub const MAX_PERIOD: usize = 30;
+/// Live cells closer than this, in the Chebyshev distance, belong to the same object
+const OBJECT_DISTANCE: isize = 2;
+/// Characters of the extended Wechsler format, the first 32 encode columns of 5 cells
+const ALPHABET: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+/// Height of the strips of the extended Wechsler format
+const STRIP_HEIGHT: isize = 5;
+
+/// Live cells as row and column, not necessarily starting at 0
+type Cells = Vec<(isize, isize)>;
+/// Maps a row and column to a rotated or reflected row and column
+type Transform = fn(isize, isize) -> (isize, isize);
+
+/// Groups of live cells that are close to each other, each one is assumed to be a separate object.
+/// The edges don't wrap around, so objects across the edges of the field are split
+pub fn find_objects(field: &Field) -> Vec<Cells> {
+    let width = field.get_width();
+    let mut unvisited: HashSet<(isize, isize)> = field.view().iter().enumerate()
+        .filter(|&(_, &value)| value == CellValue::Alive)
+        .map(|(index, _)| ((index / width) as isize, (index % width) as isize))
+        .collect();
+    let mut objects = Vec::new();
+    // the order of the cells, so that the order of the objects doesn't depend on the hashing
+    let mut starts: Vec<_> = unvisited.iter().copied().collect();
+    starts.sort_unstable();
+    for start in starts {
+        if !unvisited.remove(&start) {
+            continue;
+        }
+        let mut object = vec![start];
+        let mut index = 0;
+        while let Some(&(row, col)) = object.get(index) {
+            for delta_row in -OBJECT_DISTANCE..=OBJECT_DISTANCE {
+                for delta_col in -OBJECT_DISTANCE..=OBJECT_DISTANCE {
+                    if unvisited.remove(&(row + delta_row, col + delta_col)) {
+                        object.push((row + delta_row, col + delta_col));
+                    }
+                }
+            }
+            index += 1;
+        }
+        object.sort_unstable();
+        objects.push(object);
+    }
+    objects
+}
+
+/// Apgcode of the object, the canonical name used by Catagolue: `xs` with the population for still lifes,
+/// `xp` with the period for oscillators and `xq` with the period for spaceships, followed by the extended Wechsler format
+/// of the phase and orientation with the shortest and then alphabetically first encoding.
+/// None if the object dies out or doesn't repeat within `MAX_PERIOD` generations when alone
+pub fn apgcode(cells: &[(isize, isize)], rule: Rule, neighborhood: Neighborhood) -> Option<String> {
+    let (phases, is_moving) = evolve(cells, rule, neighborhood)?;
+    let code = phases.iter()
+        .flat_map(|phase| orientations(phase))
+        .map(|orientation| wechsler(&orientation))
+        .min_by(|left, right| left.len().cmp(&right.len()).then_with(|| left.cmp(right)))?;
+    let prefix = match (phases.len(), is_moving) {
+        (1, false) => format!("xs{}", cells.len()),
+        (period, false) => format!("xp{period}"),
+        (period, true) => format!("xq{period}"),
+    };
+    Some(format!("{prefix}_{code}"))
+}
+
+/// Number of objects by apgcode in the field, the objects that can't be classified are counted under None
+pub fn census(field: &Field) -> BTreeMap<Option<String>, usize> {
+    let mut counts = BTreeMap::new();
+    for object in find_objects(field) {
+        *counts.entry(apgcode(&object, field.get_rule(), field.get_neighborhood())).or_default() += 1;
+    }
+    counts
+}
+
+/// Cells moved so that the smallest row and column are 0, sorted
+fn normalized(cells: &[(isize, isize)]) -> Cells {
+    let top = cells.iter().map(|&(row, _)| row).min().unwrap_or(0);
+    let left = cells.iter().map(|&(_, col)| col).min().unwrap_or(0);
+    let mut res: Cells = cells.iter().map(|&(row, col)| (row - top, col - left)).collect();
+    res.sort_unstable();
+    res
+}
+
+/// Runs the object alone until it has the same shape again. Returns the shapes of all of its phases,
+/// and whether the object has moved in the period
+fn evolve(cells: &[(isize, isize)], rule: Rule, neighborhood: Neighborhood) -> Option<(Vec<Cells>, bool)> {
+    let initial = normalized(cells);
+    let height = initial.iter().map(|&(row, _)| row).max()? as usize + 1;
+    let width = initial.iter().map(|&(_, col)| col).max()? as usize + 1;
+    // the object can't reach the edges, even at the highest speed of the extended neighborhood
+    let margin = (2 * MAX_PERIOD) + 2;
+    let mut field = Field::new(NonZeroUsize::new(width + (2 * margin)).unwrap(), NonZeroUsize::new(height + (2 * margin)).unwrap())
+        .with_rule(rule)
+        .with_neighborhood(neighborhood);
+    for &(row, col) in &initial {
+        field.set_by_coords(row as usize + margin, col as usize + margin, CellValue::Alive);
+    }
+    let live_cells = |field: &Field| -> Cells {
+        let width = field.get_width();
+        field.view().iter().enumerate()
+            .filter(|&(_, &value)| value == CellValue::Alive)
+            .map(|(index, _)| ((index / width) as isize, (index % width) as isize))
+            .collect()
+    };
+    let start = live_cells(&field);
+    let mut phases = vec![initial.clone()];
+    for _ in 0..MAX_PERIOD {
+        field.update();
+        let cells = live_cells(&field);
+        if cells.is_empty() {
+            return None;
+        }
+        let shape = normalized(&cells);
+        if shape == initial {
+            return Some((phases, cells[0] != start[0]));
+        }
+        phases.push(shape);
+    }
+    None
+}
+
+/// The 8 rotations and reflections of the normalized cells
+fn orientations(cells: &[(isize, isize)]) -> Vec<Cells> {
+    let transforms: [Transform; 8] = [
+        |row, col| (row, col),
+        |row, col| (row, -col),
+        |row, col| (-row, col),
+        |row, col| (-row, -col),
+        |row, col| (col, row),
+        |row, col| (col, -row),
+        |row, col| (-col, row),
+        |row, col| (-col, -row),
+    ];
+    transforms.iter()
+        .map(|transform| normalized(&cells.iter().map(|&(row, col)| transform(row, col)).collect::<Vec<_>>()))
+        .collect()
+}
+
+/// Extended Wechsler format of the normalized cells: strips of 5 rows separated by `z`, each column of a strip
+/// is a character for the bits of its cells, the top cell in the lowest bit. Runs of empty columns are shortened to
+/// `w` for 2, `x` for 3 and `y` followed by a character for 4 to 39, the empty columns at the end of a strip are left out
+fn wechsler(cells: &[(isize, isize)]) -> String {
+    let height = cells.iter().map(|&(row, _)| row).max().unwrap_or(0) + 1;
+    let width = cells.iter().map(|&(_, col)| col).max().unwrap_or(0) + 1;
+    let cells: HashSet<_> = cells.iter().copied().collect();
+    let mut res = String::new();
+    for strip in 0..((height + STRIP_HEIGHT - 1) / STRIP_HEIGHT) {
+        if strip > 0 {
+            res.push('z');
+        }
+        let mut empty_columns = 0;
+        for col in 0..width {
+            let bits = (0..STRIP_HEIGHT)
+                .filter(|&bit| cells.contains(&((strip * STRIP_HEIGHT) + bit, col)))
+                .fold(0, |bits, bit| bits | (1 << bit));
+            if bits == 0 {
+                empty_columns += 1;
+                continue;
+            }
+            while empty_columns > 0 {
+                match empty_columns {
+                    1 => res.push('0'),
+                    2 => res.push('w'),
+                    3 => res.push('x'),
+                    _ => {
+                        let run = empty_columns.min(39);
+                        res.push('y');
+                        res.push(ALPHABET[run - 4] as char);
+                        empty_columns -= run;
+                        continue;
+                    }
+                }
+                empty_columns = 0;
+            }
+            res.push(ALPHABET[bits] as char);
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+    use crate::rle::parse_rle;
+    fn code(rle: &str) -> Option<String> {
+        let field = parse_rle(rle).unwrap();
+        let objects = find_objects(&field);
+        assert_eq!(1, objects.len());
+        apgcode(&objects[0], Rule::LIFE, Neighborhood::Moore)
+    }
+    #[test]
+    fn test_apgcode() {
+        assert_eq!(Some("xs4_33"), code("x = 2, y = 2\n2o$2o!").as_deref());
+        assert_eq!(Some("xs6_696"), code("x = 4, y = 3\nb2o$o2bo$b2o!").as_deref());
+        assert_eq!(Some("xs5_253"), code("x = 3, y = 3\n2o$obo$bo!").as_deref());
+        assert_eq!(Some("xp2_7"), code("x = 3, y = 1\n3o!").as_deref());
+        assert_eq!(Some("xp2_7e"), code("x = 4, y = 2\nb3o$3o!").as_deref());
+        assert_eq!(Some("xp2_318c"), code("x = 4, y = 4\n2o$o$3bo$2b2o!").as_deref());
+        assert_eq!(Some("xq4_153"), code("x = 3, y = 3\nbo$2bo$3o!").as_deref());
+        assert_eq!(Some("xq4_6frc"), code("x = 5, y = 4\nbo2bo$o$o3bo$4o!").as_deref());
+        // the R-pentomino takes over a thousand generations to settle
+        assert_eq!(None, code("x = 3, y = 3\nb2o$2o$bo!"));
+    }
+    #[test]
+    fn test_wechsler() {
+        assert_eq!("1y01", wechsler(&[(0, 0), (0, 5)]));
+        assert_eq!("1w1", wechsler(&[(0, 0), (0, 3)]));
+        // a second strip, and a run longer than a single `y` can encode
+        assert_eq!("1z1", wechsler(&[(0, 0), (5, 0)]));
+        assert_eq!("1yz01", wechsler(&[(0, 0), (0, 41)]));
+    }
+    #[test]
+    fn test_census() {
+        let field = Field::from_str("##_____#\n##_____#\n_______#\n________\n___##___\n___##___").unwrap();
+        let counts = census(&field);
+        assert_eq!(Some(&2), counts.get(&Some("xs4_33".to_string())));
+        assert_eq!(Some(&1), counts.get(&Some("xp2_7".to_string())));
+        assert_eq!(2, counts.len());
+    }
+}
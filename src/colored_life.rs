@@ -0,0 +1,262 @@
+use std::cell::RefCell;
+use std::mem;
+use std::num::NonZeroUsize;
+use crate::automaton::{next_coord_wrapped, parse_cells, prev_coord_wrapped, Automaton, CellState};
+use crate::game_of_life::ParseError;
+use crate::random::XorShift;
+
+/// Cell of a colored Life variant, 0 is dead, other values are colors
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColorCell(pub u8);
+impl ColorCell {
+    pub const DEAD: ColorCell = ColorCell(0);
+    pub fn is_alive(self) -> bool {
+        self.0 != 0
+    }
+}
+impl CellState for ColorCell {
+    const PALETTE: &'static [&'static str] = &["#FFFFFF", "#D62828", "#1D70A2", "#2A9D8F", "#F4A261"];
+    const NAMES: &'static [&'static str] = &["Dead", "Red", "Blue", "Green", "Orange"];
+    fn index(self) -> u8 {
+        self.0
+    }
+    fn from_index(index: u8) -> Option<Self> {
+        if (index as usize) < Self::PALETTE.len() { Some(Self(index)) } else { None }
+    }
+    fn next_in_edit_cycle(self) -> Self {
+        Self((self.0 + 1) % Self::PALETTE.len() as u8)
+    }
+    fn to_char(self) -> char {
+        if self.0 == 0 { '_' } else { (b'a' + self.0 - 1) as char }
+    }
+    fn from_char(char: char) -> Option<Self> {
+        match char {
+            '_' => Some(Self::DEAD),
+            'a'..='d' => Some(Self(char as u8 - b'a' + 1)),
+            _ => None,
+        }
+    }
+}
+
+/// Life variant where every live cell has a color, the rules for life and death are the usual B3/S23.
+/// A newborn cell takes the color of the majority of its 3 parents.
+/// With 4 colors (QuadLife), if all 3 parents have different colors, the newborn takes the remaining color.
+#[derive(Clone)]
+pub struct ColoredLife {
+    width: NonZeroUsize,
+    height: NonZeroUsize,
+    colors: u8,
+    cells: Vec<ColorCell>,
+    swap_cells: Vec<ColorCell>,
+}
+
+impl ColoredLife {
+    /// Immigration, 2 colors
+    pub const IMMIGRATION: u8 = 2;
+    /// QuadLife, 4 colors
+    pub const QUAD_LIFE: u8 = 4;
+
+    pub fn generate_by_fn(width: NonZeroUsize, height: NonZeroUsize, colors: u8, random_cell: impl Fn(usize) -> ColorCell) -> Self {
+        let cell_count = width.get() * height.get();
+        let cells = (0..cell_count)
+            .map(|i| {
+                let cell = random_cell(i);
+                if cell.0 > colors { ColorCell::DEAD } else { cell }
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            colors,
+            cells,
+            swap_cells: vec![ColorCell::DEAD; cell_count],
+        }
+    }
+    /// Immigration field where two random soups of the given share of live cells fight, the red one in the left half
+    /// and the blue one in the right half. The soups are kept apart by empty strips, also across the wrapped edges
+    pub fn battle(width: NonZeroUsize, height: NonZeroUsize, density: f64, seed: u64) -> Self {
+        let half = (width.get() / 2).max(1);
+        let gap = half / 8 + 1;
+        let random = RefCell::new(XorShift::new(seed));
+        Self::generate_by_fn(width, height, Self::IMMIGRATION, |index| {
+            let col = index % width.get();
+            let is_in_soup = (col < 2 * half) && (gap..half - gap.min(half)).contains(&(col % half));
+            if is_in_soup && random.borrow_mut().next_f64() < density {
+                ColorCell(if col < half { 1 } else { 2 })
+            } else {
+                ColorCell::DEAD
+            }
+        })
+    }
+    pub fn get_colors(&self) -> u8 {
+        self.colors
+    }
+    /// Number of live cells of every color, starting with the first one
+    pub fn team_populations(&self) -> Vec<usize> {
+        let mut populations = vec![0; self.colors as usize];
+        for cell in self.cells.iter().filter(|cell| cell.is_alive()) {
+            populations[cell.0 as usize - 1] += 1;
+        }
+        populations
+    }
+    pub fn update(&mut self) -> bool {
+        let width = self.width.get();
+        let max_row = self.height.get() - 1;
+        let max_col = width - 1;
+        let mut has_alive = false;
+        for (index, &value) in self.cells.iter().enumerate() {
+            let row = index / width;
+            let col = index % width;
+            // live neighbours by color, index 0 is unused
+            let mut counts = [0u8; 5];
+            for check_row in [prev_coord_wrapped(row, max_row), row, next_coord_wrapped(row, max_row)] {
+                for check_col in [prev_coord_wrapped(col, max_col), col, next_coord_wrapped(col, max_col)] {
+                    if (check_row == row) && (check_col == col) {
+                        continue;
+                    }
+                    counts[self.cells[(check_row * width) + check_col].0 as usize] += 1;
+                }
+            }
+            let live_neighbours = counts[1..].iter().sum::<u8>();
+            let new_value = match (value.is_alive(), live_neighbours) {
+                (true, 2) | (true, 3) => value,
+                (false, 3) => self.newborn_color(&counts),
+                _ => ColorCell::DEAD,
+            };
+            self.swap_cells[index] = new_value;
+            has_alive = has_alive || new_value.is_alive();
+        }
+        mem::swap(&mut self.cells, &mut self.swap_cells);
+        has_alive
+    }
+    fn newborn_color(&self, counts: &[u8; 5]) -> ColorCell {
+        let colors = &counts[1..=(self.colors as usize)];
+        if let Some(majority) = colors.iter().position(|&count| count >= 2) {
+            return ColorCell(majority as u8 + 1);
+        }
+        if self.colors == Self::QUAD_LIFE {
+            if let Some(missing) = colors.iter().position(|&count| count == 0) {
+                return ColorCell(missing as u8 + 1);
+            }
+        }
+        // not reachable with 2 or 4 colors and 3 parents
+        ColorCell(1)
+    }
+}
+
+impl Automaton for ColoredLife {
+    type State = ColorCell;
+    fn get_width(&self) -> usize {
+        self.width.get()
+    }
+    fn get_height(&self) -> usize {
+        self.height.get()
+    }
+    fn view(&self) -> &[ColorCell] {
+        &self.cells
+    }
+    fn view_old(&self) -> &[ColorCell] {
+        &self.swap_cells
+    }
+    fn step(&mut self) -> bool {
+        self.update()
+    }
+    fn set(&mut self, row: usize, col: usize, value: ColorCell) -> Option<()> {
+        if (row >= self.height.get()) || (col >= self.width.get()) || (value.0 > self.colors) {
+            return None;
+        }
+        self.cells[(row * self.width.get()) + col] = value;
+        Some(())
+    }
+    fn toggle(&mut self, row: usize, col: usize) -> Option<()> {
+        if (row >= self.height.get()) || (col >= self.width.get()) {
+            return None;
+        }
+        let index = (row * self.width.get()) + col;
+        self.cells[index] = ColorCell((self.cells[index].0 + 1) % (self.colors + 1));
+        Some(())
+    }
+    /// Parses QuadLife if there are colors `c` or `d`, Immigration otherwise
+    fn parse(str: &str) -> Result<Self, ParseError> {
+        let (width, height, cells) = parse_cells::<ColorCell>(str)?;
+        let colors = if cells.iter().any(|cell| cell.0 > Self::IMMIGRATION) {
+            Self::QUAD_LIFE
+        } else {
+            Self::IMMIGRATION
+        };
+        Ok(Self::generate_by_fn(width, height, colors, |i| cells[i]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_immigration_majority() {
+        let init_state = "
+_____
+_____
+_aba_
+_____
+_____
+";
+        let mut field = ColoredLife::parse(init_state).unwrap();
+        assert_eq!(ColoredLife::IMMIGRATION, field.get_colors());
+        field.update();
+        let expected_state = "
+_____
+__a__
+__b__
+__a__
+_____
+";
+        assert_eq!(expected_state.trim_start(), field.serialize());
+    }
+    #[test]
+    fn test_quad_life_missing_color() {
+        let init_state = "
+_____
+_____
+_abc_
+_____
+_____
+";
+        let mut field = ColoredLife::parse(init_state).unwrap();
+        assert_eq!(ColoredLife::QUAD_LIFE, field.get_colors());
+        field.update();
+        let expected_state = "
+_____
+__d__
+__b__
+__d__
+_____
+";
+        assert_eq!(expected_state.trim_start(), field.serialize());
+    }
+    #[test]
+    fn test_battle() {
+        let size = NonZeroUsize::new(32).unwrap();
+        let mut field = ColoredLife::battle(size, size, 0.5, 7);
+        assert_eq!(field.serialize(), ColoredLife::battle(size, size, 0.5, 7).serialize());
+        for (index, cell) in field.view().iter().enumerate().filter(|(_, cell)| cell.is_alive()) {
+            let col = index % 32;
+            assert!((3..13).contains(&col) || (19..29).contains(&col), "{col}");
+            assert_eq!(if col < 16 { ColorCell(1) } else { ColorCell(2) }, *cell);
+        }
+        let populations = field.team_populations();
+        assert_eq!(2, populations.len());
+        assert!(populations.iter().all(|&population| population > 100));
+        assert_eq!(crate::automaton::DynAutomaton::count_by_state(&field)[1..=2], populations[..]);
+        field.update();
+        assert_eq!(crate::automaton::DynAutomaton::count_by_state(&field)[1..=2], field.team_populations()[..]);
+    }
+    #[test]
+    fn test_toggle_cycles_only_used_colors() {
+        let mut field = ColoredLife::parse("___\n___\n___").unwrap();
+        field.toggle(0, 0);
+        field.toggle(0, 0);
+        assert_eq!(ColorCell(2), field.view()[0]);
+        field.toggle(0, 0);
+        assert_eq!(ColorCell::DEAD, field.view()[0]);
+    }
+}
@@ -0,0 +1,195 @@
+use std::rc::Rc;
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, Document, Element, HtmlCanvasElement, HtmlElement, HtmlInputElement};
+use crate::app::{create_button, App, AppDispatcher, Message};
+use crate::automaton::DynAutomaton;
+use crate::control_panel::Group;
+use crate::error::AppError;
+use crate::listeners::Listeners;
+use crate::render::{context_2d, draw_cells, resize_canvas, CELL_SIZE_PX};
+
+/// Rule of the right side when the rule input is empty, so that the default comparison shows a difference
+const DEFAULT_OTHER_RULE: &str = "B36/S23";
+
+/// Number of cells that have different states in the fields, the fields are expected to have the same size
+pub fn count_differences(left: &dyn DynAutomaton, right: &dyn DynAutomaton) -> usize {
+    let cell_count = (left.get_width() * left.get_height()).min(right.get_width() * right.get_height());
+    (0..cell_count).filter(|&index| left.state_at(index) != right.state_at(index)).count()
+}
+
+/// One of the compared fields with its canvas
+#[derive(Debug)]
+struct Side {
+    caption: Element,
+    canvas: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+}
+impl Side {
+    fn new(document: &Document, container: &Element) -> Result<Self, AppError> {
+        let column = document.create_element("div")?;
+        column.set_attribute("style", "display: inline-block; vertical-align: top; margin: 4px;")?;
+        let caption = document.create_element("div")?;
+        caption.set_attribute("style", "font-family: monospace;")?;
+        let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+        canvas.set_attribute("style", "display: block; background-color: #CCCCCC;")?;
+        let context = context_2d(&canvas)?;
+        column.append_child(&caption)?;
+        column.append_child(&canvas)?;
+        container.append_child(&column)?;
+        Ok(Self { caption, canvas, context })
+    }
+    fn update_caption(&self, field: &dyn DynAutomaton) {
+        let population: usize = field.count_by_state().iter().skip(1).sum();
+        let rule = field.rule().map(|rule| format!("rule {rule} | ")).unwrap_or_default();
+        self.caption.set_text_content(Some(&format!("{rule}population {population}")));
+    }
+}
+
+/// Two copies of the field with different rules side by side, they advance together with the animation.
+/// Hidden until a comparison is started
+pub struct ComparePanel {
+    element: HtmlElement,
+    rule_input: HtmlInputElement,
+    controls: Element,
+    summary: Element,
+    sides: [Side; 2],
+    fields: Option<[Box<dyn DynAutomaton>; 2]>,
+    generation: u64,
+}
+impl ComparePanel {
+    pub fn new(document: &Document) -> Result<Self, AppError> {
+        let element = document.create_element("div")?.dyn_into::<HtmlElement>()?;
+        element.set_attribute("style", "display: none; border-top: 1px solid #888888; margin-top: 8px; padding-top: 4px;")?;
+        let controls = document.create_element("div")?;
+        let rule_input = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+        rule_input.set_placeholder(DEFAULT_OTHER_RULE);
+        rule_input.set_title("Rule of the right copy, the left one keeps the rule of the field");
+        rule_input.set_size(10);
+        controls.append_child(&rule_input)?;
+        let summary = document.create_element("div")?;
+        summary.set_attribute("style", "font-family: monospace;")?;
+        element.append_child(&controls)?;
+        element.append_child(&summary)?;
+        let sides_row = document.create_element("div")?;
+        element.append_child(&sides_row)?;
+        let sides = [Side::new(document, &sides_row)?, Side::new(document, &sides_row)?];
+        Ok(Self { element, rule_input, controls, summary, sides, fields: None, generation: 0 })
+    }
+    pub fn element(&self) -> &HtmlElement {
+        &self.element
+    }
+    /// Adds a control, like a button, after the rule input
+    pub fn add_control(&self, control: &Element) -> Result<(), AppError> {
+        self.controls.append_child(control)?;
+        Ok(())
+    }
+    /// Copies the field to both sides, the right one gets the rule from the rule input, and shows them
+    pub fn open(&mut self, field: &dyn DynAutomaton, cell_size_px: usize) -> Result<(), AppError> {
+        let other_rule = match self.rule_input.value().trim() {
+            "" => DEFAULT_OTHER_RULE.to_string(),
+            rule => rule.to_string(),
+        };
+        if field.rule().is_none() {
+            return Err(AppError::Unsupported("the automaton has no rule to compare"));
+        }
+        let left = field.clone_box();
+        let mut right = field.clone_box();
+        right.set_rule(&other_rule).map_err(|_| AppError::InvalidInput(format!("invalid rule {other_rule}")))?;
+        let scale = (cell_size_px + 1) as f64 / (CELL_SIZE_PX + 1) as f64;
+        for (side, field) in self.sides.iter().zip([&left, &right]) {
+            resize_canvas(field.as_ref(), &side.canvas, &side.context, scale)?;
+            draw_cells(&side.context, field.as_ref(), true);
+            side.update_caption(field.as_ref());
+        }
+        self.fields = Some([left, right]);
+        self.generation = 0;
+        self.update_summary();
+        // setting styles can't fail for this property
+        let _ = self.element.style().set_property("display", "block");
+        Ok(())
+    }
+    pub fn close(&mut self) {
+        self.fields = None;
+        let _ = self.element.style().set_property("display", "none");
+    }
+    /// Advances both sides by a generation, if the comparison is open
+    pub fn step(&mut self) {
+        let Some(fields) = &mut self.fields else {
+            return;
+        };
+        for (side, field) in self.sides.iter().zip(fields.iter_mut()) {
+            field.step();
+            draw_cells(&side.context, field.as_ref(), false);
+            side.update_caption(field.as_ref());
+        }
+        self.generation += 1;
+        self.update_summary();
+    }
+    fn update_summary(&self) {
+        let Some([left, right]) = &self.fields else {
+            return;
+        };
+        let text = match count_differences(left.as_ref(), right.as_ref()) {
+            0 => format!("generation {} | identical", self.generation),
+            differences => format!("generation {} | {differences} cells differ", self.generation),
+        };
+        self.summary.set_text_content(Some(&text));
+    }
+}
+
+/// Requests of the compare controls
+pub enum CompareCommand {
+    /// Starts comparing copies of the field with the current rule and the rule of the compare panel
+    Open,
+    /// Advances both compared copies by a generation, also while paused
+    Step,
+    Close,
+}
+
+impl App {
+    pub fn update_compare(&mut self, command: CompareCommand) -> Result<(), AppError> {
+        match command {
+            CompareCommand::Open => self.compare.open(self.field.as_ref(), self.state.cell_size_px),
+            CompareCommand::Step => {
+                self.compare.step();
+                Ok(())
+            }
+            CompareCommand::Close => {
+                self.compare.close();
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Adds the button that starts a comparison to the group, and the buttons of the panel to the panel
+pub fn add_compare_controls(group: &Group, document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<(), AppError> {
+    let open_button = create_button("Compare Rules", || Message::Compare(CompareCommand::Open), document, listeners, dispatcher)?;
+    open_button.set_attribute("title", "Run copies of the field with this rule and another one side by side")?;
+    group.add(&open_button)?;
+    let step_button = create_button("Step Both", || Message::Compare(CompareCommand::Step), document, listeners, dispatcher)?;
+    let close_button = create_button("Close", || Message::Compare(CompareCommand::Close), document, listeners, dispatcher)?;
+    for button in [step_button, close_button] {
+        dispatcher.try_with(|app| app.compare.add_control(&button)).transpose()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+    use crate::game_of_life::{Field, Rule};
+    #[test]
+    fn test_count_differences() {
+        let field = Field::from_str("______\n_###__\n______\n______").unwrap();
+        let mut left: Box<dyn DynAutomaton> = Box::new(field.clone());
+        let mut right: Box<dyn DynAutomaton> = Box::new(field.with_rule(Rule::HIGH_LIFE));
+        assert_eq!(0, count_differences(left.as_ref(), right.as_ref()));
+        left.step();
+        right.step();
+        assert_eq!(0, count_differences(left.as_ref(), right.as_ref()));
+        right.toggle(3, 5);
+        assert_eq!(1, count_differences(left.as_ref(), right.as_ref()));
+    }
+}
@@ -0,0 +1,173 @@
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use web_sys::{Document, Element, HtmlCanvasElement, HtmlElement, MouseEvent};
+use crate::app::{add_event_listener, add_message_listener, create_button, App, AppDispatcher, Message};
+#[cfg(feature = "audio")]
+use crate::audio::Sonifier;
+use crate::error::AppError;
+use crate::field_input::client_to_cell;
+use crate::listeners::Listeners;
+use crate::rle::region_to_rle;
+use crate::share::write_clipboard_text;
+use crate::stamp_preview::stamp_cells;
+
+/// Width and height of the regions of the context menu actions
+const REGION_SIZE: usize = 16;
+
+/// Actions of the context menu of the field, on the cell that was clicked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuAction {
+    /// Puts the stamp pattern with its top left corner on the cell
+    Stamp,
+    /// Empties the region with its top left corner on the cell
+    ClearRegion,
+    /// Scrolls the page so that the cell is in the center of the window
+    CenterView,
+    /// Copies the region with its top left corner on the cell to the clipboard as RLE
+    CopyRle,
+    /// Marks the row of the cell as the playhead lane, or removes the lane from it
+    #[cfg(feature = "audio")]
+    Playhead,
+}
+
+/// Cell and point in client coordinates where the menu was opened
+pub type Target = ((usize, usize), (f64, f64));
+
+/// Menu shown at the cursor over the field, hidden until it is opened
+#[derive(Debug)]
+pub struct ContextMenu {
+    element: HtmlElement,
+    target: Option<Target>,
+}
+impl ContextMenu {
+    pub fn new(document: &Document) -> Result<Self, AppError> {
+        let element = document.create_element("div")?.dyn_into::<HtmlElement>()?;
+        element.set_attribute("role", "menu")?;
+        element.set_attribute(
+            "style",
+            "display: none; position: fixed; z-index: 1; padding: 4px; background-color: #FFFFFF; border: 1px solid #888888; box-shadow: 2px 2px 4px #888888;",
+        )?;
+        Ok(Self { element, target: None })
+    }
+    pub fn element(&self) -> &HtmlElement {
+        &self.element
+    }
+    /// Adds an item at the end of the menu, one per line
+    pub fn add_item(&self, item: &Element) -> Result<(), AppError> {
+        item.set_attribute("role", "menuitem")?;
+        item.set_attribute("style", "display: block; width: 100%; text-align: left;")?;
+        self.element.append_child(item)?;
+        Ok(())
+    }
+    pub fn show(&mut self, target: Target) {
+        let (_, (client_x, client_y)) = target;
+        self.target = Some(target);
+        // setting styles can't fail for these properties
+        let style = self.element.style();
+        let _ = style.set_property("left", &format!("{client_x}px"));
+        let _ = style.set_property("top", &format!("{client_y}px"));
+        let _ = style.set_property("display", "block");
+    }
+    /// Hides the menu and returns where it was opened, None if it wasn't shown
+    pub fn hide(&mut self) -> Option<Target> {
+        let _ = self.element.style().set_property("display", "none");
+        self.target.take()
+    }
+}
+
+/// Requests of the context menu of the field
+pub enum MenuCommand {
+    /// Opens the menu of the clicked cell if Ctrl is pressed, the menu of the browser is never shown over the field
+    Open(MouseEvent),
+    Close,
+    Action(MenuAction),
+}
+
+impl App {
+    pub fn update_menu(&mut self, command: MenuCommand) -> Result<(), AppError> {
+        match command {
+            MenuCommand::Open(event) => {
+                event.prevent_default();
+                if event.ctrl_key() {
+                    let point = (event.client_x() as f64, event.client_y() as f64);
+                    let cell = client_to_cell(&self.canvas, self.field.as_ref(), point.0, point.1);
+                    self.context_menu.show((cell, point));
+                }
+                Ok(())
+            }
+            MenuCommand::Close => {
+                self.context_menu.hide();
+                Ok(())
+            }
+            MenuCommand::Action(action) => self.menu_action(action),
+        }
+    }
+
+    fn menu_action(&mut self, action: MenuAction) -> Result<(), AppError> {
+        let Some(((row, col), point)) = self.context_menu.hide() else {
+            return Ok(());
+        };
+        match action {
+            MenuAction::CenterView => {
+                let center = (self.window.inner_width()?.as_f64().unwrap_or(0.0) / 2.0, self.window.inner_height()?.as_f64().unwrap_or(0.0) / 2.0);
+                return self.zoom(self.state.cell_size_px, point, center);
+            }
+            MenuAction::CopyRle => {
+                let rle = region_to_rle(self.field.as_ref(), row, col, REGION_SIZE, REGION_SIZE);
+                // the promise is rejected if the page doesn't have the permission, the browser reports that
+                let _ = write_clipboard_text(&rle)?;
+                self.live_region.announce("Copied the region as RLE");
+                return Ok(());
+            }
+            MenuAction::Stamp => {
+                // the dead cells of the pattern don't change the field
+                let cells = stamp_cells(&self.stamp, row as isize, col as isize, self.field.get_width(), self.field.get_height());
+                self.edit(&cells, self.draw_state().unwrap_or(1));
+            }
+            #[cfg(feature = "audio")]
+            MenuAction::Playhead => {
+                // the menu click lets the browser start the audio
+                if self.sonifier.is_none() {
+                    self.sonifier = Some(Sonifier::new()?);
+                }
+                self.playhead.toggle(row, self.field.as_ref(), self.state.cell_size_px);
+                let announcement = match self.playhead.row(self.field.as_ref()) {
+                    Some(row) => format!("Playhead on row {}", row + 1),
+                    None => "Playhead removed".to_string(),
+                };
+                self.live_region.announce(&announcement);
+                return Ok(());
+            }
+            MenuAction::ClearRegion => {
+                let rows = row..(row + REGION_SIZE).min(self.field.get_height());
+                let cells: Vec<_> = rows.flat_map(|row| (col..(col + REGION_SIZE).min(self.field.get_width())).map(move |col| (row, col))).collect();
+                self.edit(&cells, 0);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Opens the menu with Ctrl and a click on the field, a click anywhere else closes it
+pub fn add_menu_controls(canvas: &HtmlCanvasElement, document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<(), AppError> {
+    add_event_listener(listeners, canvas, &["contextmenu"], dispatcher, |event| Message::Menu(MenuCommand::Open(event)))?;
+    add_message_listener(listeners, document, &["mousedown"], dispatcher, || Message::Menu(MenuCommand::Close))?;
+    // the clicks on the items don't close the menu before they are handled
+    let stop_closure = Closure::<dyn Fn(_)>::new(|event: web_sys::Event| event.stop_propagation());
+    dispatcher.try_with(|app| listeners.add(app.context_menu.element(), &["mousedown"], stop_closure)).transpose()?;
+    for (name, action) in [
+        ("Stamp glider here", MenuAction::Stamp),
+        ("Clear region", MenuAction::ClearRegion),
+        ("Center view here", MenuAction::CenterView),
+        ("Copy RLE from here", MenuAction::CopyRle),
+        #[cfg(feature = "audio")]
+        ("Toggle playhead on this row", MenuAction::Playhead),
+    ] {
+        let item = create_button(name, move || Message::Menu(MenuCommand::Action(action)), document, listeners, dispatcher)?;
+        if matches!(action, MenuAction::ClearRegion | MenuAction::CopyRle) {
+            item.set_attribute("title", &format!("The region is {REGION_SIZE}x{REGION_SIZE} cells from the clicked one"))?;
+        }
+        dispatcher.try_with(|app| app.context_menu.add_item(&item)).transpose()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use web_sys::{Document, Element, Node};
+use crate::error::AppError;
+
+/// Controls of the app in collapsible groups, each group is a `details` element with the title in its `summary`
+#[derive(Debug)]
+pub struct ControlPanel {
+    document: Document,
+    container: Element,
+}
+impl ControlPanel {
+    pub fn new(document: &Document) -> Result<Self, AppError> {
+        let container = document.create_element("div")?;
+        container.set_attribute("role", "toolbar")?;
+        container.set_attribute("aria-label", "Controls")?;
+        Ok(Self { document: document.clone(), container })
+    }
+    pub fn element(&self) -> &Element {
+        &self.container
+    }
+    /// Adds a group of controls at the end of the panel, `is_open` is whether it is expanded at the start
+    pub fn group(&self, title: &str, is_open: bool) -> Result<Group, AppError> {
+        let details = self.document.create_element("details")?;
+        details.set_attribute("style", "display: inline-block; vertical-align: top; margin-right: 0.5em;")?;
+        if is_open {
+            details.set_attribute("open", "")?;
+        }
+        let summary = self.document.create_element("summary")?;
+        summary.set_text_content(Some(title));
+        details.append_child(&summary)?;
+        self.container.append_child(&details)?;
+        Ok(Group { details })
+    }
+}
+
+/// Group of the control panel, the controls are shown in the order they are added
+#[derive(Debug)]
+pub struct Group {
+    details: Element,
+}
+impl Group {
+    pub fn add(&self, control: &Node) -> Result<(), AppError> {
+        self.details.append_child(control)?;
+        Ok(())
+    }
+}
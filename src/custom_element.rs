@@ -0,0 +1,76 @@
+use std::cell::RefCell;
+use wasm_bindgen::prelude::*;
+use web_sys::Element;
+use crate::error::AppError;
+use crate::options::Options;
+use crate::GameOfLifeApp;
+
+const DEFAULT_ELEMENT_NAME: &str = "game-of-life";
+const OBSERVED_ATTRIBUTES: [&str; 9] = ["width", "size", "cell-size", "rule", "pattern", "density", "theme", "autoplay", "fps"];
+
+thread_local! {
+    /// Apps of the custom elements that are currently in the document
+    static ELEMENT_APPS: RefCell<Vec<(Element, GameOfLifeApp)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Registers a custom element, `<game-of-life>` by default, that runs an app configured by its attributes,
+/// like `<game-of-life width="128" rule="B3/S23" autoplay></game-of-life>`.
+/// The app is recreated when the attributes change and destroyed when the element is removed
+#[wasm_bindgen(js_name = defineGameOfLifeElement)]
+pub fn define_game_of_life_element(name: Option<String>) -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or(AppError::Missing("window"))?;
+    let connect = Closure::<dyn Fn(Element)>::new(connect);
+    let disconnect = Closure::<dyn Fn(Element)>::new(disconnect);
+    // classes extending HTMLElement can't be created from Rust, so the class only forwards the callbacks
+    let class_factory = js_sys::Function::new_with_args(
+        "connect, disconnect, observedAttributes",
+        "return class extends HTMLElement {
+            static get observedAttributes() { return observedAttributes; }
+            connectedCallback() { connect(this); }
+            disconnectedCallback() { disconnect(this); }
+            attributeChangedCallback() {
+                if (this.isConnected) {
+                    disconnect(this);
+                    connect(this);
+                }
+            }
+        }",
+    );
+    let observed_attributes = OBSERVED_ATTRIBUTES.iter().map(|&name| JsValue::from_str(name)).collect::<js_sys::Array>();
+    let class = class_factory.call3(&JsValue::NULL, connect.as_ref(), disconnect.as_ref(), &observed_attributes)?;
+    window.custom_elements().define(name.as_deref().unwrap_or(DEFAULT_ELEMENT_NAME), class.unchecked_ref())?;
+    connect.forget(); // prevent closure from dropping when going out of scope. Custom elements can't be unregistered, so they are needed forever
+    disconnect.forget();
+    Ok(())
+}
+
+fn connect(element: Element) {
+    // elements that are upgraded while in the document get both the attribute and the connected callbacks
+    if ELEMENT_APPS.with_borrow(|apps| apps.iter().any(|(app_element, _)| *app_element == element)) {
+        return;
+    }
+    let attributes = element.get_attribute_names().iter()
+        .filter_map(|name| name.as_string())
+        .filter_map(|name| element.get_attribute(&name).map(|value| (name, value)))
+        .collect::<Vec<_>>();
+    let mut options = Options::default();
+    if let Err(err) = options.apply_attributes(attributes.iter().map(|(name, value)| (name.as_str(), value.clone()))) {
+        crate::console_warn!("Invalid attributes of the game of life element: {err}");
+        return;
+    }
+    match GameOfLifeApp::create(&element, options) {
+        Ok(app) => ELEMENT_APPS.with_borrow_mut(|apps| apps.push((element, app))),
+        // the element shows the error itself
+        Err(err) => crate::console_error!("Failed to create the game of life element: {err}"),
+    }
+}
+
+fn disconnect(element: Element) {
+    let app = ELEMENT_APPS.with_borrow_mut(|apps| {
+        let index = apps.iter().position(|(app_element, _)| *app_element == element)?;
+        Some(apps.swap_remove(index).1)
+    });
+    if let Some(app) = app {
+        app.destroy();
+    }
+}
@@ -0,0 +1,85 @@
+use std::num::NonZeroU64;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use web_sys::{Document, HtmlCanvasElement};
+use crate::app::{create_button, App, AppDispatcher, Message};
+use crate::benchmark;
+use crate::control_panel::Group;
+use crate::error::AppError;
+use crate::game_of_life::Field;
+use crate::listeners::Listeners;
+use crate::render::{context_2d, draw_cells, draw_initial_state};
+use crate::verify::{verify, NaiveLife};
+use crate::{console_error, console_log};
+
+/// Generations of the engine verification, and how often the cells are compared
+const VERIFY_GENERATIONS: u64 = 1000;
+const VERIFY_INTERVAL: NonZeroU64 = NonZeroU64::new(10).unwrap();
+
+/// Checks of the speed and the correctness of the engine, their reports are shown under the controls
+pub enum DiagnosticsCommand {
+    Benchmark,
+    /// Checks the engine of the Life field against the naive one, from the current cells
+    VerifyEngine,
+}
+
+impl App {
+    pub fn update_diagnostics(&mut self, command: DiagnosticsCommand) -> Result<(), AppError> {
+        match command {
+            DiagnosticsCommand::Benchmark => self.run_benchmark(),
+            DiagnosticsCommand::VerifyEngine => self.verify_engine(),
+        }
+    }
+
+    /// Runs the benchmark workload with the current rule and renderer, and shows the report.
+    /// The workload has its own field and a detached canvas, so the app is not affected
+    fn run_benchmark(&self) -> Result<(), AppError> {
+        let performance = self.window.performance().ok_or(AppError::Missing("performance"))?;
+        let canvas = self.document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+        let context = context_2d(&canvas)?;
+        let grid_canvas = self.document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+        let grid_context = context_2d(&grid_canvas)?;
+        // the smallest cells keep the canvas of the big field at a reasonable size
+        draw_initial_state(&benchmark::soup(self.state.life_rule), &canvas, &context, &grid_context, 1)?;
+        let result = benchmark::run(benchmark::GENERATIONS, self.state.life_rule, || performance.now(), |field| match &self.state.image_renderer {
+            Some(renderer) => renderer.draw(&context, field),
+            None => draw_cells(&context, field, false),
+        });
+        console_log!("benchmark:\n{result}");
+        self.benchmark_report.set_text_content(Some(&result.to_string()));
+        Ok(())
+    }
+
+    /// Logs the first cell where the engine differs from the naive one, the field itself doesn't change
+    fn verify_engine(&mut self) -> Result<(), AppError> {
+        let Some(field) = self.field.as_any_mut().downcast_mut::<Field>() else {
+            return Err(AppError::Unsupported("only the engine of Life fields can be verified"));
+        };
+        let mut verified = field.clone();
+        let mut reference = NaiveLife::from_field(field);
+        let report = match verify(&mut reference, &mut verified, VERIFY_GENERATIONS, VERIFY_INTERVAL) {
+            Ok(()) => {
+                let report = format!("the engine matches the naive engine for {VERIFY_GENERATIONS} generations");
+                console_log!("{report}");
+                report
+            }
+            Err(divergence) => {
+                console_error!("{divergence}");
+                divergence.to_string()
+            }
+        };
+        self.live_region.announce(&report);
+        self.benchmark_report.set_text_content(Some(&report));
+        Ok(())
+    }
+}
+
+pub fn add_diagnostics_controls(group: &Group, document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<(), AppError> {
+    let benchmark_button = create_button("Benchmark", || Message::Diagnostics(DiagnosticsCommand::Benchmark), document, listeners, dispatcher)?;
+    benchmark_button.set_attribute("title", &format!("Run {} generations of a {}x{} soup", benchmark::GENERATIONS, benchmark::FIELD_SIZE, benchmark::FIELD_SIZE))?;
+    group.add(&benchmark_button)?;
+
+    let verify_button = create_button("Verify Engine", || Message::Diagnostics(DiagnosticsCommand::VerifyEngine), document, listeners, dispatcher)?;
+    verify_button.set_attribute("title", &format!("Run {VERIFY_GENERATIONS} generations of the field with the naive engine and compare the cells every {VERIFY_INTERVAL} generations"))?;
+    group.add(&verify_button)
+}
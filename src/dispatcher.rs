@@ -0,0 +1,61 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+/// Single owner of the state of an app, that handles the messages of the event listeners one at a time.
+/// Messages sent while one is handled, e.g. by a JS callback that the handler has called, are queued and handled after it,
+/// so the state is never borrowed twice
+pub struct Dispatcher<S, M> {
+    state: RefCell<S>,
+    queue: RefCell<VecDeque<M>>,
+    handler: fn(&mut S, M),
+}
+impl<S, M> Dispatcher<S, M> {
+    pub fn new(state: S, handler: fn(&mut S, M)) -> Self {
+        Self { state: RefCell::new(state), queue: RefCell::new(VecDeque::new()), handler }
+    }
+    /// Handles the message, or queues it if another message is being handled
+    pub fn send(&self, message: M) {
+        self.queue.borrow_mut().push_back(message);
+        // the loop that is already running handles the message
+        let Ok(mut state) = self.state.try_borrow_mut() else {
+            return;
+        };
+        while let Some(message) = self.next_message() {
+            (self.handler)(&mut state, message);
+        }
+    }
+    /// The queue is not borrowed while the handler runs, so that it can send more messages
+    fn next_message(&self) -> Option<M> {
+        self.queue.borrow_mut().pop_front()
+    }
+    /// Reads the state, None while a message is being handled
+    pub fn try_with<R>(&self, read: impl FnOnce(&S) -> R) -> Option<R> {
+        let state = self.state.try_borrow().ok()?;
+        Some(read(&state))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::rc::{Rc, Weak};
+    struct State {
+        handled: Vec<u32>,
+        dispatcher: Weak<Dispatcher<State, u32>>,
+    }
+    fn handle(state: &mut State, message: u32) {
+        if message < 3 {
+            // handled after this message, not in the middle of it
+            state.dispatcher.upgrade().unwrap().send(message + 1);
+        }
+        state.handled.push(message);
+    }
+    #[test]
+    fn test_nested_send_is_queued() {
+        let dispatcher = Rc::new_cyclic(|dispatcher| Dispatcher::new(State { handled: Vec::new(), dispatcher: dispatcher.clone() }, handle));
+        dispatcher.send(0);
+        assert_eq!(Some(vec![0, 1, 2, 3]), dispatcher.try_with(|state| state.handled.clone()));
+        dispatcher.send(5);
+        assert_eq!(Some(5), dispatcher.try_with(|state| *state.handled.last().unwrap()));
+    }
+}
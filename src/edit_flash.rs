@@ -0,0 +1,70 @@
+use std::collections::VecDeque;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, Document, HtmlCanvasElement};
+use crate::automaton::DynAutomaton;
+use crate::error::AppError;
+use crate::render::{cell_bounds, context_2d, resize_canvas, CELL_SIZE_PX};
+
+/// How long the outline of an edited cell stays visible
+const FLASH_MS: f64 = 800.0;
+/// Speed of the dashes moving around the outlines, in pixels per second
+const ANTS_SPEED: f64 = 20.0;
+const FLASH_COLOR: &str = "#FF6F00";
+
+/// Transparent canvas over the field where the recently edited cells are outlined with moving dashes that fade out,
+/// so that it is clear which cells a click or a touch has hit
+#[derive(Debug)]
+pub struct EditFlash {
+    canvas: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+    /// Edited cells and the time of the edit, the oldest first
+    cells: VecDeque<(usize, usize, f64)>,
+}
+impl EditFlash {
+    pub fn new(document: &Document) -> Result<Self, AppError> {
+        let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+        canvas.set_attribute("style", "position: absolute; left: 0; top: 0; pointer-events: none;")?;
+        canvas.set_attribute("aria-hidden", "true")?;
+        let context = context_2d(&canvas)?;
+        Ok(Self { canvas, context, cells: VecDeque::new() })
+    }
+    pub fn canvas(&self) -> &HtmlCanvasElement {
+        &self.canvas
+    }
+    /// Outlines the cells from now on. Returns true if nothing was outlined before, then the animation has to be started
+    pub fn add(&mut self, cells: &[(usize, usize)], now_ms: f64, field: &dyn DynAutomaton, cell_size_px: usize) -> Result<bool, AppError> {
+        let is_idle = self.cells.is_empty();
+        if is_idle {
+            // the field could have been resized or zoomed since the last edits
+            resize_canvas(field, &self.canvas, &self.context, (cell_size_px + 1) as f64 / (CELL_SIZE_PX + 1) as f64)?;
+        }
+        self.cells.extend(cells.iter().map(|&(row, col)| (row, col, now_ms)));
+        Ok(is_idle && !self.cells.is_empty())
+    }
+    /// Draws the outlines for the time of the animation frame. Returns false when all of them have faded out
+    pub fn draw(&mut self, field: &dyn DynAutomaton, timestamp_ms: f64) -> bool {
+        while self.cells.front().is_some_and(|&(_, _, edited_ms)| timestamp_ms - edited_ms >= FLASH_MS) {
+            self.cells.pop_front();
+        }
+        let (width, height) = (self.canvas.width() as f64, self.canvas.height() as f64);
+        // the transform is scaled, the whole canvas is cleared in its own pixels
+        self.context.save();
+        let _ = self.context.set_transform(1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+        self.context.clear_rect(0.0, 0.0, width, height);
+        self.context.restore();
+        if self.cells.is_empty() {
+            return false;
+        }
+        self.context.set_stroke_style(&FLASH_COLOR.into());
+        self.context.set_line_width(2.0);
+        let _ = self.context.set_line_dash(&js_sys::Array::of2(&JsValue::from_f64(3.0), &JsValue::from_f64(3.0)));
+        self.context.set_line_dash_offset(-timestamp_ms * ANTS_SPEED / 1000.0);
+        for &(row, col, edited_ms) in &self.cells {
+            self.context.set_global_alpha(1.0 - ((timestamp_ms - edited_ms) / FLASH_MS).clamp(0.0, 1.0));
+            let (x, y, cell_width, cell_height) = cell_bounds(field, row, col);
+            self.context.stroke_rect(x, y, cell_width, cell_height);
+        }
+        self.context.set_global_alpha(1.0);
+        true
+    }
+}
@@ -0,0 +1,195 @@
+use std::fmt::{Display, Formatter, Write};
+use std::num::NonZeroUsize;
+use crate::automaton::{next_coord_wrapped, parse_cells, prev_coord_wrapped, Automaton, CellState};
+use crate::game_of_life::{CellValue, ParseError};
+
+/// Wolfram-style elementary cellular automaton.
+/// Every generation is a single row, the field shows the history of generations with the newest one at the bottom.
+/// Once the field is filled, older generations scroll out at the top.
+#[derive(Clone)]
+pub struct ElementaryCa {
+    width: NonZeroUsize,
+    height: NonZeroUsize,
+    rule: u8,
+    filled_rows: usize,
+    cells: Vec<CellValue>,
+    swap_cells: Vec<CellValue>,
+}
+
+impl ElementaryCa {
+    pub fn new(width: NonZeroUsize, height: NonZeroUsize, rule: u8, random_bool: impl Fn(usize) -> bool) -> Self {
+        let cell_count = width.get() * height.get();
+        let mut cells = vec![CellValue::Dead; cell_count];
+        for (i, cell) in cells.iter_mut().take(width.get()).enumerate() {
+            if random_bool(i) {
+                *cell = CellValue::Alive;
+            }
+        }
+        Self {
+            width,
+            height,
+            rule,
+            filled_rows: 1,
+            cells,
+            swap_cells: vec![CellValue::Dead; cell_count],
+        }
+    }
+    pub fn new_single_cell(width: NonZeroUsize, height: NonZeroUsize, rule: u8) -> Self {
+        let center = width.get() / 2;
+        Self::new(width, height, rule, |i| i == center)
+    }
+    pub fn get_rule(&self) -> u8 {
+        self.rule
+    }
+    pub fn last_row(&self) -> &[CellValue] {
+        let width = self.width.get();
+        let start = (self.filled_rows - 1) * width;
+        &self.cells[start..(start + width)]
+    }
+    pub fn update(&mut self) -> bool {
+        self.swap_cells.copy_from_slice(&self.cells);
+
+        let width = self.width.get();
+        let max_col = width - 1;
+        let last_row = self.last_row();
+        let new_row = (0..width)
+            .map(|col| {
+                let left = last_row[prev_coord_wrapped(col, max_col)];
+                let right = last_row[next_coord_wrapped(col, max_col)];
+                self.calc_new_value(left, last_row[col], right)
+            })
+            .collect::<Vec<_>>();
+
+        if self.filled_rows < self.height.get() {
+            self.filled_rows += 1;
+        } else {
+            self.cells.copy_within(width.., 0);
+        }
+        let start = (self.filled_rows - 1) * width;
+        self.cells[start..(start + width)].copy_from_slice(&new_row);
+
+        self.cells != self.swap_cells
+    }
+    fn calc_new_value(&self, left: CellValue, center: CellValue, right: CellValue) -> CellValue {
+        let pattern = ((left as u8) << 2) | ((center as u8) << 1) | (right as u8);
+        if (self.rule >> pattern) & 1 == 1 {
+            CellValue::Alive
+        } else {
+            CellValue::Dead
+        }
+    }
+}
+
+impl Automaton for ElementaryCa {
+    type State = CellValue;
+    fn get_width(&self) -> usize {
+        self.width.get()
+    }
+    fn get_height(&self) -> usize {
+        self.height.get()
+    }
+    fn view(&self) -> &[CellValue] {
+        &self.cells
+    }
+    fn view_old(&self) -> &[CellValue] {
+        &self.swap_cells
+    }
+    fn step(&mut self) -> bool {
+        self.update()
+    }
+    fn set(&mut self, row: usize, col: usize, value: CellValue) -> Option<()> {
+        // only the newest generation can be edited, since it is the one the next generation is calculated from
+        if (row + 1 != self.filled_rows) || (col >= self.width.get()) {
+            return None;
+        }
+        self.cells[(row * self.width.get()) + col] = value;
+        Some(())
+    }
+    /// Expects the rule on the first line, like `rule 30`, followed by the generations
+    fn parse(str: &str) -> Result<Self, ParseError> {
+        let (rule_line, cells_str) = str.trim().split_once('\n').ok_or(ParseError::EmptyString)?;
+        let rule = rule_line.trim()
+            .strip_prefix("rule ")
+            .and_then(|rule| rule.parse().ok())
+            .ok_or(ParseError::UnknownChar)?;
+        let (width, height, cells) = parse_cells(cells_str)?;
+        let cells_len = cells.len();
+        let res = Self {
+            width,
+            height,
+            rule,
+            filled_rows: height.get(),
+            cells,
+            swap_cells: vec![CellValue::Dead; cells_len],
+        };
+        Ok(res)
+    }
+    fn serialize(&self) -> String {
+        format!("rule {}\n{self}", self.rule)
+    }
+}
+
+impl Display for ElementaryCa {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self.width.get();
+        for row in self.cells.chunks(width).take(self.filled_rows) {
+            for &value in row.iter() {
+                f.write_char(value.to_char())?;
+            }
+            f.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_rule_90() {
+        let mut field = ElementaryCa::new_single_cell(7.try_into().unwrap(), 3.try_into().unwrap(), 90);
+        assert_eq!("___#___\n", field.to_string());
+        assert!(field.update());
+        assert!(field.update());
+        let expected = "
+___#___
+__#_#__
+_#___#_
+";
+        assert_eq!(expected.trim_start(), field.to_string());
+        // the field is full, so the oldest generation scrolls out
+        assert!(field.update());
+        let expected = "
+__#_#__
+_#___#_
+#_#_#_#
+";
+        assert_eq!(expected.trim_start(), field.to_string());
+    }
+    #[test]
+    fn test_rule_30_wraps() {
+        let mut field = ElementaryCa::new(5.try_into().unwrap(), 2.try_into().unwrap(), 30, |i| i == 0);
+        field.update();
+        assert_eq!("#____\n##__#\n", field.to_string());
+    }
+    #[test]
+    fn test_only_last_row_is_editable() {
+        let mut field = ElementaryCa::new_single_cell(5.try_into().unwrap(), 3.try_into().unwrap(), 30);
+        field.update();
+        assert_eq!(None, field.toggle(0, 0));
+        assert_eq!(Some(()), field.toggle(1, 0));
+        assert_eq!(CellValue::Alive, field.last_row()[0]);
+    }
+    #[test]
+    fn test_parse_serialize() {
+        let mut field = ElementaryCa::new_single_cell(5.try_into().unwrap(), 3.try_into().unwrap(), 90);
+        field.update();
+        let serialized = field.serialize();
+        assert_eq!("rule 90\n__#__\n_#_#_\n", serialized);
+        let mut parsed = ElementaryCa::parse(&serialized).unwrap();
+        assert_eq!(90, parsed.get_rule());
+        // only the generations that were present are restored, so the parsed field is already full
+        parsed.update();
+        assert_eq!("_#_#_\n#___#\n", parsed.to_string());
+    }
+}
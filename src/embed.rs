@@ -0,0 +1,138 @@
+use js_sys::{Object, Reflect};
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+use web_sys::{MessageEvent, Window};
+use crate::app::{App, Message};
+use crate::error::AppError;
+use crate::events;
+use crate::pattern_file::FileCommand;
+use crate::rle::to_rle;
+use crate::rule_editor::RuleCommand;
+
+/// Types of the commands and the replies start with this, other messages of the host page are ignored
+const PREFIX: &str = "gol:";
+/// Type of the replies to `gol:requestSnapshot`
+pub const SNAPSHOT: &str = "gol:snapshot";
+/// Type of the messages sent with every lifecycle event
+pub const STATE: &str = "gol:state";
+/// Type of the replies to commands that failed
+pub const ERROR: &str = "gol:error";
+/// Most generations of a `gol:step`, longer runs are played and paused by the host
+pub const MAX_STEP: u64 = 1_000_000;
+
+/// Command posted by the page that embeds the app in an iframe, e.g. `{"type": "gol:step", "generations": 10}`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type")]
+pub enum EmbedCommand {
+    /// Replaces the field, the format is taken from the name like for opened files, RLE without a name.
+    /// The user is asked to discard the edits first, like for opened files
+    #[serde(rename = "gol:loadPattern")]
+    LoadPattern { pattern: String, #[serde(default)] name: Option<String> },
+    #[serde(rename = "gol:play")]
+    Play,
+    #[serde(rename = "gol:pause")]
+    Pause,
+    /// Runs this number of generations and pauses, one by default and at most `MAX_STEP`
+    #[serde(rename = "gol:step")]
+    Step { #[serde(default = "one")] generations: u64 },
+    /// Rulestring in B/S notation
+    #[serde(rename = "gol:setRule")]
+    SetRule { rule: String },
+    /// The app replies with `gol:snapshot`
+    #[serde(rename = "gol:requestSnapshot")]
+    RequestSnapshot,
+}
+
+fn one() -> u64 {
+    1
+}
+
+/// The page that embeds the app, it gets the state messages after it has sent a command
+#[derive(Debug, Clone)]
+pub struct EmbedHost {
+    parent: Window,
+    /// Origin of the page that sent the last command, the messages are only delivered to it
+    origin: String,
+}
+impl EmbedHost {
+    /// Posts the message to the host, the properties are added to a plain object with the type
+    pub fn post(&self, message_type: &str, properties: &[(&str, JsValue)]) {
+        let message = Object::new();
+        // setting properties of a plain object can't fail
+        let _ = Reflect::set(&message, &"type".into(), &message_type.into());
+        for (name, value) in properties {
+            let _ = Reflect::set(&message, &(*name).into(), value);
+        }
+        if let Err(err) = self.parent.post_message(&message, &self.origin) {
+            crate::console_warn!("Failed to post {message_type} to the host page: {err:?}");
+        }
+    }
+}
+
+/// The parent window if the app runs in an iframe
+pub fn parent(window: &Window) -> Option<Window> {
+    let parent = window.parent().ok().flatten()?;
+    (!Object::is(&parent, window)).then_some(parent)
+}
+
+/// Reads the command from a message of the parent window, together with the host to reply to.
+/// None for messages of other windows and messages that are not meant for the app
+pub fn read_command(window: &Window, event: &MessageEvent) -> Option<(EmbedHost, Result<EmbedCommand, AppError>)> {
+    let parent = parent(window)?;
+    if !event.source().is_some_and(|source| Object::is(&source, &parent)) {
+        return None;
+    }
+    let data = event.data();
+    let message_type = Reflect::get(&data, &"type".into()).ok()?.as_string()?;
+    if !message_type.starts_with(PREFIX) {
+        return None;
+    }
+    let host = EmbedHost { parent, origin: event.origin() };
+    let command = serde_wasm_bindgen::from_value(data)
+        .map_err(|err| AppError::InvalidInput(format!("invalid command {message_type}: {err}")))
+        .and_then(|command| match command {
+            EmbedCommand::Step { generations } if generations > MAX_STEP => {
+                Err(AppError::InvalidInput(format!("invalid command {message_type}: more than {MAX_STEP} generations")))
+            }
+            command => Ok(command),
+        });
+    Some((host, command))
+}
+
+impl App {
+    /// Runs the command of the page that embeds the app, like the matching control would. Failed commands are answered with `gol:error`
+    pub fn handle_host_message(&mut self, event: &web_sys::MessageEvent) -> Result<(), AppError> {
+        let Some((host, command)) = read_command(&self.window, event) else {
+            return Ok(());
+        };
+        self.state.embed_host = Some(host.clone());
+        let result = command.and_then(|command| {
+            let message = match command {
+                // the pattern is parsed like an opened file, so the host gets the same checks of its size
+                EmbedCommand::LoadPattern { .. } if !self.confirm_discard() => return Ok(()),
+                EmbedCommand::LoadPattern { pattern, name } => Message::File(FileCommand::Opened(name.unwrap_or_default(), pattern)),
+                EmbedCommand::Play => Message::Play,
+                EmbedCommand::Pause => Message::Pause,
+                EmbedCommand::Step { generations } => Message::RunFor(generations),
+                EmbedCommand::SetRule { rule } => Message::Rule(RuleCommand::Set(rule.parse().map_err(|_| AppError::InvalidInput(format!("invalid rule {rule}")))?)),
+                EmbedCommand::RequestSnapshot => {
+                    host.post(SNAPSHOT, &[
+                        ("generation", JsValue::from_f64(self.state.generation.get() as f64)),
+                        ("population", JsValue::from_f64(events::population(self.field.as_ref()) as f64)),
+                        ("running", self.state.is_running().into()),
+                        ("rule", self.field.rule().into()),
+                        ("width", self.field.get_width().into()),
+                        ("height", self.field.get_height().into()),
+                        ("rle", to_rle(self.field.as_ref()).into()),
+                    ]);
+                    return Ok(());
+                }
+            };
+            self.update(message)
+        });
+        if let Err(err) = &result {
+            host.post(ERROR, &[("message", err.to_string().into())]);
+        }
+        result
+    }
+}
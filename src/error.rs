@@ -0,0 +1,102 @@
+use std::fmt;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Document, HtmlElement};
+use crate::console_error;
+
+/// Errors of the browser ui. Errors of event handlers are shown in the error banner of the app,
+/// errors of the exported functions are thrown to JS
+#[derive(Debug)]
+pub enum AppError {
+    /// A browser API has thrown an exception
+    Js(JsValue),
+    /// Something the app needs is not available, like the document or a 2d canvas context
+    Missing(&'static str),
+    /// The options of the app are invalid
+    InvalidOptions(String),
+    /// Text from outside of the app, like a rule entered by the user, is empty or invalid
+    InvalidInput(String),
+    /// The current field can't do this, like verifying the engine of an automaton that isn't Life
+    Unsupported(&'static str),
+    /// A loaded replay can't be parsed or played back
+    InvalidReplay(String),
+    /// A saved board can't be read
+    InvalidBoard(String),
+    /// A remote file can't be fetched
+    Network(String),
+}
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Js(value) => match value.dyn_ref::<js_sys::Error>() {
+                Some(error) => write!(f, "{}", String::from(error.message())),
+                None => match value.as_string() {
+                    Some(message) => write!(f, "{message}"),
+                    None => write!(f, "{value:?}"),
+                },
+            },
+            Self::Missing(what) => write!(f, "{what} is not available"),
+            Self::InvalidOptions(message) => write!(f, "invalid options: {message}"),
+            Self::InvalidInput(message) => write!(f, "invalid input: {message}"),
+            Self::Unsupported(message) => write!(f, "not supported: {message}"),
+            Self::InvalidReplay(message) => write!(f, "invalid replay: {message}"),
+            Self::InvalidBoard(message) => write!(f, "invalid board: {message}"),
+            Self::Network(message) => write!(f, "network error: {message}"),
+        }
+    }
+}
+impl From<JsValue> for AppError {
+    fn from(value: JsValue) -> Self {
+        Self::Js(value)
+    }
+}
+/// Failed `dyn_into` casts of created elements return the element
+impl From<web_sys::Element> for AppError {
+    fn from(element: web_sys::Element) -> Self {
+        Self::Js(element.into())
+    }
+}
+impl From<AppError> for JsValue {
+    fn from(error: AppError) -> Self {
+        match error {
+            // the original exception keeps its type and stack
+            AppError::Js(value) => value,
+            error => js_sys::Error::new(&error.to_string()).into(),
+        }
+    }
+}
+
+/// Bar at the top of the app that shows the last error, hidden until there is one. Clicking it hides it again
+#[derive(Debug, Clone)]
+pub struct ErrorBanner {
+    element: HtmlElement,
+}
+impl ErrorBanner {
+    pub fn new(document: &Document) -> Result<Self, AppError> {
+        let element = document.create_element("div")?.dyn_into::<HtmlElement>()?;
+        element.set_attribute(
+            "style",
+            "display: none; margin-bottom: 20px; padding: 8px; color: #FFFFFF; background-color: #C62828; cursor: pointer;",
+        )?;
+        element.set_title("Click to dismiss");
+        Ok(Self { element })
+    }
+    pub fn element(&self) -> &HtmlElement {
+        &self.element
+    }
+    /// Shows the error in the banner and logs it to the console
+    pub fn show(&self, error: &AppError) {
+        console_error!("{error}");
+        self.element.set_text_content(Some(&format!("Error: {error}")));
+        // setting styles can't fail for this property
+        let _ = self.element.style().set_property("display", "block");
+    }
+    pub fn hide(&self) {
+        let _ = self.element.style().set_property("display", "none");
+    }
+    /// Shows the error of the result, if there is one
+    pub fn report(&self, result: Result<(), AppError>) {
+        if let Err(error) = result {
+            self.show(&error);
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use js_sys::{Object, Reflect};
+use wasm_bindgen::JsValue;
+use web_sys::{CustomEvent, CustomEventInit, EventTarget};
+use crate::automaton::DynAutomaton;
+
+/// A generation has been calculated and drawn
+pub const GENERATION: &str = "gol:generation";
+pub const PAUSED: &str = "gol:paused";
+pub const RESUMED: &str = "gol:resumed";
+/// The field has died out or stopped changing
+pub const STABILIZED: &str = "gol:stabilized";
+/// The field has been replaced or edited by the user
+pub const FIELD_CHANGED: &str = "gol:fieldchanged";
+
+/// Dispatches a bubbling `CustomEvent` with the generation number and the population in `detail`
+pub fn dispatch(target: &EventTarget, name: &str, generation: u64, field: &dyn DynAutomaton) {
+    let detail = Object::new();
+    // setting properties of a plain object can't fail
+    let _ = Reflect::set(&detail, &"generation".into(), &JsValue::from_f64(generation as f64));
+    let _ = Reflect::set(&detail, &"population".into(), &JsValue::from_f64(population(field) as f64));
+    let mut init = CustomEventInit::new();
+    init.bubbles(true).detail(&detail);
+    match CustomEvent::new_with_event_init_dict(name, &init) {
+        Ok(event) => {
+            let _ = target.dispatch_event(&event);
+        }
+        Err(err) => crate::console_error!("Failed to create event {name}: {err:?}"),
+    }
+}
+
+/// Number of cells that are not in the first state of the palette, which is the dead or empty one
+pub fn population(field: &dyn DynAutomaton) -> usize {
+    field.count_by_state().iter().skip(1).sum()
+}
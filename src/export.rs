@@ -0,0 +1,141 @@
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use web_sys::{Document, HtmlInputElement};
+use crate::app::{create_button, App, AppDispatcher, Message};
+use crate::census;
+use crate::control_panel::Group;
+use crate::error::AppError;
+use crate::game_of_life::Field;
+use crate::listeners::Listeners;
+use crate::metrics::MetricsFormat;
+use crate::render::render_png_data_url;
+use crate::{console_log, console_warn};
+
+/// Downloads of the field and of the statistics of its generations
+pub enum ExportCommand {
+    /// Downloads the field as a PNG with this number of pixels per cell
+    Image(u32),
+    Svg,
+    /// Downloads the analysis of the generations as CSV
+    Analysis,
+    /// Shows the apgcodes of the objects in the Life field with their counts
+    Census,
+    /// Downloads the metrics of the generations
+    Metrics(MetricsFormat),
+}
+
+impl App {
+    pub fn update_export(&mut self, command: ExportCommand) -> Result<(), AppError> {
+        match command {
+            ExportCommand::Image(scale) => self.save_image(scale),
+            ExportCommand::Svg => self.export_svg(),
+            ExportCommand::Analysis => self.export_analysis(),
+            ExportCommand::Census => self.census(),
+            ExportCommand::Metrics(format) => self.export_metrics(format),
+        }
+    }
+
+    fn save_image(&self, scale: u32) -> Result<(), AppError> {
+        let data_url = render_png_data_url(&self.document, self.field.as_ref(), scale)?;
+        download(&self.document, &data_url, "field.png")
+    }
+
+    fn export_svg(&self) -> Result<(), AppError> {
+        let Some(svg) = self.field.to_svg(10, &self.state.theme) else {
+            console_warn!("SVG export is not supported for this automaton");
+            return Ok(());
+        };
+        let data_url = format!("data:image/svg+xml;charset=utf-8,{}", js_sys::encode_uri_component(&svg));
+        download(&self.document, &data_url, "field.svg")
+    }
+
+    fn export_analysis(&self) -> Result<(), AppError> {
+        let csv = JsValue::from_str(&self.state.analysis.to_csv());
+        let blob = web_sys::Blob::new_with_str_sequence(&js_sys::Array::of1(&csv))?;
+        download_blob(&self.document, &blob, "analysis.csv")
+    }
+
+    /// Shows and logs the objects of the field by apgcode, the most common first
+    fn census(&mut self) -> Result<(), AppError> {
+        let Some(field) = self.field.as_any_mut().downcast_mut::<Field>() else {
+            return Err(AppError::Unsupported("the census only works with Life fields"));
+        };
+        let mut counts: Vec<_> = census::census(field).into_iter().collect();
+        counts.sort_by(|(left_code, left), (right_code, right)| right.cmp(left).then_with(|| left_code.cmp(right_code)));
+        let report = counts.iter()
+            .map(|(code, count)| format!("{count} {}", code.as_deref().unwrap_or("unclassified")))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let report = if report.is_empty() { "the field is empty".to_string() } else { report };
+        console_log!("census:\n{report}");
+        self.benchmark_report.set_text_content(Some(&report));
+        Ok(())
+    }
+
+    fn export_metrics(&self, format: MetricsFormat) -> Result<(), AppError> {
+        let content = JsValue::from_str(&self.state.metrics.export(format));
+        let mut properties = web_sys::BlobPropertyBag::new();
+        properties.type_(format.mime_type());
+        let blob = web_sys::Blob::new_with_str_sequence_and_options(&js_sys::Array::of1(&content), &properties)?;
+        download_blob(&self.document, &blob, &format!("stats.{}", format.extension()))
+    }
+}
+
+/// Adds the image, SVG and statistics downloads and the census to the group
+pub fn add_export_controls(group: &Group, document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<(), AppError> {
+    let save_button = create_save_button(document, listeners, dispatcher)?;
+    group.add(&save_button)?;
+
+    let svg_button = create_button("Export SVG", || Message::Export(ExportCommand::Svg), document, listeners, dispatcher)?;
+    group.add(&svg_button)?;
+
+    let analysis_button = create_button("Analysis CSV", || Message::Export(ExportCommand::Analysis), document, listeners, dispatcher)?;
+    analysis_button.set_attribute("title", "Density, entropy and 2x2 block patterns of every generation")?;
+    group.add(&analysis_button)?;
+
+    let census_button = create_button("Census", || Message::Export(ExportCommand::Census), document, listeners, dispatcher)?;
+    census_button.set_attribute("title", "Count the still lifes, oscillators and spaceships by their apgcode, as used by Catagolue")?;
+    group.add(&census_button)?;
+
+    for (name, format) in [("Stats CSV", MetricsFormat::Csv), ("Stats JSON", MetricsFormat::Json)] {
+        let metrics_button = create_button(name, move || Message::Export(ExportCommand::Metrics(format)), document, listeners, dispatcher)?;
+        metrics_button.set_attribute("title", "Population, births, deaths and frame time of every generation")?;
+        group.add(&metrics_button)?;
+    }
+    Ok(())
+}
+
+fn create_save_button(document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<web_sys::Element, AppError> {
+    let container = document.create_element("span")?;
+    let scale_input = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    scale_input.set_type("number");
+    scale_input.set_min("1");
+    scale_input.set_max("32");
+    scale_input.set_value("4");
+    scale_input.set_title("Pixels per cell in the saved image");
+    container.append_child(&scale_input)?;
+
+    let message = move || {
+        let scale = scale_input.value_as_number();
+        Message::Export(ExportCommand::Image(if scale.is_nan() { 4 } else { scale.clamp(1.0, 32.0) as u32 }))
+    };
+    let button = create_button("Save Image", message, document, listeners, dispatcher)?;
+    container.append_child(&button)?;
+    Ok(container)
+}
+
+pub fn download_blob(document: &Document, blob: &web_sys::Blob, file_name: &str) -> Result<(), AppError> {
+    let url = web_sys::Url::create_object_url_with_blob(blob)?;
+    download(document, &url, file_name)?;
+    web_sys::Url::revoke_object_url(&url)?;
+    Ok(())
+}
+
+/// Makes the browser download the url as a file with the given name
+pub fn download(document: &Document, url: &str, file_name: &str) -> Result<(), AppError> {
+    let link = document.create_element("a")?.dyn_into::<web_sys::HtmlElement>()?;
+    link.set_attribute("href", url)?;
+    link.set_attribute("download", file_name)?;
+    link.click();
+    Ok(())
+}
@@ -0,0 +1,243 @@
+use std::num::NonZeroUsize;
+use crate::automaton::{parse_cells, Automaton, CellState};
+use crate::game_of_life::ParseError;
+use crate::random::XorShift;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Particle {
+    Empty = 0,
+    Sand = 1,
+    Water = 2,
+    Wall = 3,
+    Fire = 4,
+}
+impl CellState for Particle {
+    const PALETTE: &'static [&'static str] = &["#000000", "#E2C275", "#3A7BD5", "#888888", "#FF5A1F"];
+    const NAMES: &'static [&'static str] = &["Empty", "Sand", "Water", "Wall", "Fire"];
+    fn index(self) -> u8 {
+        self as u8
+    }
+    fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(Particle::Empty),
+            1 => Some(Particle::Sand),
+            2 => Some(Particle::Water),
+            3 => Some(Particle::Wall),
+            4 => Some(Particle::Fire),
+            _ => None,
+        }
+    }
+    fn next_in_edit_cycle(self) -> Self {
+        match self {
+            Particle::Empty => Particle::Sand,
+            Particle::Sand => Particle::Water,
+            Particle::Water => Particle::Wall,
+            Particle::Wall => Particle::Fire,
+            Particle::Fire => Particle::Empty,
+        }
+    }
+    fn to_char(self) -> char {
+        match self {
+            Particle::Empty => '_',
+            Particle::Sand => '.',
+            Particle::Water => '~',
+            Particle::Wall => '#',
+            Particle::Fire => '*',
+        }
+    }
+    fn from_char(char: char) -> Option<Self> {
+        match char {
+            '_' => Some(Particle::Empty),
+            '.' => Some(Particle::Sand),
+            '~' => Some(Particle::Water),
+            '#' => Some(Particle::Wall),
+            '*' => Some(Particle::Fire),
+            _ => None,
+        }
+    }
+}
+
+/// Chance for a fire particle to burn out on each generation
+const FIRE_BURN_OUT_CHANCE: f64 = 0.15;
+
+/// Falling sand simulation. The edges of the field don't wrap, they behave like walls.
+/// Sand falls and piles up, sinking through water. Water falls and spreads sideways.
+/// Fire rises and burns out, water puts it out. Walls never move.
+#[derive(Clone)]
+pub struct FallingSand {
+    width: NonZeroUsize,
+    height: NonZeroUsize,
+    random: XorShift,
+    cells: Vec<Particle>,
+    old_cells: Vec<Particle>,
+    moved: Vec<bool>,
+}
+
+impl FallingSand {
+    pub fn new(width: NonZeroUsize, height: NonZeroUsize, seed: u64) -> Self {
+        let cell_count = width.get() * height.get();
+        Self {
+            width,
+            height,
+            random: XorShift::new(seed),
+            cells: vec![Particle::Empty; cell_count],
+            old_cells: vec![Particle::Empty; cell_count],
+            moved: vec![false; cell_count],
+        }
+    }
+    pub fn update(&mut self) -> bool {
+        self.old_cells.copy_from_slice(&self.cells);
+        self.moved.fill(false);
+        let width = self.width.get();
+        // bottom to top, so that a falling column moves as a whole
+        for row in (0..self.height.get()).rev() {
+            // alternate the direction, otherwise everything drifts to one side
+            let left_to_right = self.random.next_below(2) == 0;
+            for i in 0..width {
+                let col = if left_to_right { i } else { width - 1 - i };
+                let index = (row * width) + col;
+                if self.moved[index] {
+                    continue;
+                }
+                match self.cells[index] {
+                    Particle::Empty | Particle::Wall => {}
+                    Particle::Sand => self.update_sand(row, col),
+                    Particle::Water => self.update_water(row, col),
+                    Particle::Fire => self.update_fire(row, col),
+                }
+            }
+        }
+        self.cells != self.old_cells
+    }
+    fn update_sand(&mut self, row: usize, col: usize) {
+        let can_enter = |particle: Particle| matches!(particle, Particle::Empty | Particle::Water);
+        let first_side = self.random_side();
+        for (delta_row, delta_col) in [(1, 0), (1, first_side), (1, -first_side)] {
+            if self.try_move(row, col, delta_row, delta_col, can_enter) {
+                return;
+            }
+        }
+    }
+    fn update_water(&mut self, row: usize, col: usize) {
+        let can_enter = |particle: Particle| particle == Particle::Empty;
+        let first_side = self.random_side();
+        for (delta_row, delta_col) in [(1, 0), (1, first_side), (1, -first_side), (0, first_side), (0, -first_side)] {
+            if self.try_move(row, col, delta_row, delta_col, can_enter) {
+                return;
+            }
+        }
+    }
+    fn update_fire(&mut self, row: usize, col: usize) {
+        let index = (row * self.width.get()) + col;
+        let touches_water = [(-1, 0), (1, 0), (0, -1), (0, 1)].into_iter()
+            .filter_map(|(delta_row, delta_col)| self.offset_index(row, col, delta_row, delta_col))
+            .any(|neighbour| self.cells[neighbour] == Particle::Water);
+        if touches_water || (self.random.next_f64() < FIRE_BURN_OUT_CHANCE) {
+            self.cells[index] = Particle::Empty;
+            return;
+        }
+        let first_side = self.random_side();
+        for (delta_row, delta_col) in [(-1, 0), (-1, first_side), (-1, -first_side)] {
+            if self.try_move(row, col, delta_row, delta_col, |particle| particle == Particle::Empty) {
+                return;
+            }
+        }
+    }
+    fn random_side(&mut self) -> isize {
+        if self.random.next_below(2) == 0 { -1 } else { 1 }
+    }
+    fn offset_index(&self, row: usize, col: usize, delta_row: isize, delta_col: isize) -> Option<usize> {
+        let target_row = row.checked_add_signed(delta_row)?;
+        let target_col = col.checked_add_signed(delta_col)?;
+        if (target_row >= self.height.get()) || (target_col >= self.width.get()) {
+            return None;
+        }
+        Some((target_row * self.width.get()) + target_col)
+    }
+    /// Swaps the particle with the target cell if it is allowed to enter it
+    fn try_move(&mut self, row: usize, col: usize, delta_row: isize, delta_col: isize, can_enter: impl Fn(Particle) -> bool) -> bool {
+        let Some(target) = self.offset_index(row, col, delta_row, delta_col) else {
+            return false;
+        };
+        if !can_enter(self.cells[target]) {
+            return false;
+        }
+        let index = (row * self.width.get()) + col;
+        self.cells.swap(index, target);
+        self.moved[target] = true;
+        self.moved[index] = true;
+        true
+    }
+}
+
+impl Automaton for FallingSand {
+    type State = Particle;
+    fn get_width(&self) -> usize {
+        self.width.get()
+    }
+    fn get_height(&self) -> usize {
+        self.height.get()
+    }
+    fn view(&self) -> &[Particle] {
+        &self.cells
+    }
+    fn view_old(&self) -> &[Particle] {
+        &self.old_cells
+    }
+    fn step(&mut self) -> bool {
+        self.update()
+    }
+    fn set(&mut self, row: usize, col: usize, value: Particle) -> Option<()> {
+        let index = self.offset_index(row, col, 0, 0)?;
+        self.cells[index] = value;
+        Some(())
+    }
+    /// Parses the cells with a fixed seed
+    fn parse(str: &str) -> Result<Self, ParseError> {
+        let (width, height, cells) = parse_cells(str)?;
+        let mut res = Self::new(width, height, 1);
+        res.cells = cells;
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_sand_falls() {
+        let mut field = FallingSand::parse("_._\n___\n___").unwrap();
+        assert!(field.update());
+        assert_eq!("___\n_._\n___\n", field.serialize());
+        assert!(field.update());
+        assert_eq!("___\n___\n_._\n", field.serialize());
+        assert!(!field.update());
+    }
+    #[test]
+    fn test_sand_slides_off() {
+        let mut field = FallingSand::parse("_._\n_#_\n___").unwrap();
+        field.update();
+        field.update();
+        let result = field.serialize();
+        assert!((result == "___\n_#_\n.__\n") || (result == "___\n_#_\n__.\n"), "{result}");
+    }
+    #[test]
+    fn test_sand_sinks_in_water() {
+        let mut field = FallingSand::parse("#.#\n#~#\n###").unwrap();
+        field.update();
+        assert_eq!("#~#\n#.#\n###\n", field.serialize());
+    }
+    #[test]
+    fn test_water_spreads() {
+        let mut field = FallingSand::parse("__~__\n#####").unwrap();
+        field.update();
+        let result = field.serialize();
+        assert!((result == "_~___\n#####\n") || (result == "___~_\n#####\n"), "{result}");
+    }
+    #[test]
+    fn test_water_puts_out_fire() {
+        let mut field = FallingSand::parse("#*#\n#~#\n###").unwrap();
+        field.update();
+        assert_eq!("#_#\n#~#\n###\n", field.serialize());
+    }
+}
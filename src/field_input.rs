@@ -0,0 +1,397 @@
+use std::rc::Rc;
+use wasm_bindgen::JsCast;
+use web_sys::{Document, HtmlCanvasElement, HtmlSelectElement, Window};
+use crate::app::{add_event_listener, add_message_listener, update_magnifier, App, AppDispatcher, Message};
+use crate::automaton::DynAutomaton;
+use crate::brush::{self, Brush, BrushTool};
+use crate::console_warn;
+use crate::error::AppError;
+use crate::listeners::Listeners;
+use crate::pinch::Pinch;
+use crate::render::{canvas_coords_to_cell, canvas_size};
+use crate::replay::Change;
+use crate::stamp_preview::{centered_corner, stamp_cells};
+use crate::wheel::{speed_step, zoom_step};
+
+/// Mouse, touch and keyboard input on the field, and the brush that it paints with
+pub enum FieldInput {
+    /// The left button draws, the right one or Shift with the left one erases. With Ctrl the buttons open the context menu instead.
+    /// The new state of the clicked cell is painted on the cells the mouse is dragged over
+    StartPaint(web_sys::MouseEvent),
+    /// Paints while dragging, and moves the preview of the stamp brush
+    Paint(web_sys::MouseEvent),
+    StopPaint,
+    /// Stops painting and hides the preview of the stamp
+    LeaveField,
+    /// R rotates the stamp clockwise and F flips it, while its preview is shown
+    TurnStamp(web_sys::KeyboardEvent),
+    /// Tapping toggles the cell and dragging paints its new state. Only touches with a single finger edit the field,
+    /// two fingers zoom and pan
+    StartTouch(web_sys::TouchEvent),
+    MoveTouch(web_sys::TouchEvent),
+    /// The wheel changes the speed, with Ctrl it zooms around the cursor
+    Wheel(web_sys::WheelEvent),
+    /// Arrow keys move the cursor over the cells, Enter and Space toggle the cell under it
+    KeyDown(web_sys::KeyboardEvent),
+    /// The field has gained or lost the keyboard focus, the cursor is only shown while it has it
+    FocusField(bool),
+    SetBrush(BrushTool),
+    JumpMinimap(web_sys::MouseEvent),
+}
+
+impl App {
+    pub fn update_input(&mut self, input: FieldInput) -> Result<(), AppError> {
+        match input {
+            FieldInput::StartPaint(event) if matches!(event.button(), 0 | 2) && !event.ctrl_key() => {
+                let is_erase = (event.button() == 2) || event.shift_key();
+                let new_state = if is_erase { Some(0) } else { self.draw_state() };
+                self.start_paint(event.client_x() as f64, event.client_y() as f64, new_state);
+                Ok(())
+            }
+            // other buttons, like the middle one, are left to the browser
+            FieldInput::StartPaint(_) => Ok(()),
+            FieldInput::Paint(event) => {
+                let (client_x, client_y) = (event.client_x() as f64, event.client_y() as f64);
+                self.paint(client_x, client_y);
+                self.state.magnifier.move_to(Some((client_x, client_y)));
+                update_magnifier(&self.context, self.field.as_ref(), &self.state);
+                self.move_stamp_preview(client_x, client_y)
+            }
+            FieldInput::StartTouch(event) => {
+                if let Some((first, second)) = two_finger_touch(&event) {
+                    // the first finger doesn't paint anymore
+                    self.paint_state = None;
+                    self.line_draft = None;
+                    self.pinch = Pinch::new(first, second, self.state.cell_size_px);
+                } else if let Some((client_x, client_y)) = self.editing_touch(&event) {
+                    self.start_paint(client_x, client_y, None);
+                }
+                Ok(())
+            }
+            FieldInput::MoveTouch(event) if self.pinch.is_some() => match two_finger_touch(&event) {
+                Some((first, second)) => self.pinch(first, second),
+                None => Ok(()),
+            },
+            FieldInput::MoveTouch(event) => {
+                if self.paint_state.is_some() {
+                    if let Some((client_x, client_y)) = self.editing_touch(&event) {
+                        self.paint(client_x, client_y);
+                    }
+                }
+                Ok(())
+            }
+            FieldInput::Wheel(event) => self.wheel(&event),
+            FieldInput::KeyDown(event) => {
+                self.key_down(&event);
+                Ok(())
+            }
+            FieldInput::FocusField(is_focused) => {
+                self.cursor.update(self.field.as_ref(), self.state.cell_size_px);
+                self.cursor.set_visible(is_focused);
+                Ok(())
+            }
+            FieldInput::StopPaint => {
+                self.paint_state = None;
+                self.line_draft = None;
+                self.pinch = None;
+                Ok(())
+            }
+            FieldInput::LeaveField => {
+                self.paint_state = None;
+                self.line_draft = None;
+                self.stamp_preview.hide();
+                self.state.magnifier.move_to(None);
+                update_magnifier(&self.context, self.field.as_ref(), &self.state);
+                Ok(())
+            }
+            FieldInput::TurnStamp(event) => self.turn_stamp(&event),
+            FieldInput::SetBrush(brush) => {
+                self.state.brush = brush;
+                self.stamp_preview.hide();
+                Ok(())
+            }
+            FieldInput::JumpMinimap(event) => {
+                self.state.minimap.jump(&self.window, &self.canvas, &event);
+                Ok(())
+            }
+        }
+    }
+
+    /// Moves the cursor with the arrow keys, or toggles the cell under it. Other keys keep their default actions
+    fn key_down(&mut self, event: &web_sys::KeyboardEvent) {
+        let (rows, cols) = match event.key().as_str() {
+            "ArrowUp" => (-1, 0),
+            "ArrowDown" => (1, 0),
+            "ArrowLeft" => (0, -1),
+            "ArrowRight" => (0, 1),
+            "Enter" | " " => {
+                event.prevent_default();
+                self.toggle_cursor_cell();
+                return;
+            }
+            _ => return,
+        };
+        // the page doesn't scroll
+        event.prevent_default();
+        self.cursor.move_by(self.field.as_ref(), rows, cols, self.state.cell_size_px);
+        self.cursor.set_visible(true);
+        self.announce_cursor_cell();
+    }
+
+    fn toggle_cursor_cell(&mut self) {
+        let (row, col) = self.cursor.cell(self.field.as_ref());
+        let Some(state) = self.field.toggled_state(row, col) else {
+            return;
+        };
+        self.edit(&[(row, col)], state);
+        self.announce_cursor_cell();
+    }
+
+    fn announce_cursor_cell(&self) {
+        let (row, col) = self.cursor.cell(self.field.as_ref());
+        let state = self.field.state_by_coords(row, col).and_then(|state| self.field.state_names().get(state as usize));
+        self.live_region.announce(&format!("Row {}, column {}: {}", row + 1, col + 1, state.unwrap_or(&"")));
+    }
+
+    /// State drawn with the left button: the live state of two-state automata.
+    /// None for automata with more states, the clicks cycle through them
+    pub fn draw_state(&self) -> Option<u8> {
+        (self.field.palette().len() == 2).then_some(1)
+    }
+
+    /// Sets the cell to the new state and starts painting it, or toggles it if there is no new state
+    fn start_paint(&mut self, client_x: f64, client_y: f64, new_state: Option<u8>) {
+        let (row, col) = client_to_cell(&self.canvas, self.field.as_ref(), client_x, client_y);
+        let Some(new_state) = new_state.or_else(|| self.field.toggled_state(row, col)) else {
+            console_warn!("Failed to update, calced coords: row {row}, col {col}");
+            return;
+        };
+        self.paint_state = Some(new_state);
+        match self.brush() {
+            BrushTool::Shape(brush) => self.edit(&brush.cells(row, col, self.field.get_width(), self.field.get_height()), new_state),
+            BrushTool::Line => {
+                self.line_draft = Some(LineDraft { from: (row, col), to: (row, col), covered: Vec::new() });
+                self.edit(&[(row, col)], new_state);
+            }
+            BrushTool::Stamp => {
+                let (top, left) = centered_corner(&self.stamp, row, col);
+                self.edit(&stamp_cells(&self.stamp, top, left, self.field.get_width(), self.field.get_height()), new_state);
+            }
+        }
+    }
+
+    fn paint(&mut self, client_x: f64, client_y: f64) {
+        let Some(new_state) = self.paint_state else {
+            return;
+        };
+        let (row, col) = client_to_cell(&self.canvas, self.field.as_ref(), client_x, client_y);
+        match self.brush() {
+            BrushTool::Shape(brush) => {
+                // the field doesn't have the queued edits yet
+                if (self.field.state_by_coords(row, col) == Some(new_state)) || self.state.pending_edits.contains(&(row, col, new_state)) {
+                    return;
+                }
+                self.edit(&brush.cells(row, col, self.field.get_width(), self.field.get_height()), new_state);
+            }
+            BrushTool::Line => {
+                if self.move_line_end(row, col, new_state) {
+                    self.show_edit();
+                }
+            }
+            // a single paste per click, dragging doesn't paint
+            BrushTool::Stamp => {}
+        }
+    }
+
+    /// Moves the preview of the stamp to the cell under the cursor
+    fn move_stamp_preview(&mut self, client_x: f64, client_y: f64) -> Result<(), AppError> {
+        if self.state.brush != BrushTool::Stamp {
+            return Ok(());
+        }
+        let (row, col) = client_to_cell(&self.canvas, self.field.as_ref(), client_x, client_y);
+        if self.stamp_preview.cell() == Some((row, col)) {
+            return Ok(());
+        }
+        self.stamp_preview.show(&self.stamp, row, col, self.field.as_ref(), self.state.cell_size_px)
+    }
+
+    /// Rotates or flips the stamp with the keys, while its preview is over the field. Typing in the inputs and shortcuts with modifiers are left alone
+    fn turn_stamp(&mut self, event: &web_sys::KeyboardEvent) -> Result<(), AppError> {
+        let Some((row, col)) = self.stamp_preview.cell() else {
+            return Ok(());
+        };
+        if event.ctrl_key() || event.meta_key() || event.alt_key() {
+            return Ok(());
+        }
+        let is_typing = event.target()
+            .and_then(|target| target.dyn_into::<web_sys::Element>().ok())
+            .is_some_and(|element| matches!(element.tag_name().as_str(), "INPUT" | "TEXTAREA" | "SELECT"));
+        if is_typing {
+            return Ok(());
+        }
+        let (stamp, announcement) = match event.key().as_str() {
+            "r" | "R" => (self.stamp.rotated_clockwise(), "Stamp rotated"),
+            "f" | "F" => (self.stamp.flipped_horizontally(), "Stamp flipped"),
+            _ => return Ok(()),
+        };
+        event.prevent_default();
+        self.stamp = stamp;
+        self.live_region.announce(announcement);
+        self.stamp_preview.show(&self.stamp, row, col, self.field.as_ref(), self.state.cell_size_px)
+    }
+
+    /// The brush chosen by the user. The line follows the cursor over the field, so while running it is a single cell
+    fn brush(&self) -> BrushTool {
+        match self.state.brush {
+            BrushTool::Line if self.state.is_running() => BrushTool::Shape(Brush::Square(1)),
+            brush => brush,
+        }
+    }
+
+    /// Moves the end of the line that is being drawn to the cell, the cells under the previous line are restored.
+    /// Returns false if the end is already there
+    fn move_line_end(&mut self, row: usize, col: usize, new_state: u8) -> bool {
+        let Some(mut draft) = self.line_draft.take_if(|draft| draft.to != (row, col)) else {
+            return false;
+        };
+        for (row, col, state) in draft.covered.drain(..) {
+            let _ = self.field.set_state(row, col, state);
+            self.record_change(Change::SetCell { row, col, state });
+        }
+        let (from_row, from_col) = draft.from;
+        for (row, col) in Brush::Line(from_row, from_col).cells(row, col, self.field.get_width(), self.field.get_height()) {
+            if let Some(state) = self.field.state_by_coords(row, col).filter(|&state| state != new_state) {
+                draft.covered.push((row, col, state));
+                let _ = self.field.set_state(row, col, new_state);
+                self.record_change(Change::SetCell { row, col, state: new_state });
+            }
+        }
+        draft.to = (row, col);
+        self.line_draft = Some(draft);
+        true
+    }
+
+    /// Position of a touch that edits the field, None for touches with more fingers, like pinch zoom.
+    /// The page doesn't scroll under the touches that edit, and no mouse events are emulated for them
+    fn editing_touch(&self, event: &web_sys::TouchEvent) -> Option<(f64, f64)> {
+        let touches = event.touches();
+        if touches.length() != 1 {
+            return None;
+        }
+        let touch = touches.get(0)?;
+        event.prevent_default();
+        Some((touch.client_x() as f64, touch.client_y() as f64))
+    }
+
+    /// Zooms to the distance between the fingers and pans by the movement of the center between them.
+    /// The point of the field that was under the center stays under it
+    fn pinch(&mut self, first: (f64, f64), second: (f64, f64)) -> Result<(), AppError> {
+        let Some(pinch) = &mut self.pinch else {
+            return Ok(());
+        };
+        let cell_size_px = pinch.cell_size_px(first, second);
+        let (last_center, center) = pinch.move_center(first, second);
+        self.zoom(cell_size_px, last_center, center)
+    }
+
+    /// Scrolling up makes the animation faster, or zooms in with Ctrl. Horizontal scrolling is left to the browser
+    fn wheel(&mut self, event: &web_sys::WheelEvent) -> Result<(), AppError> {
+        if event.delta_y() == 0.0 {
+            return Ok(());
+        }
+        // the page doesn't scroll or zoom
+        event.prevent_default();
+        let is_up = event.delta_y() < 0.0;
+        if event.ctrl_key() {
+            let cursor = (event.client_x() as f64, event.client_y() as f64);
+            return self.zoom(zoom_step(self.state.cell_size_px, is_up), cursor, cursor);
+        }
+        self.state.target_fps = speed_step(self.state.target_fps, is_up);
+        self.state.adaptive_fps = false;
+        self.state.time_history_ms.truncate();
+        self.live_region.announce(&match self.state.target_fps {
+            Some(fps) => format!("Speed {fps} generations per second"),
+            None => "Full speed".to_string(),
+        });
+        Ok(())
+    }
+}
+
+/// Lets the mouse, the touches and the keyboard edit the field, and the clicks on the minimap move the view
+pub fn add_input_listeners(
+    canvas: &HtmlCanvasElement,
+    minimap_canvas: &HtmlCanvasElement,
+    window: &Window,
+    listeners: &mut Listeners,
+    dispatcher: &Rc<AppDispatcher>,
+) -> Result<(), AppError> {
+    add_event_listener(listeners, canvas, &["mousedown"], dispatcher, |event| Message::Input(FieldInput::StartPaint(event)))?;
+    add_event_listener(listeners, canvas, &["mousemove"], dispatcher, |event| Message::Input(FieldInput::Paint(event)))?;
+    add_message_listener(listeners, canvas, &["mouseup"], dispatcher, || Message::Input(FieldInput::StopPaint))?;
+    add_message_listener(listeners, canvas, &["mouseleave"], dispatcher, || Message::Input(FieldInput::LeaveField))?;
+    add_event_listener(listeners, canvas, &["touchstart"], dispatcher, |event| Message::Input(FieldInput::StartTouch(event)))?;
+    add_event_listener(listeners, canvas, &["touchmove"], dispatcher, |event| Message::Input(FieldInput::MoveTouch(event)))?;
+    add_event_listener(listeners, canvas, &["keydown"], dispatcher, |event| Message::Input(FieldInput::KeyDown(event)))?;
+    // the stamp is turned while the mouse is over the field, which doesn't have to be focused
+    add_event_listener(listeners, window, &["keydown"], dispatcher, |event| Message::Input(FieldInput::TurnStamp(event)))?;
+    add_event_listener(listeners, canvas, &["wheel"], dispatcher, |event| Message::Input(FieldInput::Wheel(event)))?;
+    add_message_listener(listeners, canvas, &["focus"], dispatcher, || Message::Input(FieldInput::FocusField(true)))?;
+    add_message_listener(listeners, canvas, &["blur"], dispatcher, || Message::Input(FieldInput::FocusField(false)))?;
+    add_message_listener(listeners, canvas, &["touchend", "touchcancel"], dispatcher, || Message::Input(FieldInput::StopPaint))?;
+    add_event_listener(listeners, minimap_canvas, &["click"], dispatcher, |event| Message::Input(FieldInput::JumpMinimap(event)))
+}
+
+pub fn create_brush_select(document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<HtmlSelectElement, AppError> {
+    let select = document.create_element("select")?.dyn_into::<HtmlSelectElement>()?;
+    select.set_title("Brush");
+    for (name, _) in brush::PRESETS {
+        let option = document.create_element("option")?;
+        option.set_text_content(Some(name));
+        select.append_child(&option)?;
+    }
+    let message = {
+        let select = select.clone();
+        move || {
+            let index = usize::try_from(select.selected_index()).unwrap_or(0);
+            Message::Input(FieldInput::SetBrush(brush::PRESETS.get(index).unwrap_or(&brush::PRESETS[0]).1))
+        }
+    };
+    add_message_listener(listeners, &select, &["change"], dispatcher, message)?;
+    Ok(select)
+}
+
+/// Positions of the fingers if exactly two of them are on the target. The page doesn't scroll or zoom under them
+fn two_finger_touch(event: &web_sys::TouchEvent) -> Option<((f64, f64), (f64, f64))> {
+    let touches = event.target_touches();
+    if touches.length() != 2 {
+        return None;
+    }
+    let position = |touch: web_sys::Touch| (touch.client_x() as f64, touch.client_y() as f64);
+    let positions = (position(touches.get(0)?), position(touches.get(1)?));
+    event.prevent_default();
+    Some(positions)
+}
+
+/// Cell under the point of the viewport in css pixels, like the position of a mouse event or a touch
+pub fn client_to_cell(canvas: &HtmlCanvasElement, field: &dyn DynAutomaton, client_x: f64, client_y: f64) -> (usize, usize) {
+    let bounding_rect = canvas.get_bounding_client_rect();
+
+    // css size of the canvas, the backing store can be bigger on HiDPI screens
+    let (width, height) = canvas_size(field);
+    let scale_x = (width as f64) / bounding_rect.width();
+    let scale_y = (height as f64) / bounding_rect.height();
+
+    let canvas_left = (client_x - bounding_rect.left()) * scale_x;
+    let canvas_top = (client_y - bounding_rect.top()) * scale_y;
+
+    canvas_coords_to_cell(field, canvas_left, canvas_top)
+}
+
+/// Line of a drag with the line brush, from the cell where the drag has started
+#[derive(Debug)]
+pub struct LineDraft {
+    from: (usize, usize),
+    to: (usize, usize),
+    /// Cells painted for the current end and their states before, restored when the end moves
+    covered: Vec<(usize, usize, u8)>,
+}
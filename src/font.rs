@@ -0,0 +1,77 @@
+/// Size of the glyphs of the bitmap font, in cells
+pub const GLYPH_WIDTH: usize = 5;
+pub const GLYPH_HEIGHT: usize = 7;
+
+/// Glyph of characters that the font doesn't have
+const FALLBACK: char = '?';
+
+/// 5x7 bitmap font for the uppercase letters, the digits and some punctuation, `#` for the set pixels
+const GLYPHS: &[(char, [&str; GLYPH_HEIGHT])] = &[
+    (' ', [".....", ".....", ".....", ".....", ".....", ".....", "....."]),
+    ('A', [".###.", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"]),
+    ('B', ["####.", "#...#", "#...#", "####.", "#...#", "#...#", "####."]),
+    ('C', [".###.", "#...#", "#....", "#....", "#....", "#...#", ".###."]),
+    ('D', ["####.", "#...#", "#...#", "#...#", "#...#", "#...#", "####."]),
+    ('E', ["#####", "#....", "#....", "####.", "#....", "#....", "#####"]),
+    ('F', ["#####", "#....", "#....", "####.", "#....", "#....", "#...."]),
+    ('G', [".###.", "#...#", "#....", "#.###", "#...#", "#...#", ".####"]),
+    ('H', ["#...#", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"]),
+    ('I', [".###.", "..#..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('J', ["..###", "...#.", "...#.", "...#.", "...#.", "#..#.", ".##.."]),
+    ('K', ["#...#", "#..#.", "#.#..", "##...", "#.#..", "#..#.", "#...#"]),
+    ('L', ["#....", "#....", "#....", "#....", "#....", "#....", "#####"]),
+    ('M', ["#...#", "##.##", "#.#.#", "#.#.#", "#...#", "#...#", "#...#"]),
+    ('N', ["#...#", "#...#", "##..#", "#.#.#", "#..##", "#...#", "#...#"]),
+    ('O', [".###.", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."]),
+    ('P', ["####.", "#...#", "#...#", "####.", "#....", "#....", "#...."]),
+    ('Q', [".###.", "#...#", "#...#", "#...#", "#.#.#", "#..#.", ".##.#"]),
+    ('R', ["####.", "#...#", "#...#", "####.", "#.#..", "#..#.", "#...#"]),
+    ('S', [".####", "#....", "#....", ".###.", "....#", "....#", "####."]),
+    ('T', ["#####", "..#..", "..#..", "..#..", "..#..", "..#..", "..#.."]),
+    ('U', ["#...#", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."]),
+    ('V', ["#...#", "#...#", "#...#", "#...#", "#...#", ".#.#.", "..#.."]),
+    ('W', ["#...#", "#...#", "#...#", "#.#.#", "#.#.#", "#.#.#", ".#.#."]),
+    ('X', ["#...#", "#...#", ".#.#.", "..#..", ".#.#.", "#...#", "#...#"]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#..", "..#.."]),
+    ('Z', ["#####", "....#", "...#.", "..#..", ".#...", "#....", "#####"]),
+    ('0', [".###.", "#...#", "#..##", "#.#.#", "##..#", "#...#", ".###."]),
+    ('1', ["..#..", ".##..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('2', [".###.", "#...#", "....#", "...#.", "..#..", ".#...", "#####"]),
+    ('3', ["#####", "...#.", "..#..", "...#.", "....#", "#...#", ".###."]),
+    ('4', ["...#.", "..##.", ".#.#.", "#..#.", "#####", "...#.", "...#."]),
+    ('5', ["#####", "#....", "####.", "....#", "....#", "#...#", ".###."]),
+    ('6', ["..##.", ".#...", "#....", "####.", "#...#", "#...#", ".###."]),
+    ('7', ["#####", "....#", "...#.", "..#..", ".#...", ".#...", ".#..."]),
+    ('8', [".###.", "#...#", "#...#", ".###.", "#...#", "#...#", ".###."]),
+    ('9', [".###.", "#...#", "#...#", ".####", "....#", "...#.", ".##.."]),
+    ('!', ["..#..", "..#..", "..#..", "..#..", "..#..", ".....", "..#.."]),
+    ('?', [".###.", "#...#", "....#", "...#.", "..#..", ".....", "..#.."]),
+    ('.', [".....", ".....", ".....", ".....", ".....", ".##..", ".##.."]),
+    (',', [".....", ".....", ".....", ".....", ".##..", "..#..", ".#..."]),
+    ('-', [".....", ".....", ".....", "#####", ".....", ".....", "....."]),
+    ('\'', ["..#..", "..#..", ".#...", ".....", ".....", ".....", "....."]),
+    (':', [".....", ".##..", ".##..", ".....", ".##..", ".##..", "....."]),
+];
+
+/// Rows of the glyph of the character from top to bottom, `#` for the set pixels.
+/// Lowercase letters use the uppercase glyphs, characters without a glyph get a question mark
+pub fn glyph(char: char) -> [&'static str; GLYPH_HEIGHT] {
+    let char = char.to_ascii_uppercase();
+    let find = |char| GLYPHS.iter().find(|&&(glyph_char, _)| glyph_char == char).map(|&(_, rows)| rows);
+    find(char).or_else(|| find(FALLBACK)).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_glyphs() {
+        for (char, rows) in GLYPHS {
+            assert!(rows.iter().all(|row| (row.len() == GLYPH_WIDTH) && row.chars().all(|pixel| matches!(pixel, '.' | '#'))), "invalid glyph {char}");
+        }
+        assert_eq!(glyph('A'), glyph('a'));
+        assert_eq!(glyph('?'), glyph('~'));
+        assert_eq!(glyph('?'), glyph('é'));
+        assert_ne!(glyph('O'), glyph('0'));
+    }
+}
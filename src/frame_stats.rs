@@ -0,0 +1,172 @@
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, Document, Element, HtmlCanvasElement};
+use crate::error::AppError;
+use crate::render::context_2d;
+use crate::ring_buffer::{ArrayRingBuffer, Sum};
+
+/// Number of recent frames in the statistics and in the graph
+pub const FRAME_HISTORY_SIZE: usize = 100;
+/// Recent frame times in milliseconds
+pub type FrameHistory = ArrayRingBuffer<f64, FRAME_HISTORY_SIZE, Sum>;
+const GRAPH_WIDTH_PX: u32 = FRAME_HISTORY_SIZE as u32;
+const GRAPH_HEIGHT_PX: u32 = 30;
+/// The graph is scaled to fit at least this frame time, so that small jitter doesn't look like big spikes
+const MIN_GRAPH_SCALE_MS: f64 = 2000.0 / 60.0;
+/// Frame time at 60 fps, shown as a reference line in the graph
+const TARGET_FRAME_MS: f64 = 1000.0 / 60.0;
+
+/// Statistics of the frame times in the history window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameStats {
+    pub mean_ms: f64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    /// 99% of the frames are at least this fast
+    pub p99_ms: f64,
+    /// Mean time of the slowest 1% of frames
+    pub low_1_percent_ms: f64,
+}
+impl FrameStats {
+    /// Calculates the statistics of the frame times, None if there are none
+    pub fn new(history_ms: &FrameHistory) -> Option<Self> {
+        let mean_ms = history_ms.mean()?;
+        // percentiles need the sorted times, the mean is kept by the buffer
+        let mut sorted = history_ms.iter().copied().collect::<Vec<_>>();
+        sorted.sort_by(f64::total_cmp);
+        let len = sorted.len();
+        let slowest_count = (len / 100).max(1);
+        Some(Self {
+            mean_ms,
+            min_ms: history_ms.min()?,
+            max_ms: history_ms.max()?,
+            p99_ms: sorted[((len as f64 * 0.99).ceil() as usize).max(1) - 1],
+            low_1_percent_ms: sorted[(len - slowest_count)..].iter().sum::<f64>() / slowest_count as f64,
+        })
+    }
+    pub fn fps(&self) -> f64 {
+        1000.0 / self.mean_ms
+    }
+    /// Frame rate of the slowest 1% of frames
+    pub fn low_1_percent_fps(&self) -> f64 {
+        1000.0 / self.low_1_percent_ms
+    }
+}
+
+/// Frame rate statistics as text, and a sparkline of the recent frame times.
+/// They are updated at most once per interval, so that the page isn't laid out again every frame
+#[derive(Debug)]
+pub struct FpsMeter {
+    text: Element,
+    graph: CanvasRenderingContext2d,
+    update_interval_ms: f64,
+    last_update_ts_ms: Option<f64>,
+    /// The text is only replaced when it changes
+    last_text: String,
+}
+impl FpsMeter {
+    /// Creates the meter at the end of the parent
+    pub fn new(document: &Document, parent: &Element, update_interval_ms: f64) -> Result<Self, AppError> {
+        let text = document.create_element("span")?;
+        parent.append_child(&text)?;
+        let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+        canvas.set_width(GRAPH_WIDTH_PX);
+        canvas.set_height(GRAPH_HEIGHT_PX);
+        canvas.set_title("Recent frame times, the line is 60 fps");
+        parent.append_child(&canvas)?;
+        Ok(Self {
+            text,
+            graph: context_2d(&canvas)?,
+            update_interval_ms,
+            last_update_ts_ms: None,
+            last_text: String::new(),
+        })
+    }
+    /// Shows the statistics of the frame times, if the update interval has passed since the last update
+    pub fn update(&mut self, timestamp_ms: f64, history_ms: &FrameHistory) {
+        if self.last_update_ts_ms.is_some_and(|last_ts_ms| (timestamp_ms - last_ts_ms) < self.update_interval_ms) {
+            return;
+        }
+        self.last_update_ts_ms = Some(timestamp_ms);
+        let Some(stats) = FrameStats::new(history_ms) else {
+            return;
+        };
+        let text = format!(
+            "fps: {:.1} (1% low {:.1}), frame ms: avg {:.1}, p99 {:.1}, min {:.1}, max {:.1}",
+            stats.fps(),
+            stats.low_1_percent_fps(),
+            stats.mean_ms,
+            stats.p99_ms,
+            stats.min_ms,
+            stats.max_ms,
+        );
+        if text != self.last_text {
+            self.text.set_text_content(Some(&text));
+            self.last_text = text;
+        }
+        self.draw_graph(history_ms, stats.max_ms);
+    }
+    fn draw_graph(&self, history_ms: &FrameHistory, max_ms: f64) {
+        let ctx = &self.graph;
+        let (width, height) = (GRAPH_WIDTH_PX as f64, GRAPH_HEIGHT_PX as f64);
+        ctx.set_fill_style(&"#F4F4F4".into());
+        ctx.fill_rect(0.0, 0.0, width, height);
+        let scale_y = (height - 2.0) / max_ms.max(MIN_GRAPH_SCALE_MS);
+
+        ctx.set_stroke_style(&"#BBBBBB".into());
+        ctx.begin_path();
+        let target_y = height - 1.0 - (TARGET_FRAME_MS * scale_y);
+        ctx.move_to(0.0, target_y);
+        ctx.line_to(width, target_y);
+        ctx.stroke();
+
+        // the newest frame is at the right edge, a smaller window is stretched to the width of the graph
+        let step = width / history_ms.capacity().max(1) as f64;
+        let offset = history_ms.capacity().saturating_sub(history_ms.len());
+        ctx.set_stroke_style(&"#C62828".into());
+        ctx.begin_path();
+        for (index, &time_ms) in history_ms.iter().enumerate() {
+            let x = (offset + index) as f64 * step;
+            let y = height - 1.0 - (time_ms * scale_y);
+            if index == 0 {
+                ctx.move_to(x, y);
+            } else {
+                ctx.line_to(x, y);
+            }
+        }
+        ctx.stroke();
+
+        // a dot makes the newest frame easy to spot
+        if let Some(&time_ms) = history_ms.last() {
+            ctx.set_fill_style(&"#C62828".into());
+            ctx.fill_rect(width - 2.0, height - 2.0 - (time_ms * scale_y), 2.0, 2.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_frame_stats() {
+        let mut history = FrameHistory::new();
+        assert_eq!(None, FrameStats::new(&history));
+
+        for time in [10.0; 97].into_iter().chain([30.0, 40.0, 50.0]) {
+            history.push(time);
+        }
+        let stats = FrameStats::new(&history).unwrap();
+        assert_eq!(10.9, stats.mean_ms);
+        assert_eq!(10.0, stats.min_ms);
+        assert_eq!(50.0, stats.max_ms);
+        assert_eq!(40.0, stats.p99_ms);
+        assert_eq!(50.0, stats.low_1_percent_ms);
+
+        let mut history = FrameHistory::new();
+        history.push(20.0);
+        history.push(5.0);
+        let stats = FrameStats::new(&history).unwrap();
+        assert_eq!(20.0, stats.p99_ms);
+        assert_eq!(20.0, stats.low_1_percent_ms);
+        assert_eq!(80.0, stats.fps());
+    }
+}
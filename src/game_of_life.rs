@@ -1,8 +1,21 @@
 use std::fmt::{Display, Formatter, Write};
 use std::mem;
 use std::num::NonZeroUsize;
+use std::str::FromStr;
+#[cfg(feature = "browser")]
+use wasm_bindgen::prelude::*;
+use crate::brush::Brush;
+use crate::font;
+use crate::random::XorShift;
+use crate::snapshot::{self, SnapshotError};
+use crate::automaton::{next_coord_wrapped, parse_cells, prev_coord_wrapped, Automaton, CellState};
+use crate::theme::Theme;
+
+/// Largest width and height of fields that are read, patterns also come from other pages and a bigger header would only exhaust the memory
+pub const MAX_SIDE: usize = 8192;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
 pub enum CellValue {
     Dead = 0,
     Alive = 1,
@@ -16,11 +29,246 @@ impl CellValue {
     }
 }
 
+/// Which cells count as neighbours when calculating the next generation
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub enum Neighborhood {
+    /// 8 surrounding cells
+    #[default]
+    Moore,
+    /// 4 orthogonally adjacent cells
+    VonNeumann,
+    /// 24 cells of the 5x5 square around the cell
+    ExtendedMoore,
+}
+impl Neighborhood {
+    /// (row, col) offsets of the neighbours relative to the cell
+    pub fn offsets(&self) -> &'static [(isize, isize)] {
+        match self {
+            Neighborhood::Moore => &[
+                (-1, -1), (-1, 0), (-1, 1),
+                (0, -1), (0, 1),
+                (1, -1), (1, 0), (1, 1),
+            ],
+            Neighborhood::VonNeumann => &[(-1, 0), (0, -1), (0, 1), (1, 0)],
+            Neighborhood::ExtendedMoore => &[
+                (-2, -2), (-2, -1), (-2, 0), (-2, 1), (-2, 2),
+                (-1, -2), (-1, -1), (-1, 0), (-1, 1), (-1, 2),
+                (0, -2), (0, -1), (0, 1), (0, 2),
+                (1, -2), (1, -1), (1, 0), (1, 1), (1, 2),
+                (2, -2), (2, -1), (2, 0), (2, 1), (2, 2),
+            ],
+        }
+    }
+}
+
+/// How the edges of the field are joined, which decides the neighbours of the cells on the edges
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub enum Topology {
+    /// The top edge is joined to the bottom one and the left edge to the right one
+    #[default]
+    Torus,
+    /// The edges are not joined, the cells outside of the field are dead
+    Plane,
+    /// Like the torus, but the top and bottom edges are joined mirrored, a pattern leaving at the top comes back flipped left to right
+    KleinBottle,
+    /// Both pairs of edges are joined mirrored. There are no neighbours diagonally across the corners
+    ProjectivePlane,
+}
+impl Topology {
+    /// Names of the topologies for the select of the ui
+    pub const ALL: [(&'static str, Topology); 4] = [
+        ("Torus", Topology::Torus),
+        ("Plane", Topology::Plane),
+        ("Klein Bottle", Topology::KleinBottle),
+        ("Projective Plane", Topology::ProjectivePlane),
+    ];
+    pub fn is_torus(&self) -> bool {
+        *self == Topology::Torus
+    }
+    /// Coordinates of the cell at the offset from the cell, `None` if there is no cell there.
+    /// Crossing a mirrored edge flips the other coordinate, crossing it several times flips it once per crossing
+    pub fn neighbour(&self, row: usize, col: usize, (delta_row, delta_col): (isize, isize), width: usize, height: usize) -> Option<(usize, usize)> {
+        let (width, height) = (width as isize, height as isize);
+        let (row, col) = (row as isize + delta_row, col as isize + delta_col);
+        let row_crossings = row.div_euclid(height);
+        let col_crossings = col.div_euclid(width);
+        let (flip_col, flip_row) = match self {
+            Topology::Torus => (false, false),
+            Topology::Plane if (row_crossings, col_crossings) != (0, 0) => return None,
+            Topology::Plane => (false, false),
+            Topology::KleinBottle => (row_crossings % 2 != 0, false),
+            // both crossings meet at a corner, which is a single point shared by two corners of the field
+            Topology::ProjectivePlane if (row_crossings != 0) && (col_crossings != 0) => return None,
+            Topology::ProjectivePlane => (row_crossings % 2 != 0, col_crossings % 2 != 0),
+        };
+        let row = row.rem_euclid(height);
+        let col = col.rem_euclid(width);
+        let row = if flip_row { height - 1 - row } else { row };
+        let col = if flip_col { width - 1 - col } else { col };
+        Some((row as usize, col as usize))
+    }
+}
+
+/// Spontaneous births and deaths, every cell flips with the probability after every generation
+#[derive(Clone, Debug)]
+struct Noise {
+    probability: f64,
+    random: XorShift,
+}
+
+/// Name, author and comments of a pattern, from the `#N`, `#O` and `#C` lines of RLE files
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct PatternMeta {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub comments: Vec<String>,
+}
+impl PatternMeta {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.author.is_none() && self.comments.is_empty()
+    }
+}
+
+/// Birth and survival conditions, bit `n` is set if the rule applies for `n` live neighbours
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rule {
+    birth: u16,
+    survival: u16,
+}
+impl Rule {
+    pub const LIFE: Rule = Rule::from_masks(1 << 3, (1 << 2) | (1 << 3));
+    pub const HIGH_LIFE: Rule = Rule::from_masks((1 << 3) | (1 << 6), (1 << 2) | (1 << 3));
+    pub const SEEDS: Rule = Rule::from_masks(1 << 2, 0);
+    pub const DAY_AND_NIGHT: Rule = Rule::from_masks(
+        (1 << 3) | (1 << 6) | (1 << 7) | (1 << 8),
+        (1 << 3) | (1 << 4) | (1 << 6) | (1 << 7) | (1 << 8),
+    );
+    pub const MAZE: Rule = Rule::from_masks(1 << 3, 0b11_1110);
+    pub const LIFE_WITHOUT_DEATH: Rule = Rule::from_masks(1 << 3, 0b1_1111_1111);
+
+    pub const fn from_masks(birth: u16, survival: u16) -> Self {
+        Self { birth, survival }
+    }
+    /// Birth and survival masks, the inverse of `from_masks`
+    pub fn masks(&self) -> (u16, u16) {
+        (self.birth, self.survival)
+    }
+    /// Counts above 15, possible with the extended Moore neighborhood, are never in the rule
+    pub fn is_birth(&self, live_neighbours: u8) -> bool {
+        self.birth.checked_shr(live_neighbours as u32).is_some_and(|mask| mask & 1 == 1)
+    }
+    pub fn is_survival(&self, live_neighbours: u8) -> bool {
+        self.survival.checked_shr(live_neighbours as u32).is_some_and(|mask| mask & 1 == 1)
+    }
+    pub fn set_birth(&mut self, live_neighbours: u8, value: bool) {
+        Self::set_bit(&mut self.birth, live_neighbours, value);
+    }
+    pub fn set_survival(&mut self, live_neighbours: u8, value: bool) {
+        Self::set_bit(&mut self.survival, live_neighbours, value);
+    }
+    fn set_bit(mask: &mut u16, bit: u8, value: bool) {
+        if value {
+            *mask |= 1 << bit;
+        } else {
+            *mask &= !(1 << bit);
+        }
+    }
+    fn parse_digits(str: &str) -> Result<u16, ParseError> {
+        let mut mask = 0;
+        for char in str.chars() {
+            let digit = char.to_digit(9).ok_or(ParseError::InvalidRule)?;
+            mask |= 1 << digit;
+        }
+        Ok(mask)
+    }
+}
+impl Default for Rule {
+    fn default() -> Self {
+        Self::LIFE
+    }
+}
+/// Parses rulestrings in B/S notation like `B3/S23`, or the older S/B notation like `23/3`
+impl FromStr for Rule {
+    type Err = ParseError;
+    fn from_str(str: &str) -> Result<Self, ParseError> {
+        let str = str.trim();
+        if str.is_empty() {
+            return Err(ParseError::EmptyString);
+        }
+        let (first, second) = str.split_once('/').ok_or(ParseError::InvalidRule)?;
+        let upper = (first.to_ascii_uppercase(), second.to_ascii_uppercase());
+        let (birth, survival) = match (upper.0.strip_prefix('B'), upper.1.strip_prefix('S')) {
+            (Some(birth), Some(survival)) => (birth.to_string(), survival.to_string()),
+            (None, None) => (second.to_string(), first.to_string()),
+            _ => return Err(ParseError::InvalidRule),
+        };
+        Ok(Self::from_masks(Self::parse_digits(&birth)?, Self::parse_digits(&survival)?))
+    }
+}
+impl Display for Rule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_char('B')?;
+        for count in 0..=8 {
+            if self.is_birth(count) {
+                write!(f, "{count}")?;
+            }
+        }
+        f.write_str("/S")?;
+        for count in 0..=8 {
+            if self.is_survival(count) {
+                write!(f, "{count}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Cell-wise boolean operation for combining two fields
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CombineOp {
+    And,
+    Or,
+    Xor,
+}
+impl CombineOp {
+    fn apply(self, left: CellValue, right: CellValue) -> CellValue {
+        let left = left == CellValue::Alive;
+        let right = right == CellValue::Alive;
+        let res = match self {
+            CombineOp::And => left && right,
+            CombineOp::Or => left || right,
+            CombineOp::Xor => left != right,
+        };
+        if res { CellValue::Alive } else { CellValue::Dead }
+    }
+}
+
+/// Fields with different width or height can't be combined
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SizeMismatch;
+
+/// Serialized as `FieldData`, e.g. in JSON `{"width":4,"height":2,"rule":"B3/S23","neighborhood":"moore","cells":"6a"}`
+#[cfg_attr(feature = "browser", wasm_bindgen)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(into = "FieldData", try_from = "FieldData"))]
+#[derive(Clone)]
 pub struct Field {
     width: NonZeroUsize,
     height: NonZeroUsize,
+    neighborhood: Neighborhood,
+    topology: Topology,
+    rule: Rule,
     cells: Vec<CellValue>,
     swap_cells: Vec<CellValue>,
+    /// Indexes of the cells that changed in the last update
+    changed: Vec<usize>,
+    /// Metadata of the pattern the field was loaded from, written again when the field is exported
+    meta: PatternMeta,
+    noise: Option<Noise>,
 }
 
 impl Field {
@@ -29,29 +277,89 @@ impl Field {
         Self {
             width,
             height,
+            neighborhood: Neighborhood::Moore,
+            topology: Topology::Torus,
+            rule: Rule::LIFE,
             cells: vec![CellValue::Dead; cell_count],
             swap_cells: vec![CellValue::Dead; cell_count],
+            changed: Vec::new(),
+            meta: PatternMeta::default(),
+            noise: None,
         }
     }
     pub fn generate_by_fn(width: NonZeroUsize, height: NonZeroUsize, random_bool: impl Fn(usize) -> bool) -> Self {
+        let mut field = Self::new(width, height);
+        field.fill_from_fn(width, height, random_bool);
+        field
+    }
+    /// Refills the field like `generate_by_fn`, but reuses the buffers of the cells, they only grow if the new size is bigger.
+    /// The rule, the neighborhood and the topology are kept. The metadata of the pattern is dropped, the new cells are another pattern
+    pub fn fill_from_fn(&mut self, width: NonZeroUsize, height: NonZeroUsize, random_bool: impl Fn(usize) -> bool) {
         let cell_count = width.get() * height.get();
-
-        let cells = (0..cell_count)
-            .map(|i| {
-                if random_bool(i) {
-                    CellValue::Alive
-                } else {
-                    CellValue::Dead
-                }
-            })
-            .collect();
-
-        Self {
-            width,
-            height,
-            cells,
-            swap_cells: vec![CellValue::Dead; cell_count],
-        }
+        self.width = width;
+        self.height = height;
+        self.cells.clear();
+        self.cells.extend((0..cell_count).map(|i| {
+            if random_bool(i) {
+                CellValue::Alive
+            } else {
+                CellValue::Dead
+            }
+        }));
+        self.swap_cells.clear();
+        self.swap_cells.resize(cell_count, CellValue::Dead);
+        self.changed.clear();
+        self.meta = PatternMeta::default();
+    }
+    pub fn with_rule(mut self, rule: Rule) -> Self {
+        self.rule = rule;
+        self
+    }
+    pub fn get_rule(&self) -> Rule {
+        self.rule
+    }
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+    }
+    pub fn with_neighborhood(mut self, neighborhood: Neighborhood) -> Self {
+        self.neighborhood = neighborhood;
+        self
+    }
+    pub fn with_meta(mut self, meta: PatternMeta) -> Self {
+        self.meta = meta;
+        self
+    }
+    pub fn get_meta(&self) -> &PatternMeta {
+        &self.meta
+    }
+    pub fn get_neighborhood(&self) -> Neighborhood {
+        self.neighborhood
+    }
+    pub fn set_neighborhood(&mut self, neighborhood: Neighborhood) {
+        self.neighborhood = neighborhood;
+    }
+    pub fn with_topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+    pub fn get_topology(&self) -> Topology {
+        self.topology
+    }
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
+    /// Makes every cell flip with the probability after every generation, randomly but reproducibly from the seed.
+    /// Zero turns the noise off
+    pub fn set_noise(&mut self, probability: f64, seed: u64) {
+        self.noise = (probability > 0.0).then(|| Noise { probability: probability.min(1.0), random: XorShift::new(seed) });
+    }
+    /// Probability of a cell to flip after a generation
+    pub fn get_noise(&self) -> f64 {
+        self.noise.as_ref().map_or(0.0, |noise| noise.probability)
+    }
+    /// Probability and seed for `set_noise` that make a copy of the field flip the same cells from now on
+    pub fn noise_seed(&self) -> Option<(f64, u64)> {
+        self.noise.as_ref().map(|noise| (noise.probability, noise.random.state()))
     }
     pub fn get_height(&self) -> usize {
         self.height.get()
@@ -59,11 +367,11 @@ impl Field {
     pub fn get_width(&self) -> usize {
         self.width.get()
     }
-    fn get_by_coords(&self, row: usize, col: usize) -> Option<CellValue> {
+    pub fn get_by_coords(&self, row: usize, col: usize) -> Option<CellValue> {
         let index = self.coords_to_index_checked(row, col)?;
         Some(self.cells[index])
     }
-    fn set_by_coords(&mut self, row: usize, col: usize, value: CellValue) -> Option<()> {
+    pub fn set_by_coords(&mut self, row: usize, col: usize, value: CellValue) -> Option<()> {
         let index = self.coords_to_index_checked(row, col)?;
         self.cells[index] = value;
         Some(())
@@ -73,11 +381,18 @@ impl Field {
         self.cells[index] = self.cells[index].other();
         Some(())
     }
+    /// Sets all the cells of the brush applied at the cell, the parts of the brush outside of the field are ignored
+    pub fn apply_brush(&mut self, row: usize, col: usize, brush: Brush, value: CellValue) {
+        for (row, col) in brush.cells(row, col, self.width.get(), self.height.get()) {
+            let index = self.coords_to_index_unchecked(row, col);
+            self.cells[index] = value;
+        }
+    }
     fn coords_to_index_checked(&self, row: usize, col: usize) -> Option<usize> {
-        if row >= self.width.get() {
+        if row >= self.height.get() {
             return None;
         }
-        if col >= self.height.get() {
+        if col >= self.width.get() {
             return None;
         }
         let index = self.coords_to_index_unchecked(row, col);
@@ -92,6 +407,18 @@ impl Field {
     pub fn view_old(&self) -> &[CellValue] {
         &self.swap_cells
     }
+    /// Indexes of the cells that changed in the last update
+    pub fn changed(&self) -> &[usize] {
+        &self.changed
+    }
+    /// Address of the cells in memory, one byte per cell in row-major order.
+    /// Changes after every update, because the buffers are swapped
+    pub fn cells_ptr(&self) -> *const u8 {
+        self.cells.as_ptr() as *const u8
+    }
+    pub fn cells_len(&self) -> usize {
+        self.cells.len()
+    }
     pub fn rows(&self) -> impl Iterator<Item=&[CellValue]> + '_ {
         let width = self.width.get();
         self.cells.chunks(width)
@@ -101,29 +428,111 @@ impl Field {
         self.cells.chunks(width).zip(self.swap_cells.chunks(width))
     }
     pub fn update(&mut self) -> bool {
+        let mut has_alive = self.calc_next_generation();
+        // only the generations are noisy, the previews of the next one don't use up the random numbers
+        if let Some(noise) = &mut self.noise {
+            let mut is_flipped = false;
+            for value in self.swap_cells.iter_mut() {
+                if noise.random.next_f64() < noise.probability {
+                    *value = value.other();
+                    is_flipped = true;
+                }
+            }
+            if is_flipped {
+                self.changed = (0..self.cells.len()).filter(|&index| self.cells[index] != self.swap_cells[index]).collect();
+                has_alive = self.swap_cells.contains(&CellValue::Alive);
+            }
+        }
+        mem::swap(&mut self.cells, &mut self.swap_cells);
+        has_alive
+    }
+    /// Calculates the next generation without applying it. The current cells stay the same
+    pub fn preview(&mut self) -> &[CellValue] {
+        self.calc_next_generation();
+        &self.swap_cells
+    }
+    /// Writes the next generation into the swap buffer
+    fn calc_next_generation(&mut self) -> bool {
         let max_col = self.width.get() - 1;
         let max_row = self.height.get() - 1;
 
         let width = self.width.get();
         let mut has_alive = false;
+        self.changed.clear();
         for (row_no, row) in self.cells.chunks(width).enumerate() {
             for (col_no, &value) in row.iter().enumerate() {
-                let live_neighbours = self.count_live_neighbours(row_no, col_no, max_row, max_col);
+                let live_neighbours = match (self.neighborhood, self.topology) {
+                    (Neighborhood::Moore, Topology::Torus) => self.count_live_neighbours(row_no, col_no, max_row, max_col),
+                    _ => self.count_live_neighbours_by_offsets(row_no, col_no),
+                };
                 let index = self.coords_to_index_unchecked(row_no, col_no);
-                let new_value = Self::calc_new_value(value, live_neighbours);
+                let new_value = Self::calc_new_value(self.rule, value, live_neighbours);
                 self.swap_cells[index] = new_value;
+                if new_value != value {
+                    self.changed.push(index);
+                }
                 has_alive = has_alive || (new_value == CellValue::Alive);
             }
         }
-        mem::swap(&mut self.cells, &mut self.swap_cells);
         has_alive
     }
+    /// Vector image of the field, with `cell_size` units per cell.
+    /// All live cells are drawn with a single path, and the grid lines are drawn over the cells
+    pub fn to_svg(&self, cell_size: u32, theme: &Theme) -> String {
+        let width = self.width.get() as u32 * cell_size;
+        let height = self.height.get() as u32 * cell_size;
+        let mut res = String::new();
+        // writing into a String never fails
+        let _ = write!(
+            res,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#,
+        );
+        let _ = write!(res, r#"<rect width="{width}" height="{height}" fill="{}"/>"#, theme.dead);
+        let mut path = String::new();
+        for (index, &value) in self.cells.iter().enumerate() {
+            if value == CellValue::Alive {
+                let x = (index % self.width.get()) as u32 * cell_size;
+                let y = (index / self.width.get()) as u32 * cell_size;
+                let _ = write!(path, "M{x} {y}h{cell_size}v{cell_size}h-{cell_size}z");
+            }
+        }
+        if !path.is_empty() {
+            let _ = write!(res, r#"<path d="{path}" fill="{}"/>"#, theme.alive);
+        }
+        if let Some(grid) = &theme.grid {
+            let mut path = String::new();
+            for col in 0..=self.width.get() as u32 {
+                let _ = write!(path, "M{} 0V{height}", col * cell_size);
+            }
+            for row in 0..=self.height.get() as u32 {
+                let _ = write!(path, "M0 {}H{width}", row * cell_size);
+            }
+            let _ = write!(res, r#"<path d="{path}" stroke="{grid}" stroke-width="0.5"/>"#);
+        }
+        res.push_str("</svg>");
+        res
+    }
+    /// Number of live neighbours of every cell in the current neighborhood, in row-major order
+    pub fn live_neighbour_counts(&self) -> Vec<u8> {
+        let max_col = self.width.get() - 1;
+        let max_row = self.height.get() - 1;
+        (0..self.cells.len())
+            .map(|index| {
+                let row = index / self.width.get();
+                let col = index % self.width.get();
+                match (self.neighborhood, self.topology) {
+                    (Neighborhood::Moore, Topology::Torus) => self.count_live_neighbours(row, col, max_row, max_col),
+                    _ => self.count_live_neighbours_by_offsets(row, col),
+                }
+            })
+            .collect()
+    }
     fn count_live_neighbours(&self, row: usize, col: usize, max_row: usize, max_col: usize) -> u8 {
         let mut count = 0;
-        let row_top = Self::prev_coord_wrapped(row, max_row);
-        let row_bottom = Self::next_coord_wrapped(row, max_row);
-        let col_left = Self::prev_coord_wrapped(col, max_col);
-        let col_right = Self::next_coord_wrapped(col, max_col);
+        let row_top = prev_coord_wrapped(row, max_row);
+        let row_bottom = next_coord_wrapped(row, max_row);
+        let col_left = prev_coord_wrapped(col, max_col);
+        let col_right = next_coord_wrapped(col, max_col);
         count += self.cells[self.coords_to_index_unchecked(row_top, col_left)] as u8;
         count += self.cells[self.coords_to_index_unchecked(row_top, col)] as u8;
         count += self.cells[self.coords_to_index_unchecked(row_top, col_right)] as u8;
@@ -134,20 +543,18 @@ impl Field {
         count += self.cells[self.coords_to_index_unchecked(row_bottom, col_right)] as u8;
         count
     }
-    fn next_coord_wrapped(value: usize, max_value: usize) -> usize {
-        if value >= max_value {
-            0
-        } else {
-            value + 1
-        }
-    }
-    fn prev_coord_wrapped(value: usize, max_value: usize) -> usize {
-        if value == 0 {
-            max_value
-        } else {
-            value - 1
+    /// Counts the neighbours of any neighborhood in any topology, slower than `count_live_neighbours`
+    fn count_live_neighbours_by_offsets(&self, row: usize, col: usize) -> u8 {
+        let mut count = 0;
+        for &offset in self.neighborhood.offsets() {
+            if let Some((check_row, check_col)) = self.topology.neighbour(row, col, offset, self.width.get(), self.height.get()) {
+                count += self.cells[self.coords_to_index_unchecked(check_row, check_col)] as u8;
+            }
         }
+        count
     }
+    /// Straightforward version of `count_live_neighbours` for the Moore neighborhood on a torus, the reference in tests
+    #[cfg(test)]
     fn count_live_neighbours_slow(&self, row: usize, col: usize) -> u8 {
         let width = self.width.get();
         let height = self.height.get();
@@ -165,60 +572,233 @@ impl Field {
         }
         count
     }
-    fn calc_new_value(old_value: CellValue, live_neighbours: u8) -> CellValue {
+    /// New field where each cell is the result of the operation on the cells of both fields.
+    /// The rule and neighborhood are taken from `self`
+    pub fn combine(&self, other: &Field, op: CombineOp) -> Result<Field, SizeMismatch> {
+        let mut res = self.clone();
+        res.combine_with(other, op)?;
+        Ok(res)
+    }
+    /// In-place version of `combine`
+    pub fn combine_with(&mut self, other: &Field, op: CombineOp) -> Result<(), SizeMismatch> {
+        if (self.width != other.width) || (self.height != other.height) {
+            return Err(SizeMismatch);
+        }
+        for (value, &other_value) in self.cells.iter_mut().zip(other.cells.iter()) {
+            *value = op.apply(*value, other_value);
+        }
+        Ok(())
+    }
+    /// Cells that are different in `other`, with their values from `other`, in row-major order.
+    /// Only the area that is common to both fields is compared
+    pub fn diff(&self, other: &Field) -> Vec<(usize, usize, CellValue)> {
+        let height = self.height.min(other.height).get();
+        let width = self.width.min(other.width).get();
+        let mut res = Vec::new();
+        for row in 0..height {
+            for col in 0..width {
+                let value = self.cells[self.coords_to_index_unchecked(row, col)];
+                let other_value = other.cells[other.coords_to_index_unchecked(row, col)];
+                if value != other_value {
+                    res.push((row, col, other_value));
+                }
+            }
+        }
+        res
+    }
+    /// The field in the binary snapshot format, see `snapshot::encode`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        snapshot::encode(self, None)
+    }
+    /// Reads a field from a binary snapshot, the ages of the cells in it are ignored
+    pub fn from_bytes(bytes: &[u8]) -> Result<Field, SnapshotError> {
+        snapshot::decode(bytes).map(|(field, _)| field)
+    }
+    /// Sets the cells from a list returned by `diff`, cells outside of the field are ignored
+    pub fn apply_diff(&mut self, diff: &[(usize, usize, CellValue)]) {
+        for &(row, col, value) in diff {
+            self.set_by_coords(row, col, value);
+        }
+    }
+    /// Field with the text written in live cells with the 5x7 font, one glyph per character and one row of glyphs per line.
+    /// The glyphs are one cell apart and there is a margin of dead cells around them, so that the text can evolve freely at first
+    pub fn from_text(text: &str) -> Field {
+        const SPACING: usize = 1;
+        const MARGIN: usize = 4;
+        let lines: Vec<Vec<char>> = text.lines().map(|line| line.chars().collect()).collect();
+        let columns = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+        let text_width = (columns * (font::GLYPH_WIDTH + SPACING)).saturating_sub(SPACING);
+        let text_height = (lines.len() * (font::GLYPH_HEIGHT + SPACING)).saturating_sub(SPACING);
+        let width = NonZeroUsize::new(text_width + (2 * MARGIN)).unwrap();
+        let height = NonZeroUsize::new(text_height + (2 * MARGIN)).unwrap();
+        let mut field = Field::new(width, height);
+        for (line_index, line) in lines.iter().enumerate() {
+            let top = MARGIN + (line_index * (font::GLYPH_HEIGHT + SPACING));
+            for (char_index, &char) in line.iter().enumerate() {
+                let left = MARGIN + (char_index * (font::GLYPH_WIDTH + SPACING));
+                for (row, pixels) in font::glyph(char).iter().enumerate() {
+                    for (col, _) in pixels.chars().enumerate().filter(|&(_, pixel)| pixel == '#') {
+                        field.set_by_coords(top + row, left + col, CellValue::Alive);
+                    }
+                }
+            }
+        }
+        field
+    }
+    /// New field turned a quarter clockwise, the width and the height are swapped. The rule, neighborhood and topology are kept
+    pub fn rotated_clockwise(&self) -> Field {
+        let height = self.height.get();
+        self.transformed(self.height, self.width, |row, col| (height - 1 - col, row))
+    }
+    /// New field mirrored left to right. The rule, neighborhood and topology are kept
+    pub fn flipped_horizontally(&self) -> Field {
+        let width = self.width.get();
+        self.transformed(self.width, self.height, |row, col| (row, width - 1 - col))
+    }
+    /// New field of the given size, where each cell is taken from the cell of `self` at the coordinates returned by `source`
+    fn transformed(&self, width: NonZeroUsize, height: NonZeroUsize, source: impl Fn(usize, usize) -> (usize, usize)) -> Field {
+        let mut res = Field::new(width, height)
+            .with_rule(self.rule)
+            .with_neighborhood(self.neighborhood)
+            .with_topology(self.topology)
+            .with_meta(self.meta.clone());
+        for row in 0..height.get() {
+            for col in 0..width.get() {
+                let (source_row, source_col) = source(row, col);
+                let index = res.coords_to_index_unchecked(row, col);
+                res.cells[index] = self.cells[self.coords_to_index_unchecked(source_row, source_col)];
+            }
+        }
+        res
+    }
+    fn calc_new_value(rule: Rule, old_value: CellValue, live_neighbours: u8) -> CellValue {
         match old_value {
-            CellValue::Alive => if (live_neighbours == 2) || (live_neighbours == 3) {
+            CellValue::Alive => if rule.is_survival(live_neighbours) {
                 CellValue::Alive
             } else {
                 CellValue::Dead
             },
-            CellValue::Dead => if live_neighbours == 3 {
+            CellValue::Dead => if rule.is_birth(live_neighbours) {
                 CellValue::Alive
             } else {
                 CellValue::Dead
             }
         }
     }
-    pub fn from_str(str: &str) -> Result<Self, ParseError> {
-        let str = str.trim();
-        if str.is_empty() {
-            return Err(ParseError::EmptyString);
-        };
-        let mut lines = Vec::new();
-        let mut width = None;
-        for str_line in str.lines() {
-            let line = Self::from_str_line(str_line, width)?;
-            let line_width = line.len();
-            let expected_width = *width.get_or_insert(line_width);
-            if line_width != expected_width {
-                return Err(ParseError::WidthMismatch);
-            }
-            lines.push(line);
-        }
-        let height = lines.len();
-        let cells = lines.concat();
+}
+
+/// Interface for JS code, which can read the cells directly from wasm memory
+#[cfg(feature = "browser")]
+#[wasm_bindgen]
+impl Field {
+    #[wasm_bindgen(constructor)]
+    pub fn new_js(width: usize, height: usize) -> Self {
+        Self::new(width.max(1).try_into().unwrap(), height.max(1).try_into().unwrap())
+    }
+    #[wasm_bindgen(js_name = width)]
+    pub fn width_js(&self) -> usize {
+        self.width.get()
+    }
+    #[wasm_bindgen(js_name = height)]
+    pub fn height_js(&self) -> usize {
+        self.height.get()
+    }
+    #[wasm_bindgen(js_name = update)]
+    pub fn update_js(&mut self) -> bool {
+        self.update()
+    }
+    #[wasm_bindgen(js_name = toggle)]
+    pub fn toggle_js(&mut self, row: usize, col: usize) -> bool {
+        self.toggle_by_coords(row, col).is_some()
+    }
+    #[wasm_bindgen(js_name = cells_ptr)]
+    pub fn cells_ptr_js(&self) -> *const u8 {
+        self.cells_ptr()
+    }
+    #[wasm_bindgen(js_name = cells_len)]
+    pub fn cells_len_js(&self) -> usize {
+        self.cells_len()
+    }
+    /// View of the cells without copying. It becomes invalid after the next update,
+    /// and when the wasm memory grows, so it has to be requested again every frame
+    pub fn cells_view(&self) -> js_sys::Uint8Array {
+        // SAFETY: CellValue is repr(u8), and the view is documented to be short-lived
+        unsafe { js_sys::Uint8Array::view(std::slice::from_raw_parts(self.cells_ptr(), self.cells_len())) }
+    }
+}
+
+impl FromStr for Field {
+    type Err = ParseError;
+    fn from_str(str: &str) -> Result<Self, ParseError> {
+        let (width, height, cells) = parse_cells(str)?;
         let cells_len = cells.len();
         let res = Self {
-            width: width.unwrap().try_into().unwrap(),
-            height: height.try_into().unwrap(),
+            width,
+            height,
+            neighborhood: Neighborhood::Moore,
+            topology: Topology::Torus,
+            rule: Rule::LIFE,
             cells,
             swap_cells: vec![CellValue::Dead; cells_len],
+            changed: Vec::new(),
+            meta: PatternMeta::default(),
+            noise: None,
         };
         Ok(res)
     }
-    fn from_str_line(str: &str, expected_width: Option<usize>) -> Result<Vec<CellValue>, ParseError> {
-        let mut vec = Vec::with_capacity(expected_width.unwrap_or(0));
-        for char in str.trim().chars() {
-            let val = if char == '#' {
-                CellValue::Alive
-            } else if char == '_' {
-                CellValue::Dead
-            } else {
-                return Err(ParseError::UnknownChar);
-            };
-            vec.push(val);
+}
+
+/// Serialized form of `Field`. The cells are packed four per hex digit in row-major order, the first cell in the highest bit,
+/// the last digit is padded with dead cells
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct FieldData {
+    width: usize,
+    height: usize,
+    rule: String,
+    #[serde(default)]
+    neighborhood: Neighborhood,
+    /// Left out for the torus, like in the data written before there were other topologies
+    #[serde(default, skip_serializing_if = "Topology::is_torus")]
+    topology: Topology,
+    cells: String,
+}
+#[cfg(feature = "serde")]
+impl From<Field> for FieldData {
+    fn from(field: Field) -> Self {
+        let cells = field.cells.chunks(4)
+            .map(|chunk| {
+                let digit = chunk.iter().enumerate().fold(0, |digit, (index, &value)| digit | ((value as u32) << (3 - index)));
+                char::from_digit(digit, 16).unwrap()
+            })
+            .collect();
+        Self { width: field.get_width(), height: field.get_height(), rule: field.rule.to_string(), neighborhood: field.neighborhood, topology: field.topology, cells }
+    }
+}
+#[cfg(feature = "serde")]
+impl TryFrom<FieldData> for Field {
+    type Error = ParseError;
+    fn try_from(data: FieldData) -> Result<Self, ParseError> {
+        let (Some(width), Some(height)) = (NonZeroUsize::new(data.width), NonZeroUsize::new(data.height)) else {
+            return Err(ParseError::InvalidHeader);
+        };
+        // fields also come from replays of other users, a bigger size would only exhaust the memory
+        if width.get().max(height.get()) > MAX_SIDE {
+            return Err(ParseError::InvalidHeader);
         }
-        Ok(vec)
+        let cell_count = width.get().checked_mul(height.get()).ok_or(ParseError::InvalidHeader)?;
+        if data.cells.len() != cell_count.div_ceil(4) {
+            return Err(ParseError::WidthMismatch);
+        }
+        let mut cells = Vec::with_capacity(data.cells.len() * 4);
+        for char in data.cells.chars() {
+            let digit = char.to_digit(16).ok_or(ParseError::UnknownChar)?;
+            cells.extend((0..4).map(|index| if (digit >> (3 - index)) & 1 == 1 { CellValue::Alive } else { CellValue::Dead }));
+        }
+        cells.truncate(cell_count);
+        let mut field = Field::new(width, height).with_rule(data.rule.parse()?).with_neighborhood(data.neighborhood).with_topology(data.topology);
+        field.cells = cells;
+        Ok(field)
     }
 }
 
@@ -227,16 +807,107 @@ pub enum ParseError {
     EmptyString,
     UnknownChar,
     WidthMismatch,
+    InvalidRule,
+    /// The size of the pattern is missing or invalid
+    InvalidHeader,
+}
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            Self::EmptyString => "empty string",
+            Self::UnknownChar => "unknown character",
+            Self::WidthMismatch => "the size doesn't match the cells",
+            Self::InvalidRule => "invalid rule",
+            Self::InvalidHeader => "invalid size",
+        };
+        write!(f, "{message}")
+    }
 }
 
+impl CellState for CellValue {
+    const PALETTE: &'static [&'static str] = &["#FFFFFF", "#000000"];
+    const NAMES: &'static [&'static str] = &["Dead", "Alive"];
+    fn index(self) -> u8 {
+        self as u8
+    }
+    fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(CellValue::Dead),
+            1 => Some(CellValue::Alive),
+            _ => None,
+        }
+    }
+    fn next_in_edit_cycle(self) -> Self {
+        self.other()
+    }
+    fn to_char(self) -> char {
+        if self == CellValue::Alive { '#' } else { '_' }
+    }
+    fn from_char(char: char) -> Option<Self> {
+        match char {
+            '#' => Some(CellValue::Alive),
+            '_' => Some(CellValue::Dead),
+            _ => None,
+        }
+    }
+}
+
+impl Automaton for Field {
+    type State = CellValue;
+    fn get_width(&self) -> usize {
+        self.width.get()
+    }
+    fn get_height(&self) -> usize {
+        self.height.get()
+    }
+    fn view(&self) -> &[CellValue] {
+        &self.cells
+    }
+    fn view_old(&self) -> &[CellValue] {
+        &self.swap_cells
+    }
+    fn step(&mut self) -> bool {
+        self.update()
+    }
+    fn set(&mut self, row: usize, col: usize, value: CellValue) -> Option<()> {
+        self.set_by_coords(row, col, value)
+    }
+    fn toggle(&mut self, row: usize, col: usize) -> Option<()> {
+        self.toggle_by_coords(row, col)
+    }
+    fn parse(str: &str) -> Result<Self, ParseError> {
+        str.parse()
+    }
+    fn rule(&self) -> Option<String> {
+        Some(self.rule.to_string())
+    }
+    fn set_rule(&mut self, rule: &str) -> Result<(), ParseError> {
+        self.rule = rule.parse()?;
+        Ok(())
+    }
+    fn meta(&self) -> Option<&PatternMeta> {
+        Some(&self.meta)
+    }
+    fn changed_cells(&self) -> Option<&[usize]> {
+        Some(&self.changed)
+    }
+    fn preview_next(&mut self) -> Option<&[CellValue]> {
+        Some(self.preview())
+    }
+    fn neighbour_counts(&self) -> Option<Vec<u8>> {
+        Some(self.live_neighbour_counts())
+    }
+    fn to_svg(&self, cell_size: u32, theme: &Theme) -> Option<String> {
+        Some(Field::to_svg(self, cell_size, theme))
+    }
+}
 
 impl Display for Field {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let width = self.width.get();
         for row in self.cells.chunks(width) {
             for &value in row.iter() {
-                let char = if value == CellValue::Alive { '#' } else { '_' };
-                f.write_char(char)?;
+                f.write_char(value.to_char())?;
             }
             f.write_char('\n')?;
         }
@@ -248,6 +919,104 @@ impl Display for Field {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::automaton::Activity;
+    #[test]
+    fn test_fill_from_fn() {
+        let size = NonZeroUsize::new(8).unwrap();
+        let meta = PatternMeta { name: Some("Gosper glider gun".to_string()), ..PatternMeta::default() };
+        let mut field = Field::generate_by_fn(size, size, |i| i % 3 == 0).with_rule(Rule::HIGH_LIFE).with_meta(meta);
+        field.step();
+        let cells_ptr = field.cells.as_ptr();
+        field.fill_from_fn(size, size, |i| i % 2 == 0);
+        assert_eq!(cells_ptr, field.cells.as_ptr());
+        assert_eq!(Field::generate_by_fn(size, size, |i| i % 2 == 0).to_string(), field.to_string());
+        assert_eq!(Rule::HIGH_LIFE, field.get_rule());
+        assert!(field.changed.is_empty());
+        assert!(field.get_meta().is_empty());
+
+        let smaller = NonZeroUsize::new(4).unwrap();
+        field.fill_from_fn(smaller, smaller, |_| true);
+        assert_eq!(cells_ptr, field.cells.as_ptr());
+        assert_eq!(16, field.view().len());
+    }
+    #[test]
+    fn test_apply_brush() {
+        let mut field = Field::new(NonZeroUsize::new(4).unwrap(), NonZeroUsize::new(3).unwrap());
+        field.apply_brush(0, 0, Brush::Square(3), CellValue::Alive);
+        field.apply_brush(2, 0, Brush::Line(2, 3), CellValue::Alive);
+        field.apply_brush(2, 1, Brush::Square(1), CellValue::Dead);
+        assert_eq!("##__\n##__\n#_##\n", field.to_string());
+    }
+    #[test]
+    fn test_transforms() {
+        let field = Field::from_str("_#_\n__#\n###\n_#_").unwrap().with_rule(Rule::HIGH_LIFE);
+        let rotated = field.rotated_clockwise();
+        assert_eq!("_#__\n##_#\n_##_\n", rotated.to_string());
+        assert_eq!(Rule::HIGH_LIFE, rotated.get_rule());
+        assert_eq!(field.to_string(), rotated.rotated_clockwise().rotated_clockwise().rotated_clockwise().to_string());
+        assert_eq!("_#_\n#__\n###\n_#_\n", field.flipped_horizontally().to_string());
+        assert_eq!(field.to_string(), field.flipped_horizontally().flipped_horizontally().to_string());
+    }
+    #[test]
+    fn test_from_text() {
+        let field = Field::from_text("Hi\n!");
+        assert_eq!((4 + 11 + 4, 4 + 15 + 4), (field.get_width(), field.get_height()));
+        let text = field.to_string();
+        let rows: Vec<&str> = text.lines().collect();
+        // the H, a column of space and the top of the I
+        assert_eq!("____#___#__###_____", rows[4]);
+        assert_eq!("______#____________", rows[12]);
+        assert!(rows[..4].iter().chain(&rows[19..]).all(|row| !row.contains('#')));
+
+        let empty = Field::from_text("");
+        assert_eq!((8, 8), (empty.get_width(), empty.get_height()));
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let field = Field::from_str("_##_\n#_#_\n___#").unwrap().with_rule(Rule::HIGH_LIFE).with_neighborhood(Neighborhood::VonNeumann);
+        let json = serde_json::to_string(&field).unwrap();
+        assert_eq!(r#"{"width":4,"height":3,"rule":"B36/S23","neighborhood":"vonNeumann","cells":"6a1"}"#, json);
+        let parsed: Field = serde_json::from_str(&json).unwrap();
+        assert_eq!(field.to_string(), parsed.to_string());
+        assert_eq!(Rule::HIGH_LIFE, parsed.get_rule());
+        assert_eq!(Neighborhood::VonNeumann, parsed.get_neighborhood());
+        assert_eq!(Topology::Torus, parsed.get_topology());
+        let twisted = field.clone().with_topology(Topology::ProjectivePlane);
+        let json = serde_json::to_string(&twisted).unwrap();
+        assert!(json.contains(r#""topology":"projectivePlane""#), "{json}");
+        assert_eq!(Topology::ProjectivePlane, serde_json::from_str::<Field>(&json).unwrap().get_topology());
+
+        let parsed: Field = serde_json::from_str(r#"{"width":2,"height":1,"rule":"B3/S23","cells":"8"}"#).unwrap();
+        assert_eq!("#_\n", parsed.to_string());
+        assert_eq!(Neighborhood::Moore, parsed.get_neighborhood());
+        for invalid in [
+            r#"{"width":2,"height":1,"rule":"B3/S23","cells":"80"}"#,
+            r#"{"width":0,"height":1,"rule":"B3/S23","cells":""}"#,
+            r#"{"width":2,"height":1,"rule":"B3/S23","cells":"x"}"#,
+            r#"{"width":2,"height":1,"rule":"X","cells":"8"}"#,
+            r#"{"width":10000000000,"height":10000000000,"rule":"B3/S23","cells":""}"#,
+            r#"{"width":8193,"height":1,"rule":"B3/S23","cells":""}"#,
+        ] {
+            assert!(serde_json::from_str::<Field>(invalid).is_err(), "{invalid}");
+        }
+    }
+    fn activity(field: &Field) -> Option<Activity> {
+        crate::automaton::DynAutomaton::activity(field)
+    }
+    #[test]
+    fn test_activity() {
+        let mut field = Field::from_str("_____\n_____\n_###_\n_____\n_____").unwrap();
+        field.step();
+        assert_eq!(Some(Activity { births: 2, deaths: 2 }), activity(&field));
+        assert_eq!(4, activity(&field).unwrap().total());
+
+        let mut field = Field::from_str("_____\n_#___\n_____\n___#_\n_____").unwrap();
+        field.step();
+        assert_eq!(Some(Activity { births: 0, deaths: 2 }), activity(&field));
+        field.step();
+        assert_eq!(Some(Activity::default()), activity(&field));
+    }
     #[test]
     fn test_string_parse() {
         let field_str =
@@ -263,6 +1032,14 @@ __#__
         assert_eq!(CellValue::Alive, field.get_by_coords(1, 1).unwrap());
     }
     #[test]
+    fn test_count_live_neighbours() {
+        let field = Field::from_str("_##_#\n#__##\n##_#_\n_#___").unwrap();
+        for index in 0..20 {
+            let (row, col) = (index / 5, index % 5);
+            assert_eq!(field.count_live_neighbours_slow(row, col), field.count_live_neighbours(row, col, 3, 4));
+        }
+    }
+    #[test]
     fn test_glider() {
         let init_state = "
 __#____
@@ -310,5 +1087,203 @@ _______
 ";
         assert_eq!(expected_state.trim(), field.to_string().trim());
     }
-}
+    #[test]
+    fn test_neighborhood_counts() {
+        let field_str = "
+#_#_#
+_###_
+#####
+_###_
+#_#_#
+";
+        let mut field = Field::from_str(field_str).unwrap();
+        assert_eq!(8, field.count_live_neighbours(2, 2, 4, 4));
+        field.set_neighborhood(Neighborhood::VonNeumann);
+        assert_eq!(4, field.count_live_neighbours_by_offsets(2, 2));
+        field.set_neighborhood(Neighborhood::ExtendedMoore);
+        assert_eq!(16, field.count_live_neighbours_by_offsets(2, 2));
+        // wraps around the edges
+        field.set_neighborhood(Neighborhood::VonNeumann);
+        assert_eq!(2, field.count_live_neighbours_by_offsets(0, 0));
+    }
+    #[test]
+    fn test_noise() {
+        let size = NonZeroUsize::new(16).unwrap();
+        let mut full = Field::new(size, size);
+        full.set_noise(1.0, 3);
+        full.update();
+        assert!(full.view().iter().all(|&value| value == CellValue::Alive));
+        assert_eq!(256, full.changed().len());
+
+        // the same seed gives the same run, previews don't change it
+        let mut field = Field::from_str("_____\n_____\n_###_\n_____\n_____").unwrap();
+        field.set_noise(0.05, 42);
+        let mut same = field.clone();
+        let mut other_seed = field.clone();
+        other_seed.set_noise(0.05, 43);
+        for _ in 0..20 {
+            same.preview();
+            field.update();
+            same.update();
+            other_seed.update();
+        }
+        assert_eq!(field.to_string(), same.to_string());
+        assert_ne!(field.to_string(), other_seed.to_string());
+        assert_eq!(0.05, field.get_noise());
+        field.set_noise(0.0, 42);
+        assert_eq!(0.0, field.get_noise());
+    }
+    #[test]
+    fn test_topologies() {
+        // 4 wide and 3 high
+        let neighbour = |topology: Topology, row, col, offset| topology.neighbour(row, col, offset, 4, 3);
+        assert_eq!(Some((1, 2)), neighbour(Topology::Plane, 0, 1, (1, 1)));
+        assert_eq!(Some((2, 1)), neighbour(Topology::Torus, 0, 1, (-1, 0)));
+        assert_eq!(None, neighbour(Topology::Plane, 0, 1, (-1, 0)));
+        // the top edge is joined to the bottom one mirrored, the left edge to the right one as on the torus
+        assert_eq!(Some((2, 2)), neighbour(Topology::KleinBottle, 0, 1, (-1, 0)));
+        assert_eq!(Some((1, 3)), neighbour(Topology::KleinBottle, 1, 0, (0, -1)));
+        assert_eq!(Some((2, 0)), neighbour(Topology::KleinBottle, 0, 0, (-1, -1)));
+        assert_eq!(Some((2, 2)), neighbour(Topology::ProjectivePlane, 0, 1, (-1, 0)));
+        assert_eq!(Some((1, 3)), neighbour(Topology::ProjectivePlane, 0, 0, (1, -1)));
+        assert_eq!(None, neighbour(Topology::ProjectivePlane, 0, 0, (-1, -1)));
+        // crossing twice flips twice
+        assert_eq!(Some((0, 1)), neighbour(Topology::KleinBottle, 0, 1, (6, 0)));
 
+        // a glider leaving at the top comes back flipped, moving the other way
+        let glider = "______\n______\n______\n______\n_#____\n#_____\n###___\n";
+        let mut torus = Field::from_str(glider).unwrap();
+        let mut klein = Field::from_str(glider).unwrap().with_topology(Topology::KleinBottle);
+        let mut plane = Field::from_str(glider).unwrap().with_topology(Topology::Plane);
+        for _ in 0..12 {
+            torus.update();
+            klein.update();
+            plane.update();
+        }
+        assert_eq!(5, torus.view().iter().filter(|&&value| value == CellValue::Alive).count());
+        assert_eq!(torus.flipped_horizontally().to_string(), klein.to_string());
+        // the glider turns into a block in the corner of the plane
+        assert_eq!(4, plane.view().iter().filter(|&&value| value == CellValue::Alive).count());
+    }
+    #[test]
+    fn test_von_neumann_update() {
+        let init_state = "
+_____
+_____
+_###_
+_____
+_____
+";
+        let mut field = Field::from_str(init_state).unwrap().with_neighborhood(Neighborhood::VonNeumann);
+        field.update();
+        // only the center has 2 neighbours, the ends have 1, and no dead cell has 3
+        let expected_state = "
+_____
+_____
+__#__
+_____
+_____
+";
+        assert_eq!(expected_state.trim(), field.to_string().trim());
+    }
+    #[test]
+    fn test_rule_parse() {
+        assert_eq!(Rule::LIFE, Rule::from_str("B3/S23").unwrap());
+        assert_eq!(Rule::LIFE, Rule::from_str("b3/s23").unwrap());
+        assert_eq!(Rule::LIFE, Rule::from_str("23/3").unwrap());
+        assert_eq!(Rule::SEEDS, Rule::from_str("B2/S").unwrap());
+        assert_eq!("B3678/S34678", Rule::DAY_AND_NIGHT.to_string());
+        assert_eq!("B3/S12345", Rule::MAZE.to_string());
+        assert_eq!("B3/S012345678", Rule::LIFE_WITHOUT_DEATH.to_string());
+        assert_eq!(Err(ParseError::InvalidRule), Rule::from_str("B9/S23"));
+        assert_eq!(Err(ParseError::InvalidRule), Rule::from_str("B3S23"));
+    }
+    #[test]
+    fn test_seeds() {
+        let init_state = "
+______
+______
+__##__
+______
+______
+";
+        let mut field = Field::from_str(init_state).unwrap().with_rule(Rule::SEEDS);
+        field.update();
+        let expected_state = "
+______
+__##__
+______
+__##__
+______
+";
+        assert_eq!(expected_state.trim(), field.to_string().trim());
+    }
+    #[test]
+    fn test_combine() {
+        let left = Field::from_str("##__\n#_#_").unwrap();
+        let right = Field::from_str("#_#_\n##__").unwrap();
+        assert_eq!("#___\n#___\n", left.combine(&right, CombineOp::And).unwrap().to_string());
+        assert_eq!("###_\n###_\n", left.combine(&right, CombineOp::Or).unwrap().to_string());
+        assert_eq!("_##_\n_##_\n", left.combine(&right, CombineOp::Xor).unwrap().to_string());
+
+        let mut in_place = left.clone();
+        in_place.combine_with(&right, CombineOp::Xor).unwrap();
+        assert_eq!("_##_\n_##_\n", in_place.to_string());
+
+        let small = Field::from_str("##\n#_").unwrap();
+        assert_eq!(Err(SizeMismatch), left.combine(&small, CombineOp::Or).map(|_| ()));
+    }
+    #[test]
+    fn test_diff() {
+        let old = Field::from_str("##__\n#_#_").unwrap();
+        let new = Field::from_str("#_#_\n#_#_").unwrap();
+        let diff = old.diff(&new);
+        assert_eq!(vec![(0, 1, CellValue::Dead), (0, 2, CellValue::Alive)], diff);
+        assert!(new.diff(&new).is_empty());
+
+        let mut patched = old.clone();
+        patched.apply_diff(&diff);
+        assert_eq!(new.to_string(), patched.to_string());
+    }
+    #[test]
+    fn test_changed_cells() {
+        let mut field = Field::from_str("_____\n_____\n_###_\n_____\n_____").unwrap();
+        assert!(field.changed().is_empty());
+        field.update();
+        assert_eq!(&[7, 11, 13, 17], field.changed());
+    }
+    #[test]
+    fn test_cells_ptr() {
+        let field = Field::from_str("#_\n_#").unwrap();
+        // SAFETY: the field is not changed while the slice is alive
+        let bytes = unsafe { std::slice::from_raw_parts(field.cells_ptr(), field.cells_len()) };
+        assert_eq!(&[1, 0, 0, 1], bytes);
+    }
+    #[test]
+    fn test_preview() {
+        let mut field = Field::from_str("_____\n_____\n_###_\n_____\n_____").unwrap();
+        let before = field.to_string();
+        let preview = field.preview().to_vec();
+        assert_eq!(before, field.to_string());
+        field.update();
+        assert_eq!(preview, field.view());
+    }
+    #[test]
+    fn test_live_neighbour_counts() {
+        let field = Field::from_str("___\n_##\n___").unwrap();
+        assert_eq!(vec![2, 2, 2, 2, 1, 1, 2, 2, 2], field.live_neighbour_counts());
+    }
+    #[test]
+    fn test_to_svg() {
+        let field = Field::from_str("#_\n_#").unwrap();
+        let theme = Theme { grid: None, ..Theme::light() };
+        let expected = concat!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20" viewBox="0 0 20 20">"##,
+            r##"<rect width="20" height="20" fill="#FFFFFF"/>"##,
+            r##"<path d="M0 0h10v10h-10zM10 10h10v10h-10z" fill="#000000"/>"##,
+            "</svg>",
+        );
+        assert_eq!(expected, field.to_svg(10, &theme));
+        assert!(field.to_svg(10, &Theme::light()).contains(r##"<path d="M0 0V20M10 0V20M20 0V20M0 0H20M0 10H20M0 20H20" stroke="#CCCCCC""##));
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+const MAX_CODE_SIZE: u8 = 12;
+
+/// Minimal encoder of animated GIFs with a global palette and one byte per pixel
+#[derive(Debug)]
+pub struct GifEncoder {
+    width: u16,
+    height: u16,
+    /// Number of bits per pixel, the palette has `2^color_bits` entries
+    color_bits: u8,
+    /// Delay between frames in hundredths of a second
+    delay_cs: u16,
+    bytes: Vec<u8>,
+}
+
+impl GifEncoder {
+    /// Starts an endlessly looping animation. Colors are RGB, at most 256 of them
+    pub fn new(width: u16, height: u16, palette: &[[u8; 3]], delay_cs: u16) -> Self {
+        assert!(!palette.is_empty() && palette.len() <= 256, "palette must have from 1 to 256 colors");
+        // at least 2 bits, the minimum LZW code size for GIF
+        let mut color_bits = 2;
+        while (1 << color_bits) < palette.len() {
+            color_bits += 1;
+        }
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"GIF89a");
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        // global color table, 8 bit color resolution, table size
+        bytes.push(0x80 | (0x07 << 4) | (color_bits - 1));
+        // background color index and pixel aspect ratio
+        bytes.extend_from_slice(&[0, 0]);
+        for index in 0..(1 << color_bits) {
+            bytes.extend_from_slice(palette.get(index).unwrap_or(&[0, 0, 0]));
+        }
+        // NETSCAPE2.0 extension for looping forever
+        bytes.extend_from_slice(&[0x21, 0xFF, 0x0B]);
+        bytes.extend_from_slice(b"NETSCAPE2.0");
+        bytes.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+        Self { width, height, color_bits, delay_cs, bytes }
+    }
+    /// Adds a frame, the pixels are palette indexes in row-major order
+    pub fn add_frame(&mut self, pixels: &[u8]) {
+        assert_eq!(self.width as usize * self.height as usize, pixels.len(), "frame size mismatch");
+        // graphic control extension with the delay
+        self.bytes.extend_from_slice(&[0x21, 0xF9, 0x04, 0x00]);
+        self.bytes.extend_from_slice(&self.delay_cs.to_le_bytes());
+        self.bytes.extend_from_slice(&[0x00, 0x00]);
+        // image descriptor covering the whole screen
+        self.bytes.push(0x2C);
+        self.bytes.extend_from_slice(&[0, 0, 0, 0]);
+        self.bytes.extend_from_slice(&self.width.to_le_bytes());
+        self.bytes.extend_from_slice(&self.height.to_le_bytes());
+        self.bytes.push(0x00);
+
+        self.bytes.push(self.color_bits);
+        let data = lzw_encode(pixels, self.color_bits);
+        for block in data.chunks(255) {
+            self.bytes.push(block.len() as u8);
+            self.bytes.extend_from_slice(block);
+        }
+        self.bytes.push(0x00);
+    }
+    pub fn finish(mut self) -> Vec<u8> {
+        self.bytes.push(0x3B);
+        self.bytes
+    }
+}
+
+/// Writes codes of variable size, least significant bit first
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    buffer_bits: u8,
+}
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= (code as u32) << self.buffer_bits;
+        self.buffer_bits += size;
+        while self.buffer_bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.buffer_bits -= 8;
+        }
+    }
+    fn finish(mut self) -> Vec<u8> {
+        if self.buffer_bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Variable length LZW compression as used by GIF
+fn lzw_encode(pixels: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear_code = 1u16 << min_code_size;
+    let end_code = clear_code + 1;
+    let mut writer = BitWriter { bytes: Vec::new(), buffer: 0, buffer_bits: 0 };
+    let mut code_size = min_code_size + 1;
+    let mut next_code = end_code + 1;
+    let mut dictionary = HashMap::<(u16, u8), u16>::new();
+    writer.write(clear_code, code_size);
+    let Some((&first, rest)) = pixels.split_first() else {
+        writer.write(end_code, code_size);
+        return writer.finish();
+    };
+    let mut prefix = first as u16;
+    for &pixel in rest {
+        if let Some(&code) = dictionary.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+        writer.write(prefix, code_size);
+        if next_code < (1 << MAX_CODE_SIZE) {
+            dictionary.insert((prefix, pixel), next_code);
+            next_code += 1;
+            // the decoder adds its entries one code later, so it switches to the bigger size after this code
+            if (next_code > (1 << code_size)) && (code_size < MAX_CODE_SIZE) {
+                code_size += 1;
+            }
+        } else {
+            writer.write(clear_code, code_size);
+            dictionary.clear();
+            code_size = min_code_size + 1;
+            next_code = end_code + 1;
+        }
+        prefix = pixel as u16;
+    }
+    writer.write(prefix, code_size);
+    writer.write(end_code, code_size);
+    writer.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear_code = 1usize << min_code_size;
+        let end_code = clear_code + 1;
+        let initial = || (0..clear_code).map(|value| vec![value as u8]).chain([vec![], vec![]]).collect::<Vec<_>>();
+        let mut dictionary = initial();
+        let mut code_size = min_code_size + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        let mut res = Vec::new();
+        let mut bit_pos = 0;
+        loop {
+            let mut code = 0;
+            for bit in 0..code_size as usize {
+                let byte = data[(bit_pos + bit) / 8];
+                code |= (((byte >> ((bit_pos + bit) % 8)) & 1) as usize) << bit;
+            }
+            bit_pos += code_size as usize;
+            if code == clear_code {
+                dictionary = initial();
+                code_size = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == end_code {
+                return res;
+            }
+            let entry = match (dictionary.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => [previous.as_slice(), &previous[..1]].concat(),
+                (None, None) => panic!("invalid code"),
+            };
+            res.extend_from_slice(&entry);
+            if let Some(previous) = previous {
+                if dictionary.len() < 4096 {
+                    dictionary.push([previous.as_slice(), &entry[..1]].concat());
+                }
+            }
+            if (dictionary.len() == (1 << code_size)) && (code_size < MAX_CODE_SIZE) {
+                code_size += 1;
+            }
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn test_lzw_round_trip() {
+        let mut random = crate::random::XorShift::new(7);
+        let noisy = (0..20_000).map(|_| random.next_below(4) as u8).collect::<Vec<_>>();
+        let repetitive = (0..5_000).map(|i| ((i / 7) % 3) as u8).collect::<Vec<_>>();
+        for pixels in [vec![], vec![1], noisy, repetitive] {
+            assert_eq!(pixels, lzw_decode(&lzw_encode(&pixels, 2), 2));
+        }
+    }
+    #[test]
+    fn test_gif_structure() {
+        let mut encoder = GifEncoder::new(2, 1, &[[255, 255, 255], [0, 0, 0]], 10);
+        encoder.add_frame(&[0, 1]);
+        let bytes = encoder.finish();
+        assert_eq!(b"GIF89a", &bytes[..6]);
+        assert_eq!(0x81 | 0x70, bytes[10]);
+        assert_eq!(0x3B, *bytes.last().unwrap());
+    }
+}
@@ -0,0 +1,181 @@
+use std::fmt::{Display, Formatter, Write};
+use std::mem;
+use std::num::NonZeroUsize;
+use crate::automaton::{next_coord_wrapped, parse_cells, prev_coord_wrapped, Automaton, CellState, Layout};
+use crate::game_of_life::{CellValue, ParseError};
+
+/// Birth and survival conditions for a hex grid, bit `n` is set if the rule applies for `n` live neighbours
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HexRule {
+    pub birth: u8,
+    pub survival: u8,
+}
+impl HexRule {
+    /// H:B2/S34, the most popular hexagonal Life rule
+    pub const B2_S34: HexRule = HexRule {
+        birth: 1 << 2,
+        survival: (1 << 3) | (1 << 4),
+    };
+}
+
+/// Life on a hexagonal grid, where each cell has 6 neighbours.
+/// Uses odd-r offset coordinates: odd rows are shifted to the right by half a cell.
+/// Height should be even, otherwise the neighbourhood breaks where the field wraps vertically.
+#[derive(Clone)]
+pub struct HexField {
+    width: NonZeroUsize,
+    height: NonZeroUsize,
+    rule: HexRule,
+    cells: Vec<CellValue>,
+    swap_cells: Vec<CellValue>,
+}
+
+impl HexField {
+    pub fn generate_by_fn(width: NonZeroUsize, height: NonZeroUsize, rule: HexRule, random_bool: impl Fn(usize) -> bool) -> Self {
+        let cell_count = width.get() * height.get();
+        let cells = (0..cell_count)
+            .map(|i| if random_bool(i) { CellValue::Alive } else { CellValue::Dead })
+            .collect();
+        Self {
+            width,
+            height,
+            rule,
+            cells,
+            swap_cells: vec![CellValue::Dead; cell_count],
+        }
+    }
+    pub fn get_rule(&self) -> HexRule {
+        self.rule
+    }
+    pub fn update(&mut self) -> bool {
+        let width = self.width.get();
+        let mut has_alive = false;
+        for (index, &value) in self.cells.iter().enumerate() {
+            let live_neighbours = self.neighbours(index / width, index % width)
+                .into_iter()
+                .map(|(row, col)| self.cells[(row * width) + col] as u8)
+                .sum::<u8>();
+            let mask = match value {
+                CellValue::Alive => self.rule.survival,
+                CellValue::Dead => self.rule.birth,
+            };
+            let new_value = if (mask >> live_neighbours) & 1 == 1 {
+                CellValue::Alive
+            } else {
+                CellValue::Dead
+            };
+            self.swap_cells[index] = new_value;
+            has_alive = has_alive || (new_value == CellValue::Alive);
+        }
+        mem::swap(&mut self.cells, &mut self.swap_cells);
+        has_alive
+    }
+    /// Coordinates of the 6 neighbours, wrapping around the edges
+    fn neighbours(&self, row: usize, col: usize) -> [(usize, usize); 6] {
+        let max_row = self.height.get() - 1;
+        let max_col = self.width.get() - 1;
+        let row_top = prev_coord_wrapped(row, max_row);
+        let row_bottom = next_coord_wrapped(row, max_row);
+        let col_left = prev_coord_wrapped(col, max_col);
+        let col_right = next_coord_wrapped(col, max_col);
+        // rows above and below touch the cell to the left for even rows, and to the right for odd rows
+        let (diag_left, diag_right) = if row & 1 == 0 { (col_left, col) } else { (col, col_right) };
+        [
+            (row_top, diag_left),
+            (row_top, diag_right),
+            (row, col_left),
+            (row, col_right),
+            (row_bottom, diag_left),
+            (row_bottom, diag_right),
+        ]
+    }
+}
+
+impl Automaton for HexField {
+    type State = CellValue;
+    fn get_width(&self) -> usize {
+        self.width.get()
+    }
+    fn get_height(&self) -> usize {
+        self.height.get()
+    }
+    fn view(&self) -> &[CellValue] {
+        &self.cells
+    }
+    fn view_old(&self) -> &[CellValue] {
+        &self.swap_cells
+    }
+    fn step(&mut self) -> bool {
+        self.update()
+    }
+    fn set(&mut self, row: usize, col: usize, value: CellValue) -> Option<()> {
+        if (row >= self.height.get()) || (col >= self.width.get()) {
+            return None;
+        }
+        self.cells[(row * self.width.get()) + col] = value;
+        Some(())
+    }
+    /// Parses the cells with the B2/S34 rule
+    fn parse(str: &str) -> Result<Self, ParseError> {
+        let (width, height, cells) = parse_cells(str)?;
+        let cells_len = cells.len();
+        let res = Self {
+            width,
+            height,
+            rule: HexRule::B2_S34,
+            cells,
+            swap_cells: vec![CellValue::Dead; cells_len],
+        };
+        Ok(res)
+    }
+    fn layout(&self) -> Layout {
+        Layout::Hex
+    }
+}
+
+impl Display for HexField {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self.width.get();
+        for row in self.cells.chunks(width) {
+            for &value in row.iter() {
+                f.write_char(value.to_char())?;
+            }
+            f.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_neighbours() {
+        let field = HexField::parse("____\n____\n____\n____").unwrap();
+        let mut even = field.neighbours(2, 1);
+        even.sort();
+        assert_eq!([(1, 0), (1, 1), (2, 0), (2, 2), (3, 0), (3, 1)], even);
+        let mut odd = field.neighbours(1, 1);
+        odd.sort();
+        assert_eq!([(0, 1), (0, 2), (1, 0), (1, 2), (2, 1), (2, 2)], odd);
+    }
+    #[test]
+    fn test_b2_s34() {
+        // two touching cells give birth to the two cells that touch both of them, and die themselves
+        let init_state = "
+______
+__##__
+______
+______
+";
+        let mut field = HexField::parse(init_state).unwrap();
+        assert!(field.update());
+        let expected_state = "
+___#__
+______
+___#__
+______
+";
+        assert_eq!(expected_state.trim_start(), field.to_string());
+    }
+}
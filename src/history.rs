@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+use std::fmt;
+use crate::automaton::DynAutomaton;
+
+/// Default number of generations kept by `StateHistory`
+pub const DEFAULT_HISTORY_SIZE: usize = 100;
+/// Cells of all snapshots together, older snapshots of big fields are dropped before the history is full
+pub const MAX_HISTORY_CELLS: usize = 1 << 24;
+
+/// Copy of the field at a generation
+pub struct Snapshot {
+    pub generation: u64,
+    pub field: Box<dyn DynAutomaton>,
+}
+
+/// Copies of the field at the recent generations, so that the user can go back to them.
+/// The generations only increase, a snapshot of an earlier generation starts a new run and replaces the later ones
+pub struct StateHistory {
+    snapshots: VecDeque<Snapshot>,
+    capacity: usize,
+    /// Cells of all snapshots
+    cells: usize,
+}
+impl StateHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self { snapshots: VecDeque::new(), capacity, cells: 0 }
+    }
+    /// Changes the capacity, the oldest snapshots that don't fit anymore are dropped
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+    /// Adds a copy of the field. Snapshots of the same and later generations are dropped,
+    /// e.g. the ones after a generation that the user has gone back to and edited
+    pub fn push(&mut self, generation: u64, field: &dyn DynAutomaton) {
+        self.truncate_from(generation);
+        if self.capacity == 0 {
+            return;
+        }
+        self.cells += cell_count(field);
+        self.snapshots.push_back(Snapshot { generation, field: field.clone_box() });
+        self.evict();
+    }
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.cells = 0;
+    }
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+    /// Snapshots from the oldest one to the newest one
+    pub fn get(&self, index: usize) -> Option<&Snapshot> {
+        self.snapshots.get(index)
+    }
+    pub fn newest(&self) -> Option<&Snapshot> {
+        self.snapshots.back()
+    }
+    /// Index of the snapshot of the generation
+    pub fn position(&self, generation: u64) -> Option<usize> {
+        self.snapshots.binary_search_by_key(&generation, |snapshot| snapshot.generation).ok()
+    }
+    fn truncate_from(&mut self, generation: u64) {
+        while let Some(snapshot) = self.snapshots.back().filter(|snapshot| snapshot.generation >= generation) {
+            self.cells -= cell_count(snapshot.field.as_ref());
+            self.snapshots.pop_back();
+        }
+    }
+    /// Drops the oldest snapshots over the capacity or the cell limit, the newest one is always kept
+    fn evict(&mut self) {
+        while (self.snapshots.len() > self.capacity) || ((self.snapshots.len() > 1) && (self.cells > MAX_HISTORY_CELLS)) {
+            let Some(snapshot) = self.snapshots.pop_front() else {
+                return;
+            };
+            self.cells -= cell_count(snapshot.field.as_ref());
+        }
+    }
+}
+impl fmt::Debug for StateHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StateHistory")
+            .field("generations", &self.snapshots.iter().map(|snapshot| snapshot.generation).collect::<Vec<_>>())
+            .field("capacity", &self.capacity)
+            .field("cells", &self.cells)
+            .finish()
+    }
+}
+
+fn cell_count(field: &dyn DynAutomaton) -> usize {
+    field.get_width() * field.get_height()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::num::NonZeroUsize;
+    use std::str::FromStr;
+    use crate::game_of_life::Field;
+    #[test]
+    fn test_state_history() {
+        let mut field = Field::from_str("_____\n__#__\n__#__\n__#__\n_____").unwrap();
+        let initial = DynAutomaton::serialize(&field);
+        let mut history = StateHistory::new(3);
+        history.push(0, &field);
+        for generation in 1..=4 {
+            field.update();
+            history.push(generation, &field);
+        }
+        assert_eq!(3, history.len());
+        assert_eq!(Some(0), history.position(2));
+        assert_eq!(None, history.position(1));
+        // the blinker is back after 2 generations
+        assert_eq!(initial, history.get(0).unwrap().field.serialize());
+
+        // going back to generation 3 and continuing drops generation 4
+        history.push(3, &field);
+        assert_eq!(2, history.len());
+        assert_eq!(Some(3), history.get(1).map(|snapshot| snapshot.generation));
+
+        history.set_capacity(1);
+        assert_eq!(Some(3), history.get(0).map(|snapshot| snapshot.generation));
+        history.clear();
+        assert!(history.is_empty());
+        history.set_capacity(0);
+        history.push(0, &field);
+        assert!(history.is_empty());
+    }
+    #[test]
+    fn test_cell_limit() {
+        let size = NonZeroUsize::new(2048).unwrap();
+        let big = Field::new(size, size);
+        let mut history = StateHistory::new(10);
+        for generation in 0..5 {
+            history.push(generation, &big);
+        }
+        assert_eq!(4, history.len());
+        assert_eq!(Some(1), history.get(0).map(|snapshot| snapshot.generation));
+    }
+}
@@ -0,0 +1,79 @@
+use std::num::NonZeroUsize;
+use crate::game_of_life::Field;
+
+/// Cells with a luminance below this, between 0 and 1, are alive: logos and text are usually dark on a light background
+pub const DEFAULT_THRESHOLD: f64 = 0.5;
+
+/// Relative luminance of a color with 8-bit channels, between 0 and 1. Transparent pixels count as white
+pub fn luminance([red, green, blue, alpha]: [u8; 4]) -> f64 {
+    let alpha = alpha as f64 / 255.0;
+    let channel = |value: u8| ((value as f64 / 255.0) * alpha) + (1.0 - alpha);
+    (0.2126 * channel(red)) + (0.7152 * channel(green)) + (0.0722 * channel(blue))
+}
+
+/// Field of the given size from an image in RGBA bytes, like the data of a canvas.
+/// Every cell gets the average luminance of the pixels it covers, and it is alive if that is below the threshold.
+/// Images smaller than the field are stretched, a cell then takes the nearest pixel
+pub fn field_from_rgba(pixels: &[u8], image_width: usize, image_height: usize, width: NonZeroUsize, height: NonZeroUsize, threshold: f64) -> Field {
+    assert_eq!(pixels.len(), image_width * image_height * 4, "the pixels don't match the size of the image");
+    let pixel_luminance = |x: usize, y: usize| {
+        let index = ((y * image_width) + x) * 4;
+        luminance([pixels[index], pixels[index + 1], pixels[index + 2], pixels[index + 3]])
+    };
+    // the pixels from `start` to `end` of a cell, with at least one pixel
+    let span = |cell: usize, cells: usize, size: usize| {
+        let start = (cell * size) / cells;
+        let end = (((cell + 1) * size) / cells).max(start + 1).min(size);
+        start.min(size - 1)..end
+    };
+    Field::generate_by_fn(width, height, |index| {
+        if (image_width == 0) || (image_height == 0) {
+            return false;
+        }
+        let xs = span(index % width.get(), width.get(), image_width);
+        let ys = span(index / width.get(), height.get(), image_height);
+        let count = xs.len() * ys.len();
+        let sum: f64 = ys.flat_map(|y| xs.clone().map(move |x| (x, y))).map(|(x, y)| pixel_luminance(x, y)).sum();
+        (sum / count as f64) < threshold
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    fn size(value: usize) -> NonZeroUsize {
+        NonZeroUsize::new(value).unwrap()
+    }
+    /// Image of black and white pixels from a string like the text of a field, `#` for black
+    fn image(rows: &[&str]) -> Vec<u8> {
+        rows.iter()
+            .flat_map(|row| row.chars())
+            .flat_map(|char| if char == '#' { [0, 0, 0, 255] } else { [255, 255, 255, 255] })
+            .collect()
+    }
+    #[test]
+    fn test_luminance() {
+        assert_eq!(0.0, luminance([0, 0, 0, 255]));
+        assert!((luminance([255, 255, 255, 255]) - 1.0).abs() < 1e-9);
+        assert!((luminance([0, 0, 0, 0]) - 1.0).abs() < 1e-9);
+        // green looks much brighter than blue
+        assert!(luminance([0, 255, 0, 255]) > luminance([0, 0, 255, 255]));
+    }
+    #[test]
+    fn test_field_from_rgba() {
+        let pixels = image(&["##__", "##__", "____", "___#"]);
+        let field = field_from_rgba(&pixels, 4, 4, size(4), size(4), DEFAULT_THRESHOLD);
+        assert_eq!("##__\n##__\n____\n___#\n", field.to_string());
+
+        // each cell averages 2x2 pixels, the bottom right one only has a quarter of dark pixels
+        let field = field_from_rgba(&pixels, 4, 4, size(2), size(2), DEFAULT_THRESHOLD);
+        assert_eq!("#_\n__\n", field.to_string());
+
+        // stretched
+        let field = field_from_rgba(&image(&["#_"]), 2, 1, size(4), size(2), DEFAULT_THRESHOLD);
+        assert_eq!("##__\n##__\n", field.to_string());
+
+        let field = field_from_rgba(&[], 0, 0, size(2), size(1), DEFAULT_THRESHOLD);
+        assert_eq!("__\n", field.to_string());
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use std::mem;
+use std::num::NonZeroUsize;
+use crate::automaton::{parse_cells, Automaton};
+use crate::game_of_life::{CellValue, ParseError, Rule};
+
+const TILE_SIZE: usize = 16;
+/// The view grows when live cells get closer than this to its edge
+const VIEW_MARGIN: i64 = 4;
+/// The view stops growing at this size, cells outside of it are still simulated
+const MAX_VIEW_SIZE: usize = 256;
+
+type Tile = [CellValue; TILE_SIZE * TILE_SIZE];
+
+/// Life on an unbounded plane. Cells are stored in square tiles keyed by tile coordinates,
+/// only tiles with live cells are kept. The visible part of the plane grows when live cells approach its edge.
+#[derive(Clone)]
+pub struct InfiniteField {
+    rule: Rule,
+    tiles: HashMap<(i64, i64), Box<Tile>>,
+    /// World coordinates of the top left cell of the view
+    view_row: i64,
+    view_col: i64,
+    view_width: NonZeroUsize,
+    view_height: NonZeroUsize,
+    cells: Vec<CellValue>,
+    old_cells: Vec<CellValue>,
+}
+
+impl InfiniteField {
+    pub fn new(width: NonZeroUsize, height: NonZeroUsize) -> Self {
+        let cell_count = width.get() * height.get();
+        Self {
+            rule: Rule::LIFE,
+            tiles: HashMap::new(),
+            view_row: 0,
+            view_col: 0,
+            view_width: width,
+            view_height: height,
+            cells: vec![CellValue::Dead; cell_count],
+            old_cells: vec![CellValue::Dead; cell_count],
+        }
+    }
+    pub fn get_rule(&self) -> Rule {
+        self.rule
+    }
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+    }
+    /// World coordinates of the top left cell of the view
+    pub fn view_origin(&self) -> (i64, i64) {
+        (self.view_row, self.view_col)
+    }
+    pub fn tile_count(&self) -> usize {
+        self.tiles.len()
+    }
+    pub fn get_by_world_coords(&self, row: i64, col: i64) -> CellValue {
+        let (key, index) = Self::split_coords(row, col);
+        self.tiles.get(&key).map_or(CellValue::Dead, |tile| tile[index])
+    }
+    pub fn set_by_world_coords(&mut self, row: i64, col: i64, value: CellValue) {
+        let (key, index) = Self::split_coords(row, col);
+        match value {
+            CellValue::Alive => {
+                self.tiles.entry(key).or_insert_with(|| Box::new([CellValue::Dead; TILE_SIZE * TILE_SIZE]))[index] = value;
+            }
+            CellValue::Dead => {
+                if let Some(tile) = self.tiles.get_mut(&key) {
+                    tile[index] = value;
+                    if tile.iter().all(|&cell| cell == CellValue::Dead) {
+                        self.tiles.remove(&key);
+                    }
+                }
+            }
+        }
+    }
+    /// World coordinates of all live cells, also the ones outside of the view
+    pub fn live_cells(&self) -> impl Iterator<Item = (i64, i64)> + '_ {
+        let size = TILE_SIZE as i64;
+        self.tiles.iter().flat_map(move |(&(tile_row, tile_col), tile)| {
+            tile.iter().enumerate()
+                .filter(|&(_, &cell)| cell == CellValue::Alive)
+                .map(move |(index, _)| ((tile_row * size) + (index / TILE_SIZE) as i64, (tile_col * size) + (index % TILE_SIZE) as i64))
+        })
+    }
+    /// Moves the view to the live cells set with `set_by_world_coords`, as far as its size allows
+    pub fn fit_view(&mut self) {
+        self.grow_view();
+        let mut cells = mem::take(&mut self.cells);
+        self.render_tiles(&self.tiles, &mut cells);
+        self.old_cells.clone_from(&cells);
+        self.cells = cells;
+    }
+    /// Tile key and index of the cell within the tile
+    fn split_coords(row: i64, col: i64) -> ((i64, i64), usize) {
+        let size = TILE_SIZE as i64;
+        let key = (row.div_euclid(size), col.div_euclid(size));
+        let index = (row.rem_euclid(size) as usize * TILE_SIZE) + col.rem_euclid(size) as usize;
+        (key, index)
+    }
+    pub fn update(&mut self) -> bool {
+        // tiles with live cells and their neighbours are the only ones that can have live cells in the next generation
+        let mut candidates = Vec::with_capacity(self.tiles.len() * 9);
+        for &(tile_row, tile_col) in self.tiles.keys() {
+            for delta_row in -1..=1 {
+                for delta_col in -1..=1 {
+                    candidates.push((tile_row + delta_row, tile_col + delta_col));
+                }
+            }
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        let mut new_tiles = HashMap::with_capacity(self.tiles.len());
+        for key in candidates {
+            if let Some(tile) = self.update_tile(key) {
+                new_tiles.insert(key, tile);
+            }
+        }
+        let old_tiles = mem::replace(&mut self.tiles, new_tiles);
+
+        self.grow_view();
+        let mut cells = mem::take(&mut self.cells);
+        self.render_tiles(&self.tiles, &mut cells);
+        self.cells = cells;
+        let mut old_cells = mem::take(&mut self.old_cells);
+        self.render_tiles(&old_tiles, &mut old_cells);
+        self.old_cells = old_cells;
+        !self.tiles.is_empty()
+    }
+    /// Calculates the next generation of the tile, returns None if it has no live cells
+    fn update_tile(&self, (tile_row, tile_col): (i64, i64)) -> Option<Box<Tile>> {
+        let around: [[Option<&Tile>; 3]; 3] = std::array::from_fn(|delta_row| {
+            std::array::from_fn(|delta_col| {
+                self.tiles.get(&(tile_row + delta_row as i64 - 1, tile_col + delta_col as i64 - 1)).map(|tile| tile.as_ref())
+            })
+        });
+        let cell_at = |row: isize, col: isize| {
+            let size = TILE_SIZE as isize;
+            let tile = around[(row.div_euclid(size) + 1) as usize][(col.div_euclid(size) + 1) as usize];
+            let index = (row.rem_euclid(size) as usize * TILE_SIZE) + col.rem_euclid(size) as usize;
+            tile.map_or(CellValue::Dead, |tile| tile[index])
+        };
+        let mut new_tile = Box::new([CellValue::Dead; TILE_SIZE * TILE_SIZE]);
+        let mut has_alive = false;
+        for row in 0..TILE_SIZE as isize {
+            for col in 0..TILE_SIZE as isize {
+                let mut live_neighbours = 0;
+                for delta_row in -1..=1 {
+                    for delta_col in -1..=1 {
+                        if ((delta_row, delta_col) != (0, 0)) && (cell_at(row + delta_row, col + delta_col) == CellValue::Alive) {
+                            live_neighbours += 1;
+                        }
+                    }
+                }
+                let is_alive = match cell_at(row, col) {
+                    CellValue::Alive => self.rule.is_survival(live_neighbours),
+                    CellValue::Dead => self.rule.is_birth(live_neighbours),
+                };
+                if is_alive {
+                    new_tile[(row as usize * TILE_SIZE) + col as usize] = CellValue::Alive;
+                    has_alive = true;
+                }
+            }
+        }
+        if has_alive { Some(new_tile) } else { None }
+    }
+    /// Extends the view so that all live cells are at least `VIEW_MARGIN` away from its edges, up to `MAX_VIEW_SIZE`
+    fn grow_view(&mut self) {
+        let mut top = self.view_row;
+        let mut left = self.view_col;
+        let mut bottom = self.view_row + self.view_height.get() as i64;
+        let mut right = self.view_col + self.view_width.get() as i64;
+        let size = TILE_SIZE as i64;
+        for (&(tile_row, tile_col), tile) in &self.tiles {
+            for (index, &cell) in tile.iter().enumerate() {
+                if cell == CellValue::Dead {
+                    continue;
+                }
+                let row = (tile_row * size) + (index / TILE_SIZE) as i64;
+                let col = (tile_col * size) + (index % TILE_SIZE) as i64;
+                top = top.min(row - VIEW_MARGIN);
+                left = left.min(col - VIEW_MARGIN);
+                bottom = bottom.max(row + VIEW_MARGIN + 1);
+                right = right.max(col + VIEW_MARGIN + 1);
+            }
+        }
+        let max_size = MAX_VIEW_SIZE as i64;
+        let height = (bottom - top).min(max_size.max(self.view_height.get() as i64));
+        let width = (right - left).min(max_size.max(self.view_width.get() as i64));
+        // when the size is capped, keep the view as close to its old position as possible
+        self.view_row = top.max(self.view_row + self.view_height.get() as i64 - height).min(self.view_row);
+        self.view_col = left.max(self.view_col + self.view_width.get() as i64 - width).min(self.view_col);
+        self.view_height = NonZeroUsize::new(height as usize).unwrap();
+        self.view_width = NonZeroUsize::new(width as usize).unwrap();
+    }
+    /// Copies the cells of the tiles that are within the view into a row-major buffer
+    fn render_tiles(&self, tiles: &HashMap<(i64, i64), Box<Tile>>, cells: &mut Vec<CellValue>) {
+        let width = self.view_width.get();
+        cells.clear();
+        cells.resize(width * self.view_height.get(), CellValue::Dead);
+        let size = TILE_SIZE as i64;
+        for (&(tile_row, tile_col), tile) in tiles {
+            for (index, &cell) in tile.iter().enumerate() {
+                if cell == CellValue::Dead {
+                    continue;
+                }
+                let row = (tile_row * size) + (index / TILE_SIZE) as i64 - self.view_row;
+                let col = (tile_col * size) + (index % TILE_SIZE) as i64 - self.view_col;
+                if (row >= 0) && (col >= 0) && ((row as usize) < self.view_height.get()) && ((col as usize) < width) {
+                    cells[(row as usize * width) + col as usize] = cell;
+                }
+            }
+        }
+    }
+}
+
+impl Automaton for InfiniteField {
+    type State = CellValue;
+    fn get_width(&self) -> usize {
+        self.view_width.get()
+    }
+    fn get_height(&self) -> usize {
+        self.view_height.get()
+    }
+    fn view(&self) -> &[CellValue] {
+        &self.cells
+    }
+    fn view_old(&self) -> &[CellValue] {
+        &self.old_cells
+    }
+    fn step(&mut self) -> bool {
+        self.update()
+    }
+    fn set(&mut self, row: usize, col: usize, value: CellValue) -> Option<()> {
+        if (row >= self.view_height.get()) || (col >= self.view_width.get()) {
+            return None;
+        }
+        self.set_by_world_coords(self.view_row + row as i64, self.view_col + col as i64, value);
+        self.cells[(row * self.view_width.get()) + col] = value;
+        Some(())
+    }
+    fn parse(str: &str) -> Result<Self, ParseError> {
+        let (width, height, cells) = parse_cells::<CellValue>(str)?;
+        let mut res = Self::new(width, height);
+        for (index, &cell) in cells.iter().enumerate() {
+            res.set(index / width.get(), index % width.get(), cell);
+        }
+        Ok(res)
+    }
+    fn rule(&self) -> Option<String> {
+        Some(self.rule.to_string())
+    }
+    fn set_rule(&mut self, rule: &str) -> Result<(), ParseError> {
+        self.rule = rule.parse()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_blinker_across_tiles() {
+        let mut field = InfiniteField::new(4.try_into().unwrap(), 4.try_into().unwrap());
+        for col in -1..=1 {
+            field.set_by_world_coords(0, col, CellValue::Alive);
+        }
+        assert_eq!(2, field.tile_count());
+        field.update();
+        for row in -1..=1 {
+            assert_eq!(CellValue::Alive, field.get_by_world_coords(row, 0));
+        }
+        assert_eq!(CellValue::Dead, field.get_by_world_coords(0, 1));
+        assert_eq!(2, field.tile_count());
+    }
+    #[test]
+    fn test_glider_grows_view() {
+        let init_state = "
+_#____
+__#___
+###___
+______
+______
+______
+";
+        let mut field = InfiniteField::parse(init_state).unwrap();
+        for _ in 0..40 {
+            assert!(field.update());
+        }
+        // the glider moved 10 cells down and right, the view grew to keep it in sight
+        assert_eq!((-3, -4), field.view_origin());
+        assert_eq!(20, field.get_height());
+        assert_eq!(21, field.get_width());
+        assert_eq!(5, field.view().iter().filter(|&&cell| cell == CellValue::Alive).count());
+        assert_eq!(5, field.view_old().iter().filter(|&&cell| cell == CellValue::Alive).count());
+    }
+}
@@ -0,0 +1,214 @@
+use std::mem;
+use std::num::NonZeroUsize;
+use crate::automaton::{parse_cells, Automaton, CellState};
+use crate::game_of_life::ParseError;
+
+const LEVEL_COUNT: u8 = 16;
+
+/// Cell value of a continuous automaton, quantized for rendering
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Level(pub u8);
+impl Level {
+    fn from_value(value: f32) -> Self {
+        Self((value * (LEVEL_COUNT - 1) as f32).round() as u8)
+    }
+    fn to_value(self) -> f32 {
+        self.0 as f32 / (LEVEL_COUNT - 1) as f32
+    }
+}
+impl CellState for Level {
+    // heatmap from black through red and yellow to white
+    const PALETTE: &'static [&'static str] = &[
+        "#000000", "#1A0A2E", "#2E0F4F", "#4A1268", "#661A75", "#82227A", "#A02C73", "#BC3A63",
+        "#D44D4E", "#E66438", "#F27F22", "#F99D12", "#FBBC1E", "#F8DA48", "#F4F08A", "#FFFFFF",
+    ];
+    const NAMES: &'static [&'static str] = &[
+        "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15",
+    ];
+    fn index(self) -> u8 {
+        self.0
+    }
+    fn from_index(index: u8) -> Option<Self> {
+        if index < LEVEL_COUNT { Some(Self(index)) } else { None }
+    }
+    fn next_in_edit_cycle(self) -> Self {
+        if self.0 == 0 { Self(LEVEL_COUNT - 1) } else { Self(0) }
+    }
+    fn to_char(self) -> char {
+        char::from_digit(self.0 as u32, LEVEL_COUNT as u32).unwrap()
+    }
+    fn from_char(char: char) -> Option<Self> {
+        char.to_digit(LEVEL_COUNT as u32).map(|digit| Self(digit as u8))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LeniaParams {
+    /// Radius of the kernel in cells
+    pub radius: usize,
+    /// Center of the growth function
+    pub mu: f32,
+    /// Width of the growth function
+    pub sigma: f32,
+    /// Size of the time step
+    pub dt: f32,
+}
+impl Default for LeniaParams {
+    fn default() -> Self {
+        Self {
+            radius: 10,
+            mu: 0.15,
+            sigma: 0.015,
+            dt: 0.1,
+        }
+    }
+}
+
+/// Continuous automaton where each cell has a value from 0 to 1.
+/// The neighbourhood is a smooth ring shaped kernel, and a cell grows or shrinks
+/// depending on how close the weighted sum of its neighbourhood is to `mu`.
+#[derive(Clone)]
+pub struct Lenia {
+    width: NonZeroUsize,
+    height: NonZeroUsize,
+    params: LeniaParams,
+    /// (row offset, col offset, weight) for every cell of the kernel with a non-zero weight
+    kernel: Vec<(isize, isize, f32)>,
+    values: Vec<f32>,
+    swap_values: Vec<f32>,
+    levels: Vec<Level>,
+    old_levels: Vec<Level>,
+}
+
+impl Lenia {
+    pub fn generate_by_fn(width: NonZeroUsize, height: NonZeroUsize, params: LeniaParams, random_value: impl Fn(usize) -> f32) -> Self {
+        let cell_count = width.get() * height.get();
+        let values = (0..cell_count)
+            .map(|i| random_value(i).clamp(0.0, 1.0))
+            .collect::<Vec<_>>();
+        let levels = values.iter().map(|&value| Level::from_value(value)).collect();
+        Self {
+            width,
+            height,
+            params,
+            kernel: Self::make_kernel(params.radius),
+            values,
+            swap_values: vec![0.0; cell_count],
+            levels,
+            old_levels: vec![Level(0); cell_count],
+        }
+    }
+    pub fn get_params(&self) -> LeniaParams {
+        self.params
+    }
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+    fn make_kernel(radius: usize) -> Vec<(isize, isize, f32)> {
+        let radius_i = radius as isize;
+        let mut kernel = Vec::new();
+        for delta_row in -radius_i..=radius_i {
+            for delta_col in -radius_i..=radius_i {
+                let distance = ((delta_row * delta_row + delta_col * delta_col) as f32).sqrt() / radius as f32;
+                if (distance <= 0.0) || (distance >= 1.0) {
+                    continue;
+                }
+                // smooth bump that peaks in the middle of the ring
+                let weight = (4.0 - 1.0 / (distance * (1.0 - distance))).exp();
+                kernel.push((delta_row, delta_col, weight));
+            }
+        }
+        let sum = kernel.iter().map(|&(_, _, weight)| weight).sum::<f32>();
+        for (_, _, weight) in kernel.iter_mut() {
+            *weight /= sum;
+        }
+        kernel
+    }
+    fn growth(&self, potential: f32) -> f32 {
+        let distance = (potential - self.params.mu) / self.params.sigma;
+        2.0 * (-distance * distance / 2.0).exp() - 1.0
+    }
+    pub fn update(&mut self) -> bool {
+        let width = self.width.get();
+        let height = self.height.get();
+        let mut has_alive = false;
+        for (index, &value) in self.values.iter().enumerate() {
+            let row = (index / width) as isize;
+            let col = (index % width) as isize;
+            let mut potential = 0.0;
+            for &(delta_row, delta_col, weight) in &self.kernel {
+                let check_row = (row + delta_row).rem_euclid(height as isize) as usize;
+                let check_col = (col + delta_col).rem_euclid(width as isize) as usize;
+                potential += self.values[(check_row * width) + check_col] * weight;
+            }
+            let new_value = (value + self.params.dt * self.growth(potential)).clamp(0.0, 1.0);
+            self.swap_values[index] = new_value;
+            has_alive = has_alive || (new_value > 0.0);
+        }
+        mem::swap(&mut self.values, &mut self.swap_values);
+        mem::swap(&mut self.levels, &mut self.old_levels);
+        for (level, &value) in self.levels.iter_mut().zip(self.values.iter()) {
+            *level = Level::from_value(value);
+        }
+        has_alive
+    }
+}
+
+impl Automaton for Lenia {
+    type State = Level;
+    fn get_width(&self) -> usize {
+        self.width.get()
+    }
+    fn get_height(&self) -> usize {
+        self.height.get()
+    }
+    fn view(&self) -> &[Level] {
+        &self.levels
+    }
+    fn view_old(&self) -> &[Level] {
+        &self.old_levels
+    }
+    fn step(&mut self) -> bool {
+        self.update()
+    }
+    fn set(&mut self, row: usize, col: usize, value: Level) -> Option<()> {
+        if (row >= self.height.get()) || (col >= self.width.get()) {
+            return None;
+        }
+        let index = (row * self.width.get()) + col;
+        self.levels[index] = value;
+        self.values[index] = value.to_value();
+        Some(())
+    }
+    /// Parses one hex digit per cell, using default params
+    fn parse(str: &str) -> Result<Self, ParseError> {
+        let (width, height, levels) = parse_cells::<Level>(str)?;
+        let res = Self::generate_by_fn(width, height, LeniaParams::default(), |i| levels[i].to_value());
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_kernel_is_normalized() {
+        let kernel = Lenia::make_kernel(5);
+        let sum = kernel.iter().map(|&(_, _, weight)| weight).sum::<f32>();
+        assert!((sum - 1.0).abs() < 1e-5);
+        assert!(kernel.iter().all(|&(row, col, _)| (row, col) != (0, 0)));
+    }
+    #[test]
+    fn test_empty_field_stays_empty() {
+        let mut field = Lenia::generate_by_fn(8.try_into().unwrap(), 8.try_into().unwrap(), LeniaParams::default(), |_| 0.0);
+        assert!(!field.update());
+        assert!(field.values().iter().all(|&value| value == 0.0));
+    }
+    #[test]
+    fn test_parse_serialize() {
+        let field_str = "0f8\n3a0\n";
+        let field = Lenia::parse(field_str).unwrap();
+        assert_eq!(field_str, field.serialize());
+        assert_eq!(1.0, field.values()[1]);
+    }
+}
@@ -0,0 +1,331 @@
+use std::fmt::{Display, Formatter, Write};
+use std::mem;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+use crate::automaton::{parse_cells, Automaton, CellState};
+use crate::game_of_life::{CellValue, ParseError};
+
+/// Neighbours of a cell in a cube: the 26 cells that share a face, an edge or a corner with it
+const NEIGHBOURS: usize = 26;
+
+/// Rule of 3D Life: the numbers of the 26 neighbours that bring a dead cell to life and that keep a live cell alive.
+/// Written like "B5/S4-5", with the counts separated by commas and runs of counts as ranges
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule3d {
+    /// Bit n is set if n live neighbours bring a dead cell to life
+    birth: u32,
+    /// Bit n is set if n live neighbours keep a live cell alive
+    survival: u32,
+}
+impl Rule3d {
+    /// Life 4555 of Carter Bays, the first 3D rule found to have gliders
+    pub const BAYS_4555: Self = Self { birth: 1 << 5, survival: (1 << 4) | (1 << 5) };
+    /// Life 5766 of Carter Bays, also with gliders and more stable than 4555
+    pub const BAYS_5766: Self = Self { birth: 1 << 6, survival: (1 << 5) | (1 << 6) | (1 << 7) };
+    fn is_birth(self, count: usize) -> bool {
+        self.birth & (1 << count) != 0
+    }
+    fn is_survival(self, count: usize) -> bool {
+        self.survival & (1 << count) != 0
+    }
+    /// Parses a list like "4,6-8" into a mask of the counts
+    fn parse_counts(list: &str) -> Result<u32, ParseError> {
+        let mut mask = 0;
+        for item in list.split(',').filter(|item| !item.is_empty()) {
+            let (first, last) = item.split_once('-').unwrap_or((item, item));
+            let first = first.trim().parse::<usize>().map_err(|_| ParseError::InvalidRule)?;
+            let last = last.trim().parse::<usize>().map_err(|_| ParseError::InvalidRule)?;
+            if (first > last) || (last > NEIGHBOURS) {
+                return Err(ParseError::InvalidRule);
+            }
+            for count in first..=last {
+                mask |= 1 << count;
+            }
+        }
+        Ok(mask)
+    }
+    /// Writes the counts of the mask with runs as ranges
+    fn write_counts(f: &mut Formatter<'_>, mask: u32) -> std::fmt::Result {
+        let mut is_first = true;
+        let mut count = 0;
+        while count <= NEIGHBOURS {
+            if mask & (1 << count) == 0 {
+                count += 1;
+                continue;
+            }
+            let start = count;
+            while (count < NEIGHBOURS) && (mask & (1 << (count + 1)) != 0) {
+                count += 1;
+            }
+            if !is_first {
+                f.write_char(',')?;
+            }
+            is_first = false;
+            if start == count {
+                write!(f, "{start}")?;
+            } else {
+                write!(f, "{start}-{count}")?;
+            }
+            count += 1;
+        }
+        Ok(())
+    }
+}
+impl FromStr for Rule3d {
+    type Err = ParseError;
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        let upper = str.trim().to_ascii_uppercase();
+        let (birth, survival) = upper.split_once('/').ok_or(ParseError::InvalidRule)?;
+        let birth = birth.strip_prefix('B').ok_or(ParseError::InvalidRule)?;
+        let survival = survival.strip_prefix('S').ok_or(ParseError::InvalidRule)?;
+        Ok(Self { birth: Self::parse_counts(birth)?, survival: Self::parse_counts(survival)? })
+    }
+}
+impl Display for Rule3d {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_char('B')?;
+        Self::write_counts(f, self.birth)?;
+        f.write_str("/S")?;
+        Self::write_counts(f, self.survival)
+    }
+}
+
+/// Life in a cube of cells that wraps around in all three directions, with a rule for the 26 neighbours.
+/// For the 2D views the layers of the cube are laid out as tiles of a square grid, layer 0 in the top left corner.
+/// The tiles after the last layer are padding that stays dead
+#[derive(Clone)]
+pub struct Life3d {
+    /// Cells along each edge of the cube
+    size: NonZeroUsize,
+    /// Tiles in a row of the 2D layout
+    tiles_per_row: usize,
+    rule: Rule3d,
+    cells: Vec<CellValue>,
+    swap_cells: Vec<CellValue>,
+}
+
+impl Life3d {
+    /// Cube with the cells for which the function returns true alive, it gets the x, y and z coordinates
+    pub fn generate_by_fn(size: NonZeroUsize, rule: Rule3d, is_alive: impl Fn(usize, usize, usize) -> bool) -> Self {
+        let tiles_per_row = tiles_per_row(size.get());
+        let (width, height) = layout_size(size.get(), tiles_per_row);
+        let mut field = Self {
+            size,
+            tiles_per_row,
+            rule,
+            cells: vec![CellValue::Dead; width * height],
+            swap_cells: vec![CellValue::Dead; width * height],
+        };
+        for z in 0..size.get() {
+            for y in 0..size.get() {
+                for x in 0..size.get() {
+                    if is_alive(x, y, z) {
+                        let index = field.index(x, y, z);
+                        field.cells[index] = CellValue::Alive;
+                    }
+                }
+            }
+        }
+        field
+    }
+    pub fn get_size(&self) -> usize {
+        self.size.get()
+    }
+    pub fn get_rule(&self) -> Rule3d {
+        self.rule
+    }
+    /// Index in the 2D layout of the cell at the coordinates, which have to be inside the cube
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        let size = self.size.get();
+        let width = size * self.tiles_per_row;
+        ((z / self.tiles_per_row * size + y) * width) + (z % self.tiles_per_row * size) + x
+    }
+    pub fn is_alive(&self, x: usize, y: usize, z: usize) -> bool {
+        self.cells[self.index(x, y, z)] == CellValue::Alive
+    }
+    /// Live cells of the cube as x, y and z coordinates
+    pub fn alive_cells(&self) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        let size = self.size.get();
+        (0..size).flat_map(move |z| (0..size).flat_map(move |y| (0..size).map(move |x| (x, y, z))))
+            .filter(|&(x, y, z)| self.is_alive(x, y, z))
+    }
+    /// Live cells among the 26 neighbours, wrapping around the faces of the cube
+    fn count_neighbours(&self, x: usize, y: usize, z: usize) -> usize {
+        let size = self.size.get();
+        let around = |coord: usize| [(coord + size - 1) % size, coord, (coord + 1) % size];
+        let mut count = 0;
+        for (z_offset, z) in around(z).into_iter().enumerate() {
+            for (y_offset, y) in around(y).into_iter().enumerate() {
+                for (x_offset, x) in around(x).into_iter().enumerate() {
+                    let is_center = (x_offset == 1) && (y_offset == 1) && (z_offset == 1);
+                    if !is_center && self.is_alive(x, y, z) {
+                        count += 1;
+                    }
+                }
+            }
+        }
+        count
+    }
+    pub fn update(&mut self) -> bool {
+        let size = self.size.get();
+        let mut has_alive = false;
+        for z in 0..size {
+            for y in 0..size {
+                for x in 0..size {
+                    let index = self.index(x, y, z);
+                    let count = self.count_neighbours(x, y, z);
+                    let is_alive = match self.cells[index] {
+                        CellValue::Alive => self.rule.is_survival(count),
+                        CellValue::Dead => self.rule.is_birth(count),
+                    };
+                    self.swap_cells[index] = if is_alive { CellValue::Alive } else { CellValue::Dead };
+                    has_alive |= is_alive;
+                }
+            }
+        }
+        mem::swap(&mut self.cells, &mut self.swap_cells);
+        has_alive
+    }
+    /// Whether the cell of the 2D layout is in one of the layers, and not in the padding after them
+    fn is_in_layer(&self, row: usize, col: usize) -> bool {
+        let size = self.size.get();
+        (row / size * self.tiles_per_row) + (col / size) < size
+    }
+}
+
+/// Tiles in a row of the 2D layout of a cube, the layout is about square
+fn tiles_per_row(size: usize) -> usize {
+    (1..=size).find(|tiles| tiles * tiles >= size).unwrap_or(1)
+}
+
+/// Width and height of the 2D layout in cells
+fn layout_size(size: usize, tiles_per_row: usize) -> (usize, usize) {
+    (size * tiles_per_row, size * size.div_ceil(tiles_per_row))
+}
+
+impl FromStr for Life3d {
+    type Err = ParseError;
+    /// Parses the 2D layout, the size of the cube is found from its size. The rule is `Rule3d::BAYS_4555`
+    fn from_str(str: &str) -> Result<Self, ParseError> {
+        let (width, height, cells) = parse_cells::<CellValue>(str)?;
+        let size = (1..=width.get())
+            .find(|&size| layout_size(size, tiles_per_row(size)) == (width.get(), height.get()))
+            .and_then(NonZeroUsize::new)
+            .ok_or(ParseError::WidthMismatch)?;
+        let mut field = Self::generate_by_fn(size, Rule3d::BAYS_4555, |_, _, _| false);
+        for (index, value) in cells.into_iter().enumerate() {
+            if field.is_in_layer(index / width, index % width) {
+                field.cells[index] = value;
+            }
+        }
+        Ok(field)
+    }
+}
+
+impl Automaton for Life3d {
+    type State = CellValue;
+    fn get_width(&self) -> usize {
+        self.size.get() * self.tiles_per_row
+    }
+    fn get_height(&self) -> usize {
+        self.cells.len() / self.get_width()
+    }
+    fn view(&self) -> &[CellValue] {
+        &self.cells
+    }
+    fn view_old(&self) -> &[CellValue] {
+        &self.swap_cells
+    }
+    fn step(&mut self) -> bool {
+        self.update()
+    }
+    fn set(&mut self, row: usize, col: usize, value: CellValue) -> Option<()> {
+        if (row >= self.get_height()) || (col >= self.get_width()) || !self.is_in_layer(row, col) {
+            return None;
+        }
+        let index = (row * self.get_width()) + col;
+        self.cells[index] = value;
+        Some(())
+    }
+    fn parse(str: &str) -> Result<Self, ParseError> {
+        str.parse()
+    }
+    /// Shown with the field, but the rule of the app is for 2D fields, so it is not applied
+    fn rule(&self) -> Option<String> {
+        Some(self.rule.to_string())
+    }
+}
+
+impl Display for Life3d {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for row in self.cells.chunks(self.get_width()) {
+            for &value in row.iter() {
+                f.write_char(value.to_char())?;
+            }
+            f.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::automaton::{Activity, DynAutomaton};
+    #[test]
+    fn test_rule() {
+        assert_eq!(Ok(Rule3d::BAYS_4555), "B5/S4-5".parse());
+        assert_eq!(Ok(Rule3d::BAYS_5766), "b6/s5,6,7".parse());
+        assert_eq!("B6/S5-7", Rule3d::BAYS_5766.to_string());
+        let rule: Rule3d = "B4,13-14,26/S".parse().unwrap();
+        assert_eq!("B4,13-14,26/S", rule.to_string());
+        assert_eq!(Ok(rule), rule.to_string().parse());
+        assert!("B27/S4".parse::<Rule3d>().is_err());
+        assert!("B5-4/S4".parse::<Rule3d>().is_err());
+        assert!("B3/23".parse::<Rule3d>().is_err());
+    }
+    #[test]
+    fn test_layout() {
+        assert_eq!((1, 2), (tiles_per_row(1), tiles_per_row(2)));
+        assert_eq!((3, 3, 4), (tiles_per_row(7), tiles_per_row(9), tiles_per_row(10)));
+        let size = NonZeroUsize::new(3).unwrap();
+        let field = Life3d::generate_by_fn(size, Rule3d::BAYS_4555, |x, y, z| (x, y, z) == (1, 2, 2));
+        // two tiles per row, the fourth tile is padding
+        assert_eq!((6, 6), (Automaton::get_width(&field), Automaton::get_height(&field)));
+        assert_eq!("______\n______\n______\n______\n______\n_#____\n", field.to_string());
+        assert_eq!(vec![(1, 2, 2)], field.alive_cells().collect::<Vec<_>>());
+        let mut parsed = Life3d::from_str(&field.to_string()).unwrap();
+        assert_eq!(3, parsed.get_size());
+        assert!(parsed.is_alive(1, 2, 2));
+        assert_eq!(None, parsed.set_state(4, 4, 1));
+        assert_eq!(Some(()), parsed.set_state(4, 2, 1));
+        assert!(parsed.is_alive(2, 1, 2));
+        assert!(Life3d::from_str("___\n___").is_err());
+    }
+    #[test]
+    fn test_neighbours_wrap() {
+        let size = NonZeroUsize::new(4).unwrap();
+        // the corners of the cube are neighbours of each other through the faces
+        let field = Life3d::generate_by_fn(size, Rule3d::BAYS_4555, |x, y, z| [x, y, z].iter().all(|&coord| coord == 0 || coord == 3));
+        assert_eq!(7, field.count_neighbours(0, 0, 0));
+        assert_eq!(4, field.count_neighbours(3, 0, 1));
+        let full = Life3d::generate_by_fn(size, Rule3d::BAYS_4555, |_, _, _| true);
+        assert_eq!(NEIGHBOURS, full.count_neighbours(2, 1, 3));
+    }
+    #[test]
+    fn test_update() {
+        // a 2x2x2 block is still when 7 neighbours keep a cell alive: every cell of it has 7, and no empty cell has 5
+        let size = NonZeroUsize::new(6).unwrap();
+        let block = |x: usize, y: usize, z: usize| (2..4).contains(&x) && (2..4).contains(&y) && (2..4).contains(&z);
+        let rule = "B5/S4-7".parse().unwrap();
+        let mut field = Life3d::generate_by_fn(size, rule, block);
+        assert!(field.update());
+        assert_eq!(Life3d::generate_by_fn(size, rule, block).to_string(), field.to_string());
+        assert_eq!(Some(Activity { births: 0, deaths: 0 }), DynAutomaton::activity(&field));
+
+        // a lone cell dies
+        let mut field = Life3d::generate_by_fn(size, Rule3d::BAYS_4555, |x, y, z| (x, y, z) == (1, 1, 1));
+        assert!(!field.update());
+        assert_eq!(0, field.alive_cells().count());
+    }
+}
@@ -0,0 +1,42 @@
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::EventTarget;
+
+/// Event listeners together with their closures. The listeners are removed and the closures are freed on drop
+#[derive(Default)]
+pub struct Listeners {
+    listeners: Vec<Listener>,
+}
+struct Listener {
+    target: EventTarget,
+    event_types: Vec<&'static str>,
+    closure: Box<dyn AsRef<JsValue>>,
+}
+impl Listeners {
+    /// Calls the closure on each of the given events of the target, until the listeners are dropped
+    pub fn add<T: ?Sized + 'static>(
+        &mut self,
+        target: &EventTarget,
+        event_types: &[&'static str],
+        closure: wasm_bindgen::closure::Closure<T>,
+    ) -> Result<(), JsValue> {
+        let listener = Listener { target: target.clone(), event_types: Vec::new(), closure: Box::new(closure) };
+        // pushed first, so that events that were subscribed before an error are still removed
+        self.listeners.push(listener);
+        let listener = self.listeners.last_mut().unwrap();
+        for &event_type in event_types {
+            listener.target.add_event_listener_with_callback(event_type, listener.closure.as_ref().as_ref().unchecked_ref())?;
+            listener.event_types.push(event_type);
+        }
+        Ok(())
+    }
+}
+impl Drop for Listeners {
+    fn drop(&mut self) {
+        for listener in &self.listeners {
+            for event_type in &listener.event_types {
+                // removing can only fail for invalid arguments
+                let _ = listener.target.remove_event_listener_with_callback(event_type, listener.closure.as_ref().as_ref().unchecked_ref());
+            }
+        }
+    }
+}
@@ -1,4 +1,162 @@
+use std::cell::{Cell, RefCell};
+use std::str::FromStr;
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+use web_sys::{Document, Element};
+use crate::error::AppError;
+use crate::ring_buffer::RingBuffer;
+
+/// Number of recent messages that are kept for the log panel
+const HISTORY_SIZE: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        }
+    }
+    /// Color of the messages in the log panel
+    fn color(self) -> &'static str {
+        match self {
+            Self::Debug => "#888888",
+            Self::Info => "#000000",
+            Self::Warn => "#B26A00",
+            Self::Error => "#C62828",
+        }
+    }
+}
+impl FromStr for Level {
+    type Err = ();
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "debug" => Ok(Self::Debug),
+            "info" => Ok(Self::Info),
+            "warn" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            _ => Err(()),
+        }
+    }
+}
+
+thread_local! {
+    /// Messages below this level are dropped
+    static MIN_LEVEL: Cell<Level> = const { Cell::new(Level::Info) };
+    /// Recent messages, so that the log panel also shows the ones logged before it was opened
+    static HISTORY: RefCell<RingBuffer<(Level, String)>> = RefCell::new(RingBuffer::new(HISTORY_SIZE));
+    /// Element with the messages of the log panel, if it is shown
+    static PANEL: RefCell<Option<Element>> = const { RefCell::new(None) };
+}
+
+/// Sets the minimum level of logged messages for all apps on the page
+pub fn set_level(level: Level) {
+    MIN_LEVEL.set(level);
+}
+
+/// Writes the message to the console and to the log panel, unless its level is filtered out
+pub fn log(level: Level, message: String) {
+    if level < MIN_LEVEL.get() {
+        return;
+    }
+    let value = JsValue::from_str(&message);
+    match level {
+        Level::Debug => web_sys::console::debug_1(&value),
+        Level::Info => web_sys::console::log_1(&value),
+        Level::Warn => web_sys::console::warn_1(&value),
+        Level::Error => web_sys::console::error_1(&value),
+    }
+    PANEL.with_borrow(|panel| {
+        if let Some(list) = panel {
+            append_line(list, level, &message);
+        }
+    });
+    HISTORY.with_borrow_mut(|history| history.push((level, message)));
+}
+
+/// Shows a collapsible panel with the log in the corner of the page, for browsers without devtools, e.g. on phones.
+/// The panel is shared by all apps on the page, so it is only created once
+pub fn show_panel(document: &Document) -> Result<(), AppError> {
+    if PANEL.with_borrow(Option::is_some) {
+        return Ok(());
+    }
+    let panel = document.create_element("details")?;
+    panel.set_attribute(
+        "style",
+        "position: fixed; left: 8px; bottom: 8px; max-width: 50%; padding: 4px; background-color: #FFFFFF; border: 1px solid #888888; font: 12px monospace;",
+    )?;
+    let summary = document.create_element("summary")?;
+    summary.set_text_content(Some("Log"));
+    panel.append_child(&summary)?;
+    let list = document.create_element("div")?;
+    list.set_attribute("style", "max-height: 200px; overflow-y: auto; white-space: pre-wrap;")?;
+    panel.append_child(&list)?;
+    HISTORY.with_borrow(|history| {
+        for (level, message) in history.iter() {
+            append_line(&list, *level, message);
+        }
+    });
+    document.body().ok_or(AppError::Missing("document body"))?.append_child(&panel)?;
+    PANEL.set(Some(list));
+    Ok(())
+}
+
+fn append_line(list: &Element, level: Level, message: &str) {
+    // failures can't be logged from here, so the line is skipped
+    let Some(line) = list.owner_document().and_then(|document| document.create_element("div").ok()) else {
+        return;
+    };
+    line.set_text_content(Some(&format!("[{}] {message}", level.name())));
+    let _ = line.set_attribute("style", &format!("color: {};", level.color()));
+    let _ = list.append_child(&line);
+    if list.child_element_count() as usize > HISTORY_SIZE {
+        if let Some(first) = list.first_element_child() {
+            first.remove();
+        }
+    }
+    list.set_scroll_top(list.scroll_height());
+}
+
+/// Sets the minimum level of logged messages: "debug", "info", "warn" or "error"
+#[wasm_bindgen(js_name = setLogLevel)]
+pub fn set_log_level(level: &str) -> Result<(), JsValue> {
+    let level = level.parse().map_err(|_| JsValue::from_str(&format!("unknown log level {level}")))?;
+    set_level(level);
+    Ok(())
+}
+
+/// Shows the log panel, see `show_panel`
+#[wasm_bindgen(js_name = showLogPanel)]
+pub fn show_log_panel() -> Result<(), JsValue> {
+    let document = web_sys::window().and_then(|window| window.document()).ok_or(AppError::Missing("document"))?;
+    Ok(show_panel(&document)?)
+}
+
+#[macro_export]
+macro_rules! console_debug {
+    ($($t:tt)*) => ($crate::log::log($crate::log::Level::Debug, format!($($t)*)))
+}
+
 #[macro_export]
 macro_rules! console_log {
-    ($($t:tt)*) => (web_sys::console::log_1(&(format_args!($($t)*).to_string().into())))
-}
\ No newline at end of file
+    ($($t:tt)*) => ($crate::log::log($crate::log::Level::Info, format!($($t)*)))
+}
+
+#[macro_export]
+macro_rules! console_warn {
+    ($($t:tt)*) => ($crate::log::log($crate::log::Level::Warn, format!($($t)*)))
+}
+
+#[macro_export]
+macro_rules! console_error {
+    ($($t:tt)*) => ($crate::log::log($crate::log::Level::Error, format!($($t)*)))
+}
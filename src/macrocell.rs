@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Write};
+use std::num::NonZeroUsize;
+use crate::game_of_life::{CellValue, ParseError, Rule};
+use crate::infinite_life::InfiniteField;
+
+/// Extension of macrocell files
+pub const EXTENSION: &str = "mc";
+/// Leaves are 8x8 blocks of cells
+const LEAF_LEVEL: u32 = 3;
+const LEAF_SIZE: i64 = 1 << LEAF_LEVEL;
+/// Coordinates of bigger patterns wouldn't fit into i64
+const MAX_LEVEL: u32 = 62;
+/// Patterns are only expanded into cells up to this many live cells, a small file can describe far more cells than fit into memory
+pub const MAX_EXPANDED_POPULATION: u64 = 1 << 20;
+/// Patterns are only expanded into cells if their live cells fit into a square of this side
+pub const MAX_EXPANDED_SIDE: u64 = 1 << 24;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Node {
+    /// Cells of an 8x8 block, the bit `row * 8 + col` for every live cell
+    Leaf(u64),
+    /// Indexes of the north west, north east, south west and south east quarters, 0 for empty ones
+    Inner { level: u32, children: [usize; 4] },
+}
+impl Node {
+    fn level(self) -> u32 {
+        match self {
+            Self::Leaf(_) => LEAF_LEVEL,
+            Self::Inner { level, .. } => level,
+        }
+    }
+}
+
+/// The pattern has too many cells to expand them, it can only be kept as nodes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TooBig {
+    /// Saturated at `u64::MAX`
+    pub population: u64,
+    /// Side of the square around the live cells, saturated at `u64::MAX`
+    pub side: u64,
+}
+impl Display for TooBig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the pattern has {} live cells over {} cells across, only up to {MAX_EXPANDED_POPULATION} live cells over {MAX_EXPANDED_SIDE} cells can be loaded",
+            self.population, self.side,
+        )
+    }
+}
+
+/// Pattern in Golly's macrocell format: a quadtree where identical nodes are stored once,
+/// so huge patterns with repeated parts, like metapixel constructions, stay small in the file.
+/// The root node is centered on the origin. There is no engine that runs the nodes, so the pattern is expanded into cells
+/// to run it, which is bounded by `MAX_EXPANDED_POPULATION` and `MAX_EXPANDED_SIDE`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Macrocell {
+    rule: Rule,
+    /// Every node comes after its children, the root is the last one. The node 0 stands for empty quarters and is never used
+    nodes: Vec<Node>,
+}
+
+impl Macrocell {
+    /// Macrocell of the live cells, by row and column
+    pub fn from_cells(rule: Rule, cells: impl IntoIterator<Item = (i64, i64)>) -> Self {
+        let cells: Vec<_> = cells.into_iter().collect();
+        let mut level = LEAF_LEVEL;
+        let fits = |level: u32| {
+            let half = 1_i64 << (level - 1);
+            cells.iter().all(|&(row, col)| (-half..half).contains(&row) && (-half..half).contains(&col))
+        };
+        while !fits(level) {
+            level += 1;
+        }
+        let mut builder = Builder { nodes: vec![Node::Leaf(0)], indexes: HashMap::new() };
+        let half = 1_i64 << (level - 1);
+        if builder.build(level, -half, -half, cells) == 0 {
+            builder.nodes.push(Node::Leaf(0));
+        }
+        Self { rule, nodes: builder.nodes }
+    }
+    pub fn get_rule(&self) -> Rule {
+        self.rule
+    }
+    /// Level of the root node, the pattern is `2^level` cells across
+    pub fn level(&self) -> u32 {
+        self.nodes.last().map_or(LEAF_LEVEL, |node| node.level())
+    }
+    /// Number of distinct nodes, which is the size of the file rather than of the pattern
+    pub fn node_count(&self) -> usize {
+        self.nodes.len() - 1
+    }
+    /// Number of live cells, counted once per distinct node. Saturates at `u64::MAX`, nodes that repeat their children can describe more
+    pub fn population(&self) -> u64 {
+        let mut populations = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let population = match *node {
+                Node::Leaf(bits) => bits.count_ones() as u64,
+                Node::Inner { children, .. } => children.iter().fold(0_u64, |sum, &child| sum.saturating_add(populations[child])),
+            };
+            populations.push(population);
+        }
+        // the placeholder node is counted as a leaf without cells
+        populations.last().copied().unwrap_or(0)
+    }
+    /// Side of the smallest square around the live cells, 0 without any, counted once per distinct node
+    pub fn side(&self) -> u64 {
+        // top, left, bottom and right live cell of every node, relative to its top left corner
+        let mut bounds: Vec<Option<[i64; 4]>> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let node_bounds = match *node {
+                Node::Leaf(0) => None,
+                Node::Leaf(bits) => {
+                    let cells = (0..64).filter(|&bit| bits & (1 << bit) != 0).map(|bit| (bit / LEAF_SIZE, bit % LEAF_SIZE));
+                    cells.fold(None, |bounds, (row, col)| Some(merge_bounds(bounds, [row, col, row, col])))
+                }
+                Node::Inner { level, children } => {
+                    let half = 1_i64 << (level - 1);
+                    children.iter().enumerate().filter(|&(_, &child)| child != 0).fold(None, |node_bounds, (quarter, &child)| {
+                        let (top, left) = ((quarter as i64 / 2) * half, (quarter as i64 % 2) * half);
+                        match bounds[child] {
+                            Some([child_top, child_left, bottom, right]) => {
+                                Some(merge_bounds(node_bounds, [top + child_top, left + child_left, top + bottom, left + right]))
+                            }
+                            None => node_bounds,
+                        }
+                    })
+                }
+            };
+            bounds.push(node_bounds);
+        }
+        bounds.last().copied().flatten().map_or(0, |[top, left, bottom, right]| {
+            // the differences fit, the cells of a node are less than 2^62 apart
+            ((bottom - top).max(right - left) as u64).saturating_add(1)
+        })
+    }
+    /// Calls `f` with the row and column of every live cell, empty quarters are skipped without looking at their cells.
+    /// Takes as long as the population, which can be astronomical, see `to_infinite_field`
+    pub fn for_each_cell(&self, mut f: impl FnMut(i64, i64)) {
+        let half = 1_i64 << (self.level() - 1);
+        self.visit(self.nodes.len() - 1, -half, -half, &mut f);
+    }
+    fn visit(&self, index: usize, top: i64, left: i64, f: &mut impl FnMut(i64, i64)) {
+        match self.nodes[index] {
+            Node::Leaf(bits) => {
+                for bit in (0..64).filter(|&bit| bits & (1 << bit) != 0) {
+                    f(top + (bit / LEAF_SIZE), left + (bit % LEAF_SIZE));
+                }
+            }
+            Node::Inner { level, children } => {
+                let half = 1_i64 << (level - 1);
+                for (quarter, &child) in children.iter().enumerate().filter(|&(_, &child)| child != 0) {
+                    self.visit(child, top + (quarter as i64 / 2) * half, left + (quarter as i64 % 2) * half, f);
+                }
+            }
+        }
+    }
+    /// Unbounded field with the cells of the pattern and its rule, the view is moved to the cells.
+    /// Patterns above `MAX_EXPANDED_POPULATION` or `MAX_EXPANDED_SIDE` are refused, until there is an engine that runs the nodes
+    pub fn to_infinite_field(&self, view_width: NonZeroUsize, view_height: NonZeroUsize) -> Result<InfiniteField, TooBig> {
+        let (population, side) = (self.population(), self.side());
+        if (population > MAX_EXPANDED_POPULATION) || (side > MAX_EXPANDED_SIDE) {
+            return Err(TooBig { population, side });
+        }
+        let mut field = InfiniteField::new(view_width, view_height);
+        field.set_rule(self.rule);
+        self.for_each_cell(|row, col| field.set_by_world_coords(row, col, CellValue::Alive));
+        field.fit_view();
+        Ok(field)
+    }
+}
+
+/// Smallest bounds around both, as top, left, bottom and right
+fn merge_bounds(bounds: Option<[i64; 4]>, [top, left, bottom, right]: [i64; 4]) -> [i64; 4] {
+    match bounds {
+        Some([old_top, old_left, old_bottom, old_right]) => [old_top.min(top), old_left.min(left), old_bottom.max(bottom), old_right.max(right)],
+        None => [top, left, bottom, right],
+    }
+}
+
+/// Adds the nodes of a macrocell, reusing the identical ones
+struct Builder {
+    nodes: Vec<Node>,
+    indexes: HashMap<Node, usize>,
+}
+impl Builder {
+    /// Index of the node of the given level with its top left cell at `top` and `left`, 0 if it has no cells
+    fn build(&mut self, level: u32, top: i64, left: i64, cells: Vec<(i64, i64)>) -> usize {
+        if cells.is_empty() {
+            return 0;
+        }
+        let node = if level == LEAF_LEVEL {
+            Node::Leaf(cells.iter().fold(0, |bits, &(row, col)| bits | (1 << (((row - top) * LEAF_SIZE) + (col - left)))))
+        } else {
+            let half = 1_i64 << (level - 1);
+            let mut quarters: [Vec<(i64, i64)>; 4] = Default::default();
+            for (row, col) in cells {
+                quarters[(((row - top) >= half) as usize * 2) + ((col - left) >= half) as usize].push((row, col));
+            }
+            let mut children = [0; 4];
+            for (quarter, cells) in quarters.into_iter().enumerate() {
+                children[quarter] = self.build(level - 1, top + (quarter as i64 / 2) * half, left + (quarter as i64 % 2) * half, cells);
+            }
+            Node::Inner { level, children }
+        };
+        *self.indexes.entry(node).or_insert_with(|| {
+            self.nodes.push(node);
+            self.nodes.len() - 1
+        })
+    }
+}
+
+/// Parses a macrocell file. Leaves have to be 8x8 blocks, like Golly writes for rules with 2 states
+pub fn parse_macrocell(str: &str) -> Result<Macrocell, ParseError> {
+    let mut lines = str.lines().map(str::trim).filter(|line| !line.is_empty());
+    if !lines.next().ok_or(ParseError::EmptyString)?.starts_with("[M2]") {
+        return Err(ParseError::InvalidHeader);
+    }
+    let mut rule = Rule::LIFE;
+    let mut nodes = vec![Node::Leaf(0)];
+    for line in lines {
+        if let Some(comment) = line.strip_prefix('#') {
+            if let Some(rule_str) = comment.strip_prefix('R') {
+                rule = rule_str.parse()?;
+            }
+            continue;
+        }
+        let node = if line.starts_with(['.', '*', '$']) {
+            parse_leaf(line)?
+        } else {
+            let numbers = line.split_whitespace().map(str::parse).collect::<Result<Vec<usize>, _>>().map_err(|_| ParseError::UnknownChar)?;
+            let &[level, nw, ne, sw, se] = numbers.as_slice() else {
+                return Err(ParseError::UnknownChar);
+            };
+            let level = level as u32;
+            let children = [nw, ne, sw, se];
+            let is_valid_child = |child: usize| (child == 0) || nodes.get(child).is_some_and(|node| node.level() + 1 == level);
+            if !(LEAF_LEVEL + 1..=MAX_LEVEL).contains(&level) || !children.into_iter().all(is_valid_child) {
+                return Err(ParseError::InvalidHeader);
+            }
+            Node::Inner { level, children }
+        };
+        nodes.push(node);
+    }
+    if nodes.len() == 1 {
+        return Err(ParseError::EmptyString);
+    }
+    Ok(Macrocell { rule, nodes })
+}
+
+/// Rows of a leaf end with `$`, `*` for live cells and `.` for dead ones, the dead cells at the end of a row can be left out
+fn parse_leaf(line: &str) -> Result<Node, ParseError> {
+    let mut bits = 0_u64;
+    let (mut row, mut col) = (0, 0);
+    for char in line.chars() {
+        match char {
+            '$' => {
+                row += 1;
+                col = 0;
+                continue;
+            }
+            '*' if (row < LEAF_SIZE) && (col < LEAF_SIZE) => bits |= 1 << ((row * LEAF_SIZE) + col),
+            '.' => {}
+            '*' => return Err(ParseError::WidthMismatch),
+            _ => return Err(ParseError::UnknownChar),
+        }
+        col += 1;
+    }
+    Ok(Node::Leaf(bits))
+}
+
+impl Display for Macrocell {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "[M2] (rust_wasm)")?;
+        writeln!(f, "#R {}", self.rule)?;
+        for node in &self.nodes[1..] {
+            match *node {
+                Node::Leaf(bits) => {
+                    let rows = (0..LEAF_SIZE).rev().find(|row| (bits >> (row * LEAF_SIZE)) & 0xFF != 0).map_or(0, |row| row + 1);
+                    let mut line = String::new();
+                    for row in 0..rows {
+                        let row_bits = (bits >> (row * LEAF_SIZE)) & 0xFF;
+                        for col in 0..(64 - row_bits.leading_zeros()) {
+                            line.push(if row_bits & (1 << col) != 0 { '*' } else { '.' });
+                        }
+                        line.push('$');
+                    }
+                    if line.is_empty() {
+                        line.push('$');
+                    }
+                    f.write_str(&line)?;
+                    f.write_char('\n')?;
+                }
+                Node::Inner { level, children: [nw, ne, sw, se] } => writeln!(f, "{level} {nw} {ne} {sw} {se}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<&InfiniteField> for Macrocell {
+    fn from(field: &InfiniteField) -> Self {
+        Self::from_cells(field.get_rule(), field.live_cells())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    const GLIDER: &str = "[M2] (golly 4.2)\n#R B3/S23\n.*$..*$***$\n4 0 0 0 1\n";
+    fn cells(macrocell: &Macrocell) -> Vec<(i64, i64)> {
+        let mut cells = Vec::new();
+        macrocell.for_each_cell(|row, col| cells.push((row, col)));
+        cells.sort_unstable();
+        cells
+    }
+    #[test]
+    fn test_parse_macrocell() {
+        let macrocell = parse_macrocell(GLIDER).unwrap();
+        assert_eq!(4, macrocell.level());
+        assert_eq!(5, macrocell.population());
+        // the root is centered, the glider is in its south east quarter
+        assert_eq!(vec![(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)], cells(&macrocell));
+
+        assert_eq!(Err(ParseError::InvalidHeader), parse_macrocell("x = 1, y = 1\no!"));
+        assert_eq!(Err(ParseError::InvalidHeader), parse_macrocell("[M2]\n*$\n4 0 0 0 2\n"));
+        assert_eq!(Err(ParseError::InvalidHeader), parse_macrocell("[M2]\n*$\n5 0 0 0 1\n"));
+        assert_eq!(Err(ParseError::WidthMismatch), parse_macrocell("[M2]\n.........*$\n"));
+        assert_eq!(Err(ParseError::EmptyString), parse_macrocell("[M2]\n#R B3/S23\n"));
+    }
+    #[test]
+    fn test_shared_nodes() {
+        // a 64x64 pattern of blocks, made of far fewer nodes than blocks
+        let blocks = (-32..32).step_by(8)
+            .flat_map(|row| (-32..32).step_by(8).map(move |col| (row, col)))
+            .flat_map(|(row, col)| [(row, col), (row, col + 1), (row + 1, col), (row + 1, col + 1)]);
+        let macrocell = Macrocell::from_cells(Rule::LIFE, blocks);
+        assert_eq!(6, macrocell.level());
+        assert_eq!(256, macrocell.population());
+        assert_eq!(4, macrocell.node_count());
+        assert_eq!("[M2] (rust_wasm)\n#R B3/S23\n**$**$\n4 1 1 1 1\n5 2 2 2 2\n6 3 3 3 3\n", macrocell.to_string());
+    }
+    #[test]
+    fn test_too_big() {
+        let size = NonZeroUsize::new(16).unwrap();
+        let glider = parse_macrocell(GLIDER).unwrap();
+        assert_eq!(3, glider.side());
+        assert_eq!(5, glider.to_infinite_field(size, size).unwrap().live_cells().count());
+
+        // a full leaf repeated in every quarter of every level, about 2^124 cells in a few hundred bytes
+        let mut text = "[M2]\n********$********$********$********$********$********$********$********$\n".to_string();
+        for level in 4..=MAX_LEVEL {
+            let child = level - 3;
+            text += &format!("{level} {child} {child} {child} {child}\n");
+        }
+        let huge = parse_macrocell(&text).unwrap();
+        assert_eq!(u64::MAX, huge.population());
+        assert_eq!(1 << MAX_LEVEL, huge.side());
+        assert_eq!(Some(TooBig { population: u64::MAX, side: 1 << MAX_LEVEL }), huge.to_infinite_field(size, size).err());
+
+        let wide = Macrocell::from_cells(Rule::LIFE, [(0, 0), (0, MAX_EXPANDED_SIDE as i64)]);
+        assert_eq!(MAX_EXPANDED_SIDE + 1, wide.side());
+        assert!(wide.to_infinite_field(size, size).is_err());
+    }
+    #[test]
+    fn test_round_trip() {
+        let macrocell = parse_macrocell(GLIDER).unwrap();
+        // the glider fits into a single leaf around the origin
+        let written = Macrocell::from_cells(Rule::LIFE, cells(&macrocell));
+        assert_eq!(cells(&macrocell), cells(&written));
+        assert_eq!("[M2] (rust_wasm)\n#R B3/S23\n$$$$.....*$......*$....***$\n", written.to_string());
+        assert_eq!(written, parse_macrocell(&written.to_string()).unwrap());
+
+        let far = Macrocell::from_cells(Rule::HIGH_LIFE, [(-1_000_000, 5), (999_999, -3)]);
+        assert_eq!(21, far.level());
+        assert_eq!(vec![(-1_000_000, 5), (999_999, -3)], cells(&far));
+        assert_eq!(2_000_000, far.side());
+        assert_eq!(far, parse_macrocell(&far.to_string()).unwrap());
+
+        let empty = Macrocell::from_cells(Rule::LIFE, []);
+        assert_eq!(0, empty.population());
+        assert_eq!(0, empty.side());
+        assert_eq!(empty, parse_macrocell(&empty.to_string()).unwrap());
+    }
+}
@@ -0,0 +1,83 @@
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, Document, HtmlCanvasElement};
+use crate::automaton::DynAutomaton;
+use crate::error::AppError;
+use crate::render::{canvas_coords_to_cell, canvas_size, cell_bounds, context_2d};
+
+/// Size of the sides of the magnifier
+const MAGNIFIER_SIZE_PX: u32 = 192;
+/// Number of cells across the magnified region
+const MAGNIFIED_CELLS: f64 = 16.0;
+const BACKGROUND_COLOR: &str = "#FFFFFF";
+const CURSOR_COLOR: &str = "#FF0000";
+
+/// Enlarged copy of the region of the field around the cursor in the corner of the window, for fields that are zoomed out too far
+/// to see single cells. It is shown while the cursor is over the field, if it's enabled
+#[derive(Debug)]
+pub struct Magnifier {
+    context: CanvasRenderingContext2d,
+    is_enabled: bool,
+    /// Position of the cursor in client coordinates, None when it's not over the field
+    point: Option<(f64, f64)>,
+}
+impl Magnifier {
+    pub fn new(document: &Document) -> Result<Self, AppError> {
+        let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+        canvas.set_attribute("style", "position: fixed; left: 8px; bottom: 8px; border: 1px solid #888888; pointer-events: none; display: none;")?;
+        canvas.set_attribute("aria-hidden", "true")?;
+        canvas.set_width(MAGNIFIER_SIZE_PX);
+        canvas.set_height(MAGNIFIER_SIZE_PX);
+        let context = context_2d(&canvas)?;
+        // the cells stay sharp squares
+        context.set_image_smoothing_enabled(false);
+        Ok(Self { context, is_enabled: false, point: None })
+    }
+    pub fn canvas(&self) -> HtmlCanvasElement {
+        self.context.canvas().unwrap()
+    }
+    pub fn toggle(&mut self) {
+        self.is_enabled = !self.is_enabled;
+    }
+    /// Sets where the cursor is, None when it has left the field. The magnifier is redrawn on the next `update`
+    pub fn move_to(&mut self, point: Option<(f64, f64)>) {
+        self.point = point;
+    }
+    /// Copies the region around the cell under the cursor from the layers of the field, which are drawn in the order.
+    /// Hides the magnifier if it's disabled or the cursor is not over the field
+    pub fn update(&self, field: &dyn DynAutomaton, layers: &[HtmlCanvasElement]) {
+        let canvas = self.canvas();
+        let point = self.point.filter(|_| self.is_enabled);
+        // setting styles can't fail for this property
+        let _ = canvas.style().set_property("display", if point.is_some() { "block" } else { "none" });
+        let (Some((client_x, client_y)), Some(field_canvas)) = (point, layers.last()) else {
+            return;
+        };
+
+        // the layers have the same size, in device pixels
+        let rect = field_canvas.get_bounding_client_rect();
+        let (width, height) = canvas_size(field);
+        let scale = field_canvas.width() as f64 / width as f64;
+        let (row, col) = canvas_coords_to_cell(
+            field,
+            (client_x - rect.left()) * width as f64 / rect.width(),
+            (client_y - rect.top()) * height as f64 / rect.height(),
+        );
+        let (cell_x, cell_y, cell_width, cell_height) = cell_bounds(field, row, col);
+        let region = MAGNIFIED_CELLS * (width as f64 / field.get_width() as f64) * scale;
+        let left = ((cell_x + (cell_width / 2.0)) * scale) - (region / 2.0);
+        let top = ((cell_y + (cell_height / 2.0)) * scale) - (region / 2.0);
+
+        let size = MAGNIFIER_SIZE_PX as f64;
+        self.context.set_fill_style(&BACKGROUND_COLOR.into());
+        self.context.fill_rect(0.0, 0.0, size, size);
+        for layer in layers {
+            // parts of the region outside of the field are left empty
+            let _ = self.context.draw_image_with_html_canvas_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                layer, left, top, region, region, 0.0, 0.0, size, size,
+            );
+        }
+        let zoom = size / region;
+        self.context.set_stroke_style(&CURSOR_COLOR.into());
+        self.context.stroke_rect(((cell_x * scale) - left) * zoom, ((cell_y * scale) - top) * zoom, cell_width * scale * zoom, cell_height * scale * zoom);
+    }
+}
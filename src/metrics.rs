@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+use crate::analysis::MAX_GENERATIONS;
+use crate::automaton::Activity;
+
+/// File formats of the export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricsFormat {
+    Csv,
+    Json,
+}
+impl MetricsFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv",
+            Self::Json => "application/json",
+        }
+    }
+}
+
+/// What is recorded about a single generation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GenerationMetrics {
+    pub generation: u64,
+    /// Number of cells that are not in the state with palette index 0
+    pub population: usize,
+    /// Births and deaths of the step to this generation, None if the field has been resized by it
+    pub activity: Option<Activity>,
+    /// Time since the previous frame, None for the first frame after a pause
+    pub frame_ms: Option<f64>,
+}
+
+/// Metrics of the generations of a run, kept in memory until they are exported as CSV or JSON.
+/// Empty values are written as empty CSV fields and as JSON nulls
+#[derive(Debug, Default)]
+pub struct MetricsRecorder {
+    generations: VecDeque<GenerationMetrics>,
+}
+impl MetricsRecorder {
+    pub fn push(&mut self, metrics: GenerationMetrics) {
+        if self.generations.len() >= MAX_GENERATIONS {
+            self.generations.pop_front();
+        }
+        self.generations.push_back(metrics);
+    }
+    pub fn clear(&mut self) {
+        self.generations.clear();
+    }
+    /// Forgets the generations after the given one, e.g. when the run continues from an earlier generation
+    pub fn truncate_after(&mut self, generation: u64) {
+        let len = self.generations.partition_point(|metrics| metrics.generation <= generation);
+        self.generations.truncate(len);
+    }
+    pub fn len(&self) -> usize {
+        self.generations.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.generations.is_empty()
+    }
+    pub fn export(&self, format: MetricsFormat) -> String {
+        match format {
+            MetricsFormat::Csv => self.to_csv(),
+            MetricsFormat::Json => self.to_json(),
+        }
+    }
+    /// One line per generation, with a header
+    pub fn to_csv(&self) -> String {
+        let mut res = String::from("generation,population,births,deaths,frame_ms\n");
+        for metrics in &self.generations {
+            let births = metrics.activity.map(|activity| activity.births.to_string()).unwrap_or_default();
+            let deaths = metrics.activity.map(|activity| activity.deaths.to_string()).unwrap_or_default();
+            let frame_ms = metrics.frame_ms.map(|frame_ms| format!("{frame_ms:.3}")).unwrap_or_default();
+            // writing into a String never fails
+            let _ = writeln!(res, "{},{},{births},{deaths},{frame_ms}", metrics.generation, metrics.population);
+        }
+        res
+    }
+    /// Array with an object per generation, with the same keys as the CSV header
+    pub fn to_json(&self) -> String {
+        let mut res = String::from("[");
+        for (index, metrics) in self.generations.iter().enumerate() {
+            let births = metrics.activity.map_or("null".to_string(), |activity| activity.births.to_string());
+            let deaths = metrics.activity.map_or("null".to_string(), |activity| activity.deaths.to_string());
+            // json has no infinity and nan
+            let frame_ms = metrics.frame_ms.filter(|frame_ms| frame_ms.is_finite()).map_or("null".to_string(), |frame_ms| format!("{frame_ms:.3}"));
+            let separator = if index == 0 { "" } else { "," };
+            let _ = write!(
+                res,
+                r#"{separator}{{"generation":{},"population":{},"births":{births},"deaths":{deaths},"frame_ms":{frame_ms}}}"#,
+                metrics.generation,
+                metrics.population,
+            );
+        }
+        res.push(']');
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    fn recorder() -> MetricsRecorder {
+        let mut recorder = MetricsRecorder::default();
+        recorder.push(GenerationMetrics { generation: 1, population: 10, activity: Some(Activity { births: 3, deaths: 2 }), frame_ms: None });
+        recorder.push(GenerationMetrics { generation: 2, population: 12, activity: None, frame_ms: Some(16.6667) });
+        recorder
+    }
+    #[test]
+    fn test_to_csv() {
+        let recorder = recorder();
+        assert_eq!(2, recorder.len());
+        assert_eq!("generation,population,births,deaths,frame_ms\n1,10,3,2,\n2,12,,,16.667\n", recorder.to_csv());
+    }
+    #[test]
+    fn test_to_json() {
+        let mut recorder = recorder();
+        assert_eq!(
+            r#"[{"generation":1,"population":10,"births":3,"deaths":2,"frame_ms":null},{"generation":2,"population":12,"births":null,"deaths":null,"frame_ms":16.667}]"#,
+            recorder.to_json(),
+        );
+        assert_eq!(recorder.to_json(), recorder.export(MetricsFormat::Json));
+        recorder.truncate_after(1);
+        assert_eq!(1, recorder.len());
+        recorder.clear();
+        assert!(recorder.is_empty());
+        assert_eq!("[]", recorder.to_json());
+    }
+}
@@ -0,0 +1,77 @@
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, Document, HtmlCanvasElement, Window};
+use crate::automaton::DynAutomaton;
+use crate::error::AppError;
+use crate::render::{context_2d, ImageRenderer};
+
+/// Size of the longer side of the minimap
+const MINIMAP_SIZE_PX: f64 = 160.0;
+
+/// Thumbnail of the whole field in the corner of the window, with a rectangle showing which part of the field
+/// is visible. It is only shown when the field doesn't fit into the window
+#[derive(Debug)]
+pub struct Minimap {
+    context: CanvasRenderingContext2d,
+    renderer: ImageRenderer,
+}
+impl Minimap {
+    pub fn new(document: &Document) -> Result<Self, AppError> {
+        let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+        canvas.set_attribute("style", "position: fixed; right: 8px; bottom: 8px; border: 1px solid #888888; display: none;")?;
+        let context = context_2d(&canvas)?;
+        Ok(Self { context, renderer: ImageRenderer::new(document)? })
+    }
+    pub fn canvas(&self) -> HtmlCanvasElement {
+        self.context.canvas().unwrap()
+    }
+    /// Redraws the thumbnail and the viewport rectangle, or hides the minimap if the whole field is visible
+    pub fn update(&self, window: &Window, field: &dyn DynAutomaton, field_canvas: &HtmlCanvasElement) {
+        let canvas = self.canvas();
+        let rect = field_canvas.get_bounding_client_rect();
+        let (window_width, window_height) = window_size(window);
+        let is_visible = (rect.left() >= 0.0) && (rect.top() >= 0.0)
+            && (rect.right() <= window_width) && (rect.bottom() <= window_height);
+        // setting styles can't fail for this property
+        let _ = canvas.style().set_property("display", if is_visible { "none" } else { "block" });
+        if is_visible {
+            return;
+        }
+
+        let scale = MINIMAP_SIZE_PX / rect.width().max(rect.height());
+        let width = (rect.width() * scale).round();
+        let height = (rect.height() * scale).round();
+        if (canvas.width() != width as u32) || (canvas.height() != height as u32) {
+            canvas.set_width(width as u32);
+            canvas.set_height(height as u32);
+        }
+        self.renderer.draw_scaled(&self.context, field, width, height);
+
+        let left = (-rect.left()).max(0.0);
+        let top = (-rect.top()).max(0.0);
+        let right = (window_width - rect.left()).min(rect.width());
+        let bottom = (window_height - rect.top()).min(rect.height());
+        self.context.set_stroke_style(&"#FF0000".into());
+        self.context.stroke_rect(left * scale, top * scale, (right - left) * scale, (bottom - top) * scale);
+    }
+    /// Scrolls the window so that the point of the field under the click on the minimap is in the center
+    pub fn jump(&self, window: &Window, field_canvas: &HtmlCanvasElement, event: &web_sys::MouseEvent) {
+        let canvas = self.canvas();
+        let minimap_rect = canvas.get_bounding_client_rect();
+        let fraction_x = (event.client_x() as f64 - minimap_rect.left()) / minimap_rect.width();
+        let fraction_y = (event.client_y() as f64 - minimap_rect.top()) / minimap_rect.height();
+        let rect = field_canvas.get_bounding_client_rect();
+        let (window_width, window_height) = window_size(window);
+        let scroll_x = window.scroll_x().unwrap_or(0.0);
+        let scroll_y = window.scroll_y().unwrap_or(0.0);
+        window.scroll_to_with_x_and_y(
+            scroll_x + rect.left() + (fraction_x * rect.width()) - (window_width / 2.0),
+            scroll_y + rect.top() + (fraction_y * rect.height()) - (window_height / 2.0),
+        );
+    }
+}
+
+fn window_size(window: &Window) -> (f64, f64) {
+    let width = window.inner_width().ok().and_then(|value| value.as_f64()).unwrap_or(0.0);
+    let height = window.inner_height().ok().and_then(|value| value.as_f64()).unwrap_or(0.0);
+    (width, height)
+}
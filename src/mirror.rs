@@ -0,0 +1,219 @@
+use crate::game_of_life::{CellValue, Field};
+use crate::snapshot::SnapshotError;
+
+/// Kinds of frames, the first byte of every frame
+const SNAPSHOT_FRAME: u8 = 1;
+const DELTA_FRAME: u8 = 2;
+/// Bytes of a changed cell in a delta frame: row and column as u32 and the state
+const DELTA_CELL_SIZE: usize = 9;
+
+/// Update of a mirrored field, as sent to the viewers
+#[derive(Clone)]
+pub enum Frame {
+    /// The whole field, for new viewers and when the size or the rule changes
+    Snapshot { generation: u64, field: Field },
+    /// The cells that changed since the previous frame, as returned by `Field::diff`
+    Delta { generation: u64, cells: Vec<(usize, usize, CellValue)> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameError {
+    UnknownKind(u8),
+    /// The bytes end in the middle of the frame
+    Truncated,
+    InvalidState(u8),
+    Snapshot(SnapshotError),
+}
+
+impl Frame {
+    /// Layout, little-endian: the kind as u8, the generation as u64, then the snapshot of the field,
+    /// or the changed cells as a u32 count followed by the row and column of every cell as u32 and its state as u8
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match self {
+            Self::Snapshot { generation, field } => {
+                bytes.push(SNAPSHOT_FRAME);
+                bytes.extend_from_slice(&generation.to_le_bytes());
+                bytes.extend_from_slice(&field.to_bytes());
+            }
+            Self::Delta { generation, cells } => {
+                bytes.push(DELTA_FRAME);
+                bytes.extend_from_slice(&generation.to_le_bytes());
+                bytes.extend_from_slice(&(cells.len() as u32).to_le_bytes());
+                for &(row, col, value) in cells {
+                    bytes.extend_from_slice(&(row as u32).to_le_bytes());
+                    bytes.extend_from_slice(&(col as u32).to_le_bytes());
+                    bytes.push(value as u8);
+                }
+            }
+        }
+        bytes
+    }
+    pub fn decode(bytes: &[u8]) -> Result<Self, FrameError> {
+        let (&kind, rest) = bytes.split_first().ok_or(FrameError::Truncated)?;
+        let (generation, rest) = rest.split_first_chunk::<8>().ok_or(FrameError::Truncated)?;
+        let generation = u64::from_le_bytes(*generation);
+        match kind {
+            SNAPSHOT_FRAME => {
+                let field = Field::from_bytes(rest).map_err(FrameError::Snapshot)?;
+                Ok(Self::Snapshot { generation, field })
+            }
+            DELTA_FRAME => {
+                let (count, rest) = rest.split_first_chunk::<4>().ok_or(FrameError::Truncated)?;
+                let count = u32::from_le_bytes(*count) as usize;
+                if Some(rest.len()) != count.checked_mul(DELTA_CELL_SIZE) {
+                    return Err(FrameError::Truncated);
+                }
+                let cells = rest.chunks_exact(DELTA_CELL_SIZE)
+                    .map(|cell| {
+                        let row = u32::from_le_bytes(cell[0..4].try_into().unwrap()) as usize;
+                        let col = u32::from_le_bytes(cell[4..8].try_into().unwrap()) as usize;
+                        let value = match cell[8] {
+                            0 => CellValue::Dead,
+                            1 => CellValue::Alive,
+                            state => return Err(FrameError::InvalidState(state)),
+                        };
+                        Ok((row, col, value))
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(Self::Delta { generation, cells })
+            }
+            kind => Err(FrameError::UnknownKind(kind)),
+        }
+    }
+    pub fn generation(&self) -> u64 {
+        match self {
+            Self::Snapshot { generation, .. } | Self::Delta { generation, .. } => *generation,
+        }
+    }
+}
+
+/// Turns the states of a field into frames for its mirrors, only the changes are sent when that is smaller
+#[derive(Default)]
+pub struct Mirror {
+    /// The field as the viewers have it
+    last: Option<(u64, Field)>,
+}
+impl Mirror {
+    /// Frame that brings the viewers from the previous state to this one, None if nothing has changed
+    pub fn frame(&mut self, generation: u64, field: &Field) -> Option<Frame> {
+        let frame = match &self.last {
+            Some((last_generation, last)) if is_compatible(last, field) => {
+                let cells = last.diff(field);
+                if cells.is_empty() && (*last_generation == generation) {
+                    return None;
+                }
+                // a snapshot takes about a bit per cell
+                if cells.len() * DELTA_CELL_SIZE * 8 < field.get_width() * field.get_height() {
+                    Frame::Delta { generation, cells }
+                } else {
+                    Frame::Snapshot { generation, field: field.clone() }
+                }
+            }
+            _ => Frame::Snapshot { generation, field: field.clone() },
+        };
+        self.last = Some((generation, field.clone()));
+        Some(frame)
+    }
+    /// The next frame is a snapshot, e.g. after reconnecting
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+/// Deltas can only be applied to fields with the same size and rule
+fn is_compatible(last: &Field, field: &Field) -> bool {
+    (last.get_width() == field.get_width()) && (last.get_height() == field.get_height())
+        && (last.get_rule() == field.get_rule()) && (last.get_neighborhood() == field.get_neighborhood())
+        && (last.get_topology() == field.get_topology())
+}
+
+/// Delays between the attempts to reconnect, doubling from the initial one up to the maximum
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial_ms: u32,
+    max_ms: u32,
+    attempts: u32,
+}
+impl Backoff {
+    pub fn new(initial_ms: u32, max_ms: u32) -> Self {
+        Self { initial_ms, max_ms, attempts: 0 }
+    }
+    /// Delay before the next attempt
+    pub fn next_delay_ms(&mut self) -> u32 {
+        let delay = self.initial_ms.saturating_mul(1_u32.checked_shl(self.attempts).unwrap_or(u32::MAX)).min(self.max_ms);
+        self.attempts = self.attempts.saturating_add(1);
+        delay
+    }
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+    /// The connection is back, the next failure starts from the initial delay again
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::num::NonZeroUsize;
+    #[test]
+    fn test_mirror() {
+        let size = NonZeroUsize::new(32).unwrap();
+        let mut field = Field::new(size, size);
+        for col in 1..=3 {
+            field.set_by_coords(1, col, CellValue::Alive);
+        }
+        let mut mirror = Mirror::default();
+        let Some(Frame::Snapshot { generation: 0, field: mut viewer }) = mirror.frame(0, &field) else {
+            panic!("expected a snapshot");
+        };
+        assert!(mirror.frame(0, &field).is_none());
+
+        field.update();
+        let frame = mirror.frame(1, &field).unwrap();
+        assert!(matches!(frame, Frame::Delta { generation: 1, ref cells } if cells.len() == 4));
+        let Frame::Delta { cells, .. } = Frame::decode(&frame.encode()).unwrap() else { unreachable!() };
+        viewer.apply_diff(&cells);
+        assert_eq!(field.to_string(), viewer.to_string());
+
+        // only the generation has changed
+        assert!(matches!(mirror.frame(2, &field), Some(Frame::Delta { generation: 2, cells }) if cells.is_empty()));
+
+        mirror.reset();
+        let frame = mirror.frame(2, &field).unwrap();
+        let Ok(Frame::Snapshot { generation: 2, field: decoded }) = Frame::decode(&frame.encode()) else {
+            panic!("expected a snapshot");
+        };
+        assert_eq!(field.to_string(), decoded.to_string());
+    }
+    #[test]
+    fn test_decode_errors() {
+        let error = |bytes: &[u8]| Frame::decode(bytes).err();
+        assert_eq!(Some(FrameError::Truncated), error(&[]));
+        assert_eq!(Some(FrameError::UnknownKind(9)), error(&[9, 0, 0, 0, 0, 0, 0, 0, 0]));
+        let delta = Frame::Delta { generation: 1, cells: vec![(0, 1, CellValue::Alive)] }.encode();
+        assert_eq!(Some(FrameError::Truncated), error(&delta[..delta.len() - 1]));
+        let mut invalid = delta.clone();
+        *invalid.last_mut().unwrap() = 7;
+        assert_eq!(Some(FrameError::InvalidState(7)), error(&invalid));
+        // a count that would overflow the size of the cells
+        let mut huge = delta[..9].to_vec();
+        huge.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(Some(FrameError::Truncated), error(&huge));
+        assert_eq!(Some(FrameError::Snapshot(SnapshotError::BadMagic)), error(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4]));
+    }
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new(500, 4000);
+        let delays: Vec<_> = (0..6).map(|_| backoff.next_delay_ms()).collect();
+        assert_eq!(vec![500, 1000, 2000, 4000, 4000, 4000], delays);
+        backoff.reset();
+        assert_eq!(500, backoff.next_delay_ms());
+        for _ in 0..100 {
+            backoff.next_delay_ms();
+        }
+        assert_eq!(4000, backoff.next_delay_ms());
+    }
+}
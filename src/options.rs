@@ -0,0 +1,291 @@
+use std::num::NonZeroUsize;
+use serde::Deserialize;
+use wasm_bindgen::JsValue;
+use crate::adaptive::DEFAULT_BUDGET_MS;
+use crate::frame_stats::FRAME_HISTORY_SIZE;
+use crate::history::DEFAULT_HISTORY_SIZE;
+use crate::log::Level;
+use crate::render::{CELL_SIZE_PX, MAX_CELL_SIZE_PX, MIN_CELL_SIZE_PX};
+use crate::theme::Theme;
+
+/// Snapshots are copies of the whole field, so the history can't be much longer
+const MAX_HISTORY_SIZE: usize = 10_000;
+/// Width and height of the largest initial field, the cells of a bigger one don't fit in the memory of wasm32
+pub const MAX_FIELD_SIZE: usize = 4096;
+
+/// Startup options of the app, passed from JS as an object. Missing properties use the defaults
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct Options {
+    /// Width and height of the initial field in cells
+    pub field_size: NonZeroUsize,
+    /// Size of a cell on the screen in css pixels, not including the grid line
+    pub cell_size: usize,
+    /// Make the cells smaller than `cell_size` when the field doesn't fit the width of the container, also after resizes
+    pub fit: bool,
+    /// Life rulestring, like "B3/S23"
+    pub rule: Option<String>,
+    /// Name of a predefined pattern, or cells in the text format with `#` for live cells and `_` for dead ones
+    pub pattern: Option<String>,
+    /// Percentage of live cells in random fields
+    pub density: f64,
+    pub theme: ThemeName,
+    /// Start the animation right away
+    pub autoplay: bool,
+    /// Pause when the field becomes a still life or starts repeating with a short period
+    pub auto_pause: bool,
+    /// Limit the animation speed, otherwise a generation is drawn every animation frame
+    pub target_fps: Option<f64>,
+    /// Run at a lower speed by default. Not set means following the `prefers-reduced-motion` setting of the browser
+    pub reduced_motion: Option<bool>,
+    /// Minimum time between updates of the fps statistics in milliseconds
+    pub fps_update_interval: f64,
+    /// Number of recent frames in the fps statistics, up to `FRAME_HISTORY_SIZE`
+    pub fps_window: usize,
+    /// Switch to the coarse renderer and then to a lower speed when the generations take longer than the frame budget
+    pub adaptive: bool,
+    /// Time of a generation in milliseconds that the adaptive quality tries to stay within
+    pub frame_budget: f64,
+    /// Minimum level of logged messages, shared by all apps on the page
+    pub log_level: Option<Level>,
+    /// Show the log on the page, for browsers without devtools
+    pub log_panel: bool,
+    /// Measure the phases of each frame with `performance.measure`, shared by all apps on the page
+    pub profile: bool,
+    /// Number of recent generations that can be scrubbed through while paused, up to `MAX_HISTORY_SIZE`. 0 turns the history off
+    pub history_size: usize,
+}
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            field_size: NonZeroUsize::new(64).unwrap(),
+            cell_size: CELL_SIZE_PX,
+            fit: true,
+            rule: None,
+            pattern: None,
+            density: 50.0,
+            theme: ThemeName::Light,
+            autoplay: false,
+            auto_pause: false,
+            target_fps: None,
+            reduced_motion: None,
+            fps_update_interval: 250.0,
+            fps_window: FRAME_HISTORY_SIZE,
+            adaptive: false,
+            frame_budget: DEFAULT_BUDGET_MS,
+            log_level: None,
+            log_panel: false,
+            profile: false,
+            history_size: DEFAULT_HISTORY_SIZE,
+        }
+    }
+}
+impl Options {
+    /// Reads the options from a JS object, `undefined` and `null` mean the default options
+    pub fn from_js(value: JsValue) -> Result<Self, JsValue> {
+        if value.is_undefined() || value.is_null() {
+            return Ok(Self::default());
+        }
+        let options = serde_wasm_bindgen::from_value::<Self>(value)?;
+        options.validate().map_err(|err| JsValue::from_str(&err))?;
+        Ok(options)
+    }
+    /// Overrides the options with the parameters of a query string like `?size=200&rule=B36/S23&autoplay=1`.
+    /// Unknown parameters are ignored, so that the page can have its own
+    pub fn apply_query(&mut self, query: &str) -> Result<(), String> {
+        let query = query.strip_prefix('?').unwrap_or(query);
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value).ok_or_else(|| format!("invalid encoding of {key}"))?;
+            self.set(key, value)?;
+        }
+        self.validate()
+    }
+    /// Overrides the options with attributes of an element, like `<game-of-life width="128" autoplay>`
+    pub fn apply_attributes<'a>(&mut self, attributes: impl IntoIterator<Item = (&'a str, String)>) -> Result<(), String> {
+        for (name, value) in attributes {
+            self.set(name, value)?;
+        }
+        self.validate()
+    }
+    /// Sets the option by the name used in query strings and attributes, unknown names are ignored
+    fn set(&mut self, key: &str, value: String) -> Result<(), String> {
+        let invalid = || format!("invalid value of {key}: {value}");
+        match key {
+            "size" | "width" => self.field_size = value.parse().map_err(|_| invalid())?,
+            "cellsize" | "cell-size" => self.cell_size = value.parse().map_err(|_| invalid())?,
+            "density" => self.density = value.parse().map_err(|_| invalid())?,
+            "fps" => self.target_fps = Some(value.parse().map_err(|_| invalid())?),
+            "fpsinterval" | "fps-interval" => self.fps_update_interval = value.parse().map_err(|_| invalid())?,
+            "fpswindow" | "fps-window" => self.fps_window = value.parse().map_err(|_| invalid())?,
+            "fit" => self.fit = parse_flag(&value).ok_or_else(invalid)?,
+            "reducedmotion" | "reduced-motion" => self.reduced_motion = Some(parse_flag(&value).ok_or_else(invalid)?),
+            "adaptive" => self.adaptive = parse_flag(&value).ok_or_else(invalid)?,
+            "framebudget" | "frame-budget" => self.frame_budget = value.parse().map_err(|_| invalid())?,
+            "theme" => {
+                self.theme = match value.as_str() {
+                    "light" => ThemeName::Light,
+                    "dark" => ThemeName::Dark,
+                    _ => return Err(invalid()),
+                }
+            }
+            "autoplay" => self.autoplay = parse_flag(&value).ok_or_else(invalid)?,
+            "autopause" | "auto-pause" => self.auto_pause = parse_flag(&value).ok_or_else(invalid)?,
+            "loglevel" | "log-level" => self.log_level = Some(value.parse().map_err(|_| invalid())?),
+            "logpanel" | "log-panel" => self.log_panel = parse_flag(&value).ok_or_else(invalid)?,
+            "history" | "history-size" => self.history_size = value.parse().map_err(|_| invalid())?,
+            "profile" => self.profile = parse_flag(&value).ok_or_else(invalid)?,
+            "rule" => self.rule = Some(value),
+            "pattern" => self.pattern = Some(value),
+            _ => {}
+        }
+        Ok(())
+    }
+    fn validate(&self) -> Result<(), String> {
+        if self.field_size.get() > MAX_FIELD_SIZE {
+            return Err(format!("field size must be up to {MAX_FIELD_SIZE}"));
+        }
+        if !(MIN_CELL_SIZE_PX..=MAX_CELL_SIZE_PX).contains(&self.cell_size) {
+            return Err(format!("cell size must be from {MIN_CELL_SIZE_PX} to {MAX_CELL_SIZE_PX}"));
+        }
+        if !(0.0..=100.0).contains(&self.density) {
+            return Err("density must be from 0 to 100".to_string());
+        }
+        if self.target_fps.is_some_and(|fps| fps.is_nan() || (fps <= 0.0)) {
+            return Err("target fps must be positive".to_string());
+        }
+        if self.fps_update_interval.is_nan() || (self.fps_update_interval < 0.0) {
+            return Err("fps update interval must not be negative".to_string());
+        }
+        if self.frame_budget.is_nan() || (self.frame_budget <= 0.0) {
+            return Err("frame budget must be positive".to_string());
+        }
+        if !(1..=FRAME_HISTORY_SIZE).contains(&self.fps_window) {
+            return Err(format!("fps window must be from 1 to {FRAME_HISTORY_SIZE}"));
+        }
+        if self.history_size > MAX_HISTORY_SIZE {
+            return Err(format!("history size must be up to {MAX_HISTORY_SIZE}"));
+        }
+        Ok(())
+    }
+}
+
+/// Query string that starts the app with this rule and pattern, for links to the current field.
+/// The pattern is a predefined name or cells in the text format, like the `pattern` option
+pub fn permalink_query(rule: &str, pattern: &str) -> String {
+    format!("?rule={}&pattern={}", percent_encode(rule), percent_encode(pattern))
+}
+
+/// Flags are set by an empty value, like in `?autoplay` and `<game-of-life autoplay>`
+fn parse_flag(value: &str) -> Option<bool> {
+    match value {
+        "" | "1" | "true" => Some(true),
+        "0" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Decodes `%XX` escapes and `+` as a space, returns None for invalid escapes or UTF-8
+fn percent_decode(str: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(str.len());
+    let mut iter = str.bytes();
+    while let Some(byte) = iter.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = [iter.next()?, iter.next()?];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Escapes everything except the unreserved characters of urls, the inverse of `percent_decode`
+fn percent_encode(str: &str) -> String {
+    let mut encoded = String::with_capacity(str.len());
+    for byte in str.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeName {
+    Light,
+    Dark,
+}
+impl ThemeName {
+    pub fn theme(self) -> Theme {
+        match self {
+            Self::Light => Theme::light(),
+            Self::Dark => Theme::dark(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_apply_query() {
+        let mut options = Options::default();
+        options.apply_query("?size=200&rule=B36%2FS23&density=30&autoplay=1&pattern=glidergun&other=x&loglevel=debug&logpanel&fpswindow=30&profile=0&adaptive&framebudget=25&history=20&autopause&fit=0&reducedmotion=0").unwrap();
+        assert_eq!(200, options.field_size.get());
+        assert_eq!(Some("B36/S23".to_string()), options.rule);
+        assert_eq!(30.0, options.density);
+        assert!(options.autoplay);
+        assert_eq!(Some("glidergun".to_string()), options.pattern);
+        assert_eq!(ThemeName::Light, options.theme);
+        assert_eq!(Some(Level::Debug), options.log_level);
+        assert!(options.log_panel);
+        assert_eq!(30, options.fps_window);
+        assert!(!options.profile);
+        assert!(options.adaptive);
+        assert_eq!(25.0, options.frame_budget);
+        assert_eq!(20, options.history_size);
+        assert!(options.auto_pause);
+        assert!(!options.fit);
+        assert_eq!(Some(false), options.reduced_motion);
+
+        assert!(Options::default().apply_query("").is_ok());
+        assert!(Options::default().apply_query("size=0").is_err());
+        assert!(Options::default().apply_query("size=70000").is_err());
+        assert!(Options::default().apply_query(&format!("size={MAX_FIELD_SIZE}")).is_ok());
+        assert!(Options::default().apply_query("density=101").is_err());
+        assert!(Options::default().apply_query("rule=%2").is_err());
+        assert!(Options::default().apply_query("loglevel=trace").is_err());
+        assert!(Options::default().apply_query("fpsinterval=-1").is_err());
+        assert!(Options::default().apply_query("fpswindow=0").is_err());
+        assert!(Options::default().apply_query("framebudget=0").is_err());
+        assert!(Options::default().apply_query("history=10001").is_err());
+        assert!(Options::default().apply_query("reducedmotion=x").is_err());
+        assert!(Options::default().apply_query(&format!("fpswindow={}", FRAME_HISTORY_SIZE + 1)).is_err());
+    }
+    #[test]
+    fn test_apply_attributes() {
+        let mut options = Options::default();
+        options.apply_attributes([("width", "128".to_string()), ("autoplay", String::new()), ("class", "x".to_string())]).unwrap();
+        assert_eq!(128, options.field_size.get());
+        assert!(options.autoplay);
+        assert!(Options::default().apply_attributes([("cell-size", "0".to_string())]).is_err());
+        assert!(Options::default().apply_attributes([("width", "70000".to_string())]).is_err());
+    }
+    #[test]
+    fn test_permalink_query() {
+        let pattern = "_#_\n__#\n###\n";
+        let query = permalink_query("B36/S23", pattern);
+        assert_eq!("?rule=B36%2FS23&pattern=_%23_%0A__%23%0A%23%23%23%0A", query);
+        let mut options = Options::default();
+        options.apply_query(&query).unwrap();
+        assert_eq!(Some("B36/S23".to_string()), options.rule);
+        assert_eq!(Some(pattern.to_string()), options.pattern);
+        assert_eq!("?rule=&pattern=%C3%A9%20", permalink_query("", "é "));
+    }
+}
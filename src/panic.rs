@@ -0,0 +1,103 @@
+use std::cell::{Cell, RefCell};
+use std::panic::PanicHookInfo;
+use std::rc::{Rc, Weak};
+use std::sync::Once;
+use web_sys::{Document, Element};
+use crate::automaton::DynAutomaton;
+use crate::rle::to_rle;
+
+/// Access to the field of an app for the overlay
+pub trait FieldSource {
+    /// Calls `read` with the field, returns false if it is not available because the app is being updated
+    fn with_field(&self, read: &mut dyn FnMut(&dyn DynAutomaton)) -> bool;
+}
+
+/// What the panic overlay needs to know about a running app
+struct AppInfo {
+    root: Element,
+    source: Weak<dyn FieldSource>,
+    generation: Rc<Cell<u64>>,
+}
+
+thread_local! {
+    static APPS: RefCell<Vec<AppInfo>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Installs the panic hook that shows the overlay, also logs the panic with `console_error_panic_hook` if it's enabled
+pub fn install_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        std::panic::set_hook(Box::new(|info| {
+            #[cfg(feature = "console_error_panic_hook")]
+            console_error_panic_hook::hook(info);
+            show_overlay(info);
+        }));
+    });
+}
+
+/// Adds the app to the state dump of the overlay, until it is unregistered or its field source is dropped
+pub fn register(root: &Element, source: Weak<dyn FieldSource>, generation: Rc<Cell<u64>>) {
+    APPS.with_borrow_mut(|apps| {
+        apps.retain(|app| app.source.strong_count() > 0);
+        apps.push(AppInfo { root: root.clone(), source, generation });
+    });
+}
+
+pub fn unregister(root: &Element) {
+    APPS.with_borrow_mut(|apps| apps.retain(|app| (app.root != *root) && (app.source.strong_count() > 0)));
+}
+
+/// Covers the page with the panic message and the state of every app, so that the bug can be reported and reproduced.
+/// Wasm can't be called after a panic, so the overlay only uses plain elements
+fn show_overlay(info: &PanicHookInfo) {
+    let Some(document) = web_sys::window().and_then(|window| window.document()) else {
+        return;
+    };
+    // the app is already broken, so failures to show the overlay are ignored
+    let _ = create_overlay(&document, info);
+}
+
+fn create_overlay(document: &Document, info: &PanicHookInfo) -> Option<()> {
+    let overlay = document.create_element("div").ok()?;
+    overlay.set_attribute(
+        "style",
+        "position: fixed; left: 0; top: 0; right: 0; bottom: 0; z-index: 1000; overflow: auto; padding: 20px; \
+        background-color: rgba(0, 0, 0, 0.8); color: #FFFFFF; font: 14px monospace;",
+    ).ok()?;
+    append_text(document, &overlay, "h2", "The game of life has crashed")?;
+    append_text(document, &overlay, "pre", &info.to_string())?;
+    append_text(document, &overlay, "p", "Please report the bug with this message and the state of the field below.")?;
+
+    // the failed update may still hold the field, in that case it can't be read
+    let states = APPS.try_with(|apps| {
+        let apps = apps.try_borrow().ok()?;
+        Some(apps.iter().map(|app| {
+            let mut state = None;
+            if let Some(source) = app.source.upgrade() {
+                source.with_field(&mut |field| state = Some((field.rule(), to_rle(field))));
+            }
+            let (rule, rle) = state.unwrap_or((None, "not available, the field was being updated".to_string()));
+            (app.generation.get(), rule.unwrap_or_else(|| "unknown".to_string()), rle)
+        }).collect::<Vec<_>>())
+    }).ok().flatten().unwrap_or_default();
+    for (generation, rule, rle) in states {
+        append_text(document, &overlay, "p", &format!("Generation {generation}, rule {rule}"))?;
+        let textarea = document.create_element("textarea").ok()?;
+        textarea.set_attribute("readonly", "").ok()?;
+        textarea.set_attribute("rows", "10").ok()?;
+        textarea.set_attribute("style", "width: 100%; font: 12px monospace;").ok()?;
+        // selecting on focus makes the RLE easy to copy without calling into wasm
+        textarea.set_attribute("onfocus", "this.select()").ok()?;
+        textarea.set_text_content(Some(&rle));
+        overlay.append_child(&textarea).ok()?;
+    }
+    document.body()?.append_child(&overlay).ok()?;
+    Some(())
+}
+
+fn append_text(document: &Document, parent: &Element, tag: &str, text: &str) -> Option<()> {
+    let element = document.create_element(tag).ok()?;
+    element.set_text_content(Some(text));
+    parent.append_child(&element).ok()?;
+    Some(())
+}
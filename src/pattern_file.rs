@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::rc::Rc;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Document, DragEvent, File, FileSystemFileHandle, FileSystemWritableFileStream, HtmlCanvasElement, HtmlInputElement, ImageBitmap, Response, Window};
+use crate::app::{add_event_listener, create_button, show_new_field, App, AppDispatcher, Message, Seed};
+use crate::control_panel::Group;
+use crate::error::AppError;
+use crate::export::download_blob;
+use crate::game_of_life::{CellValue, Field};
+use crate::image_import;
+use crate::infinite_life::InfiniteField;
+use crate::listeners::Listeners;
+use crate::macrocell::{self, parse_macrocell, Macrocell};
+use crate::render::context_2d;
+use crate::rle::{to_rle, PatternFormat};
+
+/// Images are decoded at up to this many pixels across a cell, so that every cell averages several pixels
+const IMAGE_SAMPLES_PER_CELL: u32 = 4;
+/// Extensions offered by the file pickers, with the mime type they are grouped under.
+/// Macrocell files can be opened, but they are saved separately
+const PATTERN_EXTENSIONS: [&str; 2] = [".rle", ".cells"];
+const OPEN_EXTENSIONS: [&str; 3] = [".rle", ".cells", ".mc"];
+const PATTERN_MIME_TYPE: &str = "text/plain";
+/// The pickers start in the directory that was used last for pattern files
+const PICKER_ID: &str = "patterns";
+
+// the pickers are unstable in web-sys
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(catch, js_name = showOpenFilePicker)]
+    fn show_open_file_picker(options: &JsValue) -> Result<js_sys::Promise, JsValue>;
+    #[wasm_bindgen(catch, js_name = showSaveFilePicker)]
+    fn show_save_file_picker(options: &JsValue) -> Result<js_sys::Promise, JsValue>;
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileType {
+    description: &'static str,
+    accept: HashMap<&'static str, Vec<&'static str>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PickerOptions<'a> {
+    id: &'static str,
+    types: Vec<FileType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suggested_name: Option<&'a str>,
+}
+impl<'a> PickerOptions<'a> {
+    fn new(extensions: &[&'static str], suggested_name: Option<&'a str>) -> Self {
+        let accept = HashMap::from([(PATTERN_MIME_TYPE, extensions.to_vec())]);
+        Self { id: PICKER_ID, types: vec![FileType { description: "Life patterns", accept }], suggested_name }
+    }
+    fn to_js(&self) -> Result<JsValue, AppError> {
+        // the accepted types are a plain object, not a Map
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        Ok(self.serialize(&serializer).map_err(JsValue::from)?)
+    }
+}
+
+/// Requests of the pattern file controls, and the files dropped on the field
+pub enum FileCommand {
+    /// Lets the user choose a pattern file, its field is loaded with `Opened`
+    Open,
+    /// Saves the field as a pattern file in the format of the chosen file name, or downloads it as RLE
+    /// where files can't be written directly
+    SaveAs,
+    /// Replaces the field with the pattern from the file with this name and text.
+    /// Macrocell files are expanded into the cells of an unbounded field, patterns with too many cells for that are refused
+    Opened(String, String),
+    ExportMacrocell,
+    /// Fetches the pattern file from the url, its field is loaded with `Opened`
+    LoadUrl(String),
+    /// Lets files be dropped on the field
+    DragOver(DragEvent),
+    /// Loads a dropped pattern file, or turns a dropped image into a life field of the same size as the field
+    Drop(DragEvent),
+}
+
+impl App {
+    pub fn update_file(&mut self, command: FileCommand) -> Result<(), AppError> {
+        match command {
+            FileCommand::Open => {
+                if self.confirm_discard() {
+                    let window = self.window.clone();
+                    self.spawn(async move { Ok(open(&window).await?.map(|(name, text)| Message::File(FileCommand::Opened(name, text)))) });
+                }
+                Ok(())
+            }
+            FileCommand::SaveAs => self.save_file_as(),
+            FileCommand::Opened(name, text) if is_macrocell_file(&name) => self.open_macrocell(&name, &text),
+            FileCommand::Opened(name, text) => match PatternFormat::from_file_name(&name).parse(&text) {
+                Ok(field) => self.show_life_field(field),
+                Err(err) => Err(AppError::InvalidBoard(format!("{name}: {err}"))),
+            },
+            FileCommand::ExportMacrocell => self.export_macrocell(),
+            FileCommand::LoadUrl(url) if url.is_empty() => Err(AppError::InvalidInput("enter the url of a pattern file".to_string())),
+            FileCommand::LoadUrl(url) => {
+                if self.confirm_discard() {
+                    let window = self.window.clone();
+                    self.spawn(async move {
+                        let (name, text) = fetch(&window, &url).await?;
+                        Ok(Some(Message::File(FileCommand::Opened(name, text))))
+                    });
+                }
+                Ok(())
+            }
+            FileCommand::DragOver(event) => {
+                // the browser would open the file otherwise
+                event.prevent_default();
+                Ok(())
+            }
+            FileCommand::Drop(event) => self.drop_file(&event),
+        }
+    }
+
+    fn save_file_as(&mut self) -> Result<(), AppError> {
+        if !is_supported(&self.window) {
+            let blob = web_sys::Blob::new_with_str_sequence(&js_sys::Array::of1(&to_rle(self.field.as_ref()).into()))?;
+            return download_blob(&self.document, &blob, "field.rle");
+        }
+        let field = self.field.clone_box();
+        self.spawn(async move {
+            save("field.rle", |format| format.write(field.as_ref())).await?;
+            Ok(None)
+        });
+        Ok(())
+    }
+
+    /// Shows the macrocell pattern in an unbounded field of the current size
+    fn open_macrocell(&mut self, name: &str, text: &str) -> Result<(), AppError> {
+        let macrocell = parse_macrocell(text).map_err(|err| AppError::InvalidBoard(format!("{name}: {err}")))?;
+        let width = NonZeroUsize::new(self.field.get_width()).ok_or(AppError::Missing("field width"))?;
+        let height = NonZeroUsize::new(self.field.get_height()).ok_or(AppError::Missing("field height"))?;
+        let field = macrocell.to_infinite_field(width, height).map_err(|err| AppError::InvalidBoard(format!("{name}: {err}")))?;
+        self.state.life_rule = macrocell.get_rule();
+        self.field = Box::new(field);
+        self.seed = Seed { field: self.field.clone_box(), replay: None };
+        let result = show_new_field(&mut self.state, &self.context, self.field.as_mut(), None);
+        self.update_scrubber();
+        self.queue_field_changed();
+        result
+    }
+
+    /// Downloads the Life field as a macrocell file, unbounded fields with their cells outside of the view
+    fn export_macrocell(&mut self) -> Result<(), AppError> {
+        let field = self.field.as_any_mut();
+        let macrocell = if let Some(field) = field.downcast_ref::<InfiniteField>() {
+            Macrocell::from(field)
+        } else if let Some(field) = field.downcast_ref::<Field>() {
+            let width = field.get_width();
+            let cells = field.view().iter().enumerate()
+                .filter(|&(_, &cell)| cell == CellValue::Alive)
+                .map(|(index, _)| ((index / width) as i64, (index % width) as i64));
+            Macrocell::from_cells(field.get_rule(), cells)
+        } else {
+            return Err(AppError::Unsupported("only Life fields can be saved as macrocells"));
+        };
+        let text = JsValue::from_str(&macrocell.to_string());
+        let blob = web_sys::Blob::new_with_str_sequence(&js_sys::Array::of1(&text))?;
+        download_blob(&self.document, &blob, &format!("field.{}", macrocell::EXTENSION))
+    }
+
+    fn drop_file(&mut self, event: &web_sys::DragEvent) -> Result<(), AppError> {
+        event.prevent_default();
+        let Some(file) = event.data_transfer().and_then(|transfer| transfer.files()).and_then(|files| files.get(0)) else {
+            return Ok(());
+        };
+        if !self.confirm_discard() {
+            return Ok(());
+        }
+        if !file.type_().starts_with("image/") {
+            self.spawn(async move { Ok(Some(Message::File(FileCommand::Opened(file.name(), read_text(&file).await?)))) });
+            return Ok(());
+        }
+        let window = self.window.clone();
+        let width = NonZeroUsize::new(self.field.get_width()).ok_or(AppError::Missing("field"))?;
+        let height = NonZeroUsize::new(self.field.get_height()).ok_or(AppError::Missing("field"))?;
+        let rule = self.state.life_rule;
+        self.spawn(async move {
+            let (pixels, image_width, image_height) = read_image(&window, &file, width.get(), height.get()).await?;
+            let field = image_import::field_from_rgba(&pixels, image_width, image_height, width, height, image_import::DEFAULT_THRESHOLD);
+            Ok(Some(Message::ShowField(field.with_rule(rule))))
+        });
+        Ok(())
+    }
+}
+
+/// Adds the buttons that open and save pattern files and the url input to the group, and lets files be dropped on the field
+pub fn add_file_controls(
+    group: &Group,
+    canvas: &HtmlCanvasElement,
+    document: &Document,
+    listeners: &mut Listeners,
+    dispatcher: &Rc<AppDispatcher>,
+) -> Result<(), AppError> {
+    let open_file_button = create_button("Open File…", || Message::File(FileCommand::Open), document, listeners, dispatcher)?;
+    open_file_button.set_attribute("title", "Open a .rle or .cells pattern file")?;
+    group.add(&open_file_button)?;
+
+    let save_file_button = create_button("Save As…", || Message::File(FileCommand::SaveAs), document, listeners, dispatcher)?;
+    save_file_button.set_attribute("title", "Save the field as a .rle or .cells pattern file")?;
+    group.add(&save_file_button)?;
+
+    let macrocell_button = create_button("Export Macrocell", || Message::File(FileCommand::ExportMacrocell), document, listeners, dispatcher)?;
+    macrocell_button.set_attribute("title", "Download the field as a Golly .mc file, which stores repeated parts of big patterns once")?;
+    group.add(&macrocell_button)?;
+
+    let url_input = create_url_input(document, listeners, dispatcher)?;
+    group.add(&url_input)?;
+
+    add_event_listener(listeners, canvas, &["dragover"], dispatcher, |event| Message::File(FileCommand::DragOver(event)))?;
+    add_event_listener(listeners, canvas, &["drop"], dispatcher, |event| Message::File(FileCommand::Drop(event)))
+}
+
+fn create_url_input(document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<web_sys::Element, AppError> {
+    let container = document.create_element("span")?;
+    let url_input = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    url_input.set_type("url");
+    url_input.set_placeholder("https://…/pattern.rle");
+    url_input.set_title("Url of a .rle or .cells pattern file");
+    container.append_child(&url_input)?;
+
+    let message = move || Message::File(FileCommand::LoadUrl(url_input.value().trim().to_string()));
+    let button = create_button("Load from URL", message, document, listeners, dispatcher)?;
+    container.append_child(&button)?;
+    Ok(container)
+}
+
+fn is_macrocell_file(name: &str) -> bool {
+    name.to_ascii_lowercase().ends_with(&format!(".{}", macrocell::EXTENSION))
+}
+
+/// True if the browser can open and save files on disk directly, with the File System Access API
+pub fn is_supported(window: &Window) -> bool {
+    js_sys::Reflect::has(window, &"showOpenFilePicker".into()).unwrap_or(false)
+}
+
+/// The user has closed the picker without choosing a file
+fn is_cancelled(err: &JsValue) -> bool {
+    err.dyn_ref::<web_sys::DomException>().is_some_and(|err| err.name() == "AbortError")
+}
+
+/// Lets the user choose a pattern file and returns its name and text, None if the choice was cancelled.
+/// Uses a file input where the File System Access API is not supported
+pub async fn open(window: &Window) -> Result<Option<(String, String)>, AppError> {
+    let file = if is_supported(window) {
+        let picked = match JsFuture::from(show_open_file_picker(&PickerOptions::new(&OPEN_EXTENSIONS, None).to_js()?)?).await {
+            Ok(picked) => picked,
+            Err(err) if is_cancelled(&err) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let handle = js_sys::Array::from(&picked).get(0).dyn_into::<FileSystemFileHandle>()?;
+        JsFuture::from(handle.get_file()).await?.dyn_into::<File>()?
+    } else {
+        let document = window.document().ok_or(AppError::Missing("document"))?;
+        match choose_file(&document).await? {
+            Some(file) => file,
+            None => return Ok(None),
+        }
+    };
+    let text = read_text(&file).await?;
+    Ok(Some((file.name(), text)))
+}
+
+/// Lets the user choose where to save the pattern and writes it there, in the format of the chosen file name.
+/// Returns false if the choice was cancelled. Only for browsers that support the File System Access API, see `is_supported`
+pub async fn save(suggested_name: &str, write: impl FnOnce(PatternFormat) -> String) -> Result<bool, AppError> {
+    let handle = match JsFuture::from(show_save_file_picker(&PickerOptions::new(&PATTERN_EXTENSIONS, Some(suggested_name)).to_js()?)?).await {
+        Ok(handle) => handle.dyn_into::<FileSystemFileHandle>()?,
+        Err(err) if is_cancelled(&err) => return Ok(false),
+        Err(err) => return Err(err.into()),
+    };
+    let text = write(PatternFormat::from_file_name(&handle.name()));
+    let stream = JsFuture::from(handle.create_writable()).await?.dyn_into::<FileSystemWritableFileStream>()?;
+    JsFuture::from(stream.write_with_str(&text)?).await?;
+    // the file is only replaced when the stream is closed
+    JsFuture::from(stream.close()).await?;
+    Ok(true)
+}
+
+/// Downloads the pattern file from the url, relative to the page, and returns its name and text.
+/// The server has to allow cross-origin requests for urls on other sites
+pub async fn fetch(window: &Window, url: &str) -> Result<(String, String), AppError> {
+    let url = web_sys::Url::new_with_base(url, &window.location().href()?).map_err(|_| AppError::Network(format!("invalid url {url}")))?;
+    let href = url.href();
+    let response = match JsFuture::from(window.fetch_with_str(&href)).await {
+        Ok(response) => response.dyn_into::<Response>()?,
+        // the browser doesn't tell why for security reasons, blocked cross-origin requests are the usual cause
+        Err(_) => return Err(AppError::Network(format!("can't fetch {href}, the server may not allow requests from this page"))),
+    };
+    if !response.ok() {
+        return Err(AppError::Network(format!("{href} responded with {} {}", response.status(), response.status_text())));
+    }
+    let text = JsFuture::from(response.text()?).await?.as_string().ok_or(AppError::Missing("response text"))?;
+    let name = url.pathname().rsplit('/').next().unwrap_or_default().to_string();
+    Ok((name, text))
+}
+
+/// Opens the file dialog of a temporary file input. Resolves to None if the dialog is cancelled,
+/// in browsers that report it
+async fn choose_file(document: &Document) -> Result<Option<File>, AppError> {
+    let input = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    input.set_type("file");
+    input.set_accept(&OPEN_EXTENSIONS.join(","));
+    let chosen = js_sys::Promise::new(&mut |resolve, _reject| {
+        let on_change = {
+            let input = input.clone();
+            let resolve = resolve.clone();
+            Closure::once_into_js(move || {
+                let file = input.files().and_then(|files| files.get(0));
+                let _ = resolve.call1(&JsValue::NULL, &file.map_or(JsValue::NULL, JsValue::from));
+            })
+        };
+        input.set_onchange(Some(on_change.unchecked_ref()));
+        let on_cancel = Closure::once_into_js(move || {
+            let _ = resolve.call1(&JsValue::NULL, &JsValue::NULL);
+        });
+        let _ = input.add_event_listener_with_callback("cancel", on_cancel.unchecked_ref());
+    });
+    input.click();
+    let file = JsFuture::from(chosen).await?;
+    Ok(file.dyn_into::<File>().ok())
+}
+
+/// Reads the file as text with a `FileReader`
+pub async fn read_text(file: &File) -> Result<String, AppError> {
+    let reader = web_sys::FileReader::new()?;
+    let loaded = js_sys::Promise::new(&mut |resolve, reject| {
+        let on_load = {
+            let reader = reader.clone();
+            Closure::once_into_js(move || {
+                let _ = resolve.call1(&JsValue::NULL, &reader.result().unwrap_or(JsValue::NULL));
+            })
+        };
+        reader.set_onload(Some(on_load.unchecked_ref()));
+        let on_error = {
+            let reader = reader.clone();
+            Closure::once_into_js(move || {
+                let _ = reject.call1(&JsValue::NULL, &reader.error().map_or(JsValue::NULL, JsValue::from));
+            })
+        };
+        reader.set_onerror(Some(on_error.unchecked_ref()));
+    });
+    reader.read_as_text(file)?;
+    JsFuture::from(loaded).await?.as_string().ok_or(AppError::Missing("file text"))
+}
+
+/// Decodes the image file and returns its RGBA bytes with the width and height. Big images are scaled down first,
+/// to no more than a few pixels per cell of a field of the given size
+pub async fn read_image(window: &Window, file: &File, field_width: usize, field_height: usize) -> Result<(Vec<u8>, usize, usize), AppError> {
+    let bitmap = JsFuture::from(window.create_image_bitmap_with_blob(file)?).await
+        .map_err(|_| AppError::InvalidBoard(format!("{} is not an image the browser can read", file.name())))?
+        .dyn_into::<ImageBitmap>()?;
+    let width = bitmap.width().min(field_width as u32 * IMAGE_SAMPLES_PER_CELL).max(1);
+    let height = bitmap.height().min(field_height as u32 * IMAGE_SAMPLES_PER_CELL).max(1);
+    let document = window.document().ok_or(AppError::Missing("document"))?;
+    let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+    canvas.set_width(width);
+    canvas.set_height(height);
+    let context = context_2d(&canvas)?;
+    context.draw_image_with_image_bitmap_and_dw_and_dh(&bitmap, 0.0, 0.0, width as f64, height as f64)?;
+    bitmap.close();
+    let image = context.get_image_data(0.0, 0.0, width as f64, height as f64)?;
+    Ok((image.data().0, width as usize, height as usize))
+}
@@ -0,0 +1,430 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Document, MessageEvent, RtcConfiguration, RtcDataChannel, RtcDataChannelEvent, RtcDataChannelState, RtcIceGatheringState, RtcIceServer, RtcPeerConnection, RtcSdpType, RtcSessionDescriptionInit};
+use crate::app::{create_button, App, AppDispatcher, Message};
+use crate::console_warn;
+use crate::error::AppError;
+use crate::game_of_life::{Field, Rule, Topology};
+use crate::listeners::Listeners;
+use crate::options;
+use crate::replay::Change;
+use crate::rle::{to_rle, PatternFormat};
+
+/// Public STUN server, so that the peers can find each other also behind a NAT
+const STUN_SERVER: &str = "stun:stun.l.google.com:19302";
+const CHANNEL_LABEL: &str = "life";
+
+/// Handlers of the connection and its channel
+type Handlers = RefCell<Vec<Closure<dyn Fn(JsValue)>>>;
+
+/// What the peers tell each other, as JSON text messages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum PeerMessage {
+    /// The whole board, sent by the side that made the offer when the channel opens. RLE has no topology, so it is sent
+    /// next to it, a board without one is a torus
+    Board {
+        rle: String,
+        generation: u64,
+        #[serde(default)]
+        topology: Topology,
+    },
+    /// An edit of the board
+    Change { change: Change },
+    /// Play or pause
+    #[serde(rename_all = "camelCase")]
+    Running { is_running: bool },
+}
+
+/// Events of the data channel, they are passed to the app that owns the `PeerSession`
+#[derive(Debug, Clone)]
+pub enum PeerEvent {
+    Opened,
+    Received(String),
+    Closed,
+}
+
+/// Connection to another browser over a WebRTC data channel. There is no signaling server: the offer and the answer
+/// are copied between the browsers by the users, with all ICE candidates in them
+pub struct PeerSession {
+    connection: RtcPeerConnection,
+    channel: Rc<RefCell<Option<RtcDataChannel>>>,
+    is_offerer: bool,
+    /// Play or pause as the other peer knows it
+    is_running: Option<bool>,
+    /// Dropped with the session
+    handlers: Rc<Handlers>,
+    on_event: Rc<dyn Fn(PeerEvent)>,
+}
+
+impl PeerSession {
+    pub fn new(on_event: impl Fn(PeerEvent) + 'static) -> Result<Self, AppError> {
+        let mut server = RtcIceServer::new();
+        server.urls(&STUN_SERVER.into());
+        let mut configuration = RtcConfiguration::new();
+        configuration.ice_servers(&js_sys::Array::of1(&server));
+        let session = Self {
+            connection: RtcPeerConnection::new_with_configuration(&configuration)?,
+            channel: Rc::new(RefCell::new(None)),
+            is_offerer: false,
+            is_running: None,
+            handlers: Rc::new(RefCell::new(Vec::new())),
+            on_event: Rc::new(on_event),
+        };
+        // the answering side gets the channel of the offer
+        let on_data_channel = {
+            let channel = Rc::clone(&session.channel);
+            let handlers = Rc::clone(&session.handlers);
+            let on_event = Rc::clone(&session.on_event);
+            Closure::<dyn Fn(JsValue)>::new(move |event: JsValue| {
+                let event = event.unchecked_into::<RtcDataChannelEvent>();
+                attach(&event.channel(), &handlers, &on_event);
+                channel.replace(Some(event.channel()));
+            })
+        };
+        session.connection.set_ondatachannel(Some(on_data_channel.as_ref().unchecked_ref()));
+        session.handlers.borrow_mut().push(on_data_channel);
+        Ok(session)
+    }
+    pub fn is_offerer(&self) -> bool {
+        self.is_offerer
+    }
+    /// Starts the connection as the offering side. Resolves to the offer for the other peer
+    pub fn create_offer(&mut self) -> impl std::future::Future<Output = Result<String, AppError>> + 'static {
+        self.is_offerer = true;
+        let channel = self.connection.create_data_channel(CHANNEL_LABEL);
+        attach(&channel, &self.handlers, &self.on_event);
+        self.channel.replace(Some(channel));
+        let connection = self.connection.clone();
+        async move {
+            let offer = JsFuture::from(connection.create_offer()).await?;
+            JsFuture::from(connection.set_local_description(offer.unchecked_ref())).await?;
+            local_description(&connection).await
+        }
+    }
+    /// Accepts the offer of the other peer. Resolves to the answer for it
+    pub fn accept_offer(&self, offer: &str) -> impl std::future::Future<Output = Result<String, AppError>> + 'static {
+        let connection = self.connection.clone();
+        let mut description = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
+        description.sdp(offer);
+        async move {
+            JsFuture::from(connection.set_remote_description(&description)).await.map_err(invalid_description)?;
+            let answer = JsFuture::from(connection.create_answer()).await?;
+            JsFuture::from(connection.set_local_description(answer.unchecked_ref())).await?;
+            local_description(&connection).await
+        }
+    }
+    /// Accepts the answer to our offer, the channel opens when the peers have connected
+    pub fn accept_answer(&self, answer: &str) -> impl std::future::Future<Output = Result<(), AppError>> + 'static {
+        let connection = self.connection.clone();
+        let mut description = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
+        description.sdp(answer);
+        async move {
+            JsFuture::from(connection.set_remote_description(&description)).await.map_err(invalid_description)?;
+            Ok(())
+        }
+    }
+    pub fn send(&self, message: &PeerMessage) -> Result<(), AppError> {
+        let channel = self.channel.borrow();
+        let Some(channel) = channel.as_ref().filter(|channel| channel.ready_state() == RtcDataChannelState::Open) else {
+            return Ok(());
+        };
+        let value = serde_wasm_bindgen::to_value(message).map_err(JsValue::from)?;
+        let text = js_sys::JSON::stringify(&value)?;
+        channel.send_with_str(&String::from(text))?;
+        Ok(())
+    }
+    /// Tells the other peer about play or pause, if that has changed
+    pub fn share_running(&mut self, is_running: bool) -> Result<(), AppError> {
+        if self.is_running == Some(is_running) {
+            return Ok(());
+        }
+        self.is_running = Some(is_running);
+        self.send(&PeerMessage::Running { is_running })
+    }
+    /// Play or pause of the other peer, it is not sent back
+    pub fn set_running(&mut self, is_running: bool) {
+        self.is_running = Some(is_running);
+    }
+    pub fn close(self) {
+        if let Some(channel) = self.channel.take() {
+            channel.set_onopen(None);
+            channel.set_onmessage(None);
+            channel.set_onclose(None);
+            channel.close();
+        }
+        self.connection.set_ondatachannel(None);
+        self.connection.close();
+        // the handler of new channels holds the list, it would never be dropped otherwise
+        self.handlers.borrow_mut().clear();
+    }
+}
+
+/// Requests of the peer controls and the events of the session
+pub enum PeerCommand {
+    /// Starts a peer session, the offer is shown for the user to copy to the other browser
+    CreateOffer,
+    /// Accepts the offer or the answer pasted from the other browser
+    AcceptSignal,
+    /// Shows the offer or the answer of this browser, for the user to copy to the other browser
+    ShowSignal(String),
+    Event(PeerEvent),
+    Disconnect,
+}
+
+impl App {
+    pub fn update_peer(&mut self, command: PeerCommand) -> Result<(), AppError> {
+        match command {
+            PeerCommand::CreateOffer => self.create_peer_offer(),
+            PeerCommand::AcceptSignal => self.accept_peer_signal(),
+            PeerCommand::ShowSignal(signal) => {
+                self.peer_signal.set_value(&signal);
+                self.peer_signal.select();
+                self.live_region.announce("Copy the text to the other browser");
+                Ok(())
+            }
+            PeerCommand::Event(event) => self.handle_peer(event),
+            PeerCommand::Disconnect => {
+                if let Some(peer) = self.peer.take() {
+                    peer.close();
+                    self.live_region.announce("Disconnected");
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn create_peer_offer(&mut self) -> Result<(), AppError> {
+        if let Some(peer) = self.peer.take() {
+            peer.close();
+        }
+        let mut peer = self.new_peer_session()?;
+        let offer = peer.create_offer();
+        self.peer = Some(peer);
+        self.spawn(async move { Ok(Some(Message::Peer(PeerCommand::ShowSignal(offer.await?)))) });
+        Ok(())
+    }
+
+    /// Accepts the offer of the other browser and shows the answer, or accepts the answer to our offer
+    fn accept_peer_signal(&mut self) -> Result<(), AppError> {
+        let signal = self.peer_signal.value();
+        if signal.trim().is_empty() {
+            return Err(AppError::InvalidInput("paste the offer or the answer of the other browser".to_string()));
+        }
+        match &self.peer {
+            Some(peer) if peer.is_offerer() => {
+                let accepted = peer.accept_answer(&signal);
+                self.spawn(async move {
+                    accepted.await?;
+                    Ok(None)
+                });
+            }
+            _ => {
+                if let Some(peer) = self.peer.take() {
+                    peer.close();
+                }
+                let peer = self.new_peer_session()?;
+                let answer = peer.accept_offer(&signal);
+                self.peer = Some(peer);
+                self.spawn(async move { Ok(Some(Message::Peer(PeerCommand::ShowSignal(answer.await?)))) });
+            }
+        }
+        Ok(())
+    }
+
+    fn new_peer_session(&self) -> Result<PeerSession, AppError> {
+        let dispatcher = self.dispatcher.clone();
+        PeerSession::new(move |event| {
+            if let Some(dispatcher) = dispatcher.upgrade() {
+                dispatcher.send(Message::Peer(PeerCommand::Event(event)));
+            }
+        })
+    }
+
+    /// The side that made the offer shares its board when the channel opens, then both sides share their edits and play or pause
+    fn handle_peer(&mut self, event: PeerEvent) -> Result<(), AppError> {
+        let Some(peer) = &mut self.peer else {
+            return Ok(());
+        };
+        match event {
+            PeerEvent::Opened => {
+                self.live_region.announce("Connected to the other browser");
+                if peer.is_offerer() {
+                    let field = self.field.as_any_mut().downcast_ref::<Field>();
+                    let (topology, noise) = (field.map_or(Topology::Torus, Field::get_topology), field.and_then(Field::noise_seed));
+                    peer.send(&PeerMessage::Board { rle: to_rle(self.field.as_ref()), generation: self.state.generation.get(), topology })?;
+                    // with the same seed both boards flip the same cells
+                    if let Some((probability, seed)) = noise {
+                        peer.send(&PeerMessage::Change { change: Change::SetNoise { probability, seed } })?;
+                    }
+                    peer.share_running(self.state.is_running())?;
+                }
+                Ok(())
+            }
+            PeerEvent::Received(text) => match parse_message(&text)? {
+                // an invalid board is only shown in the banner, the session stays open for the next one
+                PeerMessage::Board { rle, generation, topology } => {
+                    let field = PatternFormat::Rle.parse(&rle).map_err(|err| AppError::InvalidBoard(format!("board of the peer: {err}")))?;
+                    if field.get_width().max(field.get_height()) > options::MAX_FIELD_SIZE {
+                        return Err(AppError::InvalidBoard(format!("board of the peer: wider or higher than {} cells", options::MAX_FIELD_SIZE)));
+                    }
+                    let field = field.with_topology(topology);
+                    self.show_life_field(field)?;
+                    self.state.generation.set(generation);
+                    self.update_scrubber();
+                    Ok(())
+                }
+                PeerMessage::Change { change } => self.apply_peer_change(change),
+                PeerMessage::Running { is_running } => {
+                    peer.set_running(is_running);
+                    match (is_running, self.state.is_running()) {
+                        (true, false) => self.play(),
+                        (false, true) => {
+                            self.pause();
+                            Ok(())
+                        }
+                        _ => Ok(()),
+                    }
+                }
+            },
+            PeerEvent::Closed => {
+                self.peer = None;
+                self.live_region.announce("The other browser has disconnected");
+                Ok(())
+            }
+        }
+    }
+
+    /// Makes the edit of the other peer, without sending it back
+    fn apply_peer_change(&mut self, change: Change) -> Result<(), AppError> {
+        match &change {
+            &Change::SetCell { row, col, state } => {
+                if self.field.set_state(row, col, state).is_none() {
+                    return Ok(());
+                }
+                self.flash(&[(row, col)]);
+                self.record_in_replay(change);
+                self.show_edit();
+            }
+            Change::SetRule { rule } => {
+                let parsed = rule.parse::<Rule>().map_err(|err| AppError::InvalidInput(format!("rule of the peer: {err}")))?;
+                self.state.life_rule = parsed;
+                let _ = self.field.set_rule(rule);
+                self.record_in_replay(change);
+                self.state.history.push(self.state.generation.get(), self.field.as_ref());
+                self.state.reset_periods();
+            }
+            &Change::SetTopology { topology } => {
+                self.set_topology(topology)?;
+                self.record_in_replay(change);
+            }
+            &Change::SetNoise { probability, seed } => {
+                self.set_noise(probability, seed)?;
+                self.record_in_replay(change);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the edit of the user to the other browser, if there is a session
+    pub fn share_change(&self, change: &Change) {
+        if let Some(peer) = &self.peer {
+            if let Err(err) = peer.send(&PeerMessage::Change { change: change.clone() }) {
+                console_warn!("Failed to send the edit to the peer: {err}");
+            }
+        }
+    }
+
+    /// Tells the other browser that the animation has been started or stopped by the last message
+    pub fn share_running(&mut self, was_running: bool) {
+        if let Some(peer) = self.peer.as_mut().filter(|_| self.state.is_running() != was_running) {
+            let result = peer.share_running(self.state.is_running());
+            self.state.banner.report(result);
+        }
+    }
+}
+
+/// Text area for the offer and the answer of a peer session, with the buttons to create and accept them and to disconnect
+pub fn create_peer_controls(
+    signal: &web_sys::HtmlTextAreaElement,
+    document: &Document,
+    listeners: &mut Listeners,
+    dispatcher: &Rc<AppDispatcher>,
+) -> Result<web_sys::Element, AppError> {
+    let container = document.create_element("div")?;
+    signal.set_placeholder("Offer or answer of the other browser");
+    signal.set_rows(3);
+    signal.set_cols(40);
+    container.append_child(signal)?;
+    let buttons = [
+        ("Create Offer", (|| Message::Peer(PeerCommand::CreateOffer)) as fn() -> Message, "Start a session, then copy the offer to the other browser"),
+        ("Accept", || Message::Peer(PeerCommand::AcceptSignal), "Accept the offer or the answer pasted from the other browser"),
+        ("Disconnect Peer", || Message::Peer(PeerCommand::Disconnect), "End the session"),
+    ];
+    for (name, message, title) in buttons {
+        let button = create_button(name, message, document, listeners, dispatcher)?;
+        button.set_attribute("title", title)?;
+        container.append_child(&button)?;
+    }
+    Ok(container)
+}
+
+/// Reads a message of the other peer
+pub fn parse_message(text: &str) -> Result<PeerMessage, AppError> {
+    let value = js_sys::JSON::parse(text)?;
+    serde_wasm_bindgen::from_value(value).map_err(|err| AppError::InvalidInput(format!("invalid peer message: {err}")))
+}
+
+fn invalid_description(_: JsValue) -> AppError {
+    AppError::InvalidInput("the pasted text is not a valid offer or answer".to_string())
+}
+
+/// Passes the events of the channel to `on_event`
+fn attach(channel: &RtcDataChannel, handlers: &Handlers, on_event: &Rc<dyn Fn(PeerEvent)>) {
+    let on_open = {
+        let on_event = Rc::clone(on_event);
+        Closure::<dyn Fn(JsValue)>::new(move |_: JsValue| on_event(PeerEvent::Opened))
+    };
+    let on_message = {
+        let on_event = Rc::clone(on_event);
+        Closure::<dyn Fn(JsValue)>::new(move |event: JsValue| {
+            if let Some(text) = event.unchecked_into::<MessageEvent>().data().as_string() {
+                on_event(PeerEvent::Received(text));
+            }
+        })
+    };
+    let on_close = {
+        let on_event = Rc::clone(on_event);
+        Closure::<dyn Fn(JsValue)>::new(move |_: JsValue| on_event(PeerEvent::Closed))
+    };
+    channel.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+    channel.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    channel.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+    handlers.borrow_mut().extend([on_open, on_message, on_close]);
+}
+
+/// The local description once all ICE candidates have been gathered, so that it can be copied in one go
+async fn local_description(connection: &RtcPeerConnection) -> Result<String, AppError> {
+    if connection.ice_gathering_state() != RtcIceGatheringState::Complete {
+        let gathered = js_sys::Promise::new(&mut |resolve, _reject| {
+            let connection = connection.clone();
+            let on_change = Closure::<dyn Fn()>::new({
+                let connection = connection.clone();
+                move || {
+                    if connection.ice_gathering_state() == RtcIceGatheringState::Complete {
+                        connection.set_onicegatheringstatechange(None);
+                        let _ = resolve.call0(&JsValue::NULL);
+                    }
+                }
+            });
+            // freed by the garbage collector once the handler is removed
+            connection.set_onicegatheringstatechange(Some(&on_change.into_js_value().unchecked_into()));
+        });
+        JsFuture::from(gathered).await?;
+    }
+    let description = connection.local_description().ok_or(AppError::Missing("local description"))?;
+    Ok(description.sdp())
+}
@@ -0,0 +1,83 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use crate::automaton::DynAutomaton;
+
+/// Longest cycle that is detected, longer ones are rare in soups
+pub const MAX_PERIOD: usize = 30;
+
+/// Finds out when a field becomes a still life or starts repeating with a short period.
+/// Only hashes of the recent generations are kept, a match of 64 bit hashes is taken as a repeat
+#[derive(Debug)]
+pub struct PeriodDetector {
+    hashes: VecDeque<u64>,
+    max_period: usize,
+}
+impl PeriodDetector {
+    pub fn new(max_period: usize) -> Self {
+        Self { hashes: VecDeque::with_capacity(max_period), max_period }
+    }
+    /// Records the next generation, returns the period if it is the same as one of the recent generations.
+    /// Still lifes have period 1
+    pub fn push(&mut self, field: &dyn DynAutomaton) -> Option<usize> {
+        let hash = field_hash(field);
+        let period = self.hashes.iter().rev().position(|&old| old == hash).map(|index| index + 1);
+        if self.hashes.len() >= self.max_period {
+            self.hashes.pop_front();
+        }
+        if self.max_period > 0 {
+            self.hashes.push_back(hash);
+        }
+        period
+    }
+    /// Forgets the generations, e.g. after the field has been edited
+    pub fn clear(&mut self) {
+        self.hashes.clear();
+    }
+}
+impl Default for PeriodDetector {
+    fn default() -> Self {
+        Self::new(MAX_PERIOD)
+    }
+}
+
+/// Hash of the size and the cells of the field
+fn field_hash(field: &dyn DynAutomaton) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (field.get_width(), field.get_height()).hash(&mut hasher);
+    for index in 0..(field.get_width() * field.get_height()) {
+        field.state_at(index).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+    use crate::game_of_life::Field;
+    fn detect(field: &mut Field, detector: &mut PeriodDetector, generations: usize) -> Option<(usize, usize)> {
+        detector.push(field);
+        (1..=generations).find_map(|generation| {
+            field.update();
+            detector.push(field).map(|period| (generation, period))
+        })
+    }
+    #[test]
+    fn test_still_life_and_oscillator() {
+        let mut detector = PeriodDetector::default();
+        let mut block = Field::from_str("____\n_##_\n_##_\n____").unwrap();
+        assert_eq!(Some((1, 1)), detect(&mut block, &mut detector, 10));
+
+        detector.clear();
+        let mut blinker = Field::from_str("_____\n__#__\n__#__\n__#__\n_____").unwrap();
+        assert_eq!(Some((2, 2)), detect(&mut blinker, &mut detector, 10));
+    }
+    #[test]
+    fn test_long_period() {
+        // a glider comes back to the same cells after 4 generations per cell of the field size
+        let mut glider = Field::from_str("_#____\n__#___\n###___\n______\n______\n______").unwrap();
+        assert_eq!(Some((24, 24)), detect(&mut glider, &mut PeriodDetector::default(), 100));
+        assert_eq!(None, detect(&mut glider, &mut PeriodDetector::new(10), 100));
+    }
+}
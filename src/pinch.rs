@@ -0,0 +1,64 @@
+use crate::render::{MAX_CELL_SIZE_PX, MIN_CELL_SIZE_PX};
+
+/// Position of a finger in css pixels of the viewport
+pub type Point = (f64, f64);
+
+/// Two finger gesture over the field. Spreading the fingers zooms in by changing the cell size,
+/// moving them together pans the page
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pinch {
+    start_distance: f64,
+    start_cell_size_px: usize,
+    /// Center between the fingers at the previous move, the page is panned by how far it has moved since then
+    last_center: Point,
+}
+impl Pinch {
+    /// None if both fingers are at the same point, the zoom can't be calculated then
+    pub fn new(first: Point, second: Point, cell_size_px: usize) -> Option<Self> {
+        let start_distance = distance(first, second);
+        (start_distance > 0.0).then_some(Self { start_distance, start_cell_size_px: cell_size_px, last_center: center(first, second) })
+    }
+    /// Cell size for the new positions of the fingers, clamped to the sizes allowed by the options.
+    /// It follows the distance between the fingers since the start, so that rounding doesn't add up over the moves
+    pub fn cell_size_px(&self, first: Point, second: Point) -> usize {
+        // the grid line is a part of the cell on the screen
+        let start_pitch = (self.start_cell_size_px + 1) as f64;
+        let pitch = start_pitch * (distance(first, second) / self.start_distance);
+        ((pitch - 1.0).round().max(0.0) as usize).clamp(MIN_CELL_SIZE_PX, MAX_CELL_SIZE_PX)
+    }
+    /// Returns the center between the fingers at the previous move and at this one
+    pub fn move_center(&mut self, first: Point, second: Point) -> (Point, Point) {
+        let last_center = self.last_center;
+        self.last_center = center(first, second);
+        (last_center, self.last_center)
+    }
+}
+
+fn distance((x1, y1): Point, (x2, y2): Point) -> f64 {
+    (x2 - x1).hypot(y2 - y1)
+}
+
+fn center((x1, y1): Point, (x2, y2): Point) -> Point {
+    ((x1 + x2) / 2.0, (y1 + y2) / 2.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_cell_size() {
+        assert_eq!(None, Pinch::new((5.0, 5.0), (5.0, 5.0), 13));
+        let pinch = Pinch::new((0.0, 0.0), (100.0, 0.0), 13).unwrap();
+        assert_eq!(13, pinch.cell_size_px((10.0, 10.0), (110.0, 10.0)));
+        // the pitch with the grid line doubles from 14 to 28
+        assert_eq!(27, pinch.cell_size_px((0.0, 0.0), (0.0, 200.0)));
+        assert_eq!(MAX_CELL_SIZE_PX, pinch.cell_size_px((0.0, 0.0), (1000.0, 0.0)));
+        assert_eq!(MIN_CELL_SIZE_PX, pinch.cell_size_px((0.0, 0.0), (1.0, 0.0)));
+    }
+    #[test]
+    fn test_move_center() {
+        let mut pinch = Pinch::new((0.0, 0.0), (100.0, 50.0), 13).unwrap();
+        assert_eq!(((50.0, 25.0), (60.0, 15.0)), pinch.move_center((10.0, -10.0), (110.0, 40.0)));
+        assert_eq!(((60.0, 15.0), (60.0, 15.0)), pinch.move_center((10.0, -10.0), (110.0, 40.0)));
+    }
+}
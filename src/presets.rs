@@ -0,0 +1,405 @@
+use std::num::NonZeroUsize;
+use std::rc::Rc;
+use std::str::FromStr;
+use wasm_bindgen::JsCast;
+use web_sys::{Document, HtmlInputElement};
+use crate::app::{create_button, AppDispatcher, Message};
+use crate::automaton::{Automaton, DynAutomaton};
+use crate::brians_brain::BriansBrain;
+use crate::colored_life::{ColorCell, ColoredLife};
+use crate::control_panel::Group;
+use crate::elementary::ElementaryCa;
+use crate::error::AppError;
+use crate::falling_sand::{FallingSand, Particle};
+use crate::game_of_life::Field;
+use crate::hex_life::{HexField, HexRule};
+use crate::infinite_life::InfiniteField;
+use crate::lenia::{Lenia, LeniaParams};
+use crate::life_3d::{Life3d, Rule3d};
+use crate::listeners::Listeners;
+use crate::wator::{Wator, WatorCell, WatorParams};
+use crate::wireworld::Wireworld;
+
+const BIG_FIELD_SIZE: NonZeroUsize = NonZeroUsize::new(400).unwrap();
+/// Edge of the cube of the 3D Life field, its layers are laid out in a 5x5 square of this size
+const LIFE_3D_SIZE: NonZeroUsize = NonZeroUsize::new(24).unwrap();
+
+/// Adds the buttons that load the example fields of every automaton to the group, the life fields have this size and density
+pub fn add_preset_controls(
+    group: &Group,
+    size: NonZeroUsize,
+    density: f64,
+    document: &Document,
+    listeners: &mut Listeners,
+    dispatcher: &Rc<AppDispatcher>,
+) -> Result<(), AppError> {
+    let init_button = create_fill_button(
+        "Clear",
+        move |field| field.fill_from_fn(size, size, |_| false),
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    group.add(&init_button)?;
+
+    let init_button = create_button("Random", move || Message::LoadSoup(size, density), document, listeners, dispatcher)?;
+    group.add(&init_button)?;
+
+    let init_button = create_init_button(
+        "Glider",
+        move || Box::new(make_glider_field()),
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    group.add(&init_button)?;
+
+    let init_button = create_init_button(
+        "Glider Gun",
+        move || Box::new(make_glider_gun_field()),
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    group.add(&init_button)?;
+
+    let init_button = create_fill_button(
+        "Fixed",
+        move |field| field.fill_from_fn(size, size, |i| i % 2 == 0 || i % 7 == 0),
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    group.add(&init_button)?;
+
+    let init_button = create_button("Random Big", || Message::LoadSoup(BIG_FIELD_SIZE, 0.5), document, listeners, dispatcher)?;
+    group.add(&init_button)?;
+
+    let init_button = create_fill_button(
+        "Fixed Big",
+        |field| field.fill_from_fn(BIG_FIELD_SIZE, BIG_FIELD_SIZE, |i| i % 2 == 0 || i % 7 == 0),
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    group.add(&init_button)?;
+
+    let init_button = create_init_button(
+        "Infinite Glider",
+        move || Box::new(InfiniteField::parse(&make_glider_field().to_string()).unwrap()),
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    group.add(&init_button)?;
+
+    let init_button = create_init_button(
+        "Wireworld",
+        move || Box::new(make_wireworld_field()),
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    group.add(&init_button)?;
+
+    let init_button = create_init_button(
+        "Immigration",
+        move || Box::new(make_colored_life_field(size, ColoredLife::IMMIGRATION)),
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    group.add(&init_button)?;
+
+    let init_button = create_init_button(
+        "Battle",
+        move || {
+            let seed = (js_sys::Math::random() * u32::MAX as f64) as u64;
+            Box::new(ColoredLife::battle(size, size, 0.5, seed))
+        },
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    init_button.set_attribute("title", "Two Immigration soups fight, the team of a newborn cell is the team of most of its parents")?;
+    group.add(&init_button)?;
+
+    let init_button = create_init_button(
+        "QuadLife",
+        move || Box::new(make_colored_life_field(size, ColoredLife::QUAD_LIFE)),
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    group.add(&init_button)?;
+
+    let init_button = create_init_button(
+        "Brian's Brain",
+        move || Box::new(BriansBrain::generate_by_fn(size, size, |_| js_sys::Math::random() > 0.8)),
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    group.add(&init_button)?;
+
+    let init_button = create_init_button(
+        "Hex Life",
+        move || Box::new(HexField::generate_by_fn(size, size, HexRule::B2_S34, |_| js_sys::Math::random() > 0.7)),
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    group.add(&init_button)?;
+
+    let init_button = create_init_button(
+        "Lenia",
+        move || Box::new(make_lenia_field(size)),
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    group.add(&init_button)?;
+
+    let init_button = create_init_button(
+        "Wa-Tor",
+        move || {
+            let seed = (js_sys::Math::random() * u32::MAX as f64) as u64;
+            Box::new(Wator::generate_by_fn(size, size, WatorParams::default(), seed, |_| {
+                let random = js_sys::Math::random();
+                if random < 0.02 {
+                    WatorCell::Shark
+                } else if random < 0.3 {
+                    WatorCell::Fish
+                } else {
+                    WatorCell::Water
+                }
+            }))
+        },
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    group.add(&init_button)?;
+
+    let init_button = create_init_button(
+        "Falling Sand",
+        move || Box::new(make_falling_sand_field(size)),
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    group.add(&init_button)?;
+
+    let init_button = create_init_button(
+        "3D Life",
+        || Box::new(make_life_3d_field(LIFE_3D_SIZE)),
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    init_button.set_attribute("title", "Life in a cube that wraps around, its layers are shown side by side")?;
+    group.add(&init_button)?;
+
+    let rule_input = create_rule_input(document)?;
+    group.add(&rule_input)?;
+
+    let init_button = create_init_button(
+        "1D Random",
+        {
+            let rule_input = rule_input.clone();
+            move || Box::new(ElementaryCa::new(size, size, read_rule(&rule_input), |_| js_sys::Math::random() > 0.5))
+        },
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    group.add(&init_button)?;
+
+    let init_button = create_init_button(
+        "1D Single Cell",
+        move || Box::new(ElementaryCa::new_single_cell(size, size, read_rule(&rule_input))),
+        document,
+        listeners,
+        dispatcher,
+    )?;
+    group.add(&init_button)?;
+
+    let text_input = create_text_input(document, listeners, dispatcher)?;
+    group.add(&text_input)
+}
+
+fn make_glider_field() -> Field {
+    let init_state = "
+__#____________________
+___#___________________
+_###___________________
+_______________________
+_______________________
+_______________________
+_______________________
+_______________________
+_______________________
+_______________________
+";
+    Field::from_str(init_state).unwrap()
+}
+
+fn make_glider_gun_field() -> Field {
+    let init_state = "
+______________________________________________________________
+______________________________________________________________
+______________________________________________________________
+____________________________#_________________________________
+__________________________#_#_________________________________
+________________##______##____________##______________________
+_______________#___#____##____________##______________________
+____##________#_____#___##____________________________________
+____##________#___#_##____#_#_________________________________
+______________#_____#_______#_________________________________
+_______________#___#__________________________________________
+________________##____________________________________________
+______________________________________________________________
+______________________________________________________________
+______________________________________________________________
+______________________________________________________________
+______________________________________________________________
+______________________________________________________________
+______________________________________________________________
+______________________________________________________________
+______________________________________________________________
+______________________________________________________________
+______________________________________________________________
+______________________________________________________________
+______________________________________________________________
+______________________________________________________________
+______________________________________________________________
+";
+    Field::from_str(init_state).unwrap()
+}
+
+/// Field with a predefined pattern by name, or with the cells from the text format.
+/// `density` is the share of live cells in random fields
+pub fn make_pattern_field(pattern: &str, size: NonZeroUsize, density: f64) -> Result<Box<dyn DynAutomaton>, AppError> {
+    let field: Box<dyn DynAutomaton> = match pattern {
+        "random" => Box::new(Field::generate_by_fn(size, size, |_| js_sys::Math::random() < density)),
+        "clear" => Box::new(Field::new(size, size)),
+        "fixed" => Box::new(Field::generate_by_fn(size, size, |i| i % 2 == 0 || i % 7 == 0)),
+        "glider" => Box::new(make_glider_field()),
+        "glidergun" => Box::new(make_glider_gun_field()),
+        cells => Box::new(Field::from_str(cells).map_err(|err| AppError::InvalidOptions(format!("invalid pattern: {err:?}")))?),
+    };
+    Ok(field)
+}
+
+fn make_colored_life_field(size: NonZeroUsize, colors: u8) -> ColoredLife {
+    ColoredLife::generate_by_fn(size, size, colors, |_| {
+        if js_sys::Math::random() > 0.5 {
+            ColorCell((js_sys::Math::random() * colors as f64) as u8 + 1)
+        } else {
+            ColorCell::DEAD
+        }
+    })
+}
+
+fn make_lenia_field(size: NonZeroUsize) -> Lenia {
+    // random noise in a square in the middle of the field, the rest is empty
+    let width = size.get();
+    let blob_start = (width / 2).saturating_sub(10);
+    let blob_end = width / 2 + 10;
+    Lenia::generate_by_fn(size, size, LeniaParams::default(), |i| {
+        let in_blob = (blob_start..blob_end).contains(&(i / width)) && (blob_start..blob_end).contains(&(i % width));
+        if in_blob { js_sys::Math::random() as f32 } else { 0.0 }
+    })
+}
+
+fn make_life_3d_field(size: NonZeroUsize) -> Life3d {
+    // random soup in a cube in the middle of the field, the rest is empty
+    let soup = (size.get() / 2).saturating_sub(4)..size.get() / 2 + 4;
+    Life3d::generate_by_fn(size, Rule3d::BAYS_4555, |x, y, z| {
+        [x, y, z].iter().all(|coord| soup.contains(coord)) && js_sys::Math::random() > 0.6
+    })
+}
+
+fn make_falling_sand_field(size: NonZeroUsize) -> FallingSand {
+    // a bowl to fill with sand and water
+    let seed = (js_sys::Math::random() * u32::MAX as f64) as u64;
+    let mut field = FallingSand::new(size, size, seed);
+    let size = size.get();
+    for col in (size / 4)..(size * 3 / 4) {
+        field.set(size * 3 / 4, col, Particle::Wall);
+    }
+    for row in (size / 2)..(size * 3 / 4) {
+        field.set(row, size / 4, Particle::Wall);
+        field.set(row, size * 3 / 4, Particle::Wall);
+    }
+    field
+}
+
+fn make_wireworld_field() -> Wireworld {
+    // a clock loop that keeps sending electrons into the wire on the right
+    let init_state = "
+______________________________________
+__@~####______________________________
+_#______#_____________________________
+__######_#############################
+______________________________________
+";
+    Wireworld::from_str(init_state).unwrap()
+}
+
+fn create_init_button(
+    name: &'static str,
+    factory: impl Fn() -> Box<dyn DynAutomaton> + 'static,
+    document: &Document,
+    listeners: &mut Listeners,
+    dispatcher: &Rc<AppDispatcher>,
+) -> Result<web_sys::Element, AppError> {
+    let factory: Rc<dyn Fn() -> Box<dyn DynAutomaton>> = Rc::new(factory);
+    create_button(name, move || Message::LoadField(Rc::clone(&factory)), document, listeners, dispatcher)
+}
+
+/// Like `create_init_button` for life fields, but the current field is refilled in place if it is a life field,
+/// so that repeated clicks don't allocate new buffers
+fn create_fill_button(
+    name: &'static str,
+    fill: impl Fn(&mut Field) + 'static,
+    document: &Document,
+    listeners: &mut Listeners,
+    dispatcher: &Rc<AppDispatcher>,
+) -> Result<web_sys::Element, AppError> {
+    let fill: Rc<dyn Fn(&mut Field)> = Rc::new(fill);
+    create_button(name, move || Message::FillField(Rc::clone(&fill)), document, listeners, dispatcher)
+}
+
+fn create_text_input(document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<web_sys::Element, AppError> {
+    let container = document.create_element("span")?;
+    let text_input = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    text_input.set_placeholder("Your name");
+    text_input.set_title("Text to write in live cells, letters, digits and some punctuation");
+    text_input.set_size(12);
+    container.append_child(&text_input)?;
+
+    let message = move || Message::LoadText(text_input.value());
+    let button = create_button("Write Text", message, document, listeners, dispatcher)?;
+    container.append_child(&button)?;
+    Ok(container)
+}
+
+fn create_rule_input(document: &Document) -> Result<HtmlInputElement, AppError> {
+    let input = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    input.set_type("number");
+    input.set_min("0");
+    input.set_max("255");
+    input.set_value("30");
+    input.set_title("Elementary automaton rule");
+    Ok(input)
+}
+
+fn read_rule(input: &HtmlInputElement) -> u8 {
+    let rule = input.value_as_number();
+    if rule.is_nan() {
+        30
+    } else {
+        rule.clamp(0.0, 255.0) as u8
+    }
+}
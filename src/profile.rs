@@ -0,0 +1,49 @@
+use std::cell::Cell;
+use wasm_bindgen::prelude::*;
+use web_sys::Performance;
+
+/// Names of the measured phases of a frame, prefixed so that they are easy to find among the page's own measures
+pub const FRAME: &str = "life:frame";
+pub const UPDATE: &str = "life:update";
+pub const DRAW: &str = "life:draw";
+pub const STATS: &str = "life:stats";
+
+thread_local! {
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Enables the measures of the phases for all apps on the page
+pub fn set_enabled(is_enabled: bool) {
+    ENABLED.set(is_enabled);
+}
+
+/// Phase that is measured with `performance.measure` when it is dropped, so that it shows up in the browser profilers
+pub struct Span {
+    performance: Performance,
+    name: &'static str,
+    start_mark: String,
+}
+impl Drop for Span {
+    fn drop(&mut self) {
+        // profiling must never break the app, so failures only lose the measure
+        let _ = self.performance.measure_with_start_mark(self.name, &self.start_mark);
+        self.performance.clear_marks_with_mark_name(&self.start_mark);
+    }
+}
+
+/// Starts measuring the phase until the span is dropped, None if profiling is disabled
+pub fn span(name: &'static str) -> Option<Span> {
+    if !ENABLED.get() {
+        return None;
+    }
+    let performance = web_sys::window()?.performance()?;
+    let start_mark = format!("{name}:start");
+    performance.mark(&start_mark).ok()?;
+    Some(Span { performance, name, start_mark })
+}
+
+/// Enables or disables measuring the phases of each frame with `performance.measure`
+#[wasm_bindgen(js_name = setProfiling)]
+pub fn set_profiling(is_enabled: bool) {
+    set_enabled(is_enabled);
+}
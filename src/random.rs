@@ -0,0 +1,59 @@
+/// Small xorshift pseudo random generator.
+/// Used by the engines instead of `Math.random()`, so that runs can be reproduced from a seed and work outside the browser.
+#[derive(Clone, Debug)]
+pub struct XorShift {
+    state: u64,
+}
+impl XorShift {
+    pub fn new(seed: u64) -> Self {
+        // zero state would only ever produce zeros
+        let state = if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed };
+        Self { state }
+    }
+    /// Seed of a generator that continues with the same numbers as this one
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+    /// Random number in `0..bound`
+    pub fn next_below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+    /// Random number in `0.0..1.0`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut first = XorShift::new(42);
+        let mut second = XorShift::new(42);
+        for _ in 0..100 {
+            assert_eq!(first.next_u64(), second.next_u64());
+        }
+        let mut continued = XorShift::new(first.state());
+        assert_eq!(first.next_u64(), continued.next_u64());
+        let mut zero = XorShift::new(0);
+        assert_ne!(0, zero.next_u64());
+    }
+    #[test]
+    fn test_ranges() {
+        let mut random = XorShift::new(7);
+        for _ in 0..1000 {
+            assert!(random.next_below(10) < 10);
+            let value = random.next_f64();
+            assert!((0.0..1.0).contains(&value));
+        }
+    }
+}
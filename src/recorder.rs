@@ -0,0 +1,236 @@
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use web_sys::{Blob, BlobEvent, BlobPropertyBag, Document, HtmlCanvasElement, HtmlInputElement, MediaRecorder, MediaRecorderOptions};
+use crate::app::{add_message_listener, create_button, App, AppDispatcher, Message};
+use crate::automaton::DynAutomaton;
+use crate::console_error;
+use crate::control_panel::Group;
+use crate::error::AppError;
+use crate::export::download_blob;
+use crate::gif::GifEncoder;
+use crate::listeners::Listeners;
+use crate::render::parse_color;
+
+/// Recorded frames are scaled up so that their bigger side is about this size
+const TARGET_SIZE_PX: usize = 400;
+/// Delay between frames in hundredths of a second
+const FRAME_DELAY_CS: u16 = 10;
+/// Frame rate of the captured canvas stream
+const VIDEO_FPS: f64 = 30.0;
+/// Preferred video formats, the browser default is used if none of them is supported
+const VIDEO_MIME_TYPES: [&str; 2] = ["video/webm;codecs=vp9", "video/webm"];
+
+/// Records one frame per generation into an animated GIF
+#[derive(Debug)]
+pub struct GifRecorder {
+    encoder: GifEncoder,
+    width: usize,
+    height: usize,
+    pixels_per_cell: usize,
+    frames_left: usize,
+    pixels: Vec<u8>,
+}
+impl GifRecorder {
+    pub fn new(field: &dyn DynAutomaton, frame_count: usize) -> Self {
+        let width = field.get_width();
+        let height = field.get_height();
+        let pixels_per_cell = (TARGET_SIZE_PX / width.max(height)).max(1);
+        let palette = field.palette().iter()
+            .map(|&color| {
+                let [red, green, blue, _] = parse_color(color);
+                [red, green, blue]
+            })
+            .collect::<Vec<_>>();
+        let encoder = GifEncoder::new(
+            (width * pixels_per_cell) as u16,
+            (height * pixels_per_cell) as u16,
+            &palette,
+            FRAME_DELAY_CS,
+        );
+        Self {
+            encoder,
+            width,
+            height,
+            pixels_per_cell,
+            frames_left: frame_count,
+            pixels: Vec::new(),
+        }
+    }
+    /// Adds the current state of the field as a frame. Returns false when the recording is complete,
+    /// which is also the case if the field has been resized
+    pub fn capture(&mut self, field: &dyn DynAutomaton) -> bool {
+        if (self.frames_left == 0) || (field.get_width() != self.width) || (field.get_height() != self.height) {
+            self.frames_left = 0;
+            return false;
+        }
+        self.pixels.clear();
+        for row in 0..self.height {
+            let row_start = self.pixels.len();
+            for col in 0..self.width {
+                let state = field.state_at((row * self.width) + col);
+                self.pixels.extend(std::iter::repeat_n(state, self.pixels_per_cell));
+            }
+            for _ in 1..self.pixels_per_cell {
+                self.pixels.extend_from_within(row_start..(row_start + (self.width * self.pixels_per_cell)));
+            }
+        }
+        self.encoder.add_frame(&self.pixels);
+        self.frames_left -= 1;
+        self.frames_left > 0
+    }
+    pub fn finish(self) -> Vec<u8> {
+        self.encoder.finish()
+    }
+}
+
+/// Records the canvas into a WebM video using the browser's `MediaRecorder`
+#[derive(Debug)]
+pub struct VideoRecorder {
+    recorder: MediaRecorder,
+}
+impl VideoRecorder {
+    /// Starts recording everything that is drawn on the canvas. `on_finish` receives the video after `stop`
+    pub fn start(canvas: &HtmlCanvasElement, on_finish: impl FnOnce(Blob) + 'static) -> Result<Self, JsValue> {
+        let stream = canvas.capture_stream_with_frame_request_rate(VIDEO_FPS)?;
+        let mut options = MediaRecorderOptions::new();
+        if let Some(mime_type) = VIDEO_MIME_TYPES.iter().find(|mime_type| MediaRecorder::is_type_supported(mime_type)) {
+            options.mime_type(mime_type);
+        }
+        let recorder = MediaRecorder::new_with_media_stream_and_media_recorder_options(&stream, &options)?;
+
+        let chunks = Rc::new(js_sys::Array::new());
+        let on_data = {
+            let chunks = Rc::clone(&chunks);
+            Closure::<dyn Fn(BlobEvent)>::new(move |event: BlobEvent| {
+                if let Some(data) = event.data() {
+                    chunks.push(&data);
+                }
+            })
+        };
+        recorder.set_ondataavailable(Some(on_data.as_ref().unchecked_ref()));
+        // the last chunk arrives right before the stop event, the closures are dropped after the stop event
+        let on_stop = Closure::once_into_js(move || {
+            drop(on_data);
+            let mut properties = BlobPropertyBag::new();
+            properties.type_("video/webm");
+            match Blob::new_with_blob_sequence_and_options(&chunks, &properties) {
+                Ok(blob) => on_finish(blob),
+                Err(err) => console_error!("Failed to save the video: {err:?}"),
+            }
+        });
+        recorder.set_onstop(Some(on_stop.unchecked_ref()));
+        recorder.start()?;
+        Ok(Self { recorder })
+    }
+    pub fn stop(self) {
+        if let Err(err) = self.recorder.stop() {
+            console_error!("Failed to stop the video recording: {err:?}");
+        }
+    }
+}
+
+/// Requests of the recording controls
+pub enum RecordCommand {
+    /// Starts recording a GIF with this number of generations, or saves the recording if one is running
+    Gif(usize),
+    ToggleVideo,
+}
+
+/// Record Video button with the indicator of a running recording
+pub struct VideoControls {
+    container: web_sys::Element,
+    button: web_sys::Element,
+    indicator: web_sys::HtmlElement,
+    recorder: Option<VideoRecorder>,
+}
+impl VideoControls {
+    pub fn new(document: &Document) -> Result<Self, AppError> {
+        let container = document.create_element("span")?;
+        let button = document.create_element("button")?;
+        button.set_text_content(Some("Record Video"));
+        container.append_child(&button)?;
+        let indicator = document.create_element("span")?.dyn_into::<web_sys::HtmlElement>()?;
+        indicator.set_text_content(Some("\u{25CF} REC"));
+        indicator.set_attribute("style", "color: #FF0000; display: none;")?;
+        container.append_child(&indicator)?;
+        Ok(Self { container, button, indicator, recorder: None })
+    }
+}
+
+impl App {
+    pub fn update_recording(&mut self, command: RecordCommand) -> Result<(), AppError> {
+        match command {
+            RecordCommand::Gif(frames) => self.record_gif(frames),
+            RecordCommand::ToggleVideo => self.toggle_video(),
+        }
+    }
+
+    fn record_gif(&mut self, frames: usize) -> Result<(), AppError> {
+        // clicking during the recording saves what has been recorded so far
+        if let Some(recorder) = self.state.recorder.take() {
+            return save_recording(recorder);
+        }
+        let mut recorder = GifRecorder::new(self.field.as_ref(), frames);
+        if recorder.capture(self.field.as_ref()) {
+            self.state.recorder = Some(recorder);
+            Ok(())
+        } else {
+            save_recording(recorder)
+        }
+    }
+
+    fn toggle_video(&mut self) -> Result<(), AppError> {
+        let video = &mut self.video;
+        if let Some(recorder) = video.recorder.take() {
+            recorder.stop();
+            video.button.set_text_content(Some("Record Video"));
+            // setting styles can't fail for this property
+            let _ = video.indicator.style().set_property("display", "none");
+            return Ok(());
+        }
+        let document = self.document.clone();
+        let banner = self.state.banner.clone();
+        let on_finish = move |blob: web_sys::Blob| banner.report(download_blob(&document, &blob, "field.webm"));
+        video.recorder = Some(VideoRecorder::start(&self.canvas, on_finish)?);
+        video.button.set_text_content(Some("Stop Video"));
+        let _ = video.indicator.style().set_property("display", "inline");
+        Ok(())
+    }
+}
+
+/// Adds the GIF recording with its number of generations and the video recording to the group
+pub fn add_record_controls(group: &Group, document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<(), AppError> {
+    let record_button = create_record_button(document, listeners, dispatcher)?;
+    group.add(&record_button)?;
+    dispatcher.try_with(|app| {
+        add_message_listener(listeners, &app.video.button, &["click"], dispatcher, || Message::Record(RecordCommand::ToggleVideo))?;
+        group.add(&app.video.container)
+    }).transpose()?;
+    Ok(())
+}
+
+fn create_record_button(document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<web_sys::Element, AppError> {
+    let container = document.create_element("span")?;
+    let frames_input = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    frames_input.set_type("number");
+    frames_input.set_min("1");
+    frames_input.set_max("1000");
+    frames_input.set_value("100");
+    frames_input.set_title("Number of generations to record");
+    container.append_child(&frames_input)?;
+
+    let message = move || {
+        let frames = frames_input.value_as_number();
+        Message::Record(RecordCommand::Gif(if frames.is_nan() { 100 } else { frames.clamp(1.0, 1000.0) as usize }))
+    };
+    let button = create_button("Record GIF", message, document, listeners, dispatcher)?;
+    container.append_child(&button)?;
+    Ok(container)
+}
+
+pub fn save_recording(recorder: GifRecorder) -> Result<(), AppError> {
+    let document = web_sys::window().and_then(|window| window.document()).ok_or(AppError::Missing("document"))?;
+    let bytes = js_sys::Uint8Array::from(recorder.finish().as_slice());
+    let blob = web_sys::Blob::new_with_u8_array_sequence(&js_sys::Array::of1(&bytes))?;
+    download_blob(&document, &blob, "field.gif")
+}
@@ -0,0 +1,498 @@
+use std::cell::RefCell;
+use std::fmt::Write;
+use wasm_bindgen::{Clamped, JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, Document, HtmlCanvasElement, ImageData, Path2d};
+use crate::automaton::{DynAutomaton, Layout};
+use crate::error::AppError;
+
+/// Cells are drawn with this size, the canvas is scaled to show them with the size chosen by the user
+pub const CELL_SIZE_PX: usize = 13;
+/// Limits of the size of cells on the screen chosen by the user
+pub const MIN_CELL_SIZE_PX: usize = 1;
+pub const MAX_CELL_SIZE_PX: usize = 64;
+/// Distance from the center of a hex cell to its corners
+const HEX_RADIUS_PX: f64 = (CELL_SIZE_PX + 1) as f64 / 2.0;
+const SQRT_3: f64 = 1.732_050_807_568_877_2;
+const GRID_COLOR: &str = "#CCCCCC";
+
+/// Resizes the canvases for the field, draws the grid on the background canvas and all cells on the cell canvas
+pub fn draw_initial_state(
+    field: &dyn DynAutomaton,
+    canvas: &HtmlCanvasElement,
+    context: &CanvasRenderingContext2d,
+    grid_context: &CanvasRenderingContext2d,
+    cell_size_px: usize,
+) -> Result<(), AppError> {
+    let scale = (cell_size_px + 1) as f64 / (CELL_SIZE_PX + 1) as f64;
+    resize_canvas(field, canvas, context, scale)?;
+    let grid_canvas = grid_context.canvas().ok_or(AppError::Missing("grid canvas"))?;
+    resize_canvas(field, &grid_canvas, grid_context, scale)?;
+    draw_grid(grid_context, field);
+    // hex cells are outlined with the grid color
+    context.set_stroke_style(&GRID_COLOR.into());
+    draw_cells(context, field, true);
+    Ok(())
+}
+
+/// The backing store is scaled by `devicePixelRatio`, so that the field stays crisp on HiDPI screens.
+/// `scale` is the ratio of the size of cells on the screen to `CELL_SIZE_PX`
+pub fn resize_canvas(field: &dyn DynAutomaton, canvas: &HtmlCanvasElement, context: &CanvasRenderingContext2d, scale: f64) -> Result<(), AppError> {
+    let pixel_ratio = web_sys::window().map_or(1.0, |window| window.device_pixel_ratio());
+    let (width, height) = canvas_size(field);
+    let (css_width, css_height) = ((width as f64 * scale).round(), (height as f64 * scale).round());
+    canvas.set_width((css_width * pixel_ratio).round() as u32);
+    canvas.set_height((css_height * pixel_ratio).round() as u32);
+    let style = canvas.style();
+    // setting styles can't fail for these properties
+    let _ = style.set_property("width", &format!("{css_width}px"));
+    let _ = style.set_property("height", &format!("{css_height}px"));
+    // resizing resets the transform, everything is drawn in unscaled css pixels from here on
+    let total_scale = pixel_ratio * scale;
+    context.set_transform(total_scale, 0.0, 0.0, total_scale, 0.0, 0.0)?;
+    Ok(())
+}
+
+/// Gets the 2d context of the canvas, it is missing if the canvas already has a context of another type
+pub fn context_2d(canvas: &HtmlCanvasElement) -> Result<CanvasRenderingContext2d, AppError> {
+    let context = canvas.get_context("2d")?.ok_or(AppError::Missing("2d canvas context"))?;
+    context.dyn_into::<CanvasRenderingContext2d>().map_err(|_| AppError::Missing("2d canvas context"))
+}
+
+/// Size of a cell including the grid line in device pixels, which depends on the browser zoom
+pub fn cell_size_device_px(cell_size_px: usize) -> f64 {
+    let pixel_ratio = web_sys::window().map_or(1.0, |window| window.device_pixel_ratio());
+    (cell_size_px + 1) as f64 * pixel_ratio
+}
+
+/// Largest cell size up to `max_cell_size_px` with which the field is at most `available_width` css pixels wide.
+/// Fields that don't fit even with the smallest cells get them anyway
+pub fn fit_cell_size(field: &dyn DynAutomaton, available_width: f64, max_cell_size_px: usize) -> usize {
+    let (width, _) = canvas_size(field);
+    // the canvas is scaled by the ratio of the cell sizes including the grid line
+    let pitch = (available_width * (CELL_SIZE_PX + 1) as f64 / width as f64).floor().max(0.0) as usize;
+    pitch.saturating_sub(1).min(max_cell_size_px).max(MIN_CELL_SIZE_PX)
+}
+
+/// Size of the canvas for the field in css pixels, before scaling to the chosen cell size
+pub fn canvas_size(field: &dyn DynAutomaton) -> (u32, u32) {
+    match field.layout() {
+        Layout::Square => (
+            ((field.get_width() * (CELL_SIZE_PX + 1)) + 1) as u32,
+            ((field.get_height() * (CELL_SIZE_PX + 1)) + 1) as u32,
+        ),
+        Layout::Hex => {
+            let hex_width = HEX_RADIUS_PX * SQRT_3;
+            (
+                (hex_width * (field.get_width() as f64 + 0.5)).ceil() as u32 + 2,
+                (HEX_RADIUS_PX * (1.5 * field.get_height() as f64 + 0.5)).ceil() as u32 + 2,
+            )
+        }
+    }
+}
+
+fn draw_grid(ctx: &CanvasRenderingContext2d, field: &dyn DynAutomaton) {
+    ctx.begin_path();
+    ctx.set_stroke_style(&GRID_COLOR.into());
+
+    if field.layout() == Layout::Hex {
+        for row in 0..field.get_height() {
+            for col in 0..field.get_width() {
+                hex_path(ctx, row, col);
+            }
+        }
+        ctx.stroke();
+        return;
+    }
+
+    // Vertical lines.
+    let width = field.get_width();
+    let height = field.get_height();
+    for i in 0..=width {
+        let x = (i * (CELL_SIZE_PX + 1) + 1) as f64;
+        let y = ((CELL_SIZE_PX + 1) * height + 1) as f64;
+        ctx.move_to(x, 0.0);
+        ctx.line_to(x, y);
+    }
+
+    // Horizontal lines.
+    for j in 0..=height {
+        let x = ((CELL_SIZE_PX + 1) * width + 1) as f64;
+        let y = (j * (CELL_SIZE_PX + 1) + 1) as f64;
+        ctx.move_to(0.0, y);
+        ctx.line_to(x, y);
+    }
+
+    ctx.stroke();
+}
+
+pub fn draw_cells(ctx: &CanvasRenderingContext2d, field: &dyn DynAutomaton, force: bool) {
+    if field.layout() == Layout::Hex {
+        for (state, &style) in field.palette().iter().enumerate() {
+            ctx.set_fill_style(&JsValue::from_str(style));
+            draw_hex_cells_with_value(ctx, field, state as u8, force);
+        }
+        return;
+    }
+
+    ctx.begin_path();
+
+    for (state, &style) in field.palette().iter().enumerate() {
+        ctx.set_fill_style(&JsValue::from_str(style));
+        draw_cells_with_value(ctx, field, state as u8, force);
+    }
+
+    ctx.stroke();
+}
+/// Fills all cells with the value that need redrawing with a single path, so that the number of calls into JS
+/// doesn't depend on the number of cells
+fn draw_cells_with_value(ctx: &CanvasRenderingContext2d, field: &dyn DynAutomaton, filter_value: u8, force: bool) {
+    let width = field.get_width();
+    let mut path = String::new();
+    if let (false, Some(changed)) = (force, field.changed_cells()) {
+        for &index in changed {
+            if field.state_at(index) == filter_value {
+                push_cell_rect(&mut path, index / width, index % width);
+            }
+        }
+    } else {
+        for index in 0..(width * field.get_height()) {
+            let value = field.state_at(index);
+            if (value == filter_value) && ((value != field.old_state_at(index)) || force) {
+                push_cell_rect(&mut path, index / width, index % width);
+            }
+        }
+    }
+    if path.is_empty() {
+        return;
+    }
+    ctx.fill_with_path_2d(&Path2d::new_with_path_string(&path).unwrap());
+}
+/// Appends the square of the cell to an SVG path string
+fn push_cell_rect(path: &mut String, row: usize, col: usize) {
+    let increment = CELL_SIZE_PX + 1;
+    let x = (col * increment) + 1;
+    let y = (row * increment) + 1;
+    // writing into a String never fails
+    let _ = write!(path, "M{x} {y}h{CELL_SIZE_PX}v{CELL_SIZE_PX}h-{CELL_SIZE_PX}z");
+}
+fn draw_hex_cells_with_value(ctx: &CanvasRenderingContext2d, field: &dyn DynAutomaton, filter_value: u8, force: bool) {
+    ctx.begin_path();
+    let width = field.get_width();
+    if let (false, Some(changed)) = (force, field.changed_cells()) {
+        for &index in changed {
+            if field.state_at(index) == filter_value {
+                hex_path(ctx, index / width, index % width);
+            }
+        }
+        ctx.fill();
+        ctx.stroke();
+        return;
+    }
+    for index in 0..(width * field.get_height()) {
+        let value = field.state_at(index);
+        if (value == filter_value) && ((value != field.old_state_at(index)) || force) {
+            hex_path(ctx, index / width, index % width);
+        }
+    }
+    ctx.fill();
+    ctx.stroke();
+}
+
+/// Renders one pixel per cell into an offscreen canvas, and scales it up to the visible canvas.
+/// Much faster than drawing every cell separately on big fields, but the grid is not visible
+#[derive(Debug)]
+pub struct ImageRenderer {
+    context: CanvasRenderingContext2d,
+    /// RGBA bytes of every cell
+    pixels: RefCell<Vec<u8>>,
+}
+impl ImageRenderer {
+    pub fn new(document: &Document) -> Result<Self, AppError> {
+        let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+        let context = context_2d(&canvas)?;
+        Ok(Self { context, pixels: RefCell::new(Vec::new()) })
+    }
+    /// Draws the whole field over the target canvas
+    pub fn draw(&self, target: &CanvasRenderingContext2d, field: &dyn DynAutomaton) {
+        let (target_width, target_height) = canvas_size(field);
+        self.draw_scaled(target, field, target_width as f64, target_height as f64);
+    }
+    /// Draws the whole field into the top left corner of the target canvas, scaled to the given size
+    pub fn draw_scaled(&self, target: &CanvasRenderingContext2d, field: &dyn DynAutomaton, target_width: f64, target_height: f64) {
+        let width = field.get_width();
+        let height = field.get_height();
+        let colors = field.palette().iter().map(|&color| parse_color(color)).collect::<Vec<_>>();
+        let mut pixels = self.pixels.borrow_mut();
+        pixels.clear();
+        for index in 0..(width * height) {
+            pixels.extend_from_slice(&colors[field.state_at(index) as usize]);
+        }
+
+        let canvas = self.context.canvas().unwrap();
+        if (canvas.width() as usize != width) || (canvas.height() as usize != height) {
+            canvas.set_width(width as u32);
+            canvas.set_height(height as u32);
+        }
+        let image = ImageData::new_with_u8_clamped_array_and_sh(Clamped(&pixels), width as u32, height as u32).unwrap();
+        self.context.put_image_data(&image, 0.0, 0.0).unwrap();
+
+        target.set_image_smoothing_enabled(false);
+        target.draw_image_with_html_canvas_element_and_dw_and_dh(&canvas, 0.0, 0.0, target_width, target_height).unwrap();
+    }
+}
+
+/// Renders the field with the given number of pixels per cell and without the grid, as a PNG data url.
+/// Hex cells are rendered as squares
+pub fn render_png_data_url(document: &Document, field: &dyn DynAutomaton, pixels_per_cell: u32) -> Result<String, AppError> {
+    let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+    let width = field.get_width() as u32 * pixels_per_cell;
+    let height = field.get_height() as u32 * pixels_per_cell;
+    canvas.set_width(width);
+    canvas.set_height(height);
+    let context = context_2d(&canvas)?;
+    ImageRenderer::new(document)?.draw_scaled(&context, field, width as f64, height as f64);
+    Ok(canvas.to_data_url()?)
+}
+
+/// Number of generations it takes for a trail to fade out
+const TRAIL_LENGTH: u8 = 8;
+
+/// Draws fading trails over cells that were recently in a non-background state,
+/// so that moving patterns leave visible traces. Only supports the square layout
+#[derive(Debug, Default)]
+pub struct TrailRenderer {
+    /// Generations since each cell was last in a non-background state, saturating at `TRAIL_LENGTH + 1`
+    ages: RefCell<Vec<u8>>,
+}
+impl TrailRenderer {
+    /// Draws the trails over the cells. The trails only fade when `advance` is set, which should happen once per generation
+    pub fn draw(&self, ctx: &CanvasRenderingContext2d, field: &dyn DynAutomaton, advance: bool) {
+        if field.layout() != Layout::Square {
+            return;
+        }
+        let width = field.get_width();
+        let cell_count = width * field.get_height();
+        let mut ages = self.ages.borrow_mut();
+        if ages.len() != cell_count {
+            ages.clear();
+            ages.resize(cell_count, TRAIL_LENGTH + 1);
+        }
+        // one path per age, so that each shade is filled once
+        let mut paths = vec![String::new(); TRAIL_LENGTH as usize + 1];
+        for (index, age) in ages.iter_mut().enumerate() {
+            if field.state_at(index) != 0 {
+                *age = 0;
+                continue;
+            }
+            let previous_age = *age;
+            if advance && (previous_age <= TRAIL_LENGTH) {
+                *age += 1;
+            }
+            let has_faded_out = (previous_age == TRAIL_LENGTH) && (*age == TRAIL_LENGTH + 1);
+            if (1..=TRAIL_LENGTH).contains(age) || has_faded_out {
+                push_cell_rect(&mut paths[*age as usize - 1], index / width, index % width);
+            }
+        }
+        let palette = field.palette();
+        let [red, green, blue, _] = parse_color(palette.get(1).unwrap_or(&palette[0]));
+        for (age_no, path) in paths.iter().enumerate() {
+            if path.is_empty() {
+                continue;
+            }
+            let path = Path2d::new_with_path_string(path).unwrap();
+            ctx.set_fill_style(&JsValue::from_str(palette[0]));
+            ctx.fill_with_path_2d(&path);
+            // the last path only clears the cells that have faded out
+            let age = age_no as u8 + 1;
+            if age <= TRAIL_LENGTH {
+                let alpha = 0.5 * (TRAIL_LENGTH + 1 - age) as f64 / (TRAIL_LENGTH + 1) as f64;
+                ctx.set_fill_style(&JsValue::from_str(&format!("rgba({red}, {green}, {blue}, {alpha:.3})")));
+                ctx.fill_with_path_2d(&path);
+            }
+        }
+    }
+}
+
+/// Draws the cells that are going to change in the next generation translucently in their new color
+pub fn draw_ghost(ctx: &CanvasRenderingContext2d, field: &dyn DynAutomaton, next: &[u8]) {
+    if field.layout() != Layout::Square {
+        return;
+    }
+    let width = field.get_width();
+    let mut paths = vec![String::new(); field.palette().len()];
+    for (index, &next_state) in next.iter().enumerate() {
+        if next_state != field.state_at(index) {
+            push_cell_rect(&mut paths[next_state as usize], index / width, index % width);
+        }
+    }
+    for (path, &color) in paths.iter().zip(field.palette()) {
+        if path.is_empty() {
+            continue;
+        }
+        let [red, green, blue, _] = parse_color(color);
+        ctx.set_fill_style(&JsValue::from_str(&format!("rgba({red}, {green}, {blue}, 0.4)")));
+        ctx.fill_with_path_2d(&Path2d::new_with_path_string(path).unwrap());
+    }
+}
+
+/// Writes the number of live neighbours into every cell that has any, in a color that is readable on the cell
+pub fn draw_neighbour_counts(ctx: &CanvasRenderingContext2d, field: &dyn DynAutomaton, counts: &[u8]) {
+    if field.layout() != Layout::Square {
+        return;
+    }
+    let width = field.get_width();
+    let text_colors = field.palette().iter()
+        .map(|&color| {
+            let [red, green, blue, _] = parse_color(color);
+            let luminance = (0.299 * red as f64) + (0.587 * green as f64) + (0.114 * blue as f64);
+            if luminance > 128.0 { "#000000" } else { "#FFFFFF" }
+        })
+        .collect::<Vec<_>>();
+    ctx.set_font("9px sans-serif");
+    ctx.set_text_align("center");
+    ctx.set_text_baseline("middle");
+    let increment = (CELL_SIZE_PX + 1) as f64;
+    for (index, &count) in counts.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        ctx.set_fill_style(&JsValue::from_str(text_colors[field.state_at(index) as usize]));
+        let center_x = 1.0 + ((index % width) as f64 * increment) + (CELL_SIZE_PX as f64 / 2.0);
+        let center_y = 1.0 + ((index / width) as f64 * increment) + (CELL_SIZE_PX as f64 / 2.0);
+        // can only fail for invalid arguments
+        let _ = ctx.fill_text(&count.to_string(), center_x, center_y);
+    }
+}
+
+/// Parses a css color in `#RRGGBB` format into RGBA bytes, unknown formats are black
+pub(crate) fn parse_color(color: &str) -> [u8; 4] {
+    if !color.starts_with('#') || (color.len() != 7) {
+        return [0, 0, 0, 255];
+    }
+    let channel = |range| color.get(range).and_then(|hex| u8::from_str_radix(hex, 16).ok()).unwrap_or(0);
+    [channel(1..3), channel(3..5), channel(5..7), 255]
+}
+
+/// Adds the outline of a pointy-top hex cell to the current path. Odd rows are shifted to the right by half a cell
+fn hex_path(ctx: &CanvasRenderingContext2d, row: usize, col: usize) {
+    let (center_x, center_y) = hex_center(row, col);
+    // slightly smaller than the cell, so that the grid stays visible
+    let radius = HEX_RADIUS_PX - 0.5;
+    let half_width = radius * SQRT_3 / 2.0;
+    ctx.move_to(center_x, center_y - radius);
+    ctx.line_to(center_x + half_width, center_y - radius / 2.0);
+    ctx.line_to(center_x + half_width, center_y + radius / 2.0);
+    ctx.line_to(center_x, center_y + radius);
+    ctx.line_to(center_x - half_width, center_y + radius / 2.0);
+    ctx.line_to(center_x - half_width, center_y - radius / 2.0);
+    ctx.close_path();
+}
+
+fn hex_center(row: usize, col: usize) -> (f64, f64) {
+    let hex_width = HEX_RADIUS_PX * SQRT_3;
+    let x = 1.0 + hex_width * (col as f64 + 0.5 + 0.5 * (row & 1) as f64);
+    let y = 1.0 + HEX_RADIUS_PX * (1.0 + 1.5 * row as f64);
+    (x, y)
+}
+
+/// Left, top, width and height of the cell on the canvas, without the grid lines around it
+pub fn cell_bounds(field: &dyn DynAutomaton, row: usize, col: usize) -> (f64, f64, f64, f64) {
+    match field.layout() {
+        Layout::Square => {
+            let increment = (CELL_SIZE_PX + 1) as f64;
+            ((col as f64 * increment) + 1.0, (row as f64 * increment) + 1.0, CELL_SIZE_PX as f64, CELL_SIZE_PX as f64)
+        }
+        Layout::Hex => {
+            // box of the hexagon
+            let (x, y) = hex_center(row, col);
+            let half_width = HEX_RADIUS_PX * SQRT_3 / 2.0;
+            (x - half_width, y - HEX_RADIUS_PX, 2.0 * half_width, 2.0 * HEX_RADIUS_PX)
+        }
+    }
+}
+
+/// Maps a point on the canvas to the coordinates of the cell under it, clamped to the field
+pub fn canvas_coords_to_cell(field: &dyn DynAutomaton, canvas_left: f64, canvas_top: f64) -> (usize, usize) {
+    let (row, col) = match field.layout() {
+        Layout::Square => {
+            let row = (canvas_top / ((CELL_SIZE_PX + 1) as f64)).floor();
+            let col = (canvas_left / ((CELL_SIZE_PX + 1) as f64)).floor();
+            (row.max(0.0) as usize, col.max(0.0) as usize)
+        }
+        Layout::Hex => canvas_coords_to_hex(canvas_left, canvas_top),
+    };
+    let row = std::cmp::min(row, field.get_height() - 1);
+    let col = std::cmp::min(col, field.get_width() - 1);
+    (row, col)
+}
+
+fn canvas_coords_to_hex(canvas_left: f64, canvas_top: f64) -> (usize, usize) {
+    let (origin_x, origin_y) = hex_center(0, 0);
+    let x = canvas_left - origin_x;
+    let y = canvas_top - origin_y;
+    // fractional axial coordinates
+    let q = (x * SQRT_3 / 3.0 - y / 3.0) / HEX_RADIUS_PX;
+    let r = (y * 2.0 / 3.0) / HEX_RADIUS_PX;
+    // round in cube coordinates, fixing up the component with the largest rounding error
+    let s = -q - r;
+    let mut round_q = q.round();
+    let mut round_r = r.round();
+    let round_s = s.round();
+    let diff_q = (round_q - q).abs();
+    let diff_r = (round_r - r).abs();
+    let diff_s = (round_s - s).abs();
+    if (diff_q > diff_r) && (diff_q > diff_s) {
+        round_q = -round_r - round_s;
+    } else if diff_r > diff_s {
+        round_r = -round_q - round_s;
+    }
+    // axial to odd-r offset coordinates
+    let row = round_r as i64;
+    let col = round_q as i64 + (row - (row & 1)) / 2;
+    (row.max(0) as usize, col.max(0) as usize)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_hex_coords_round_trip() {
+        for row in 0..6 {
+            for col in 0..6 {
+                let (x, y) = hex_center(row, col);
+                assert_eq!((row, col), canvas_coords_to_hex(x, y));
+                assert_eq!((row, col), canvas_coords_to_hex(x + 3.0, y - 3.0));
+            }
+        }
+    }
+    #[test]
+    fn test_cell_bounds() {
+        let field = crate::game_of_life::Field::new(std::num::NonZeroUsize::new(3).unwrap(), std::num::NonZeroUsize::new(3).unwrap());
+        assert_eq!((29.0, 15.0, 13.0, 13.0), cell_bounds(&field, 1, 2));
+        let (x, y, width, height) = cell_bounds(&field, 2, 1);
+        assert_eq!((2, 1), canvas_coords_to_cell(&field, x + (width / 2.0), y + (height / 2.0)));
+    }
+    #[test]
+    fn test_cell_rect_path() {
+        let mut path = String::new();
+        push_cell_rect(&mut path, 0, 0);
+        push_cell_rect(&mut path, 2, 1);
+        assert_eq!("M1 1h13v13h-13zM15 29h13v13h-13z", path);
+    }
+    #[test]
+    fn test_fit_cell_size() {
+        use std::num::NonZeroUsize;
+        use crate::game_of_life::Field;
+        let field = Field::new(NonZeroUsize::new(10).unwrap(), NonZeroUsize::new(5).unwrap());
+        // 141 css pixels wide with the default cells
+        assert_eq!(CELL_SIZE_PX, fit_cell_size(&field, 141.0, MAX_CELL_SIZE_PX));
+        assert_eq!(8, fit_cell_size(&field, 100.0, MAX_CELL_SIZE_PX));
+        assert_eq!(20, fit_cell_size(&field, 1000.0, 20));
+        assert_eq!(MIN_CELL_SIZE_PX, fit_cell_size(&field, 5.0, MAX_CELL_SIZE_PX));
+    }
+    #[test]
+    fn test_parse_color() {
+        assert_eq!([0xE2, 0xC2, 0x75, 255], parse_color("#E2C275"));
+        assert_eq!([0, 0, 0, 255], parse_color("red"));
+    }
+}
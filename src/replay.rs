@@ -0,0 +1,207 @@
+use std::cell::RefCell;
+use std::num::NonZeroUsize;
+use crate::game_of_life::{CellValue, Field, ParseError, Rule, Topology};
+use crate::random::XorShift;
+use crate::rle::{parse_rle, to_rle};
+
+/// Field at generation 0 of a replay
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase", tag = "type"))]
+pub enum Initial {
+    /// Cells in the RLE format
+    Cells { rle: String },
+    /// Random soup with the given share of live cells, generated from the seed
+    Soup { width: usize, height: usize, density: f64, seed: u64 },
+}
+impl Initial {
+    pub fn cells(field: &Field) -> Self {
+        Self::Cells { rle: to_rle(field) }
+    }
+    /// Fills the field with the initial cells, the rule and the topology of the field are kept
+    pub fn fill(&self, field: &mut Field) -> Result<(), ParseError> {
+        match self {
+            Self::Cells { rle } => {
+                let rule = field.get_rule();
+                *field = parse_rle(rle)?.with_rule(rule).with_topology(field.get_topology());
+            }
+            &Self::Soup { width, height, density, seed } => {
+                let width = NonZeroUsize::new(width).ok_or(ParseError::EmptyString)?;
+                let height = NonZeroUsize::new(height).ok_or(ParseError::EmptyString)?;
+                let random = RefCell::new(XorShift::new(seed));
+                field.fill_from_fn(width, height, |_| random.borrow_mut().next_f64() < density);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Change made by the user between two generations
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase", tag = "type"))]
+pub enum Change {
+    /// Sets the cell to the state with the given palette index
+    SetCell { row: usize, col: usize, state: u8 },
+    /// Rulestring in B/S notation
+    SetRule { rule: String },
+    /// How the edges of the field are joined
+    SetTopology { topology: Topology },
+    /// Probability of every cell to flip after a generation, and the seed of the random flips
+    SetNoise { probability: f64, seed: u64 },
+}
+
+/// Change and the generation that was shown when it was made, on playback it is applied when the field reaches the generation
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Event {
+    pub generation: u64,
+    pub change: Change,
+}
+
+/// Everything needed to reproduce a run of a life field exactly: the initial field, the rule and the edits of the user.
+/// The automaton is deterministic, so the generations in between are calculated again on playback
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Replay {
+    pub initial: Initial,
+    /// Rule at generation 0, in B/S notation
+    pub rule: String,
+    /// Topology at generation 0, left out for the torus like in the replays written before there were other topologies
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Topology::is_torus"))]
+    pub topology: Topology,
+    /// Ordered by generation
+    pub events: Vec<Event>,
+}
+impl Replay {
+    pub fn new(initial: Initial, rule: Rule) -> Self {
+        Self { initial, rule: rule.to_string(), topology: Topology::Torus, events: Vec::new() }
+    }
+    pub fn with_topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+    /// Replay starting from the field, with its rule and topology. The noise of the field is recorded as a change at generation 0
+    pub fn from_field(initial: Initial, field: &Field) -> Self {
+        let mut replay = Self::new(initial, field.get_rule()).with_topology(field.get_topology());
+        if let Some((probability, seed)) = field.noise_seed() {
+            replay.record(0, Change::SetNoise { probability, seed });
+        }
+        replay
+    }
+    /// Records a change made at the generation. Changes can't be made before the recorded ones
+    pub fn record(&mut self, generation: u64, change: Change) {
+        debug_assert!(self.events.last().is_none_or(|event| event.generation <= generation));
+        self.events.push(Event { generation, change });
+    }
+    /// Forgets the changes made after the generation, e.g. when the user edits the field during playback
+    pub fn truncate_after(&mut self, generation: u64) {
+        let len = self.events.partition_point(|event| event.generation <= generation);
+        self.events.truncate(len);
+    }
+    /// Field at generation 0
+    pub fn initial_field(&self) -> Result<Field, ParseError> {
+        let mut field = Field::new(NonZeroUsize::MIN, NonZeroUsize::MIN).with_rule(self.rule.parse()?).with_topology(self.topology);
+        self.initial.fill(&mut field)?;
+        Ok(field)
+    }
+    /// Applies the changes made at the generation to the field, returns the number of changes
+    pub fn apply(&self, generation: u64, field: &mut Field) -> Result<usize, ParseError> {
+        let start = self.events.partition_point(|event| event.generation < generation);
+        let events = self.events[start..].iter().take_while(|event| event.generation == generation);
+        let mut count = 0;
+        for event in events {
+            count += 1;
+            match &event.change {
+                // edits outside of the field are ignored, like when they were made
+                &Change::SetCell { row, col, state } => {
+                    let value = if state == 0 { CellValue::Dead } else { CellValue::Alive };
+                    let _ = field.set_by_coords(row, col, value);
+                }
+                Change::SetRule { rule } => field.set_rule(rule.parse()?),
+                &Change::SetTopology { topology } => field.set_topology(topology),
+                &Change::SetNoise { probability, seed } => field.set_noise(probability, seed),
+            }
+        }
+        Ok(count)
+    }
+    /// Plays the replay back to the generation, including the changes made at it
+    pub fn field_at(&self, generation: u64) -> Result<Field, ParseError> {
+        let mut field = self.initial_field()?;
+        self.apply(0, &mut field)?;
+        for current in 1..=generation {
+            field.update();
+            self.apply(current, &mut field)?;
+        }
+        Ok(field)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_playback_reproduces_the_run() {
+        let initial = Initial::Soup { width: 16, height: 12, density: 0.4, seed: 7 };
+        let mut field = Field::new(NonZeroUsize::MIN, NonZeroUsize::MIN);
+        initial.fill(&mut field).unwrap();
+        let mut replay = Replay::new(initial, Rule::LIFE);
+        for generation in 0..30 {
+            field.update();
+            if generation == 5 {
+                field.set_by_coords(3, 4, CellValue::Alive);
+                replay.record(generation + 1, Change::SetCell { row: 3, col: 4, state: 1 });
+            }
+            if generation == 12 {
+                field.set_rule(Rule::HIGH_LIFE);
+                replay.record(generation + 1, Change::SetRule { rule: Rule::HIGH_LIFE.to_string() });
+            }
+            if generation == 8 {
+                field.set_noise(0.01, 99);
+                replay.record(generation + 1, Change::SetNoise { probability: 0.01, seed: 99 });
+            }
+            if generation == 20 {
+                field.set_topology(Topology::KleinBottle);
+                replay.record(generation + 1, Change::SetTopology { topology: Topology::KleinBottle });
+            }
+        }
+        let played = replay.field_at(30).unwrap();
+        assert_eq!(field.to_string(), played.to_string());
+        assert_eq!(Rule::HIGH_LIFE, played.get_rule());
+        assert_eq!(Topology::KleinBottle, played.get_topology());
+        assert_ne!(field.to_string(), Replay::new(replay.initial.clone(), Rule::LIFE).field_at(30).unwrap().to_string());
+
+        let mut applied = replay.field_at(5).unwrap();
+        assert_eq!(1, replay.apply(6, &mut applied).unwrap());
+        assert_eq!(0, replay.apply(7, &mut applied).unwrap());
+
+        replay.truncate_after(6);
+        assert_eq!(1, replay.events.len());
+        assert_eq!(Rule::LIFE, replay.field_at(30).unwrap().get_rule());
+    }
+    #[test]
+    fn test_noisy_start() {
+        let initial = Initial::Soup { width: 16, height: 12, density: 0.4, seed: 7 };
+        let mut field = Field::new(NonZeroUsize::MIN, NonZeroUsize::MIN);
+        field.set_noise(0.02, 5);
+        // the random numbers used before the replay starts are not replayed
+        field.update();
+        initial.fill(&mut field).unwrap();
+        let replay = Replay::from_field(initial, &field);
+        for _ in 0..30 {
+            field.update();
+        }
+        let played = replay.field_at(30).unwrap();
+        assert_eq!(field.to_string(), played.to_string());
+        assert_eq!(0.02, played.get_noise());
+    }
+    #[test]
+    fn test_initial_cells() {
+        let field = "_#_\n_#_\n_#_".parse::<Field>().unwrap().with_rule(Rule::SEEDS);
+        let replay = Replay::new(Initial::cells(&field), field.get_rule()).with_topology(Topology::ProjectivePlane);
+        let initial = replay.initial_field().unwrap();
+        assert_eq!(field.to_string(), initial.to_string());
+        assert_eq!(Rule::SEEDS, initial.get_rule());
+        assert_eq!(Topology::ProjectivePlane, initial.get_topology());
+    }
+}
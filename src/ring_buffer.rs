@@ -1,31 +1,311 @@
 use std::collections::VecDeque;
+use std::ops::Index;
+
+/// Value that is kept up to date as items are pushed into and evicted from a ring buffer
+pub trait Aggregate<T>: Default {
+    fn add(&mut self, value: &T);
+    fn remove(&mut self, value: &T);
+}
+/// No aggregate, for buffers of values that can't be summed
+impl<T> Aggregate<T> for () {
+    fn add(&mut self, _value: &T) {}
+    fn remove(&mut self, _value: &T) {}
+}
+/// Running sum of the items, so that the mean doesn't have to go over all of them
+#[derive(Debug, Default)]
+pub struct Sum(f64);
+impl<T: Copy + Into<f64>> Aggregate<T> for Sum {
+    fn add(&mut self, value: &T) {
+        self.0 += (*value).into();
+    }
+    fn remove(&mut self, value: &T) {
+        self.0 -= (*value).into();
+    }
+}
 
 #[derive(Debug)]
-pub struct RingBuffer<T> {
+pub struct RingBuffer<T, A = ()> {
     inner: VecDeque<T>,
+    /// The deque can allocate more than requested, so its capacity is not the capacity of the buffer
+    capacity: usize,
+    aggregate: A,
 }
-impl<T> RingBuffer<T> {
+impl<T, A: Aggregate<T>> RingBuffer<T, A> {
     pub fn new(capacity: usize) -> Self {
         Self {
             inner: VecDeque::with_capacity(capacity),
+            capacity,
+            aggregate: A::default(),
         }
     }
     pub fn push(&mut self, value: T) {
-        if self.inner.len() >= self.inner.capacity() {
-            self.inner.pop_front();
+        if self.capacity == 0 {
+            return;
+        }
+        if self.inner.len() >= self.capacity {
+            self.evict_oldest();
         }
+        self.aggregate.add(&value);
         self.inner.push_back(value);
     }
+    /// Changes the capacity, the oldest items that don't fit anymore are evicted
+    pub fn set_capacity(&mut self, capacity: usize) {
+        while self.inner.len() > capacity {
+            self.evict_oldest();
+        }
+        self.capacity = capacity;
+    }
+    fn evict_oldest(&mut self) {
+        if let Some(evicted) = self.inner.pop_front() {
+            self.aggregate.remove(&evicted);
+        }
+    }
+    /// The newest item
+    pub fn last(&self) -> Option<&T> {
+        self.inner.back()
+    }
     pub fn as_slices(&self) -> (&[T], &[T]) {
         self.inner.as_slices()
     }
+    /// Items from the oldest one to the newest one
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.inner.iter()
+    }
     pub fn len(&self) -> usize {
         self.inner.len()
     }
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
     pub fn capacity(&self) -> usize {
-        self.inner.capacity()
+        self.capacity
     }
     pub fn truncate(&mut self) {
         self.inner.truncate(0);
+        self.aggregate = A::default();
+    }
+}
+/// Items are indexed from the oldest one
+impl<T, A> Index<usize> for RingBuffer<T, A> {
+    type Output = T;
+    fn index(&self, index: usize) -> &T {
+        &self.inner[index]
+    }
+}
+impl<T: Copy + PartialOrd, A> RingBuffer<T, A> {
+    pub fn min(&self) -> Option<T> {
+        self.inner.iter().copied().reduce(|min, value| if value < min { value } else { min })
+    }
+    pub fn max(&self) -> Option<T> {
+        self.inner.iter().copied().reduce(|max, value| if value > max { value } else { max })
+    }
+}
+impl<T> RingBuffer<T, Sum> {
+    pub fn sum(&self) -> f64 {
+        self.aggregate.0
+    }
+    /// Mean of the items, None if there are none
+    pub fn mean(&self) -> Option<f64> {
+        if self.inner.is_empty() {
+            None
+        } else {
+            Some(self.aggregate.0 / self.inner.len() as f64)
+        }
+    }
+}
+
+/// Ring buffer with a capacity of up to `N` items, stored inline without heap allocations
+#[derive(Debug)]
+pub struct ArrayRingBuffer<T, const N: usize, A = ()> {
+    items: [T; N],
+    /// Index of the oldest item
+    start: usize,
+    len: usize,
+    /// Can be lowered to use only a part of the array
+    capacity: usize,
+    aggregate: A,
+}
+impl<T: Copy + Default, const N: usize, A: Aggregate<T>> ArrayRingBuffer<T, N, A> {
+    pub fn new() -> Self {
+        Self {
+            items: [T::default(); N],
+            start: 0,
+            len: 0,
+            capacity: N,
+            aggregate: A::default(),
+        }
+    }
+    pub fn push(&mut self, value: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.len == self.capacity {
+            self.evict_oldest();
+        }
+        self.items[(self.start + self.len) % N] = value;
+        self.len += 1;
+        self.aggregate.add(&value);
+    }
+    /// Changes the capacity, up to `N`. The oldest items that don't fit anymore are evicted
+    pub fn set_capacity(&mut self, capacity: usize) {
+        let capacity = capacity.min(N);
+        while self.len > capacity {
+            self.evict_oldest();
+        }
+        self.capacity = capacity;
+    }
+    fn evict_oldest(&mut self) {
+        self.aggregate.remove(&self.items[self.start]);
+        self.start = (self.start + 1) % N;
+        self.len -= 1;
+    }
+    /// The newest item
+    pub fn last(&self) -> Option<&T> {
+        self.len.checked_sub(1).map(|index| &self[index])
+    }
+    pub fn as_slices(&self) -> (&[T], &[T]) {
+        let end = self.start + self.len;
+        if end <= N {
+            (&self.items[self.start..end], &[])
+        } else {
+            (&self.items[self.start..], &self.items[..(end - N)])
+        }
+    }
+    /// Items from the oldest one to the newest one
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let (first, second) = self.as_slices();
+        first.iter().chain(second)
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    pub fn truncate(&mut self) {
+        self.start = 0;
+        self.len = 0;
+        self.aggregate = A::default();
+    }
+}
+/// Items are indexed from the oldest one
+impl<T, const N: usize, A> Index<usize> for ArrayRingBuffer<T, N, A> {
+    type Output = T;
+    fn index(&self, index: usize) -> &T {
+        assert!(index < self.len, "index {index} is out of bounds of the ring buffer with {} items", self.len);
+        &self.items[(self.start + index) % N]
+    }
+}
+impl<T: Copy + Default, const N: usize, A: Aggregate<T>> Default for ArrayRingBuffer<T, N, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T: Copy + Default + PartialOrd, const N: usize, A: Aggregate<T>> ArrayRingBuffer<T, N, A> {
+    pub fn min(&self) -> Option<T> {
+        self.iter().copied().reduce(|min, value| if value < min { value } else { min })
+    }
+    pub fn max(&self) -> Option<T> {
+        self.iter().copied().reduce(|max, value| if value > max { value } else { max })
+    }
+}
+impl<T, const N: usize> ArrayRingBuffer<T, N, Sum> {
+    pub fn sum(&self) -> f64 {
+        self.aggregate.0
+    }
+    /// Mean of the items, None if there are none
+    pub fn mean(&self) -> Option<f64> {
+        if self.len == 0 {
+            None
+        } else {
+            Some(self.aggregate.0 / self.len as f64)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_running_sum() {
+        let mut buffer = RingBuffer::<f64, Sum>::new(3);
+        assert_eq!(None, buffer.mean());
+        assert_eq!(None, buffer.min());
+        let capacity = buffer.capacity();
+        for value in 1..=(capacity + 2) {
+            buffer.push(value as f64);
+        }
+        // the first two items are evicted
+        let expected = (3..=(capacity + 2)).sum::<usize>() as f64;
+        assert_eq!(expected, buffer.sum());
+        assert_eq!(Some(expected / capacity as f64), buffer.mean());
+        assert_eq!(Some(3.0), buffer.min());
+        assert_eq!(Some((capacity + 2) as f64), buffer.max());
+        assert_eq!(Some(&3.0), buffer.iter().next());
+
+        buffer.truncate();
+        assert_eq!(0.0, buffer.sum());
+        assert_eq!(None, buffer.max());
+    }
+    #[test]
+    fn test_array_ring_buffer() {
+        let mut buffer = ArrayRingBuffer::<f64, 3, Sum>::new();
+        assert_eq!(3, buffer.capacity());
+        assert_eq!(None, buffer.mean());
+        buffer.push(1.0);
+        buffer.push(2.0);
+        assert_eq!((&[1.0, 2.0][..], &[][..]), buffer.as_slices());
+        for value in [3.0, 4.0, 5.0] {
+            buffer.push(value);
+        }
+        assert_eq!((&[3.0][..], &[4.0, 5.0][..]), buffer.as_slices());
+        assert_eq!(vec![3.0, 4.0, 5.0], buffer.iter().copied().collect::<Vec<_>>());
+        assert_eq!(12.0, buffer.sum());
+        assert_eq!(Some(4.0), buffer.mean());
+        assert_eq!(Some(3.0), buffer.min());
+        assert_eq!(Some(5.0), buffer.max());
+
+        buffer.truncate();
+        assert_eq!(0, buffer.len());
+        assert_eq!(None, buffer.mean());
+    }
+    #[test]
+    fn test_set_capacity() {
+        let mut buffer = RingBuffer::<f64, Sum>::new(4);
+        for value in 1..=6 {
+            buffer.push(value as f64);
+        }
+        assert_eq!(4, buffer.capacity());
+        assert_eq!(3.0, buffer[0]);
+        buffer.set_capacity(2);
+        assert_eq!(vec![5.0, 6.0], buffer.iter().copied().collect::<Vec<_>>());
+        assert_eq!(11.0, buffer.sum());
+        assert_eq!(Some(&6.0), buffer.last());
+        buffer.set_capacity(3);
+        buffer.push(7.0);
+        assert_eq!(5.0, buffer[0]);
+        assert_eq!(3, buffer.len());
+
+        let mut buffer = ArrayRingBuffer::<f64, 4, Sum>::new();
+        assert_eq!(None, buffer.last());
+        for value in 1..=6 {
+            buffer.push(value as f64);
+        }
+        assert_eq!(3.0, buffer[0]);
+        assert_eq!(6.0, buffer[3]);
+        buffer.set_capacity(2);
+        assert_eq!(vec![5.0, 6.0], buffer.iter().copied().collect::<Vec<_>>());
+        assert_eq!(11.0, buffer.sum());
+        buffer.set_capacity(10);
+        assert_eq!(4, buffer.capacity());
+        for value in 7..=9 {
+            buffer.push(value as f64);
+        }
+        assert_eq!(vec![6.0, 7.0, 8.0, 9.0], buffer.iter().copied().collect::<Vec<_>>());
+        assert_eq!(Some(&9.0), buffer.last());
+        assert_eq!(30.0, buffer.sum());
     }
 }
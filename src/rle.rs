@@ -0,0 +1,335 @@
+use std::fmt::Write;
+use std::num::NonZeroUsize;
+use crate::automaton::DynAutomaton;
+use crate::game_of_life::{CellValue, Field, ParseError, PatternMeta, Rule, MAX_SIDE};
+
+/// Lines of the written RLE are wrapped at this length, as recommended by the format
+const MAX_LINE_LENGTH: usize = 70;
+
+/// Parses a pattern in the run length encoded format, like `x = 3, y = 3, rule = B3/S23` followed by `bo$2bo$3o!`.
+/// The field has the size from the header and the rule from it, if there is one. Sides above `MAX_SIDE` are refused.
+/// The name, author and comments from the `#N`, `#O` and `#C` lines are kept in the metadata of the field
+pub fn parse_rle(str: &str) -> Result<Field, ParseError> {
+    let mut meta = PatternMeta::default();
+    for line in str.lines().map(|line| line.trim()) {
+        let value = |prefix_length: usize| line.get(prefix_length..).unwrap_or_default().trim().to_string();
+        match line.get(..2) {
+            Some("#N") => meta.name = Some(value(2)),
+            Some("#O") => meta.author = Some(value(2)),
+            Some("#C" | "#c") => meta.comments.push(value(2)),
+            _ => {}
+        }
+    }
+    let mut lines = str.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+    let header = lines.next().ok_or(ParseError::EmptyString)?;
+    let mut width = None;
+    let mut height = None;
+    let mut rule = None;
+    let side = |value: &str| value.trim().parse::<NonZeroUsize>().ok().filter(|side| side.get() <= MAX_SIDE);
+    for part in header.split(',') {
+        let (key, value) = part.split_once('=').ok_or(ParseError::InvalidHeader)?;
+        match key.trim() {
+            "x" => width = side(value),
+            "y" => height = side(value),
+            "rule" => rule = Some(value.trim().parse::<Rule>()?),
+            _ => {}
+        }
+    }
+    let (Some(width), Some(height)) = (width, height) else {
+        return Err(ParseError::InvalidHeader);
+    };
+    let mut field = Field::new(width, height).with_meta(meta);
+    if let Some(rule) = rule {
+        field = field.with_rule(rule);
+    }
+
+    let mut row: usize = 0;
+    let mut col: usize = 0;
+    let mut count: Option<usize> = None;
+    'outer: for line in lines {
+        for char in line.chars() {
+            match char {
+                '0'..='9' => {
+                    // a run longer than the field doesn't fit in it anyway
+                    count = Some(count.unwrap_or(0).checked_mul(10)
+                        .and_then(|count| count.checked_add(char.to_digit(10).unwrap() as usize))
+                        .ok_or(ParseError::WidthMismatch)?);
+                    continue;
+                }
+                '$' => {
+                    row = row.checked_add(count.unwrap_or(1)).ok_or(ParseError::WidthMismatch)?;
+                    col = 0;
+                }
+                '!' => break 'outer,
+                // dead cells are `b`, the other letters are states of multi-state automata, all of them are alive here
+                'b' | '.' => col = col.checked_add(count.unwrap_or(1)).ok_or(ParseError::WidthMismatch)?,
+                char if char.is_ascii_alphabetic() => {
+                    for _ in 0..count.unwrap_or(1) {
+                        field.set_by_coords(row, col, CellValue::Alive).ok_or(ParseError::WidthMismatch)?;
+                        col += 1;
+                    }
+                }
+                char if char.is_whitespace() => {}
+                _ => return Err(ParseError::UnknownChar),
+            }
+            count = None;
+        }
+    }
+    Ok(field)
+}
+
+/// Writes the field in the run length encoded format with its rule and metadata, if it has them.
+/// Automata with more than two states use `.` for the first state and letters for the others, like Golly
+pub fn to_rle(field: &dyn DynAutomaton) -> String {
+    let mut res = String::new();
+    if let Some(meta) = field.meta() {
+        if let Some(name) = &meta.name {
+            let _ = writeln!(res, "#N {name}");
+        }
+        if let Some(author) = &meta.author {
+            let _ = writeln!(res, "#O {author}");
+        }
+        for comment in &meta.comments {
+            let _ = writeln!(res, "#C {comment}");
+        }
+    }
+    res + &region_to_rle(field, 0, 0, field.get_width(), field.get_height())
+}
+
+/// Like `to_rle` for the part of the field with the top left corner at the cell, cut off at the edges of the field
+pub fn region_to_rle(field: &dyn DynAutomaton, top: usize, left: usize, width: usize, height: usize) -> String {
+    let field_width = field.get_width();
+    let width = width.min(field_width.saturating_sub(left));
+    let height = height.min(field.get_height().saturating_sub(top));
+    let is_multi_state = field.palette().len() > 2;
+    let mut tokens = Vec::new();
+    // rows at the end of the pattern and cells at the end of rows are dead, so they are skipped
+    let mut pending_rows = 0;
+    for row in top..(top + height) {
+        let mut runs: Vec<(u8, usize)> = Vec::new();
+        for col in left..(left + width) {
+            let state = field.state_at((row * field_width) + col);
+            match runs.last_mut() {
+                Some((value, count)) if *value == state => *count += 1,
+                _ => runs.push((state, 1)),
+            }
+        }
+        if runs.last().is_some_and(|&(value, _)| value == 0) {
+            runs.pop();
+        }
+        if runs.is_empty() {
+            pending_rows += 1;
+            continue;
+        }
+        if !tokens.is_empty() {
+            tokens.push(run_token(pending_rows + 1, "$"));
+        } else if pending_rows > 0 {
+            // empty rows at the start of the pattern are kept
+            tokens.push(run_token(pending_rows, "$"));
+        }
+        pending_rows = 0;
+        for (value, count) in runs {
+            tokens.push(run_token(count, &state_symbol(value, is_multi_state)));
+        }
+    }
+    tokens.push("!".to_string());
+
+    let mut res = format!("x = {width}, y = {height}");
+    if let Some(rule) = field.rule() {
+        let _ = write!(res, ", rule = {rule}");
+    }
+    res.push('\n');
+    let mut line_length = 0;
+    for token in tokens {
+        if line_length + token.len() > MAX_LINE_LENGTH {
+            res.push('\n');
+            line_length = 0;
+        }
+        res.push_str(&token);
+        line_length += token.len();
+    }
+    res.push('\n');
+    res
+}
+
+/// Parses a pattern in the plaintext format of `.cells` files, `O` for live and `.` for dead cells.
+/// Lines starting with `!` are comments. Rows may be shorter than the widest one, the missing cells are dead.
+/// Like in RLE, sides above `MAX_SIDE` are refused
+pub fn parse_plaintext(str: &str) -> Result<Field, ParseError> {
+    let rows: Vec<&str> = str.lines()
+        .map(|line| line.trim_end())
+        .filter(|line| !line.starts_with('!'))
+        .collect();
+    // empty rows at the end are only line breaks
+    let row_count = rows.iter().rposition(|row| !row.is_empty()).ok_or(ParseError::EmptyString)? + 1;
+    let width = NonZeroUsize::new(rows.iter().map(|row| row.chars().count()).max().unwrap_or(0)).ok_or(ParseError::EmptyString)?;
+    if width.get().max(row_count) > MAX_SIDE {
+        return Err(ParseError::InvalidHeader);
+    }
+    let mut field = Field::new(width, NonZeroUsize::new(row_count).unwrap());
+    for (row, line) in rows[..row_count].iter().enumerate() {
+        for (col, char) in line.chars().enumerate() {
+            match char {
+                'O' | '*' => field.set_by_coords(row, col, CellValue::Alive).ok_or(ParseError::WidthMismatch)?,
+                '.' => {}
+                _ => return Err(ParseError::UnknownChar),
+            }
+        }
+    }
+    Ok(field)
+}
+
+/// Writes the field in the plaintext format, automata with more than two states have all the other states alive.
+/// The dead cells at the end of rows are skipped
+pub fn to_plaintext(field: &dyn DynAutomaton) -> String {
+    let mut res = String::new();
+    for row in 0..field.get_height() {
+        let line: String = (0..field.get_width())
+            .map(|col| if field.state_at((row * field.get_width()) + col) == 0 { '.' } else { 'O' })
+            .collect();
+        res.push_str(line.trim_end_matches('.'));
+        res.push('\n');
+    }
+    res
+}
+
+/// Text formats of pattern files
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PatternFormat {
+    Rle,
+    /// `.cells` files
+    Plaintext,
+}
+impl PatternFormat {
+    /// Format of the file by its extension, RLE for unknown ones
+    pub fn from_file_name(name: &str) -> Self {
+        if name.to_ascii_lowercase().ends_with(".cells") { Self::Plaintext } else { Self::Rle }
+    }
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Rle => "rle",
+            Self::Plaintext => "cells",
+        }
+    }
+    pub fn parse(self, str: &str) -> Result<Field, ParseError> {
+        match self {
+            Self::Rle => parse_rle(str),
+            Self::Plaintext => parse_plaintext(str),
+        }
+    }
+    pub fn write(self, field: &dyn DynAutomaton) -> String {
+        match self {
+            Self::Rle => to_rle(field),
+            Self::Plaintext => to_plaintext(field),
+        }
+    }
+}
+
+fn run_token(count: usize, symbol: &str) -> String {
+    if count > 1 {
+        format!("{count}{symbol}")
+    } else {
+        symbol.to_string()
+    }
+}
+
+/// States after the first 24 letters get a prefix, `pA` to `pX`, then `qA` to `qX` and so on
+fn state_symbol(state: u8, is_multi_state: bool) -> String {
+    match (state, is_multi_state) {
+        (0, false) => "b".to_string(),
+        (_, false) => "o".to_string(),
+        (0, true) => ".".to_string(),
+        (1..=24, true) => char::from(b'A' + state - 1).to_string(),
+        _ => {
+            let index = state - 25;
+            format!("{}{}", char::from(b'p' + (index / 24)), char::from(b'A' + (index % 24)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::automaton::Automaton;
+    use crate::brians_brain::BriansBrain;
+    #[test]
+    fn test_parse_rle() {
+        let field = parse_rle("#N Glider\nx = 3, y = 3, rule = B36/S23\nbo$2bo$3o!").unwrap();
+        assert_eq!(Rule::HIGH_LIFE, field.get_rule());
+        assert_eq!("_#_\n__#\n###\n", field.to_string());
+
+        let field = parse_rle("x = 4, y = 3\n2o$\n\n$3bo!").unwrap();
+        assert_eq!("##__\n____\n___#\n", field.to_string());
+
+        assert_eq!(Some(ParseError::InvalidHeader), parse_rle("bo$2bo$3o!").err());
+        assert_eq!(Some(ParseError::WidthMismatch), parse_rle("x = 2, y = 1\n3o!").err());
+    }
+    #[test]
+    fn test_parse_rle_too_big() {
+        assert_eq!(Some(ParseError::WidthMismatch), parse_rle("x = 2, y = 1\n99999999999999999999o!").err());
+        assert_eq!(Some(ParseError::WidthMismatch), parse_rle(&format!("x = 2, y = 2\n{}$99$o!", usize::MAX)).err());
+        assert_eq!(Some(ParseError::InvalidHeader), parse_rle("x = 10000000000, y = 10000000000\no!").err());
+        assert_eq!(Some(ParseError::InvalidHeader), parse_rle(&format!("x = {}, y = 1\no!", MAX_SIDE + 1)).err());
+        assert!(parse_rle(&format!("x = {MAX_SIDE}, y = 1\no!")).is_ok());
+    }
+    #[test]
+    fn test_rle_round_trip() {
+        let field = parse_rle("x = 5, y = 4\nbo$2bo$3o2$!").unwrap();
+        let rle = to_rle(&field);
+        assert_eq!("x = 5, y = 4, rule = B3/S23\nbo$2bo$3o!\n", rle);
+        assert_eq!(field.to_string(), parse_rle(&rle).unwrap().to_string());
+
+        let field = parse_rle("x = 3, y = 4\n2$o!").unwrap();
+        assert_eq!("x = 3, y = 4, rule = B3/S23\n2$o!\n", to_rle(&field));
+    }
+    #[test]
+    fn test_rle_meta() {
+        let rle = "#N Glider\n#O Richard K. Guy\n#C The smallest spaceship\n#c  Found in 1969\nx = 3, y = 3\nbo$2bo$3o!";
+        let field = parse_rle(rle).unwrap();
+        let meta = PatternMeta {
+            name: Some("Glider".to_string()),
+            author: Some("Richard K. Guy".to_string()),
+            comments: vec!["The smallest spaceship".to_string(), "Found in 1969".to_string()],
+        };
+        assert_eq!(&meta, field.get_meta());
+        let written = to_rle(&field);
+        assert!(written.starts_with("#N Glider\n#O Richard K. Guy\n#C The smallest spaceship\n#C Found in 1969\nx = 3, y = 3"));
+        assert_eq!(&meta, parse_rle(&written).unwrap().get_meta());
+        // the metadata is kept by the transforms, but not written for regions
+        assert_eq!(&meta, field.rotated_clockwise().get_meta());
+        assert!(region_to_rle(&field, 0, 0, 3, 3).starts_with("x = 3"));
+        assert!(parse_rle("x = 1, y = 1\no!").unwrap().get_meta().is_empty());
+    }
+    #[test]
+    fn test_region_to_rle() {
+        let field = parse_rle("x = 5, y = 4\nbo$2bo$3o2$!").unwrap();
+        assert_eq!("x = 2, y = 2, rule = B3/S23\nbo$2o!\n", region_to_rle(&field, 1, 1, 2, 2));
+        // cut off at the edges
+        assert_eq!("x = 3, y = 3, rule = B3/S23\n2bo$3o!\n", region_to_rle(&field, 1, 0, 3, 10));
+        assert_eq!("x = 0, y = 0, rule = B3/S23\n!\n", region_to_rle(&field, 4, 5, 3, 3));
+    }
+    #[test]
+    fn test_plaintext() {
+        let field = parse_plaintext("!Name: Glider\n!\n.O\n..O\nOOO\n\n").unwrap();
+        assert_eq!("_#_\n__#\n###\n", field.to_string());
+        assert_eq!(".O\n..O\nOOO\n", to_plaintext(&field));
+        assert_eq!(field.to_string(), parse_plaintext(&to_plaintext(&field)).unwrap().to_string());
+
+        assert_eq!("___\n__#\n", parse_plaintext("\n..*").unwrap().to_string());
+        assert_eq!(Some(ParseError::EmptyString), parse_plaintext("!only a comment\n").err());
+        assert_eq!(Some(ParseError::UnknownChar), parse_plaintext(".o.").err());
+        assert_eq!(Some(ParseError::InvalidHeader), parse_plaintext(&"O".repeat(MAX_SIDE + 1)).err());
+
+        assert_eq!(PatternFormat::Plaintext, PatternFormat::from_file_name("glider.CELLS"));
+        assert_eq!(PatternFormat::Rle, PatternFormat::from_file_name("glider.rle"));
+    }
+    #[test]
+    fn test_multi_state_rle() {
+        let field = BriansBrain::parse("#~_\n___\n_~#").unwrap();
+        assert_eq!("x = 3, y = 3\nAB2$.BA!\n", to_rle(&field));
+        assert_eq!("pA", state_symbol(25, true));
+        assert_eq!("qB", state_symbol(50, true));
+    }
+}
@@ -0,0 +1,250 @@
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use web_sys::{Document, Element, HtmlInputElement, HtmlSelectElement};
+use crate::app::{add_message_listener, App, AppDispatcher, Message};
+use crate::control_panel::Group;
+use crate::error::AppError;
+use crate::game_of_life::{Field, Rule, Topology};
+use crate::listeners::Listeners;
+use crate::replay::Change;
+
+const PRESETS: [(&str, Rule); 6] = [
+    ("Life", Rule::LIFE),
+    ("HighLife", Rule::HIGH_LIFE),
+    ("Seeds", Rule::SEEDS),
+    ("Day & Night", Rule::DAY_AND_NIGHT),
+    ("Maze", Rule::MAZE),
+    ("Life without Death", Rule::LIFE_WITHOUT_DEATH),
+];
+/// Highest probability of a cell to flip after a generation that can be set with the noise slider, more noise drowns every pattern
+const MAX_NOISE: f64 = 0.01;
+
+/// Requests of the rule controls
+pub enum RuleCommand {
+    Set(Rule),
+    /// Joins the edges of the Life field in another way
+    SetTopology(Topology),
+    /// Probability of every cell of the Life field to flip after a generation, from a new seed
+    SetNoise(f64),
+}
+
+impl App {
+    pub fn update_rule(&mut self, command: RuleCommand) -> Result<(), AppError> {
+        match command {
+            RuleCommand::Set(rule) => {
+                self.state.life_rule = rule;
+                let _ = self.field.set_rule(&rule.to_string());
+                self.record_change(Change::SetRule { rule: rule.to_string() });
+                // the snapshot of the current generation has to have the new rule
+                self.state.history.push(self.state.generation.get(), self.field.as_ref());
+                self.state.reset_periods();
+                Ok(())
+            }
+            RuleCommand::SetTopology(topology) => {
+                self.set_topology(topology)?;
+                self.record_change(Change::SetTopology { topology });
+                Ok(())
+            }
+            RuleCommand::SetNoise(probability) => {
+                // the seed is recorded, so that replays and peers flip the same cells
+                let seed = (js_sys::Math::random() * u32::MAX as f64) as u64;
+                self.set_noise(probability, seed)?;
+                self.record_change(Change::SetNoise { probability, seed });
+                Ok(())
+            }
+        }
+    }
+
+    pub fn set_noise(&mut self, probability: f64, seed: u64) -> Result<(), AppError> {
+        let Some(field) = self.field.as_any_mut().downcast_mut::<Field>() else {
+            return Err(AppError::Unsupported("only Life fields can have noise"));
+        };
+        field.set_noise(probability, seed);
+        // noisy fields don't repeat, the periods found so far don't hold anymore
+        self.state.reset_periods();
+        Ok(())
+    }
+
+    /// Changes how the edges of the Life field are joined, the cells stay the same
+    pub fn set_topology(&mut self, topology: Topology) -> Result<(), AppError> {
+        let Some(field) = self.field.as_any_mut().downcast_mut::<Field>() else {
+            return Err(AppError::Unsupported("only Life fields have a topology"));
+        };
+        field.set_topology(topology);
+        // the snapshot of the current generation has to have the new topology
+        self.state.history.push(self.state.generation.get(), self.field.as_ref());
+        self.state.reset_periods();
+        Ok(())
+    }
+
+    /// Shows the topology and the noise of the current Life field, the controls are disabled for other fields
+    pub fn update_rule_controls(&mut self) {
+        let field = self.field.as_any_mut().downcast_ref::<Field>();
+        self.topology_select.set_disabled(field.is_none());
+        self.noise_input.set_disabled(field.is_none());
+        if let Some(field) = field {
+            let index = Topology::ALL.iter().position(|&(_, topology)| topology == field.get_topology()).unwrap_or(0);
+            self.topology_select.set_selected_index(index as i32);
+            self.noise_input.set_value_as_number(field.get_noise());
+        }
+    }
+}
+
+/// Adds the rule editor, starting with `initial`, and the topology and noise controls to the group
+pub fn add_rule_controls(
+    group: &Group,
+    initial: Rule,
+    topology_select: &HtmlSelectElement,
+    noise_input: &HtmlInputElement,
+    document: &Document,
+    listeners: &mut Listeners,
+    dispatcher: &Rc<AppDispatcher>,
+) -> Result<(), AppError> {
+    let rule_editor = {
+        let dispatcher = Rc::clone(dispatcher);
+        create_rule_editor(document, listeners, initial, move |rule| dispatcher.send(Message::Rule(RuleCommand::Set(rule))))?
+    };
+    group.add(&rule_editor)?;
+
+    let message = {
+        let select = topology_select.clone();
+        move || {
+            let index = usize::try_from(select.selected_index()).unwrap_or(0);
+            Message::Rule(RuleCommand::SetTopology(Topology::ALL.get(index).unwrap_or(&Topology::ALL[0]).1))
+        }
+    };
+    add_message_listener(listeners, topology_select, &["change"], dispatcher, message)?;
+    group.add(topology_select)?;
+
+    let message = {
+        let input = noise_input.clone();
+        move || Message::Rule(RuleCommand::SetNoise(input.value_as_number().clamp(0.0, MAX_NOISE)))
+    };
+    add_message_listener(listeners, noise_input, &["change"], dispatcher, message)?;
+    group.add(noise_input)
+}
+
+pub fn create_noise_input(document: &Document) -> Result<HtmlInputElement, AppError> {
+    let input = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    input.set_type("range");
+    input.set_min("0");
+    input.set_max(&MAX_NOISE.to_string());
+    input.set_step("any");
+    input.set_value("0");
+    input.set_title("Noise: the probability of every cell to be born or to die spontaneously after a generation, to test how robust the patterns are");
+    Ok(input)
+}
+
+pub fn create_topology_select(document: &Document) -> Result<HtmlSelectElement, AppError> {
+    let select = document.create_element("select")?.dyn_into::<HtmlSelectElement>()?;
+    select.set_title("How the edges of the Life field are joined, a pattern leaving at one edge comes back at the joined one");
+    for (name, _) in Topology::ALL {
+        let option = document.create_element("option")?;
+        option.set_text_content(Some(name));
+        select.append_child(&option)?;
+    }
+    Ok(select)
+}
+
+/// Checkboxes for birth and survival counts, a rulestring input and preset buttons, all kept in sync.
+/// `on_change` is called with the new rule every time it is edited, `initial` is shown at the start
+pub fn create_rule_editor(document: &Document, listeners: &mut Listeners, initial: Rule, on_change: impl Fn(Rule) + 'static) -> Result<Element, JsValue> {
+    let fieldset = document.create_element("fieldset")?;
+    let legend = document.create_element("legend")?;
+    legend.set_text_content(Some("Life rule"));
+    fieldset.append_child(&legend)?;
+
+    let mut birth_boxes = Vec::with_capacity(9);
+    let mut survival_boxes = Vec::with_capacity(9);
+    for (prefix, boxes) in [("B", &mut birth_boxes), ("S", &mut survival_boxes)] {
+        for count in 0..=8 {
+            let label = document.create_element("label")?;
+            let checkbox = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+            checkbox.set_type("checkbox");
+            label.append_child(&checkbox)?;
+            let text = document.create_element("span")?;
+            text.set_text_content(Some(&format!("{prefix}{count}")));
+            label.append_child(&text)?;
+            fieldset.append_child(&label)?;
+            boxes.push(checkbox);
+        }
+        let br = document.create_element("br")?;
+        fieldset.append_child(&br)?;
+    }
+
+    let text_input = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    text_input.set_type("text");
+    text_input.set_size(14);
+    text_input.set_title("Rulestring, like B3/S23");
+    fieldset.append_child(&text_input)?;
+
+    let editor = Rc::new(RuleEditor { birth_boxes, survival_boxes, text_input, on_change: Box::new(on_change) });
+    editor.show(initial);
+
+    for checkbox in editor.birth_boxes.iter().chain(editor.survival_boxes.iter()) {
+        let closure = {
+            let editor = Rc::clone(&editor);
+            Closure::<dyn Fn()>::new(move || {
+                let rule = editor.read_checkboxes();
+                editor.show(rule);
+                (editor.on_change)(rule);
+            })
+        };
+        listeners.add(checkbox, &["change"], closure)?;
+    }
+
+    let closure = {
+        let editor = Rc::clone(&editor);
+        Closure::<dyn Fn()>::new(move || {
+            match editor.text_input.value().parse::<Rule>() {
+                Ok(rule) => {
+                    editor.show(rule);
+                    (editor.on_change)(rule);
+                }
+                // restore the text of the rule that is still in use
+                Err(_) => editor.show(editor.read_checkboxes()),
+            }
+        })
+    };
+    listeners.add(&editor.text_input, &["change"], closure)?;
+
+    for (name, rule) in PRESETS {
+        let button = document.create_element("button")?;
+        button.set_text_content(Some(name));
+        let closure = {
+            let editor = Rc::clone(&editor);
+            Closure::<dyn Fn()>::new(move || {
+                editor.show(rule);
+                (editor.on_change)(rule);
+            })
+        };
+        listeners.add(&button, &["click"], closure)?;
+        fieldset.append_child(&button)?;
+    }
+
+    Ok(fieldset)
+}
+
+struct RuleEditor {
+    birth_boxes: Vec<HtmlInputElement>,
+    survival_boxes: Vec<HtmlInputElement>,
+    text_input: HtmlInputElement,
+    on_change: Box<dyn Fn(Rule)>,
+}
+impl RuleEditor {
+    fn read_checkboxes(&self) -> Rule {
+        let mut rule = Rule::from_masks(0, 0);
+        for count in 0..=8 {
+            rule.set_birth(count, self.birth_boxes[count as usize].checked());
+            rule.set_survival(count, self.survival_boxes[count as usize].checked());
+        }
+        rule
+    }
+    fn show(&self, rule: Rule) {
+        for count in 0..=8 {
+            self.birth_boxes[count as usize].set_checked(rule.is_birth(count));
+            self.survival_boxes[count as usize].set_checked(rule.is_survival(count));
+        }
+        self.text_input.set_value(&rule.to_string());
+    }
+}
@@ -0,0 +1,55 @@
+use wasm_bindgen::JsCast;
+use web_sys::{Document, Element, HtmlInputElement};
+use crate::error::AppError;
+use crate::history::StateHistory;
+
+/// Range input under the field for going back and forth through the recent generations.
+/// It can only be moved while the animation is paused, the value is the index of the snapshot in the history
+#[derive(Debug)]
+pub struct Scrubber {
+    container: Element,
+    input: HtmlInputElement,
+    label: Element,
+}
+impl Scrubber {
+    pub fn new(document: &Document) -> Result<Self, AppError> {
+        let container = document.create_element("div")?;
+        let input = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+        input.set_type("range");
+        input.set_min("0");
+        input.set_title("Go back to one of the recent generations while paused");
+        input.set_disabled(true);
+        container.append_child(&input)?;
+        let label = document.create_element("span")?;
+        container.append_child(&label)?;
+        Ok(Self { container, input, label })
+    }
+    pub fn element(&self) -> &Element {
+        &self.container
+    }
+    pub fn input(&self) -> &HtmlInputElement {
+        &self.input
+    }
+    /// Index of the snapshot chosen by the user
+    pub fn position(&self) -> Option<usize> {
+        let value = self.input.value_as_number();
+        (value >= 0.0).then_some(value as usize)
+    }
+    /// Shows where the generation is in the history, the input is disabled while the animation is running
+    pub fn update(&self, history: &StateHistory, generation: u64, is_enabled: bool) {
+        let position = history.position(generation);
+        self.input.set_max(&history.len().saturating_sub(1).to_string());
+        if let Some(position) = position {
+            self.input.set_value(&position.to_string());
+        }
+        self.input.set_disabled(!is_enabled || (history.len() < 2));
+        let newest = history.newest().map(|snapshot| snapshot.generation);
+        let text = match newest {
+            Some(newest) if newest > generation => format!(" generation {generation} ({} back)", newest - generation),
+            _ => format!(" generation {generation}"),
+        };
+        // the index of the snapshot means nothing to screen reader users
+        let _ = self.input.set_attribute("aria-valuetext", text.trim_start());
+        self.label.set_text_content(Some(&text));
+    }
+}
@@ -0,0 +1,134 @@
+use std::future::Future;
+use std::rc::Rc;
+use js_sys::{Array, Object, Promise, Reflect, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Document, DomException, File, FilePropertyBag, Window};
+use crate::app::{create_button, App, AppDispatcher, Message};
+use crate::console_warn;
+use crate::control_panel::Group;
+use crate::error::AppError;
+use crate::game_of_life::Field;
+use crate::listeners::Listeners;
+use crate::options;
+use crate::render::render_png_data_url;
+
+/// Pixels per cell of the image attached to shared links
+const SHARE_IMAGE_SCALE: u32 = 4;
+
+#[wasm_bindgen]
+extern "C" {
+    /// `navigator.share`, web-sys only has it among the unstable apis. Throws if the browser doesn't have it
+    #[wasm_bindgen(js_namespace = navigator, js_name = share, catch)]
+    fn navigator_share(data: &Object) -> Result<Promise, JsValue>;
+    /// `navigator.canShare`, missing in some browsers that have `share`
+    #[wasm_bindgen(js_namespace = navigator, js_name = canShare, catch)]
+    fn navigator_can_share(data: &Object) -> Result<bool, JsValue>;
+    /// `navigator.clipboard.writeText`, web-sys only has it among the unstable apis
+    #[wasm_bindgen(js_namespace = ["navigator", "clipboard"], js_name = writeText, catch)]
+    pub fn write_clipboard_text(text: &str) -> Result<Promise, JsValue>;
+}
+
+/// How the share sheet was closed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShareOutcome {
+    Shared,
+    /// The user closed the sheet without sharing
+    Cancelled,
+    /// The browser can't share, or sharing failed, the caller can fall back to the clipboard
+    Unavailable,
+}
+
+/// PNG file from a `data:image/png;base64,` url, as made by `canvas.toDataURL`
+pub fn png_file(window: &Window, data_url: &str, name: &str) -> Result<File, AppError> {
+    let (_, base64) = data_url.split_once(',').ok_or(AppError::Missing("image data"))?;
+    // the bytes are the code points of the decoded string
+    let bytes: Vec<u8> = window.atob(base64)?.chars().map(|char| char as u8).collect();
+    let mut properties = FilePropertyBag::new();
+    properties.type_("image/png");
+    Ok(File::new_with_u8_array_sequence_and_options(&Array::of1(&Uint8Array::from(bytes.as_slice())), name, &properties)?)
+}
+
+/// Opens the share sheet of the system with the url and the files, the files are left out if the browser can't share them.
+/// The sheet is opened right away, as browsers only allow it during the handling of a click
+pub fn share(title: &str, url: &str, files: &[File]) -> impl Future<Output = ShareOutcome> + 'static {
+    let data = Object::new();
+    // setting properties of a plain object can't fail
+    let _ = Reflect::set(&data, &"title".into(), &title.into());
+    let _ = Reflect::set(&data, &"url".into(), &url.into());
+    if !files.is_empty() {
+        let _ = Reflect::set(&data, &"files".into(), &files.iter().collect::<Array>());
+        if navigator_can_share(&data) != Ok(true) {
+            let _ = Reflect::delete_property(&data, &"files".into());
+        }
+    }
+    let shared = navigator_share(&data);
+    async move {
+        let Ok(shared) = shared else {
+            return ShareOutcome::Unavailable;
+        };
+        match JsFuture::from(shared).await {
+            Ok(_) => ShareOutcome::Shared,
+            Err(err) if err.dyn_ref::<DomException>().is_some_and(|err| err.name() == "AbortError") => ShareOutcome::Cancelled,
+            Err(err) => {
+                console_warn!("Sharing failed: {err:?}");
+                ShareOutcome::Unavailable
+            }
+        }
+    }
+}
+
+/// Requests of the share button
+pub enum ShareCommand {
+    /// Shares the permalink and an image of the field through the share sheet of the system
+    Open,
+    /// The share sheet was closed, or the link was copied instead
+    Closed(ShareOutcome),
+}
+
+impl App {
+    pub fn update_share(&mut self, command: ShareCommand) -> Result<(), AppError> {
+        match command {
+            ShareCommand::Open => self.share(),
+            ShareCommand::Closed(outcome) => {
+                match outcome {
+                    ShareOutcome::Shared => self.live_region.announce("Shared the field"),
+                    ShareOutcome::Unavailable => self.live_region.announce("Copied the link to the field"),
+                    ShareOutcome::Cancelled => {}
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Url that opens the app with the current Life field, other automata get the url of the page
+    fn permalink(&mut self) -> Result<String, AppError> {
+        let location = self.window.location();
+        let page = format!("{}{}", location.origin()?, location.pathname()?);
+        match self.field.as_any_mut().downcast_ref::<Field>() {
+            Some(field) => Ok(format!("{page}{}", options::permalink_query(&field.get_rule().to_string(), &field.to_string()))),
+            None => Ok(format!("{page}{}", location.search()?)),
+        }
+    }
+
+    fn share(&mut self) -> Result<(), AppError> {
+        let url = self.permalink()?;
+        let data_url = render_png_data_url(&self.document, self.field.as_ref(), SHARE_IMAGE_SCALE)?;
+        let image = png_file(&self.window, &data_url, "field.png")?;
+        let shared = share("Game of Life", &url, &[image]);
+        self.spawn(async move {
+            let outcome = shared.await;
+            if outcome == ShareOutcome::Unavailable {
+                wasm_bindgen_futures::JsFuture::from(write_clipboard_text(&url)?).await?;
+            }
+            Ok(Some(Message::Share(ShareCommand::Closed(outcome))))
+        });
+        Ok(())
+    }
+}
+
+pub fn add_share_button(group: &Group, document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<(), AppError> {
+    let share_button = create_button("Share", || Message::Share(ShareCommand::Open), document, listeners, dispatcher)?;
+    share_button.set_attribute("title", "Share a link to the field with an image of it, or copy the link where sharing isn't supported")?;
+    group.add(&share_button)
+}
@@ -0,0 +1,230 @@
+use std::num::NonZeroUsize;
+use crate::game_of_life::{CellValue, Field, Neighborhood, Rule, Topology, MAX_SIDE};
+
+/// First bytes of every snapshot
+pub const MAGIC: [u8; 4] = *b"GOLS";
+/// Readers reject snapshots with a newer major version. Minor versions only add sections, which older readers skip
+pub const MAJOR_VERSION: u8 = 1;
+pub const MINOR_VERSION: u8 = 1;
+
+/// Cells packed eight per byte in row-major order, the first cell in the lowest bit
+const CELLS_SECTION: u8 = 1;
+/// Age of every cell as a little-endian u16, in row-major order
+const AGES_SECTION: u8 = 2;
+/// Topology of the field as a u8, since version 1.1. Left out for the torus, which is the topology of older snapshots
+const TOPOLOGY_SECTION: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SnapshotError {
+    /// Not a snapshot at all
+    BadMagic,
+    /// Written by a newer version with an incompatible layout
+    UnsupportedVersion(u8),
+    /// The bytes end in the middle of a field or a section
+    Truncated,
+    /// Zero width or height, a side above `MAX_SIDE`, or a section that doesn't match the size
+    InvalidSize,
+    InvalidNeighborhood(u8),
+    InvalidTopology(u8),
+    /// The snapshot has no cells section
+    MissingCells,
+}
+
+/// Writes the field in the binary snapshot format, with the age of every cell if given.
+/// Layout, little-endian: magic, major and minor version, width and height as u32, birth and survival masks of the rule
+/// as u16, neighborhood as u8, then sections of a u8 tag, a u32 length and the data
+pub fn encode(field: &Field, ages: Option<&[u16]>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&[MAJOR_VERSION, MINOR_VERSION]);
+    bytes.extend_from_slice(&(field.get_width() as u32).to_le_bytes());
+    bytes.extend_from_slice(&(field.get_height() as u32).to_le_bytes());
+    let (birth, survival) = field.get_rule().masks();
+    bytes.extend_from_slice(&birth.to_le_bytes());
+    bytes.extend_from_slice(&survival.to_le_bytes());
+    bytes.push(neighborhood_code(field.get_neighborhood()));
+
+    let mut cells = vec![0u8; field.view().len().div_ceil(8)];
+    for (index, &value) in field.view().iter().enumerate() {
+        cells[index / 8] |= (value as u8) << (index % 8);
+    }
+    push_section(&mut bytes, CELLS_SECTION, &cells);
+    if let Some(ages) = ages {
+        let ages: Vec<u8> = ages.iter().flat_map(|age| age.to_le_bytes()).collect();
+        push_section(&mut bytes, AGES_SECTION, &ages);
+    }
+    if !field.get_topology().is_torus() {
+        push_section(&mut bytes, TOPOLOGY_SECTION, &[topology_code(field.get_topology())]);
+    }
+    bytes
+}
+
+/// Reads a snapshot written by `encode`, also by a newer minor version. Returns the ages if the snapshot has them
+pub fn decode(bytes: &[u8]) -> Result<(Field, Option<Vec<u16>>), SnapshotError> {
+    let mut reader = Reader { bytes };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(SnapshotError::BadMagic);
+    }
+    let [major, _minor] = reader.array()?;
+    if major != MAJOR_VERSION {
+        return Err(SnapshotError::UnsupportedVersion(major));
+    }
+    let width = reader.side()?;
+    let height = reader.side()?;
+    let birth = u16::from_le_bytes(reader.array()?);
+    let survival = u16::from_le_bytes(reader.array()?);
+    let [neighborhood] = reader.array()?;
+    let neighborhood = match neighborhood {
+        0 => Neighborhood::Moore,
+        1 => Neighborhood::VonNeumann,
+        2 => Neighborhood::ExtendedMoore,
+        code => return Err(SnapshotError::InvalidNeighborhood(code)),
+    };
+
+    let cell_count = width.get().checked_mul(height.get()).ok_or(SnapshotError::InvalidSize)?;
+    let mut field = None;
+    let mut ages = None;
+    let mut topology = Topology::Torus;
+    while !reader.bytes.is_empty() {
+        let [tag] = reader.array()?;
+        let length = u32::from_le_bytes(reader.array()?) as usize;
+        let data = reader.take(length)?;
+        match tag {
+            CELLS_SECTION => {
+                if data.len() != cell_count.div_ceil(8) {
+                    return Err(SnapshotError::InvalidSize);
+                }
+                let mut cells = Field::new(width, height).with_rule(Rule::from_masks(birth, survival)).with_neighborhood(neighborhood);
+                for index in (0..cell_count).filter(|&index| (data[index / 8] >> (index % 8)) & 1 == 1) {
+                    cells.set_by_coords(index / width.get(), index % width.get(), CellValue::Alive);
+                }
+                field = Some(cells);
+            }
+            AGES_SECTION => {
+                if Some(data.len()) != cell_count.checked_mul(2) {
+                    return Err(SnapshotError::InvalidSize);
+                }
+                ages = Some(data.chunks_exact(2).map(|age| u16::from_le_bytes([age[0], age[1]])).collect());
+            }
+            TOPOLOGY_SECTION => {
+                topology = match data {
+                    [0] => Topology::Torus,
+                    [1] => Topology::Plane,
+                    [2] => Topology::KleinBottle,
+                    [3] => Topology::ProjectivePlane,
+                    [code] => return Err(SnapshotError::InvalidTopology(*code)),
+                    _ => return Err(SnapshotError::InvalidSize),
+                };
+            }
+            // sections of newer minor versions
+            _ => {}
+        }
+    }
+    // the topology can come before or after the cells
+    Ok((field.ok_or(SnapshotError::MissingCells)?.with_topology(topology), ages))
+}
+
+fn neighborhood_code(neighborhood: Neighborhood) -> u8 {
+    match neighborhood {
+        Neighborhood::Moore => 0,
+        Neighborhood::VonNeumann => 1,
+        Neighborhood::ExtendedMoore => 2,
+    }
+}
+
+fn topology_code(topology: Topology) -> u8 {
+    match topology {
+        Topology::Torus => 0,
+        Topology::Plane => 1,
+        Topology::KleinBottle => 2,
+        Topology::ProjectivePlane => 3,
+    }
+}
+
+fn push_section(bytes: &mut Vec<u8>, tag: u8, data: &[u8]) {
+    bytes.push(tag);
+    bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(data);
+}
+
+/// Takes the bytes from the front
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.bytes.len() < count {
+            return Err(SnapshotError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], SnapshotError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+    /// Width or height as u32, from 1 to `MAX_SIDE`
+    fn side(&mut self) -> Result<NonZeroUsize, SnapshotError> {
+        let side = usize::try_from(u32::from_le_bytes(self.array()?)).map_err(|_| SnapshotError::InvalidSize)?;
+        NonZeroUsize::new(side).filter(|side| side.get() <= MAX_SIDE).ok_or(SnapshotError::InvalidSize)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+    #[test]
+    fn test_round_trip() {
+        let field = Field::from_str("_##_\n#_#_\n___#").unwrap().with_rule(Rule::HIGH_LIFE).with_neighborhood(Neighborhood::ExtendedMoore);
+        let bytes = field.to_bytes();
+        assert_eq!(&MAGIC, &bytes[..4]);
+        let parsed = Field::from_bytes(&bytes).unwrap();
+        assert_eq!(field.to_string(), parsed.to_string());
+        assert_eq!(Rule::HIGH_LIFE, parsed.get_rule());
+        assert_eq!(Neighborhood::ExtendedMoore, parsed.get_neighborhood());
+
+        let ages: Vec<u16> = (0..12).map(|age| age * 300).collect();
+        let (parsed, parsed_ages) = decode(&encode(&field, Some(&ages))).unwrap();
+        assert_eq!(field.to_string(), parsed.to_string());
+        assert_eq!(Some(ages), parsed_ages);
+
+        let twisted = field.clone().with_topology(Topology::KleinBottle);
+        assert_eq!(Topology::KleinBottle, Field::from_bytes(&twisted.to_bytes()).unwrap().get_topology());
+        // the torus adds no section, the snapshots are the same as before there were other topologies
+        assert_eq!(bytes.len() + 6, twisted.to_bytes().len());
+        assert_eq!(Topology::Torus, parsed.get_topology());
+    }
+    #[test]
+    fn test_versions() {
+        let field = Field::from_str("#_\n_#").unwrap();
+        let mut bytes = field.to_bytes();
+        // a section of a newer minor version is skipped
+        bytes[5] = MINOR_VERSION + 1;
+        push_section(&mut bytes, 99, &[1, 2, 3]);
+        assert_eq!(field.to_string(), Field::from_bytes(&bytes).unwrap().to_string());
+
+        bytes[4] = MAJOR_VERSION + 1;
+        assert_eq!(Some(SnapshotError::UnsupportedVersion(MAJOR_VERSION + 1)), Field::from_bytes(&bytes).err());
+    }
+    #[test]
+    fn test_invalid() {
+        let bytes = Field::from_str("#_\n_#").unwrap().to_bytes();
+        assert_eq!(Some(SnapshotError::BadMagic), Field::from_bytes(b"JUNK").err());
+        assert_eq!(Some(SnapshotError::Truncated), Field::from_bytes(&bytes[..bytes.len() - 1]).err());
+        assert_eq!(Some(SnapshotError::MissingCells), Field::from_bytes(&bytes[..19]).err());
+        let mut bytes = bytes;
+        bytes[18] = 7;
+        assert_eq!(Some(SnapshotError::InvalidNeighborhood(7)), Field::from_bytes(&bytes).err());
+        // the sizes of a header from a peer can't make the reader allocate or overflow
+        let mut oversized = bytes.clone();
+        oversized[6..14].fill(0xFF);
+        assert_eq!(Some(SnapshotError::InvalidSize), Field::from_bytes(&oversized).err());
+        oversized[6..10].copy_from_slice(&(MAX_SIDE as u32 + 1).to_le_bytes());
+        oversized[10..14].copy_from_slice(&1u32.to_le_bytes());
+        assert_eq!(Some(SnapshotError::InvalidSize), Field::from_bytes(&oversized).err());
+        bytes[18] = 0;
+        push_section(&mut bytes, TOPOLOGY_SECTION, &[9]);
+        assert_eq!(Some(SnapshotError::InvalidTopology(9)), Field::from_bytes(&bytes).err());
+    }
+}
@@ -0,0 +1,104 @@
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, Document, HtmlCanvasElement};
+use crate::automaton::DynAutomaton;
+use crate::error::AppError;
+use crate::game_of_life::{CellValue, Field};
+use crate::render::{cell_bounds, context_2d, resize_canvas, CELL_SIZE_PX};
+
+const PREVIEW_COLOR: &str = "#2196F3";
+const PREVIEW_ALPHA: f64 = 0.5;
+
+/// Cells of the field under the live cells of the pattern placed with its top left corner at the row and column.
+/// The corner can be outside of the field, the parts of the pattern outside are cut off
+pub fn stamp_cells(pattern: &Field, top: isize, left: isize, width: usize, height: usize) -> Vec<(usize, usize)> {
+    let mut cells = Vec::new();
+    for pattern_row in 0..pattern.get_height() {
+        for pattern_col in 0..pattern.get_width() {
+            if pattern.get_by_coords(pattern_row, pattern_col) != Some(CellValue::Alive) {
+                continue;
+            }
+            let row = top.checked_add_unsigned(pattern_row).and_then(|row| usize::try_from(row).ok());
+            let col = left.checked_add_unsigned(pattern_col).and_then(|col| usize::try_from(col).ok());
+            if let (Some(row), Some(col)) = (row, col) {
+                if (row < height) && (col < width) {
+                    cells.push((row, col));
+                }
+            }
+        }
+    }
+    cells
+}
+
+/// Top left corner of the pattern placed with its center on the cell
+pub fn centered_corner(pattern: &Field, row: usize, col: usize) -> (isize, isize) {
+    (row as isize - (pattern.get_height() / 2) as isize, col as isize - (pattern.get_width() / 2) as isize)
+}
+
+/// Transparent canvas over the field where the stamp pattern is drawn translucently under the cursor, before it is pasted
+#[derive(Debug)]
+pub struct StampPreview {
+    canvas: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+    /// Cell under the cursor, None while the preview is hidden
+    cell: Option<(usize, usize)>,
+}
+impl StampPreview {
+    pub fn new(document: &Document) -> Result<Self, AppError> {
+        let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+        canvas.set_attribute("style", "position: absolute; left: 0; top: 0; pointer-events: none;")?;
+        canvas.set_attribute("aria-hidden", "true")?;
+        let context = context_2d(&canvas)?;
+        Ok(Self { canvas, context, cell: None })
+    }
+    pub fn canvas(&self) -> &HtmlCanvasElement {
+        &self.canvas
+    }
+    pub fn cell(&self) -> Option<(usize, usize)> {
+        self.cell
+    }
+    /// Draws the pattern with its center on the cell, instead of the previous preview
+    pub fn show(&mut self, pattern: &Field, row: usize, col: usize, field: &dyn DynAutomaton, cell_size_px: usize) -> Result<(), AppError> {
+        if self.cell.is_none() {
+            // the field could have been resized or zoomed since the preview was hidden
+            resize_canvas(field, &self.canvas, &self.context, (cell_size_px + 1) as f64 / (CELL_SIZE_PX + 1) as f64)?;
+        }
+        self.clear();
+        self.cell = Some((row, col));
+        let (top, left) = centered_corner(pattern, row, col);
+        self.context.set_fill_style(&PREVIEW_COLOR.into());
+        self.context.set_global_alpha(PREVIEW_ALPHA);
+        for (row, col) in stamp_cells(pattern, top, left, field.get_width(), field.get_height()) {
+            let (x, y, width, height) = cell_bounds(field, row, col);
+            self.context.fill_rect(x, y, width, height);
+        }
+        self.context.set_global_alpha(1.0);
+        Ok(())
+    }
+    pub fn hide(&mut self) {
+        if self.cell.take().is_some() {
+            self.clear();
+        }
+    }
+    fn clear(&self) {
+        // the transform is scaled, the whole canvas is cleared in its own pixels
+        self.context.save();
+        let _ = self.context.set_transform(1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+        self.context.clear_rect(0.0, 0.0, self.canvas.width() as f64, self.canvas.height() as f64);
+        self.context.restore();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+    #[test]
+    fn test_stamp_cells() {
+        let glider = Field::from_str("_#_\n__#\n###").unwrap();
+        assert_eq!(vec![(1, 3), (2, 4), (3, 2), (3, 3), (3, 4)], stamp_cells(&glider, 1, 2, 8, 8));
+        // cut off at the edges
+        assert_eq!(vec![(0, 0), (0, 1)], stamp_cells(&glider, -2, -1, 8, 8));
+        assert_eq!(vec![(5, 7), (7, 6), (7, 7)], stamp_cells(&glider, 5, 6, 8, 8));
+        assert_eq!((-1, 1), centered_corner(&glider, 0, 2));
+    }
+}
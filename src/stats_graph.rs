@@ -0,0 +1,63 @@
+use wasm_bindgen::JsValue;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+use crate::ring_buffer::RingBuffer;
+
+const GRAPH_WIDTH_PX: u32 = 200;
+const GRAPH_HEIGHT_PX: u32 = 60;
+
+/// Small line graph of the number of cells in each state over the last generations.
+/// The background state (index 0) is not drawn.
+#[derive(Debug)]
+pub struct StatsGraph {
+    context: CanvasRenderingContext2d,
+    history: RingBuffer<Vec<usize>>,
+}
+impl StatsGraph {
+    pub fn new(context: CanvasRenderingContext2d) -> Self {
+        let canvas = context.canvas().unwrap();
+        canvas.set_width(GRAPH_WIDTH_PX);
+        canvas.set_height(GRAPH_HEIGHT_PX);
+        Self {
+            context,
+            history: RingBuffer::new(GRAPH_WIDTH_PX as usize),
+        }
+    }
+    pub fn canvas(&self) -> HtmlCanvasElement {
+        self.context.canvas().unwrap()
+    }
+    pub fn push(&mut self, counts: Vec<usize>) {
+        self.history.push(counts);
+    }
+    pub fn clear(&mut self) {
+        self.history.truncate();
+        self.context.clear_rect(0.0, 0.0, GRAPH_WIDTH_PX as f64, GRAPH_HEIGHT_PX as f64);
+    }
+    pub fn draw(&self, palette: &[&str]) {
+        let ctx = &self.context;
+        ctx.set_fill_style(&"#F4F4F4".into());
+        ctx.fill_rect(0.0, 0.0, GRAPH_WIDTH_PX as f64, GRAPH_HEIGHT_PX as f64);
+
+        let max = self.history.iter()
+            .flat_map(|counts| counts.iter().skip(1))
+            .copied()
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let scale_y = (GRAPH_HEIGHT_PX - 2) as f64 / max as f64;
+
+        for (state, &style) in palette.iter().enumerate().skip(1) {
+            ctx.begin_path();
+            ctx.set_stroke_style(&JsValue::from_str(style));
+            for (x, counts) in self.history.iter().enumerate() {
+                let count = counts.get(state).copied().unwrap_or(0);
+                let y = GRAPH_HEIGHT_PX as f64 - 1.0 - (count as f64 * scale_y);
+                if x == 0 {
+                    ctx.move_to(x as f64, y);
+                } else {
+                    ctx.line_to(x as f64, y);
+                }
+            }
+            ctx.stroke();
+        }
+    }
+}
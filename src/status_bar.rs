@@ -0,0 +1,96 @@
+use web_sys::{Document, Element};
+use crate::error::AppError;
+
+/// What the clicks and touches on the field do
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Paused, the cells are edited
+    Edit,
+    /// Running, the edits are applied with the next generation
+    Run,
+    /// Playing back a replay, editing ends the playback
+    Playback,
+}
+impl Mode {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Edit => "edit",
+            Self::Run => "run",
+            Self::Playback => "playback",
+        }
+    }
+}
+
+/// Everything shown in the status bar
+#[derive(Debug, Clone, PartialEq)]
+pub struct Status {
+    /// Name of the loaded pattern, if it has one
+    pub name: Option<String>,
+    pub generation: u64,
+    pub population: usize,
+    /// Names and populations of the teams, for automata where cells belong to teams
+    pub teams: Vec<(&'static str, usize)>,
+    /// Not set for automata without a configurable rule
+    pub rule: Option<String>,
+    pub width: usize,
+    pub height: usize,
+    /// Size of cells on the screen relative to the size they are drawn with, in percent
+    pub zoom_percent: usize,
+    pub mode: Mode,
+}
+impl Status {
+    fn text(&self) -> String {
+        let mut text = self.name.as_ref().map(|name| format!("{name} | ")).unwrap_or_default();
+        text += &format!("generation {} | population {}", self.generation, self.population);
+        if !self.teams.is_empty() {
+            let teams: Vec<_> = self.teams.iter().map(|(name, population)| format!("{name} {population}")).collect();
+            text += &format!(" ({})", teams.join(", "));
+        }
+        if let Some(rule) = &self.rule {
+            text += &format!(" | rule {rule}");
+        }
+        text + &format!(" | {}x{} | zoom {}% | {}", self.width, self.height, self.zoom_percent, self.mode.name())
+    }
+}
+
+/// Line under the field with the state of the app. It is only written when the status changes
+#[derive(Debug)]
+pub struct StatusBar {
+    element: Element,
+    last: Option<Status>,
+}
+impl StatusBar {
+    pub fn new(document: &Document) -> Result<Self, AppError> {
+        let element = document.create_element("div")?;
+        element.set_attribute("style", "font-family: monospace; white-space: nowrap;")?;
+        Ok(Self { element, last: None })
+    }
+    pub fn element(&self) -> &Element {
+        &self.element
+    }
+    pub fn render(&mut self, status: Status) {
+        if self.last.as_ref() == Some(&status) {
+            return;
+        }
+        self.element.set_text_content(Some(&status.text()));
+        self.last = Some(status);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_status_text() {
+        let mut status = Status { name: None, generation: 12, population: 30, teams: Vec::new(), rule: Some("B3/S23".to_string()), width: 64, height: 32, zoom_percent: 50, mode: Mode::Edit };
+        assert_eq!("generation 12 | population 30 | rule B3/S23 | 64x32 | zoom 50% | edit", status.text());
+        status.rule = None;
+        status.mode = Mode::Run;
+        assert_eq!("generation 12 | population 30 | 64x32 | zoom 50% | run", status.text());
+        status.name = Some("Gosper glider gun".to_string());
+        assert_eq!("Gosper glider gun | generation 12 | population 30 | 64x32 | zoom 50% | run", status.text());
+        status.name = None;
+        status.teams = vec![("Red", 18), ("Blue", 12)];
+        assert_eq!("generation 12 | population 30 (Red 18, Blue 12) | 64x32 | zoom 50% | run", status.text());
+    }
+}
@@ -0,0 +1,210 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+use crate::automaton::DynAutomaton;
+use crate::game_of_life::{Field, Neighborhood, ParseError, Rule, Topology};
+use crate::random::XorShift;
+use crate::rle::{parse_rle, to_rle};
+
+/// Largest width and height of the generated fields
+const MAX_GENERATED_SIZE: usize = 24;
+/// Rules, neighborhoods and topologies of the generated fields
+const GENERATED_RULES: [Rule; 5] = [Rule::LIFE, Rule::HIGH_LIFE, Rule::SEEDS, Rule::DAY_AND_NIGHT, Rule::MAZE];
+const GENERATED_NEIGHBORHOODS: [Neighborhood; 3] = [Neighborhood::Moore, Neighborhood::VonNeumann, Neighborhood::ExtendedMoore];
+const GENERATED_TOPOLOGIES: [Topology; 4] = [Topology::Torus, Topology::Plane, Topology::KleinBottle, Topology::ProjectivePlane];
+
+/// Known pattern and the cells it must have after a number of generations, both in RLE.
+/// The expected field has the same size as the initial one, the edges wrap around
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GoldenCase {
+    pub name: &'static str,
+    pub initial: &'static str,
+    pub generations: u64,
+    pub expected: &'static str,
+}
+
+/// Cases for Life, B3/S23 with the Moore neighborhood
+pub const LIFE_CASES: &[GoldenCase] = &[
+    GoldenCase { name: "block", initial: "x = 4, y = 4\n$b2o$b2o!", generations: 10, expected: "x = 4, y = 4\n$b2o$b2o!" },
+    GoldenCase { name: "beehive", initial: "x = 6, y = 5\n$2b2o$bo2bo$2b2o!", generations: 5, expected: "x = 6, y = 5\n$2b2o$bo2bo$2b2o!" },
+    GoldenCase { name: "blinker", initial: "x = 5, y = 5\n2$b3o!", generations: 1, expected: "x = 5, y = 5\n$2bo$2bo$2bo!" },
+    GoldenCase { name: "blinker period", initial: "x = 5, y = 5\n2$b3o!", generations: 2, expected: "x = 5, y = 5\n2$b3o!" },
+    GoldenCase { name: "glider", initial: "x = 8, y = 8\nbo$2bo$3o!", generations: 4, expected: "x = 8, y = 8\n$2bo$3bo$b3o!" },
+    GoldenCase { name: "glider around the torus", initial: "x = 8, y = 8\nbo$2bo$3o!", generations: 32, expected: "x = 8, y = 8\nbo$2bo$3o!" },
+];
+
+/// Why a golden case has failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum GoldenFailure {
+    /// The RLE of the case itself is invalid
+    InvalidInitial(ParseError),
+    InvalidExpected(ParseError),
+    /// The cells are different, both fields are written as RLE
+    Mismatch { expected: String, actual: String },
+}
+
+/// Failed case with the reason
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseFailure {
+    pub name: &'static str,
+    pub failure: GoldenFailure,
+}
+impl fmt::Display for CaseFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.failure {
+            GoldenFailure::InvalidInitial(err) => write!(f, "{}: invalid initial pattern: {err:?}", self.name),
+            GoldenFailure::InvalidExpected(err) => write!(f, "{}: invalid expected pattern: {err:?}", self.name),
+            GoldenFailure::Mismatch { expected, actual } => write!(f, "{}: expected\n{expected}got\n{actual}", self.name),
+        }
+    }
+}
+
+/// Runs a single case on the automaton that `load` makes from the initial field, the states of both are compared by palette index
+pub fn run_case(case: &GoldenCase, load: impl Fn(Field) -> Box<dyn DynAutomaton>) -> Result<(), CaseFailure> {
+    let fail = |failure| CaseFailure { name: case.name, failure };
+    let initial = parse_rle(case.initial).map_err(|err| fail(GoldenFailure::InvalidInitial(err)))?;
+    let expected = parse_rle(case.expected).map_err(|err| fail(GoldenFailure::InvalidExpected(err)))?;
+    let mut automaton = load(initial);
+    for _ in 0..case.generations {
+        automaton.step();
+    }
+    let is_same_size = (automaton.get_width() == expected.get_width()) && (automaton.get_height() == expected.get_height());
+    let is_same = is_same_size && (0..expected.get_height()).all(|row| {
+        (0..expected.get_width()).all(|col| automaton.state_by_coords(row, col) == DynAutomaton::state_by_coords(&expected, row, col))
+    });
+    if is_same {
+        return Ok(());
+    }
+    Err(fail(GoldenFailure::Mismatch { expected: to_rle(&expected), actual: to_rle(automaton.as_ref()) }))
+}
+
+/// Runs all the cases, also after a failure, and returns the failures in the order of the cases
+pub fn run_golden(cases: &[GoldenCase], load: impl Fn(Field) -> Box<dyn DynAutomaton>) -> Vec<CaseFailure> {
+    cases.iter().filter_map(|case| run_case(case, &load).err()).collect()
+}
+
+/// Panics with all the failures, for use in the tests of automata
+pub fn assert_golden(cases: &[GoldenCase], load: impl Fn(Field) -> Box<dyn DynAutomaton>) {
+    let failures = run_golden(cases, load);
+    let messages: Vec<_> = failures.iter().map(|failure| failure.to_string()).collect();
+    assert!(failures.is_empty(), "{} golden cases failed:\n{}", failures.len(), messages.join("\n"));
+}
+
+/// Field of a random size, density, rule, neighborhood and topology. The same state of the generator gives the same field
+pub fn random_field(random: &mut XorShift) -> Field {
+    let width = NonZeroUsize::new(random.next_below(MAX_GENERATED_SIZE) + 1).unwrap();
+    let height = NonZeroUsize::new(random.next_below(MAX_GENERATED_SIZE) + 1).unwrap();
+    let density = random.next_f64();
+    let rule = GENERATED_RULES[random.next_below(GENERATED_RULES.len())];
+    let neighborhood = GENERATED_NEIGHBORHOODS[random.next_below(GENERATED_NEIGHBORHOODS.len())];
+    let topology = GENERATED_TOPOLOGIES[random.next_below(GENERATED_TOPOLOGIES.len())];
+    let random = RefCell::new(random);
+    Field::generate_by_fn(width, height, |_| random.borrow_mut().next_f64() < density)
+        .with_rule(rule)
+        .with_neighborhood(neighborhood)
+        .with_topology(topology)
+}
+
+/// Property of `Field` that must hold for any cells
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Invariant {
+    /// Parsing the text of the field gives the same cells
+    TextRoundTrip,
+    /// Parsing the RLE of the field gives the same cells and rule
+    RleRoundTrip,
+    /// Four quarter turns give the same field
+    RotateFourTimes,
+    /// Two flips give the same field
+    FlipTwice,
+    /// A field without live cells stays empty, for rules without birth on 0 neighbours
+    EmptyStaysEmpty,
+    /// The population is never bigger than the number of cells
+    PopulationWithinCells,
+}
+
+/// Invariant that doesn't hold for a generated field, with the field written as RLE so that the case can be reproduced
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub invariant: Invariant,
+    pub field: String,
+}
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} doesn't hold for\n{}", self.invariant, self.field)
+    }
+}
+
+/// Checks all the invariants on the field, and on the field after a step
+pub fn check_invariants(field: &Field) -> Result<(), Violation> {
+    let violation = |invariant| Violation { invariant, field: to_rle(field) };
+    let is_same = |other: &Field| (other.to_string() == field.to_string()) && (other.get_rule() == field.get_rule());
+    if Field::from_str(&field.to_string()).map(|parsed| parsed.to_string()) != Ok(field.to_string()) {
+        return Err(violation(Invariant::TextRoundTrip));
+    }
+    if !parse_rle(&to_rle(field)).is_ok_and(|parsed| (parsed.get_rule() == field.get_rule()) && (parsed.to_string() == field.to_string())) {
+        return Err(violation(Invariant::RleRoundTrip));
+    }
+    if !is_same(&field.rotated_clockwise().rotated_clockwise().rotated_clockwise().rotated_clockwise()) {
+        return Err(violation(Invariant::RotateFourTimes));
+    }
+    if !is_same(&field.flipped_horizontally().flipped_horizontally()) {
+        return Err(violation(Invariant::FlipTwice));
+    }
+    if !field.get_rule().is_birth(0) {
+        let width = NonZeroUsize::new(field.get_width()).unwrap();
+        let height = NonZeroUsize::new(field.get_height()).unwrap();
+        let mut empty = Field::new(width, height)
+            .with_rule(field.get_rule())
+            .with_neighborhood(field.get_neighborhood())
+            .with_topology(field.get_topology());
+        empty.update();
+        if empty.view().iter().any(|&value| value as u8 != 0) {
+            return Err(violation(Invariant::EmptyStaysEmpty));
+        }
+    }
+    let mut next = field.clone();
+    next.update();
+    if next.count_by_state().iter().skip(1).sum::<usize>() > next.get_width() * next.get_height() {
+        return Err(violation(Invariant::PopulationWithinCells));
+    }
+    Ok(())
+}
+
+/// Checks the invariants on the number of random fields from the seed, and returns the first violation
+pub fn check_random_fields(seed: u64, count: usize) -> Result<(), Violation> {
+    let mut random = XorShift::new(seed);
+    (0..count).try_for_each(|_| check_invariants(&random_field(&mut random)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_of_life::Rule;
+    use crate::verify::NaiveLife;
+    #[test]
+    fn test_life_cases() {
+        assert_golden(LIFE_CASES, |field| Box::new(field));
+        assert_golden(LIFE_CASES, |field| Box::new(NaiveLife::from_field(&field)));
+    }
+    #[test]
+    fn test_failures() {
+        let failures = run_golden(LIFE_CASES, |field| Box::new(field.with_rule(Rule::SEEDS)));
+        assert_eq!(vec!["block", "beehive", "blinker", "blinker period", "glider", "glider around the torus"], failures.iter().map(|failure| failure.name).collect::<Vec<_>>());
+        assert!(matches!(failures[0].failure, GoldenFailure::Mismatch { .. }));
+
+        let case = GoldenCase { name: "invalid", initial: "bo$2bo$3o!", generations: 1, expected: "x = 1, y = 1\n!" };
+        assert_eq!(Err(CaseFailure { name: "invalid", failure: GoldenFailure::InvalidInitial(ParseError::InvalidHeader) }), run_case(&case, |field| Box::new(field)));
+    }
+    #[test]
+    fn test_invariants() {
+        if let Err(violation) = check_random_fields(1133, 200) {
+            panic!("{violation}");
+        }
+        let mut random = XorShift::new(5);
+        assert_eq!(random_field(&mut XorShift::new(5)).to_string(), random_field(&mut random).to_string());
+        // a rule with birth on 0 neighbours fills an empty field
+        let field = Field::from_str("___\n___").unwrap().with_rule("B0/S".parse().unwrap());
+        assert_eq!(Ok(()), check_invariants(&field));
+    }
+}
@@ -0,0 +1,29 @@
+/// Colors of a two state field, used for exports
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub dead: String,
+    pub alive: String,
+    /// Grid lines are not drawn if this is not set
+    pub grid: Option<String>,
+}
+impl Theme {
+    pub fn light() -> Self {
+        Self {
+            dead: "#FFFFFF".to_string(),
+            alive: "#000000".to_string(),
+            grid: Some("#CCCCCC".to_string()),
+        }
+    }
+    pub fn dark() -> Self {
+        Self {
+            dead: "#111111".to_string(),
+            alive: "#7FD1B9".to_string(),
+            grid: Some("#333333".to_string()),
+        }
+    }
+}
+impl Default for Theme {
+    fn default() -> Self {
+        Self::light()
+    }
+}
@@ -0,0 +1,258 @@
+use std::f32::consts::TAU;
+use std::rc::Rc;
+use wasm_bindgen::JsCast;
+use web_sys::{Document, HtmlCanvasElement, WebGlRenderingContext as Gl, WebGlTexture, WebGlUniformLocation};
+use crate::app::{create_button, now_ms, App, AppDispatcher, Message};
+use crate::automaton::DynAutomaton;
+use crate::control_panel::Group;
+use crate::error::AppError;
+use crate::listeners::Listeners;
+use crate::render::parse_color;
+use crate::webgl::{bind_attributes, context_webgl, link_program, perspective, resize};
+
+/// Width and height of the view in css pixels
+const VIEW_SIZE_PX: u32 = 320;
+/// Radius of the ring through the middle of the tube, and of the tube
+const MAJOR_RADIUS: f32 = 1.0;
+const MINOR_RADIUS: f32 = 0.45;
+/// Quads around the ring and around the tube, enough for fields of a few hundred cells to look round
+const MAJOR_SEGMENTS: usize = 96;
+const MINOR_SEGMENTS: usize = 48;
+/// Floats of a vertex: the position, the normal and the texture coordinates
+const VERTEX_FLOATS: usize = 8;
+/// Turns of the torus per second around its axis
+const TURNS_PER_S: f32 = 0.05;
+/// The torus is tilted towards the viewer, so that the hole and the inner side are visible
+const TILT: f32 = 1.0;
+/// Distance of the center of the torus from the camera and the vertical angle of the view, the whole torus stays in it
+const CAMERA_DISTANCE: f32 = 4.5;
+const FIELD_OF_VIEW: f32 = 0.9;
+
+const VERTEX_SHADER: &str = r#"
+attribute vec3 position;
+attribute vec3 normal;
+attribute vec2 uv;
+uniform mat4 projection;
+uniform mat4 model_view;
+varying vec2 v_uv;
+varying float v_light;
+void main() {
+    gl_Position = projection * model_view * vec4(position, 1.0);
+    vec3 turned_normal = normalize((model_view * vec4(normal, 0.0)).xyz);
+    v_light = 0.45 + 0.55 * max(dot(turned_normal, normalize(vec3(0.3, 0.5, 1.0))), 0.0);
+    v_uv = uv;
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+uniform sampler2D cells;
+varying vec2 v_uv;
+varying float v_light;
+void main() {
+    gl_FragColor = vec4(texture2D(cells, v_uv).rgb * v_light, 1.0);
+}
+"#;
+
+/// Vertices and triangles of a torus around the z axis. The columns of the texture go around the ring and the rows around
+/// the tube, so the left and right edges of the field meet, and so do the top and bottom ones, like the cells that wrap.
+/// The vertices of the seams are doubled, so that the texture coordinates can go from 0 to 1 on both sides of them
+pub fn torus_mesh(major_segments: usize, minor_segments: usize) -> (Vec<f32>, Vec<u16>) {
+    let mut vertices = Vec::with_capacity((major_segments + 1) * (minor_segments + 1) * VERTEX_FLOATS);
+    for row in 0..=minor_segments {
+        let v = row as f32 / minor_segments as f32;
+        let (tube_sin, tube_cos) = (v * TAU).sin_cos();
+        for col in 0..=major_segments {
+            let u = col as f32 / major_segments as f32;
+            let (ring_sin, ring_cos) = (u * TAU).sin_cos();
+            let normal = [tube_cos * ring_cos, tube_cos * ring_sin, tube_sin];
+            let radius = MAJOR_RADIUS + MINOR_RADIUS * tube_cos;
+            vertices.extend_from_slice(&[radius * ring_cos, radius * ring_sin, MINOR_RADIUS * tube_sin]);
+            vertices.extend_from_slice(&normal);
+            vertices.extend_from_slice(&[u, v]);
+        }
+    }
+    let stride = major_segments + 1;
+    let mut indices = Vec::with_capacity(major_segments * minor_segments * 6);
+    for row in 0..minor_segments {
+        for col in 0..major_segments {
+            let top_left = (row * stride + col) as u16;
+            let bottom_left = top_left + stride as u16;
+            indices.extend_from_slice(&[top_left, bottom_left, top_left + 1, top_left + 1, bottom_left, bottom_left + 1]);
+        }
+    }
+    (vertices, indices)
+}
+
+/// Column-major transform that spins the torus around its axis by the angle, tilts it by `TILT` around the x axis
+/// and moves it away from the camera
+fn model_view(angle: f32) -> [f32; 16] {
+    let (spin_sin, spin_cos) = angle.sin_cos();
+    let (tilt_sin, tilt_cos) = (-TILT).sin_cos();
+    [
+        spin_cos, spin_sin * tilt_cos, spin_sin * tilt_sin, 0.0,
+        -spin_sin, spin_cos * tilt_cos, spin_cos * tilt_sin, 0.0,
+        0.0, -tilt_sin, tilt_cos, 0.0,
+        0.0, 0.0, -CAMERA_DISTANCE, 1.0,
+    ]
+}
+
+/// Shows the field as the texture of a slowly turning torus, the shape of a field whose edges wrap around.
+/// Hidden until it is turned on, and only drawn while it is visible
+pub struct TorusView {
+    canvas: HtmlCanvasElement,
+    gl: Gl,
+    texture: WebGlTexture,
+    model_view: Option<WebGlUniformLocation>,
+    index_count: i32,
+    /// RGBA bytes of every cell
+    pixels: Vec<u8>,
+    is_visible: bool,
+}
+impl TorusView {
+    pub fn new(document: &Document) -> Result<Self, AppError> {
+        let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+        canvas.set_attribute("aria-label", "The field on a torus, the shape it has when its edges wrap around")?;
+        canvas.set_attribute("role", "img")?;
+        canvas.set_attribute("style", &format!("display: none; vertical-align: top; width: {VIEW_SIZE_PX}px; height: {VIEW_SIZE_PX}px; border: 1px solid #888888; background-color: #000000;"))?;
+        let gl = context_webgl(&canvas)?;
+
+        let program = link_program(&gl, VERTEX_SHADER, FRAGMENT_SHADER)?;
+        let (vertices, indices) = torus_mesh(MAJOR_SEGMENTS, MINOR_SEGMENTS);
+        let vertex_buffer = gl.create_buffer().ok_or(AppError::Missing("WebGL buffer"))?;
+        gl.bind_buffer(Gl::ARRAY_BUFFER, Some(&vertex_buffer));
+        gl.buffer_data_with_array_buffer_view(Gl::ARRAY_BUFFER, &js_sys::Float32Array::from(vertices.as_slice()), Gl::STATIC_DRAW);
+        let index_buffer = gl.create_buffer().ok_or(AppError::Missing("WebGL buffer"))?;
+        gl.bind_buffer(Gl::ELEMENT_ARRAY_BUFFER, Some(&index_buffer));
+        gl.buffer_data_with_array_buffer_view(Gl::ELEMENT_ARRAY_BUFFER, &js_sys::Uint16Array::from(indices.as_slice()), Gl::STATIC_DRAW);
+        bind_attributes(&gl, &program, VERTEX_FLOATS, &[("position", 3, 0), ("normal", 3, 3), ("uv", 2, 6)])?;
+
+        let texture = gl.create_texture().ok_or(AppError::Missing("WebGL texture"))?;
+        gl.bind_texture(Gl::TEXTURE_2D, Some(&texture));
+        // fields of any size, WebGL 1 only repeats and mipmaps textures with power of two sizes
+        gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MIN_FILTER, Gl::NEAREST as i32);
+        gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MAG_FILTER, Gl::NEAREST as i32);
+        gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_WRAP_S, Gl::CLAMP_TO_EDGE as i32);
+        gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_WRAP_T, Gl::CLAMP_TO_EDGE as i32);
+
+        resize(&gl, &canvas, VIEW_SIZE_PX, VIEW_SIZE_PX);
+        gl.uniform_matrix4fv_with_f32_array(gl.get_uniform_location(&program, "projection").as_ref(), false, &perspective(FIELD_OF_VIEW, 1.0, 0.1, 10.0));
+        gl.enable(Gl::DEPTH_TEST);
+        gl.clear_color(0.0, 0.0, 0.0, 1.0);
+        Ok(Self {
+            canvas,
+            model_view: gl.get_uniform_location(&program, "model_view"),
+            gl,
+            texture,
+            index_count: indices.len() as i32,
+            pixels: Vec::new(),
+            is_visible: false,
+        })
+    }
+    pub fn canvas(&self) -> &HtmlCanvasElement {
+        &self.canvas
+    }
+    pub fn is_visible(&self) -> bool {
+        self.is_visible
+    }
+    pub fn set_visible(&mut self, is_visible: bool) {
+        self.is_visible = is_visible;
+        // setting styles can't fail for this property
+        let _ = self.canvas.style().set_property("display", if is_visible { "inline-block" } else { "none" });
+    }
+    /// Puts the current cells on the torus and turns it to the angle for the time, if the view is visible
+    pub fn draw(&mut self, field: &dyn DynAutomaton, timestamp_ms: f64) {
+        if !self.is_visible {
+            return;
+        }
+        let (width, height) = (field.get_width(), field.get_height());
+        let colors = field.palette().iter().map(|&color| parse_color(color)).collect::<Vec<_>>();
+        self.pixels.clear();
+        for index in 0..(width * height) {
+            self.pixels.extend_from_slice(&colors[field.state_at(index) as usize]);
+        }
+        let gl = &self.gl;
+        gl.bind_texture(Gl::TEXTURE_2D, Some(&self.texture));
+        // can only fail for invalid arguments
+        let _ = gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+            Gl::TEXTURE_2D, 0, Gl::RGBA as i32, width as i32, height as i32, 0, Gl::RGBA, Gl::UNSIGNED_BYTE, Some(&self.pixels),
+        );
+        let angle = (timestamp_ms / 1000.0) as f32 * TURNS_PER_S * TAU;
+        gl.uniform_matrix4fv_with_f32_array(self.model_view.as_ref(), false, &model_view(angle % TAU));
+        gl.clear(Gl::COLOR_BUFFER_BIT | Gl::DEPTH_BUFFER_BIT);
+        gl.draw_elements_with_i32(Gl::TRIANGLES, self.index_count, Gl::UNSIGNED_SHORT, 0);
+    }
+}
+
+impl App {
+    /// Shows or hides the field on a 3D torus
+    pub fn toggle_torus(&mut self) -> Result<(), AppError> {
+        let torus = match &mut self.torus {
+            Some(torus) => torus,
+            // WebGL is only set up when the view is first shown, the app works without it
+            None => {
+                let torus = TorusView::new(&self.document)?;
+                self.root.append_child(torus.canvas())?;
+                self.torus.insert(torus)
+            }
+        };
+        torus.set_visible(!torus.is_visible());
+        torus.draw(self.field.as_ref(), now_ms(&self.window));
+        Ok(())
+    }
+}
+
+/// Adds the button that shows the field on a torus to the group
+pub fn add_torus_button(group: &Group, document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<(), AppError> {
+    let torus_button = create_button("Toggle Torus View", || Message::ToggleTorus, document, listeners, dispatcher)?;
+    torus_button.set_attribute("title", "Show the field on a turning torus, the shape of a field whose edges wrap around")?;
+    group.add(&torus_button)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::webgl::transform;
+    #[test]
+    fn test_torus_mesh() {
+        let (vertices, indices) = torus_mesh(8, 4);
+        assert_eq!(9 * 5 * VERTEX_FLOATS, vertices.len());
+        assert_eq!(8 * 4 * 6, indices.len());
+        assert!(indices.iter().all(|&index| (index as usize) < 9 * 5));
+        for vertex in vertices.chunks_exact(VERTEX_FLOATS) {
+            let [x, y, z] = [vertex[0], vertex[1], vertex[2]];
+            // on the surface of the tube
+            let from_ring = ((x.hypot(y) - MAJOR_RADIUS).powi(2) + z * z).sqrt();
+            assert!((from_ring - MINOR_RADIUS).abs() < 1e-5);
+            let normal_length = (vertex[3].powi(2) + vertex[4].powi(2) + vertex[5].powi(2)).sqrt();
+            assert!((normal_length - 1.0).abs() < 1e-5);
+            assert!((0.0..=1.0).contains(&vertex[6]) && (0.0..=1.0).contains(&vertex[7]));
+        }
+        // the first and the last column of the texture meet at the seam
+        let first = &vertices[0..3];
+        let last = &vertices[8 * VERTEX_FLOATS..8 * VERTEX_FLOATS + 3];
+        assert!(first.iter().zip(last).all(|(a, b)| (a - b).abs() < 1e-5));
+        assert_eq!((0.0, 1.0), (vertices[6], vertices[8 * VERTEX_FLOATS + 6]));
+    }
+    #[test]
+    fn test_transforms() {
+        // the center of the torus is in front of the camera, in the middle of the view
+        let center = transform(&model_view(0.5), [0.0, 0.0, 0.0]);
+        assert_eq!([0.0, 0.0, -CAMERA_DISTANCE, 1.0], center);
+        let projected = transform(&perspective(FIELD_OF_VIEW, 1.0, 0.1, 10.0), [center[0], center[1], center[2]]);
+        assert_eq!(CAMERA_DISTANCE, projected[3]);
+        assert!((-1.0..=1.0).contains(&(projected[2] / projected[3])));
+        // rotations keep the distances
+        let point = transform(&model_view(1.3), [MAJOR_RADIUS + MINOR_RADIUS, 0.0, 0.0]);
+        let distance = (point[0].powi(2) + point[1].powi(2) + (point[2] + CAMERA_DISTANCE).powi(2)).sqrt();
+        assert!((distance - (MAJOR_RADIUS + MINOR_RADIUS)).abs() < 1e-5);
+        // the whole torus fits into the view
+        let (vertices, _) = torus_mesh(MAJOR_SEGMENTS, MINOR_SEGMENTS);
+        let projection = perspective(FIELD_OF_VIEW, 1.0, 0.1, 10.0);
+        for vertex in vertices.chunks_exact(VERTEX_FLOATS) {
+            let [x, y, z, _] = transform(&model_view(0.7), [vertex[0], vertex[1], vertex[2]]);
+            let [clip_x, clip_y, _, w] = transform(&projection, [x, y, z]);
+            assert!((clip_x / w).abs() < 1.0 && (clip_y / w).abs() < 1.0);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::fmt;
+use std::num::{NonZeroU64, NonZeroUsize};
+use crate::automaton::{parse_cells, Automaton, DynAutomaton};
+use crate::game_of_life::{CellValue, Field, Neighborhood, ParseError, Rule, Topology};
+
+/// Straightforward Life engine without any optimizations, the reference that the optimized engines are verified against.
+/// Every step counts the neighbours of every cell from scratch, the edges are joined like in `Field`
+#[derive(Clone)]
+pub struct NaiveLife {
+    width: NonZeroUsize,
+    height: NonZeroUsize,
+    rule: Rule,
+    neighborhood: Neighborhood,
+    topology: Topology,
+    cells: Vec<CellValue>,
+    old_cells: Vec<CellValue>,
+}
+impl NaiveLife {
+    pub fn new(width: NonZeroUsize, height: NonZeroUsize) -> Self {
+        let cell_count = width.get() * height.get();
+        Self {
+            width,
+            height,
+            rule: Rule::LIFE,
+            neighborhood: Neighborhood::Moore,
+            topology: Topology::Torus,
+            cells: vec![CellValue::Dead; cell_count],
+            old_cells: vec![CellValue::Dead; cell_count],
+        }
+    }
+    /// Copy of the cells, the rule, the neighborhood and the topology of the field
+    pub fn from_field(field: &Field) -> Self {
+        let width = NonZeroUsize::new(field.get_width()).unwrap();
+        let height = NonZeroUsize::new(field.get_height()).unwrap();
+        Self {
+            rule: field.get_rule(),
+            neighborhood: field.get_neighborhood(),
+            topology: field.get_topology(),
+            cells: field.view().to_vec(),
+            ..Self::new(width, height)
+        }
+    }
+    fn live_neighbours(&self, row: usize, col: usize) -> u8 {
+        let (width, height) = (self.width.get(), self.height.get());
+        self.neighborhood.offsets().iter()
+            .filter_map(|&offset| self.topology.neighbour(row, col, offset, width, height))
+            .map(|(neighbour_row, neighbour_col)| self.cells[(neighbour_row * width) + neighbour_col] as u8)
+            .sum()
+    }
+}
+impl Automaton for NaiveLife {
+    type State = CellValue;
+    fn get_width(&self) -> usize {
+        self.width.get()
+    }
+    fn get_height(&self) -> usize {
+        self.height.get()
+    }
+    fn view(&self) -> &[CellValue] {
+        &self.cells
+    }
+    fn view_old(&self) -> &[CellValue] {
+        &self.old_cells
+    }
+    fn step(&mut self) -> bool {
+        let width = self.width.get();
+        let next: Vec<_> = (0..self.cells.len())
+            .map(|index| {
+                let live_neighbours = self.live_neighbours(index / width, index % width);
+                let is_alive = match self.cells[index] {
+                    CellValue::Alive => self.rule.is_survival(live_neighbours),
+                    CellValue::Dead => self.rule.is_birth(live_neighbours),
+                };
+                if is_alive { CellValue::Alive } else { CellValue::Dead }
+            })
+            .collect();
+        self.old_cells = std::mem::replace(&mut self.cells, next);
+        self.cells != self.old_cells
+    }
+    fn set(&mut self, row: usize, col: usize, value: CellValue) -> Option<()> {
+        if (row >= self.height.get()) || (col >= self.width.get()) {
+            return None;
+        }
+        self.cells[(row * self.width.get()) + col] = value;
+        Some(())
+    }
+    fn parse(str: &str) -> Result<Self, ParseError> {
+        let (width, height, cells) = parse_cells::<CellValue>(str)?;
+        Ok(Self { cells, ..Self::new(width, height) })
+    }
+    fn rule(&self) -> Option<String> {
+        Some(self.rule.to_string())
+    }
+    fn set_rule(&mut self, rule: &str) -> Result<(), ParseError> {
+        self.rule = rule.parse()?;
+        Ok(())
+    }
+}
+
+/// First cell where the verified engine differs from the reference one.
+/// The state is None for a cell that is outside of one of the fields, when their sizes differ
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Divergence {
+    pub generation: u64,
+    pub row: usize,
+    pub col: usize,
+    pub expected: Option<u8>,
+    pub actual: Option<u8>,
+}
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = |state: Option<u8>| state.map_or("no cell".to_string(), |state| format!("state {state}"));
+        write!(
+            f,
+            "engines diverged at generation {}, row {}, column {}: expected {}, got {}",
+            self.generation, self.row, self.col, state(self.expected), state(self.actual),
+        )
+    }
+}
+
+/// First cell in row-major order that is different in the fields, the whole area of both fields is compared
+fn first_difference(reference: &dyn DynAutomaton, verified: &dyn DynAutomaton, generation: u64) -> Option<Divergence> {
+    let height = reference.get_height().max(verified.get_height());
+    let width = reference.get_width().max(verified.get_width());
+    (0..height).flat_map(|row| (0..width).map(move |col| (row, col))).find_map(|(row, col)| {
+        let expected = reference.state_by_coords(row, col);
+        let actual = verified.state_by_coords(row, col);
+        (expected != actual).then_some(Divergence { generation, row, col, expected, actual })
+    })
+}
+
+/// Runs both engines from the same cells for the number of generations, and compares all of their cells
+/// at the start, every `check_every` generations and after the last one. Stops at the first divergence
+pub fn verify(reference: &mut dyn DynAutomaton, verified: &mut dyn DynAutomaton, generations: u64, check_every: NonZeroU64) -> Result<(), Divergence> {
+    for generation in 0..=generations {
+        if generation > 0 {
+            reference.step();
+            verified.step();
+        }
+        if (generation % check_every.get() == 0) || (generation == generations) {
+            if let Some(divergence) = first_difference(reference, verified, generation) {
+                return Err(divergence);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::RefCell;
+    use std::str::FromStr;
+    use crate::random::XorShift;
+    fn soup(rule: Rule, neighborhood: Neighborhood) -> Field {
+        let size = NonZeroUsize::new(32).unwrap();
+        let random = RefCell::new(XorShift::new(7));
+        Field::generate_by_fn(size, size, |_| random.borrow_mut().next_below(2) == 0).with_rule(rule).with_neighborhood(neighborhood)
+    }
+    #[test]
+    fn test_field_matches_naive() {
+        let every = NonZeroU64::new(5).unwrap();
+        for (rule, neighborhood) in [(Rule::LIFE, Neighborhood::Moore), (Rule::HIGH_LIFE, Neighborhood::Moore), (Rule::LIFE, Neighborhood::VonNeumann)] {
+            let mut field = soup(rule, neighborhood);
+            let mut naive = NaiveLife::from_field(&field);
+            assert_eq!(Ok(()), verify(&mut naive, &mut field, 100, every));
+        }
+        for topology in [Topology::Plane, Topology::KleinBottle, Topology::ProjectivePlane] {
+            let mut field = soup(Rule::LIFE, Neighborhood::Moore).with_topology(topology);
+            let mut naive = NaiveLife::from_field(&field);
+            assert_eq!(Ok(()), verify(&mut naive, &mut field, 100, every));
+        }
+    }
+    #[test]
+    fn test_divergence() {
+        let every = NonZeroU64::new(5).unwrap();
+        let mut field = soup(Rule::LIFE, Neighborhood::Moore);
+        let mut naive = NaiveLife::from_field(&field);
+        field.toggle_by_coords(1, 2);
+        let divergence = verify(&mut naive, &mut field, 100, every).unwrap_err();
+        assert_eq!((0, 1, 2), (divergence.generation, divergence.row, divergence.col));
+        assert_ne!(divergence.expected, divergence.actual);
+
+        // only checked every few generations
+        let mut field = soup(Rule::HIGH_LIFE, Neighborhood::Moore);
+        let mut naive = NaiveLife::from_field(&soup(Rule::LIFE, Neighborhood::Moore));
+        let divergence = verify(&mut naive, &mut field, 100, every).unwrap_err();
+        assert_eq!(0, divergence.generation % 5);
+
+        let mut field = Field::from_str("_#\n#_").unwrap();
+        let mut naive = NaiveLife::parse("_#_\n#__").unwrap();
+        let divergence = verify(&mut naive, &mut field, 1, every).unwrap_err();
+        assert_eq!(Divergence { generation: 0, row: 0, col: 2, expected: Some(0), actual: None }, divergence);
+        assert_eq!("engines diverged at generation 0, row 0, column 2: expected state 0, got no cell", divergence.to_string());
+    }
+}
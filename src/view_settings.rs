@@ -0,0 +1,96 @@
+use std::rc::Rc;
+use web_sys::Document;
+use crate::app::{create_button, draw_field, draw_ghost_if_paused, update_grid_visibility, update_magnifier, App, AppDispatcher, Message};
+use crate::control_panel::Group;
+use crate::error::AppError;
+use crate::listeners::Listeners;
+use crate::render::{draw_initial_state, ImageRenderer, TrailRenderer};
+
+/// What is drawn over and around the field, each request turns one of them on or off
+pub enum ViewCommand {
+    Trails,
+    Preview,
+    Counts,
+    Grid,
+    Magnifier,
+    Renderer,
+}
+
+impl App {
+    pub fn update_view(&mut self, command: ViewCommand) -> Result<(), AppError> {
+        match command {
+            ViewCommand::Trails => self.toggle_trails(),
+            ViewCommand::Preview => {
+                self.toggle_preview();
+                Ok(())
+            }
+            ViewCommand::Counts => {
+                self.state.show_counts = !self.state.show_counts;
+                // also removes the counts when they are turned off
+                draw_field(&self.context, self.field.as_ref(), &self.state, true);
+                Ok(())
+            }
+            ViewCommand::Grid => {
+                self.state.show_grid = !self.state.show_grid;
+                update_grid_visibility(&self.state)
+            }
+            ViewCommand::Magnifier => {
+                self.state.magnifier.toggle();
+                update_magnifier(&self.context, self.field.as_ref(), &self.state);
+                Ok(())
+            }
+            ViewCommand::Renderer => self.toggle_renderer(),
+        }
+    }
+
+    fn toggle_trails(&mut self) -> Result<(), AppError> {
+        if self.state.trail_renderer.take().is_some() {
+            // remove the trails that are still visible
+            draw_initial_state(self.field.as_ref(), &self.canvas, &self.context, &self.state.grid_context, self.state.cell_size_px)?;
+        } else {
+            self.state.trail_renderer = Some(TrailRenderer::default());
+        }
+        Ok(())
+    }
+
+    fn toggle_preview(&mut self) {
+        self.state.show_ghost = !self.state.show_ghost;
+        if self.state.is_running() {
+            return;
+        }
+        // also removes the ghost when it is turned off
+        draw_field(&self.context, self.field.as_ref(), &self.state, true);
+        draw_ghost_if_paused(&self.context, self.field.as_mut(), &self.state);
+    }
+
+    fn toggle_renderer(&mut self) -> Result<(), AppError> {
+        self.state.adaptive_renderer = false;
+        if self.state.image_renderer.take().is_some() {
+            // bring the grid back
+            return draw_initial_state(self.field.as_ref(), &self.canvas, &self.context, &self.state.grid_context, self.state.cell_size_px);
+        }
+        self.state.image_renderer = Some(ImageRenderer::new(&self.document)?);
+        draw_field(&self.context, self.field.as_ref(), &self.state, true);
+        Ok(())
+    }
+}
+
+/// Adds the toggles to the group
+pub fn add_view_controls(group: &Group, document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<(), AppError> {
+    let toggles = [
+        ("Toggle Trails", (|| Message::View(ViewCommand::Trails)) as fn() -> Message, None),
+        ("Toggle Preview", || Message::View(ViewCommand::Preview), None),
+        ("Toggle Counts", || Message::View(ViewCommand::Counts), None),
+        ("Toggle Grid", || Message::View(ViewCommand::Grid), None),
+        ("Toggle Magnifier", || Message::View(ViewCommand::Magnifier), Some("Show the cells around the cursor enlarged")),
+        ("Toggle Fast Render", || Message::View(ViewCommand::Renderer), None),
+    ];
+    for (name, message, title) in toggles {
+        let button = create_button(name, message, document, listeners, dispatcher)?;
+        if let Some(title) = title {
+            button.set_attribute("title", title)?;
+        }
+        group.add(&button)?;
+    }
+    Ok(())
+}
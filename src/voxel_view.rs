@@ -0,0 +1,311 @@
+use std::f32::consts::FRAC_PI_2;
+use wasm_bindgen::prelude::*;
+use web_sys::{Document, HtmlCanvasElement, MouseEvent, WebGlBuffer, WebGlRenderingContext as Gl, WebGlUniformLocation, WheelEvent};
+use crate::app::{App, Message};
+use crate::error::AppError;
+use crate::life_3d::Life3d;
+use crate::webgl::{bind_attributes, context_webgl, link_program, perspective, resize};
+
+/// Width and height of the view in css pixels
+const VIEW_SIZE_PX: u32 = 480;
+/// Floats of a vertex: the position and the normal
+const VERTEX_FLOATS: usize = 6;
+/// Radians the camera turns for a pixel of dragging
+const ORBIT_PER_PX: f32 = 0.01;
+/// The camera stops short of the poles, where the up direction would flip
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.05;
+/// Closest and farthest distances of the camera from the center of the cube, whose edges are 2 long
+const MIN_DISTANCE: f32 = 2.0;
+const MAX_DISTANCE: f32 = 12.0;
+/// Zoom of a wheel step of 100 pixels
+const ZOOM_PER_100_PX: f32 = 1.1;
+const FIELD_OF_VIEW: f32 = 0.9;
+/// Cubes are a bit smaller than their cells, so that the neighbours stay apart
+const CUBE_SCALE: f32 = 0.9;
+
+const VERTEX_SHADER: &str = r#"
+attribute vec3 position;
+attribute vec3 normal;
+uniform mat4 projection;
+uniform mat4 view;
+varying vec3 v_color;
+void main() {
+    gl_Position = projection * view * vec4(position, 1.0);
+    vec3 turned_normal = normalize((view * vec4(normal, 0.0)).xyz);
+    float light = 0.35 + 0.65 * max(dot(turned_normal, normalize(vec3(0.4, 0.6, 1.0))), 0.0);
+    // the color follows the position in the cube, so that the layers can be told apart
+    v_color = (0.35 + 0.65 * (position * 0.5 + 0.5)) * light;
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+varying vec3 v_color;
+void main() {
+    gl_FragColor = vec4(v_color, 1.0);
+}
+"#;
+
+/// Input of the camera from the pointer over the view, passed to the app that owns the `VoxelView`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Orbit {
+    /// Dragging with the primary button by this many css pixels
+    Drag(f64, f64),
+    /// Wheel scrolling by this many pixels, down moves away
+    Zoom(f64),
+}
+
+/// Camera that circles around the center of the cube, looking at it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitCamera {
+    /// Turn around the vertical axis
+    yaw: f32,
+    /// Angle above the horizontal plane
+    pitch: f32,
+    distance: f32,
+}
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self { yaw: 0.6, pitch: 0.45, distance: 5.0 }
+    }
+}
+impl OrbitCamera {
+    pub fn apply(&mut self, orbit: Orbit) {
+        match orbit {
+            Orbit::Drag(dx, dy) => {
+                self.yaw = (self.yaw + dx as f32 * ORBIT_PER_PX) % std::f32::consts::TAU;
+                self.pitch = (self.pitch + dy as f32 * ORBIT_PER_PX).clamp(-MAX_PITCH, MAX_PITCH);
+            }
+            Orbit::Zoom(delta_px) => {
+                let factor = ZOOM_PER_100_PX.powf(delta_px as f32 / 100.0);
+                self.distance = (self.distance * factor).clamp(MIN_DISTANCE, MAX_DISTANCE);
+            }
+        }
+    }
+    /// Column-major transform from the coordinates of the cube to the ones of the camera
+    pub fn view_matrix(&self) -> [f32; 16] {
+        let (yaw_sin, yaw_cos) = self.yaw.sin_cos();
+        let (pitch_sin, pitch_cos) = self.pitch.sin_cos();
+        // turning around the y axis by the yaw, then tilting around the x axis by the pitch
+        [
+            yaw_cos, pitch_sin * yaw_sin, -pitch_cos * yaw_sin, 0.0,
+            0.0, pitch_cos, pitch_sin, 0.0,
+            yaw_sin, -pitch_sin * yaw_cos, pitch_cos * yaw_cos, 0.0,
+            0.0, 0.0, -self.distance, 1.0,
+        ]
+    }
+}
+
+/// Directions of the faces of a cube, with the corners of each face in counter-clockwise order seen from outside
+const FACES: [([i32; 3], [[f32; 3]; 4]); 6] = [
+    ([1, 0, 0], [[1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [1.0, 1.0, 1.0], [1.0, 0.0, 1.0]]),
+    ([-1, 0, 0], [[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 1.0], [0.0, 1.0, 0.0]]),
+    ([0, 1, 0], [[0.0, 1.0, 0.0], [0.0, 1.0, 1.0], [1.0, 1.0, 1.0], [1.0, 1.0, 0.0]]),
+    ([0, -1, 0], [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 0.0, 1.0], [0.0, 0.0, 1.0]]),
+    ([0, 0, 1], [[0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [1.0, 1.0, 1.0], [0.0, 1.0, 1.0]]),
+    ([0, 0, -1], [[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.0], [1.0, 0.0, 0.0]]),
+];
+
+/// Triangles of a cube for every live cell, in a cube from -1 to 1. Faces between two live cells are left out,
+/// the faces on the sides of the cube are always drawn, the wrapped neighbours are far away on the screen
+pub fn voxel_mesh(field: &Life3d) -> Vec<f32> {
+    let size = field.get_size();
+    let cell = 2.0 / size as f32;
+    let is_alive = |x: i32, y: i32, z: i32| {
+        let range = 0..size as i32;
+        range.contains(&x) && range.contains(&y) && range.contains(&z) && field.is_alive(x as usize, y as usize, z as usize)
+    };
+    let mut vertices = Vec::new();
+    for (x, y, z) in field.alive_cells() {
+        let corner = [x, y, z].map(|coord| -1.0 + coord as f32 * cell + cell * (1.0 - CUBE_SCALE) / 2.0);
+        for (normal, corners) in FACES {
+            if is_alive(x as i32 + normal[0], y as i32 + normal[1], z as i32 + normal[2]) {
+                continue;
+            }
+            // two triangles of the quad
+            for index in [0, 1, 2, 0, 2, 3] {
+                let position: [f32; 3] = std::array::from_fn(|axis| corner[axis] + corners[index][axis] * cell * CUBE_SCALE);
+                vertices.extend_from_slice(&position);
+                vertices.extend_from_slice(&normal.map(|coord| coord as f32));
+            }
+        }
+    }
+    vertices
+}
+
+/// Handlers of the canvas, they are dropped with it
+struct Handlers {
+    _on_mouse_move: Closure<dyn Fn(MouseEvent)>,
+    _on_wheel: Closure<dyn Fn(WheelEvent)>,
+}
+
+/// Shows the live cells of a 3D field as cubes, seen by a camera that is turned by dragging and zoomed with the wheel
+pub struct VoxelView {
+    canvas: HtmlCanvasElement,
+    gl: Gl,
+    buffer: WebGlBuffer,
+    view: Option<WebGlUniformLocation>,
+    camera: OrbitCamera,
+    _handlers: Handlers,
+}
+impl VoxelView {
+    pub fn new(document: &Document, on_orbit: impl Fn(Orbit) + 'static) -> Result<Self, AppError> {
+        let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+        canvas.set_attribute("aria-label", "The live cells of the 3D field as cubes, drag to turn the view and scroll to zoom")?;
+        canvas.set_attribute("role", "img")?;
+        canvas.set_attribute("style", &format!("display: none; vertical-align: top; width: {VIEW_SIZE_PX}px; height: {VIEW_SIZE_PX}px; border: 1px solid #888888; background-color: #101018; cursor: grab;"))?;
+        let gl = context_webgl(&canvas)?;
+        let program = link_program(&gl, VERTEX_SHADER, FRAGMENT_SHADER)?;
+        let buffer = gl.create_buffer().ok_or(AppError::Missing("WebGL buffer"))?;
+        gl.bind_buffer(Gl::ARRAY_BUFFER, Some(&buffer));
+        bind_attributes(&gl, &program, VERTEX_FLOATS, &[("position", 3, 0), ("normal", 3, 3)])?;
+        resize(&gl, &canvas, VIEW_SIZE_PX, VIEW_SIZE_PX);
+        gl.uniform_matrix4fv_with_f32_array(gl.get_uniform_location(&program, "projection").as_ref(), false, &perspective(FIELD_OF_VIEW, 1.0, 0.1, 50.0));
+        gl.enable(Gl::DEPTH_TEST);
+        gl.enable(Gl::CULL_FACE);
+        gl.clear_color(0.063, 0.063, 0.094, 1.0);
+
+        let on_orbit = std::rc::Rc::new(on_orbit);
+        let on_mouse_move = {
+            let on_orbit = std::rc::Rc::clone(&on_orbit);
+            Closure::<dyn Fn(MouseEvent)>::new(move |event: MouseEvent| {
+                if event.buttons() & 1 != 0 {
+                    on_orbit(Orbit::Drag(event.movement_x() as f64, event.movement_y() as f64));
+                }
+            })
+        };
+        let on_wheel = Closure::<dyn Fn(WheelEvent)>::new(move |event: WheelEvent| {
+            // the page doesn't scroll while zooming
+            event.prevent_default();
+            on_orbit(Orbit::Zoom(event.delta_y()));
+        });
+        canvas.set_onmousemove(Some(on_mouse_move.as_ref().unchecked_ref()));
+        canvas.set_onwheel(Some(on_wheel.as_ref().unchecked_ref()));
+        Ok(Self {
+            canvas,
+            view: gl.get_uniform_location(&program, "view"),
+            gl,
+            buffer,
+            camera: OrbitCamera::default(),
+            _handlers: Handlers { _on_mouse_move: on_mouse_move, _on_wheel: on_wheel },
+        })
+    }
+    pub fn canvas(&self) -> &HtmlCanvasElement {
+        &self.canvas
+    }
+    /// Shown while the field is 3D
+    pub fn set_visible(&self, is_visible: bool) {
+        // setting styles can't fail for this property
+        let _ = self.canvas.style().set_property("display", if is_visible { "inline-block" } else { "none" });
+    }
+    pub fn orbit(&mut self, orbit: Orbit) {
+        self.camera.apply(orbit);
+    }
+    /// Draws the live cells of the field from the camera
+    pub fn draw(&self, field: &Life3d) {
+        let gl = &self.gl;
+        let vertices = voxel_mesh(field);
+        gl.bind_buffer(Gl::ARRAY_BUFFER, Some(&self.buffer));
+        gl.buffer_data_with_array_buffer_view(Gl::ARRAY_BUFFER, &js_sys::Float32Array::from(vertices.as_slice()), Gl::DYNAMIC_DRAW);
+        gl.uniform_matrix4fv_with_f32_array(self.view.as_ref(), false, &self.camera.view_matrix());
+        gl.clear(Gl::COLOR_BUFFER_BIT | Gl::DEPTH_BUFFER_BIT);
+        gl.draw_arrays(Gl::TRIANGLES, 0, (vertices.len() / VERTEX_FLOATS) as i32);
+    }
+}
+impl Drop for VoxelView {
+    fn drop(&mut self) {
+        // the handlers are dropped, the canvas must not call them anymore
+        self.canvas.set_onmousemove(None);
+        self.canvas.set_onwheel(None);
+    }
+}
+
+impl App {
+    /// Sets up the view of the 3D field when one is first loaded, the layers are still shown side by side without WebGL
+    pub fn create_voxels(&mut self) -> Result<(), AppError> {
+        if self.voxels.is_some() || !self.field.as_any_mut().is::<Life3d>() {
+            return Ok(());
+        }
+        let dispatcher = self.dispatcher.clone();
+        let voxels = VoxelView::new(&self.document, move |orbit| {
+            if let Some(dispatcher) = dispatcher.upgrade() {
+                dispatcher.send(Message::Orbit(orbit));
+            }
+        })?;
+        self.root.append_child(voxels.canvas())?;
+        self.voxels = Some(voxels);
+        Ok(())
+    }
+
+    /// Turns or zooms the camera of the 3D field
+    pub fn orbit(&mut self, orbit: Orbit) {
+        if let Some(voxels) = &mut self.voxels {
+            voxels.orbit(orbit);
+            if let Some(field) = self.field.as_any_mut().downcast_ref::<Life3d>() {
+                voxels.draw(field);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::num::NonZeroUsize;
+    use crate::life_3d::Rule3d;
+    use crate::webgl::transform;
+    #[test]
+    fn test_voxel_mesh() {
+        let size = NonZeroUsize::new(4).unwrap();
+        let single = Life3d::generate_by_fn(size, Rule3d::BAYS_4555, |x, y, z| (x, y, z) == (0, 0, 0));
+        let vertices = voxel_mesh(&single);
+        assert_eq!(6 * 6 * VERTEX_FLOATS, vertices.len());
+        for vertex in vertices.chunks_exact(VERTEX_FLOATS) {
+            // in the first cell of the cube, inside its margin
+            assert!(vertex[..3].iter().all(|&coord| (-0.99..=-0.51).contains(&coord)));
+        }
+        // the counter-clockwise triangles face the way of their normals
+        for triangle in vertices.chunks_exact(3 * VERTEX_FLOATS) {
+            let corner = |index: usize| -> [f32; 3] { std::array::from_fn(|axis| triangle[index * VERTEX_FLOATS + axis]) };
+            let (a, b, c) = (corner(0), corner(1), corner(2));
+            let (ab, ac) = ([b[0] - a[0], b[1] - a[1], b[2] - a[2]], [c[0] - a[0], c[1] - a[1], c[2] - a[2]]);
+            let cross = [ab[1] * ac[2] - ab[2] * ac[1], ab[2] * ac[0] - ab[0] * ac[2], ab[0] * ac[1] - ab[1] * ac[0]];
+            let normal = &triangle[3..6];
+            assert!(cross.iter().zip(normal).map(|(a, b)| a * b).sum::<f32>() > 0.0);
+        }
+
+        // the faces between the two cells are hidden
+        let pair = Life3d::generate_by_fn(size, Rule3d::BAYS_4555, |x, y, z| (y, z) == (1, 1) && (1..3).contains(&x));
+        assert_eq!(10 * 6 * VERTEX_FLOATS, voxel_mesh(&pair).len());
+        let empty = Life3d::generate_by_fn(size, Rule3d::BAYS_4555, |_, _, _| false);
+        assert!(voxel_mesh(&empty).is_empty());
+    }
+    #[test]
+    fn test_orbit_camera() {
+        let mut camera = OrbitCamera::default();
+        // the center of the cube is straight ahead
+        assert_eq!([0.0, 0.0, -camera.distance, 1.0], transform(&camera.view_matrix(), [0.0, 0.0, 0.0]));
+        // the view only turns and moves, the corners stay as far from the center
+        let corner = transform(&camera.view_matrix(), [1.0, 1.0, 1.0]);
+        let length = (corner[0].powi(2) + corner[1].powi(2) + (corner[2] + camera.distance).powi(2)).sqrt();
+        assert!((length - 3.0_f32.sqrt()).abs() < 1e-5);
+
+        camera.apply(Orbit::Drag(0.0, 10_000.0));
+        assert_eq!(MAX_PITCH, camera.pitch);
+        camera.apply(Orbit::Zoom(-100_000.0));
+        assert_eq!(MIN_DISTANCE, camera.distance);
+        camera.apply(Orbit::Zoom(100.0));
+        assert!((camera.distance - MIN_DISTANCE * ZOOM_PER_100_PX).abs() < 1e-5);
+        camera.apply(Orbit::Zoom(100_000.0));
+        assert_eq!(MAX_DISTANCE, camera.distance);
+        // the whole cube is in the view from the first distance, also after turning
+        let mut camera = OrbitCamera::default();
+        camera.apply(Orbit::Drag(123.0, -45.0));
+        let projection = perspective(FIELD_OF_VIEW, 1.0, 0.1, 50.0);
+        for corner in [[-1.0, -1.0, -1.0], [1.0, 1.0, 1.0], [1.0, -1.0, 1.0], [-1.0, 1.0, -1.0]] {
+            let [x, y, z, _] = transform(&camera.view_matrix(), corner);
+            let [clip_x, clip_y, _, w] = transform(&projection, [x, y, z]);
+            assert!((clip_x / w).abs() < 1.0 && (clip_y / w).abs() < 1.0);
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::num::NonZeroUsize;
+use crate::automaton::{next_coord_wrapped, parse_cells, prev_coord_wrapped, Automaton, CellState};
+use crate::game_of_life::ParseError;
+use crate::random::XorShift;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WatorCell {
+    Water = 0,
+    Fish = 1,
+    Shark = 2,
+}
+impl CellState for WatorCell {
+    const PALETTE: &'static [&'static str] = &["#0B2545", "#7FD1B9", "#E4572E"];
+    const NAMES: &'static [&'static str] = &["Water", "Fish", "Shark"];
+    fn index(self) -> u8 {
+        self as u8
+    }
+    fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(WatorCell::Water),
+            1 => Some(WatorCell::Fish),
+            2 => Some(WatorCell::Shark),
+            _ => None,
+        }
+    }
+    fn next_in_edit_cycle(self) -> Self {
+        match self {
+            WatorCell::Water => WatorCell::Fish,
+            WatorCell::Fish => WatorCell::Shark,
+            WatorCell::Shark => WatorCell::Water,
+        }
+    }
+    fn to_char(self) -> char {
+        match self {
+            WatorCell::Water => '_',
+            WatorCell::Fish => 'f',
+            WatorCell::Shark => 'S',
+        }
+    }
+    fn from_char(char: char) -> Option<Self> {
+        match char {
+            '_' => Some(WatorCell::Water),
+            'f' => Some(WatorCell::Fish),
+            'S' => Some(WatorCell::Shark),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WatorParams {
+    /// Generations a fish has to survive before it breeds
+    pub fish_breed: u16,
+    /// Generations a shark has to survive before it breeds
+    pub shark_breed: u16,
+    /// Generations a shark can live without eating
+    pub shark_starve: u16,
+}
+impl Default for WatorParams {
+    fn default() -> Self {
+        Self {
+            fish_breed: 3,
+            shark_breed: 8,
+            shark_starve: 3,
+        }
+    }
+}
+
+/// Wa-Tor predator-prey simulation.
+/// Fish and sharks move to a random orthogonal neighbour, sharks prefer neighbours with fish and eat them.
+/// Both breed after surviving long enough, sharks die if they don't eat for too long.
+#[derive(Clone)]
+pub struct Wator {
+    width: NonZeroUsize,
+    height: NonZeroUsize,
+    params: WatorParams,
+    random: XorShift,
+    cells: Vec<WatorCell>,
+    old_cells: Vec<WatorCell>,
+    /// Generations since the creature has been born or has bred
+    ages: Vec<u16>,
+    /// Generations since the shark has eaten
+    hunger: Vec<u16>,
+    moved: Vec<bool>,
+}
+
+impl Wator {
+    pub fn generate_by_fn(
+        width: NonZeroUsize,
+        height: NonZeroUsize,
+        params: WatorParams,
+        seed: u64,
+        random_cell: impl Fn(usize) -> WatorCell,
+    ) -> Self {
+        let cell_count = width.get() * height.get();
+        Self {
+            width,
+            height,
+            params,
+            random: XorShift::new(seed),
+            cells: (0..cell_count).map(random_cell).collect(),
+            old_cells: vec![WatorCell::Water; cell_count],
+            ages: vec![0; cell_count],
+            hunger: vec![0; cell_count],
+            moved: vec![false; cell_count],
+        }
+    }
+    pub fn get_params(&self) -> WatorParams {
+        self.params
+    }
+    /// Number of fish and sharks
+    pub fn populations(&self) -> (usize, usize) {
+        let fish = self.cells.iter().filter(|&&cell| cell == WatorCell::Fish).count();
+        let sharks = self.cells.iter().filter(|&&cell| cell == WatorCell::Shark).count();
+        (fish, sharks)
+    }
+    pub fn update(&mut self) -> bool {
+        self.old_cells.copy_from_slice(&self.cells);
+        self.moved.fill(false);
+        // start from a random cell, so that creatures at the top don't always get to move first
+        let cell_count = self.cells.len();
+        let start = self.random.next_below(cell_count);
+        for offset in 0..cell_count {
+            let index = (start + offset) % cell_count;
+            if self.moved[index] {
+                continue;
+            }
+            match self.cells[index] {
+                WatorCell::Water => {}
+                WatorCell::Fish => self.update_fish(index),
+                WatorCell::Shark => self.update_shark(index),
+            }
+        }
+        self.cells.iter().any(|&cell| cell != WatorCell::Water)
+    }
+    fn update_fish(&mut self, index: usize) {
+        self.ages[index] += 1;
+        if let Some(target) = self.random_neighbour_with_value(index, WatorCell::Water) {
+            self.move_creature(index, target, self.params.fish_breed);
+        }
+    }
+    fn update_shark(&mut self, index: usize) {
+        self.ages[index] += 1;
+        if let Some(target) = self.random_neighbour_with_value(index, WatorCell::Fish) {
+            self.hunger[index] = 0;
+            self.move_creature(index, target, self.params.shark_breed);
+            return;
+        }
+        self.hunger[index] += 1;
+        if self.hunger[index] >= self.params.shark_starve {
+            self.cells[index] = WatorCell::Water;
+            return;
+        }
+        if let Some(target) = self.random_neighbour_with_value(index, WatorCell::Water) {
+            self.move_creature(index, target, self.params.shark_breed);
+        }
+    }
+    /// Moves the creature, leaving an offspring in the old place if it is old enough to breed
+    fn move_creature(&mut self, from: usize, to: usize, breed_age: u16) {
+        self.cells[to] = self.cells[from];
+        self.hunger[to] = self.hunger[from];
+        self.moved[to] = true;
+        if self.ages[from] >= breed_age {
+            self.ages[to] = 0;
+            self.ages[from] = 0;
+            self.hunger[from] = 0;
+        } else {
+            self.ages[to] = self.ages[from];
+            self.cells[from] = WatorCell::Water;
+        }
+    }
+    fn random_neighbour_with_value(&mut self, index: usize, value: WatorCell) -> Option<usize> {
+        let width = self.width.get();
+        let row = index / width;
+        let col = index % width;
+        let max_row = self.height.get() - 1;
+        let max_col = width - 1;
+        let neighbours = [
+            (prev_coord_wrapped(row, max_row) * width) + col,
+            (next_coord_wrapped(row, max_row) * width) + col,
+            (row * width) + prev_coord_wrapped(col, max_col),
+            (row * width) + next_coord_wrapped(col, max_col),
+        ];
+        let mut candidates = [0; 4];
+        let mut count = 0;
+        for neighbour in neighbours {
+            if self.cells[neighbour] == value {
+                candidates[count] = neighbour;
+                count += 1;
+            }
+        }
+        if count == 0 {
+            return None;
+        }
+        Some(candidates[self.random.next_below(count)])
+    }
+}
+
+impl Automaton for Wator {
+    type State = WatorCell;
+    fn get_width(&self) -> usize {
+        self.width.get()
+    }
+    fn get_height(&self) -> usize {
+        self.height.get()
+    }
+    fn view(&self) -> &[WatorCell] {
+        &self.cells
+    }
+    fn view_old(&self) -> &[WatorCell] {
+        &self.old_cells
+    }
+    fn step(&mut self) -> bool {
+        self.update()
+    }
+    fn set(&mut self, row: usize, col: usize, value: WatorCell) -> Option<()> {
+        if (row >= self.height.get()) || (col >= self.width.get()) {
+            return None;
+        }
+        let index = (row * self.width.get()) + col;
+        self.cells[index] = value;
+        self.ages[index] = 0;
+        self.hunger[index] = 0;
+        Some(())
+    }
+    /// Parses the cells with default params and a fixed seed
+    fn parse(str: &str) -> Result<Self, ParseError> {
+        let (width, height, cells) = parse_cells(str)?;
+        let res = Self::generate_by_fn(width, height, WatorParams::default(), 1, |i| cells[i]);
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_shark_starves() {
+        let mut field = Wator::parse("___\n_S_\n___").unwrap();
+        assert!(field.update());
+        assert!(field.update());
+        assert_eq!((0, 1), field.populations());
+        assert!(!field.update());
+        assert_eq!((0, 0), field.populations());
+    }
+    #[test]
+    fn test_fish_breeds() {
+        let mut field = Wator::parse("_____\n_____\n__f__\n_____\n_____").unwrap();
+        field.update();
+        field.update();
+        assert_eq!((1, 0), field.populations());
+        field.update();
+        assert_eq!((2, 0), field.populations());
+    }
+    #[test]
+    fn test_shark_eats_fish() {
+        // the fish is surrounded, so it can't escape
+        let mut field = Wator::parse("_____\n__S__\n_SfS_\n__S__\n_____").unwrap();
+        field.update();
+        assert_eq!((0, 4), field.populations());
+    }
+}
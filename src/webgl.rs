@@ -0,0 +1,73 @@
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlCanvasElement, WebGlProgram, WebGlRenderingContext as Gl, WebGlShader};
+use crate::error::AppError;
+
+/// WebGL 1 context of the canvas
+pub fn context_webgl(canvas: &HtmlCanvasElement) -> Result<Gl, AppError> {
+    let context = canvas.get_context("webgl")?.ok_or(AppError::Missing("WebGL"))?;
+    context.dyn_into::<Gl>().map_err(|_| AppError::Missing("WebGL"))
+}
+
+/// Sizes the canvas for the device pixels of its css size, and the viewport of the context to it
+pub fn resize(gl: &Gl, canvas: &HtmlCanvasElement, width_px: u32, height_px: u32) {
+    let pixel_ratio = web_sys::window().map_or(1.0, |window| window.device_pixel_ratio());
+    let (width, height) = ((width_px as f64 * pixel_ratio).round() as u32, (height_px as f64 * pixel_ratio).round() as u32);
+    canvas.set_width(width);
+    canvas.set_height(height);
+    gl.viewport(0, 0, width as i32, height as i32);
+}
+
+fn compile_shader(gl: &Gl, shader_type: u32, source: &str) -> Result<WebGlShader, AppError> {
+    let shader = gl.create_shader(shader_type).ok_or(AppError::Missing("WebGL shader"))?;
+    gl.shader_source(&shader, source);
+    gl.compile_shader(&shader);
+    if !gl.get_shader_parameter(&shader, Gl::COMPILE_STATUS).as_bool().unwrap_or(false) {
+        let log = gl.get_shader_info_log(&shader).unwrap_or_default();
+        return Err(AppError::Js(format!("can't compile the shader: {log}").into()));
+    }
+    Ok(shader)
+}
+
+/// Compiles the shaders and links them into a program, which is made the current one
+pub fn link_program(gl: &Gl, vertex_source: &str, fragment_source: &str) -> Result<WebGlProgram, AppError> {
+    let program = gl.create_program().ok_or(AppError::Missing("WebGL program"))?;
+    gl.attach_shader(&program, &compile_shader(gl, Gl::VERTEX_SHADER, vertex_source)?);
+    gl.attach_shader(&program, &compile_shader(gl, Gl::FRAGMENT_SHADER, fragment_source)?);
+    gl.link_program(&program);
+    if !gl.get_program_parameter(&program, Gl::LINK_STATUS).as_bool().unwrap_or(false) {
+        let log = gl.get_program_info_log(&program).unwrap_or_default();
+        return Err(AppError::Js(format!("can't link the shaders: {log}").into()));
+    }
+    gl.use_program(Some(&program));
+    Ok(program)
+}
+
+/// Points the attributes at the floats of the vertices in the bound array buffer. The attributes are given by their names,
+/// numbers of floats and offsets in floats from the start of a vertex
+pub fn bind_attributes(gl: &Gl, program: &WebGlProgram, vertex_floats: usize, attributes: &[(&str, i32, i32)]) -> Result<(), AppError> {
+    let stride = (vertex_floats * size_of::<f32>()) as i32;
+    for &(name, size, offset) in attributes {
+        let location = u32::try_from(gl.get_attrib_location(program, name)).map_err(|_| AppError::Missing("WebGL attribute"))?;
+        gl.enable_vertex_attrib_array(location);
+        gl.vertex_attrib_pointer_with_i32(location, size, Gl::FLOAT, false, stride, offset * size_of::<f32>() as i32);
+    }
+    Ok(())
+}
+
+/// Column-major perspective projection, like `gluPerspective`
+pub fn perspective(field_of_view: f32, aspect: f32, near: f32, far: f32) -> [f32; 16] {
+    let f = 1.0 / (field_of_view / 2.0).tan();
+    let depth = 1.0 / (near - far);
+    [
+        f / aspect, 0.0, 0.0, 0.0,
+        0.0, f, 0.0, 0.0,
+        0.0, 0.0, (near + far) * depth, -1.0,
+        0.0, 0.0, 2.0 * near * far * depth, 0.0,
+    ]
+}
+
+/// Applies the column-major matrix to the point, the result has the w coordinate last, for the tests of the views
+#[cfg(test)]
+pub fn transform(matrix: &[f32; 16], [x, y, z]: [f32; 3]) -> [f32; 4] {
+    std::array::from_fn(|row| matrix[row] * x + matrix[4 + row] * y + matrix[8 + row] * z + matrix[12 + row])
+}
@@ -0,0 +1,253 @@
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use web_sys::{BinaryType, Document, HtmlInputElement, MessageEvent, WebSocket, Window};
+use crate::app::{create_button, draw_field, App, AppDispatcher, Message};
+use crate::error::AppError;
+use crate::game_of_life::Field;
+use crate::listeners::Listeners;
+use crate::mirror::{Backoff, Frame, Mirror};
+use crate::{console_log, console_warn};
+
+/// Delays between the attempts to reconnect after the connection is lost
+const RECONNECT_INITIAL_MS: u32 = 500;
+const RECONNECT_MAX_MS: u32 = 30_000;
+
+/// Whether the field is sent to the server or replaced by the frames from it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncRole {
+    Broadcast,
+    View,
+}
+
+/// Events of the connection, they are passed to the app that owns the `WebSocketSync`
+#[derive(Debug, Clone)]
+pub enum SyncEvent {
+    Opened,
+    /// A binary message from the server, the frame of a broadcasting peer
+    Received(Vec<u8>),
+    Closed,
+    /// The delay after a lost connection has passed
+    Reconnect,
+}
+
+/// Handlers of the socket, they are dropped with it
+struct Handlers {
+    _on_open: Closure<dyn Fn()>,
+    _on_message: Closure<dyn Fn(MessageEvent)>,
+    _on_close: Closure<dyn Fn()>,
+}
+
+/// Mirrors the Life field through a WebSocket server that relays every binary message to the other clients.
+/// The broadcaster sends a snapshot first and then the changed cells of every generation, viewers apply them.
+/// Lost connections are retried with an exponential backoff until `close`
+pub struct WebSocketSync {
+    url: String,
+    role: SyncRole,
+    socket: Option<(WebSocket, Handlers)>,
+    mirror: Mirror,
+    backoff: Backoff,
+    reconnect_timeout: Option<i32>,
+    on_event: Rc<dyn Fn(SyncEvent)>,
+}
+
+impl WebSocketSync {
+    pub fn connect(url: &str, role: SyncRole, on_event: impl Fn(SyncEvent) + 'static) -> Result<Self, AppError> {
+        let mut sync = Self {
+            url: url.to_string(),
+            role,
+            socket: None,
+            mirror: Mirror::default(),
+            backoff: Backoff::new(RECONNECT_INITIAL_MS, RECONNECT_MAX_MS),
+            reconnect_timeout: None,
+            on_event: Rc::new(on_event),
+        };
+        sync.open()?;
+        Ok(sync)
+    }
+    pub fn role(&self) -> SyncRole {
+        self.role
+    }
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+    pub fn is_open(&self) -> bool {
+        self.socket.as_ref().is_some_and(|(socket, _)| socket.ready_state() == WebSocket::OPEN)
+    }
+    fn open(&mut self) -> Result<(), AppError> {
+        let socket = WebSocket::new(&self.url).map_err(|_| AppError::Network(format!("invalid WebSocket url {}", self.url)))?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+        let on_event = Rc::clone(&self.on_event);
+        let on_open = Closure::<dyn Fn()>::new(move || on_event(SyncEvent::Opened));
+        let on_event = Rc::clone(&self.on_event);
+        let on_message = Closure::<dyn Fn(MessageEvent)>::new(move |event: MessageEvent| {
+            // text messages are not frames, e.g. greetings of the server
+            if let Ok(buffer) = event.data().dyn_into::<js_sys::ArrayBuffer>() {
+                on_event(SyncEvent::Received(js_sys::Uint8Array::new(&buffer).to_vec()));
+            }
+        });
+        let on_event = Rc::clone(&self.on_event);
+        // a failed connection is closed too, so errors don't need their own handler
+        let on_close = Closure::<dyn Fn()>::new(move || on_event(SyncEvent::Closed));
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+        self.socket = Some((socket, Handlers { _on_open: on_open, _on_message: on_message, _on_close: on_close }));
+        Ok(())
+    }
+    /// Updates the connection for the event. Returns the frame for `Received` events of viewers
+    pub fn handle(&mut self, window: &Window, event: SyncEvent) -> Result<Option<Frame>, AppError> {
+        match event {
+            SyncEvent::Opened => {
+                console_log!("connected to {}", self.url);
+                self.backoff.reset();
+                // whoever joined since the connection was lost needs the whole field
+                self.mirror.reset();
+                Ok(None)
+            }
+            SyncEvent::Received(bytes) if self.role == SyncRole::View => match Frame::decode(&bytes) {
+                Ok(frame) => Ok(Some(frame)),
+                Err(err) => {
+                    console_warn!("ignored an invalid frame from {}: {err:?}", self.url);
+                    Ok(None)
+                }
+            },
+            SyncEvent::Received(_) => Ok(None),
+            SyncEvent::Closed => {
+                self.socket = None;
+                let delay = self.backoff.next_delay_ms();
+                console_warn!("lost the connection to {}, retrying in {delay} ms", self.url);
+                let on_event = Rc::clone(&self.on_event);
+                let reconnect = Closure::once_into_js(move || on_event(SyncEvent::Reconnect));
+                self.reconnect_timeout = Some(window.set_timeout_with_callback_and_timeout_and_arguments_0(reconnect.unchecked_ref(), delay as i32)?);
+                Ok(None)
+            }
+            SyncEvent::Reconnect => {
+                self.reconnect_timeout = None;
+                self.open()?;
+                Ok(None)
+            }
+        }
+    }
+    /// Sends the changes of the field since the last frame, if broadcasting and connected
+    pub fn send(&mut self, generation: u64, field: &Field) -> Result<(), AppError> {
+        if (self.role != SyncRole::Broadcast) || !self.is_open() {
+            return Ok(());
+        }
+        if let (Some(frame), Some((socket, _))) = (self.mirror.frame(generation, field), &self.socket) {
+            socket.send_with_u8_array(&frame.encode())?;
+        }
+        Ok(())
+    }
+    /// Closes the connection for good, without reconnecting
+    pub fn close(mut self, window: &Window) {
+        if let Some(timeout) = self.reconnect_timeout.take() {
+            window.clear_timeout_with_handle(timeout);
+        }
+        if let Some((socket, _handlers)) = self.socket.take() {
+            // the handlers are dropped, the socket must not call them anymore
+            socket.set_onopen(None);
+            socket.set_onmessage(None);
+            socket.set_onclose(None);
+            let _ = socket.close();
+        }
+    }
+}
+
+/// Requests of the sync controls and the events of the connection
+pub enum SyncCommand {
+    /// Connects to the WebSocket server at the url, to send the field to other viewers or to show theirs
+    Connect(String, SyncRole),
+    Event(SyncEvent),
+    Disconnect,
+}
+
+impl App {
+    pub fn update_sync(&mut self, command: SyncCommand) -> Result<(), AppError> {
+        match command {
+            SyncCommand::Connect(url, role) => self.connect_sync(&url, role),
+            SyncCommand::Event(event) => self.handle_sync(event),
+            SyncCommand::Disconnect => {
+                if let Some(sync) = self.sync.take() {
+                    sync.close(&self.window);
+                    self.live_region.announce("Disconnected");
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn connect_sync(&mut self, url: &str, role: SyncRole) -> Result<(), AppError> {
+        if url.is_empty() {
+            return Err(AppError::InvalidInput("enter the url of a WebSocket server".to_string()));
+        }
+        if let Some(sync) = self.sync.take() {
+            sync.close(&self.window);
+        }
+        let dispatcher = self.dispatcher.clone();
+        self.sync = Some(WebSocketSync::connect(url, role, move |event| {
+            if let Some(dispatcher) = dispatcher.upgrade() {
+                dispatcher.send(Message::Sync(SyncCommand::Event(event)));
+            }
+        })?);
+        Ok(())
+    }
+
+    /// Keeps the connection up, and shows the frames received by viewers
+    fn handle_sync(&mut self, event: SyncEvent) -> Result<(), AppError> {
+        let Some(sync) = &mut self.sync else {
+            // an event of a closed connection
+            return Ok(());
+        };
+        if matches!(event, SyncEvent::Opened) {
+            let action = if sync.role() == SyncRole::Broadcast { "Broadcasting" } else { "Viewing" };
+            self.live_region.announce(&format!("{action} through {}", sync.url()));
+        }
+        match sync.handle(&self.window, event)? {
+            Some(Frame::Snapshot { generation, field }) => {
+                self.show_life_field(field)?;
+                self.state.generation.set(generation);
+                self.update_scrubber();
+            }
+            Some(Frame::Delta { generation, cells }) => {
+                // a delta without the snapshot before it, the next snapshot brings the viewer back in sync
+                let Some(field) = self.field.as_any_mut().downcast_mut::<Field>() else {
+                    return Ok(());
+                };
+                field.apply_diff(&cells);
+                self.state.generation.set(generation);
+                draw_field(&self.context, field, &self.state, true);
+                self.queue_field_changed();
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Sends the changes of the Life field to the viewers, while broadcasting
+    pub fn broadcast(&mut self) -> Result<(), AppError> {
+        let (Some(sync), Some(field)) = (&mut self.sync, self.field.as_any_mut().downcast_ref::<Field>()) else {
+            return Ok(());
+        };
+        sync.send(self.state.generation.get(), field)
+    }
+}
+
+/// Url input of the WebSocket server, with the buttons to broadcast the field, to view a broadcast and to disconnect
+pub fn create_sync_controls(document: &Document, listeners: &mut Listeners, dispatcher: &Rc<AppDispatcher>) -> Result<web_sys::Element, AppError> {
+    let container = document.create_element("span")?;
+    let url_input = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    url_input.set_type("url");
+    url_input.set_placeholder("wss://…");
+    url_input.set_title("Url of a WebSocket server that relays every message to the other clients");
+    container.append_child(&url_input)?;
+
+    for (name, role) in [("Broadcast", SyncRole::Broadcast), ("View", SyncRole::View)] {
+        let url_input = url_input.clone();
+        let message = move || Message::Sync(SyncCommand::Connect(url_input.value().trim().to_string(), role));
+        let button = create_button(name, message, document, listeners, dispatcher)?;
+        container.append_child(&button)?;
+    }
+    let button = create_button("Disconnect", || Message::Sync(SyncCommand::Disconnect), document, listeners, dispatcher)?;
+    container.append_child(&button)?;
+    Ok(container)
+}
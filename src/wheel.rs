@@ -0,0 +1,55 @@
+use crate::render::{MAX_CELL_SIZE_PX, MIN_CELL_SIZE_PX};
+
+/// Speed limits that the wheel steps through, in generations per second. Above the last one the speed is not limited
+const SPEEDS: [f64; 8] = [1.0, 2.0, 5.0, 10.0, 15.0, 20.0, 30.0, 60.0];
+/// Every step of the wheel changes the size of the cells with the grid line by this factor
+const ZOOM_FACTOR: f64 = 1.25;
+
+/// Next speed limit from `SPEEDS`, None means not limited. Limits between the steps go to the nearest step in the direction
+pub fn speed_step(target_fps: Option<f64>, is_faster: bool) -> Option<f64> {
+    match (target_fps, is_faster) {
+        (None, true) => None,
+        (None, false) => SPEEDS.last().copied(),
+        (Some(fps), true) => SPEEDS.iter().copied().find(|&speed| speed > fps),
+        (Some(fps), false) => Some(SPEEDS.iter().copied().rev().find(|&speed| speed < fps).unwrap_or(SPEEDS[0])),
+    }
+}
+
+/// Size of cells on the screen after a step of the wheel, it changes by at least a pixel until the limits
+pub fn zoom_step(cell_size_px: usize, is_zoom_in: bool) -> usize {
+    // the grid line is a part of the cell on the screen, like for the pinch
+    let pitch = (cell_size_px + 1) as f64;
+    let new_size = if is_zoom_in {
+        ((pitch * ZOOM_FACTOR).round() as usize - 1).max(cell_size_px + 1)
+    } else {
+        ((pitch / ZOOM_FACTOR).round() as usize).saturating_sub(1).min(cell_size_px.saturating_sub(1))
+    };
+    new_size.clamp(MIN_CELL_SIZE_PX, MAX_CELL_SIZE_PX)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_speed_step() {
+        assert_eq!(None, speed_step(None, true));
+        assert_eq!(Some(60.0), speed_step(None, false));
+        assert_eq!(None, speed_step(Some(60.0), true));
+        assert_eq!(Some(15.0), speed_step(Some(10.0), true));
+        assert_eq!(Some(5.0), speed_step(Some(10.0), false));
+        assert_eq!(Some(10.0), speed_step(Some(7.0), true));
+        assert_eq!(Some(5.0), speed_step(Some(7.0), false));
+        assert_eq!(Some(1.0), speed_step(Some(1.0), false));
+        assert_eq!(Some(1.0), speed_step(Some(0.5), true));
+    }
+    #[test]
+    fn test_zoom_step() {
+        assert_eq!(17, zoom_step(13, true));
+        assert_eq!(10, zoom_step(13, false));
+        // at least a pixel
+        assert_eq!(2, zoom_step(1, true));
+        assert_eq!(1, zoom_step(2, false));
+        assert_eq!(MIN_CELL_SIZE_PX, zoom_step(MIN_CELL_SIZE_PX, false));
+        assert_eq!(MAX_CELL_SIZE_PX, zoom_step(MAX_CELL_SIZE_PX, true));
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt::{Display, Formatter, Write};
+use std::mem;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+use crate::automaton::{count_neighbours_with_value, parse_cells, Automaton, CellState};
+use crate::game_of_life::ParseError;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WireCell {
+    Empty = 0,
+    Conductor = 1,
+    ElectronHead = 2,
+    ElectronTail = 3,
+}
+impl CellState for WireCell {
+    const PALETTE: &'static [&'static str] = &["#000000", "#FFAA00", "#3399FF", "#FF3300"];
+    const NAMES: &'static [&'static str] = &["Empty", "Conductor", "Electron head", "Electron tail"];
+    fn index(self) -> u8 {
+        self as u8
+    }
+    fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(WireCell::Empty),
+            1 => Some(WireCell::Conductor),
+            2 => Some(WireCell::ElectronHead),
+            3 => Some(WireCell::ElectronTail),
+            _ => None,
+        }
+    }
+    fn next_in_edit_cycle(self) -> Self {
+        match self {
+            WireCell::Empty => WireCell::Conductor,
+            WireCell::Conductor => WireCell::ElectronHead,
+            WireCell::ElectronHead => WireCell::ElectronTail,
+            WireCell::ElectronTail => WireCell::Empty,
+        }
+    }
+    fn to_char(self) -> char {
+        match self {
+            WireCell::Empty => '_',
+            WireCell::Conductor => '#',
+            WireCell::ElectronHead => '@',
+            WireCell::ElectronTail => '~',
+        }
+    }
+    fn from_char(char: char) -> Option<Self> {
+        match char {
+            '_' => Some(WireCell::Empty),
+            '#' => Some(WireCell::Conductor),
+            '@' => Some(WireCell::ElectronHead),
+            '~' => Some(WireCell::ElectronTail),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Wireworld {
+    width: NonZeroUsize,
+    height: NonZeroUsize,
+    cells: Vec<WireCell>,
+    swap_cells: Vec<WireCell>,
+}
+
+impl Wireworld {
+    pub fn new(width: NonZeroUsize, height: NonZeroUsize) -> Self {
+        let cell_count = width.get() * height.get();
+        Self {
+            width,
+            height,
+            cells: vec![WireCell::Empty; cell_count],
+            swap_cells: vec![WireCell::Empty; cell_count],
+        }
+    }
+    pub fn get_by_coords(&self, row: usize, col: usize) -> Option<WireCell> {
+        let index = self.coords_to_index_checked(row, col)?;
+        Some(self.cells[index])
+    }
+    pub fn set_by_coords(&mut self, row: usize, col: usize, value: WireCell) -> Option<()> {
+        let index = self.coords_to_index_checked(row, col)?;
+        self.cells[index] = value;
+        Some(())
+    }
+    fn coords_to_index_checked(&self, row: usize, col: usize) -> Option<usize> {
+        if row >= self.height.get() {
+            return None;
+        }
+        if col >= self.width.get() {
+            return None;
+        }
+        Some(self.coords_to_index_unchecked(row, col))
+    }
+    fn coords_to_index_unchecked(&self, row: usize, col: usize) -> usize {
+        (row * self.width.get()) + col
+    }
+    pub fn update(&mut self) -> bool {
+        let width = self.width.get();
+        let height = self.height.get();
+        let mut has_electrons = false;
+        for (row_no, row) in self.cells.chunks(width).enumerate() {
+            for (col_no, &value) in row.iter().enumerate() {
+                let new_value = match value {
+                    WireCell::Empty => WireCell::Empty,
+                    WireCell::ElectronHead => WireCell::ElectronTail,
+                    WireCell::ElectronTail => WireCell::Conductor,
+                    WireCell::Conductor => {
+                        let heads = count_neighbours_with_value(&self.cells, width, height, row_no, col_no, WireCell::ElectronHead);
+                        if (heads == 1) || (heads == 2) {
+                            WireCell::ElectronHead
+                        } else {
+                            WireCell::Conductor
+                        }
+                    }
+                };
+                let index = self.coords_to_index_unchecked(row_no, col_no);
+                self.swap_cells[index] = new_value;
+                has_electrons = has_electrons || matches!(new_value, WireCell::ElectronHead | WireCell::ElectronTail);
+            }
+        }
+        mem::swap(&mut self.cells, &mut self.swap_cells);
+        has_electrons
+    }
+}
+
+impl FromStr for Wireworld {
+    type Err = ParseError;
+    fn from_str(str: &str) -> Result<Self, ParseError> {
+        let (width, height, cells) = parse_cells(str)?;
+        let cells_len = cells.len();
+        let res = Self {
+            width,
+            height,
+            cells,
+            swap_cells: vec![WireCell::Empty; cells_len],
+        };
+        Ok(res)
+    }
+}
+
+impl Automaton for Wireworld {
+    type State = WireCell;
+    fn get_width(&self) -> usize {
+        self.width.get()
+    }
+    fn get_height(&self) -> usize {
+        self.height.get()
+    }
+    fn view(&self) -> &[WireCell] {
+        &self.cells
+    }
+    fn view_old(&self) -> &[WireCell] {
+        &self.swap_cells
+    }
+    fn step(&mut self) -> bool {
+        self.update()
+    }
+    fn set(&mut self, row: usize, col: usize, value: WireCell) -> Option<()> {
+        self.set_by_coords(row, col, value)
+    }
+    fn parse(str: &str) -> Result<Self, ParseError> {
+        str.parse()
+    }
+}
+
+impl Display for Wireworld {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self.width.get();
+        for row in self.cells.chunks(width) {
+            for &value in row.iter() {
+                f.write_char(value.to_char())?;
+            }
+            f.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_string_parse() {
+        let field_str =
+"_##@~_
+#____#
+";
+        let field = Wireworld::from_str(field_str).unwrap();
+        assert_eq!(field_str, field.to_string());
+        assert_eq!(WireCell::ElectronHead, field.get_by_coords(0, 3).unwrap());
+        assert_eq!(Some(ParseError::UnknownChar), Wireworld::from_str("_x_").err());
+    }
+    #[test]
+    fn test_electron_moves_along_wire() {
+        let mut field = Wireworld::from_str("
+________
+_~@####_
+________
+").unwrap();
+        assert!(field.update());
+        assert_eq!("_#~@###_", field.to_string().lines().nth(1).unwrap());
+        assert!(field.update());
+        assert_eq!("_##~@##_", field.to_string().lines().nth(1).unwrap());
+        field.update();
+        field.update();
+        assert!(field.update());
+        assert_eq!("_#####~_", field.to_string().lines().nth(1).unwrap());
+        assert!(!field.update());
+        assert_eq!("_######_", field.to_string().lines().nth(1).unwrap());
+    }
+    #[test]
+    fn test_edit_cycle() {
+        let mut field = Wireworld::new(2.try_into().unwrap(), 1.try_into().unwrap());
+        field.toggle(0, 1).unwrap();
+        assert_eq!(WireCell::Conductor, field.get_by_coords(0, 1).unwrap());
+        field.toggle(0, 1).unwrap();
+        field.toggle(0, 1).unwrap();
+        field.toggle(0, 1).unwrap();
+        assert_eq!(WireCell::Empty, field.get_by_coords(0, 1).unwrap());
+        assert_eq!(None, field.toggle(1, 0));
+    }
+}